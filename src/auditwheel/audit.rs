@@ -293,6 +293,7 @@ pub fn auditwheel_rs(
     target: &Target,
     platform_tag: Option<PlatformTag>,
     allow_linking_libpython: bool,
+    allowed_libs: &[String],
 ) -> Result<(Policy, bool), AuditWheelError> {
     if !target.is_linux() || platform_tag == Some(PlatformTag::Linux) {
         return Ok((Policy::default(), false));
@@ -309,7 +310,7 @@ pub fn auditwheel_rs(
     let versioned_libraries = find_versioned_libraries(&elf);
 
     // Find the highest possible policy, if any
-    let platform_policies = match platform_tag {
+    let mut platform_policies = match platform_tag {
         Some(PlatformTag::Manylinux { .. }) => MANYLINUX_POLICIES.clone(),
         Some(PlatformTag::Musllinux { x, y }) => MUSLLINUX_POLICIES
             .clone()
@@ -329,6 +330,9 @@ pub fn auditwheel_rs(
         }
         Some(PlatformTag::Linux) => unreachable!(),
     };
+    for policy in &mut platform_policies {
+        policy.allow_libs(allowed_libs);
+    }
     let mut highest_policy = None;
     let mut should_repair = false;
     for policy in platform_policies.iter() {
@@ -365,6 +369,7 @@ pub fn auditwheel_rs(
         let tag = platform_tag.to_string();
         let mut policy = Policy::from_name(&tag).ok_or(AuditWheelError::UndefinedPolicy(tag))?;
         policy.fixup_musl_libc_so_name(target.target_arch());
+        policy.allow_libs(allowed_libs);
 
         if let Some(highest_policy) = highest_policy {
             // Don't recommend manylinux1 because rust doesn't support it anymore
@@ -470,17 +475,22 @@ pub fn get_policy_and_libs(
     platform_tag: Option<PlatformTag>,
     target: &Target,
     allow_linking_libpython: bool,
+    allowed_libs: &[String],
 ) -> Result<(Policy, Vec<Library>)> {
-    let (policy, should_repair) =
-        auditwheel_rs(artifact, target, platform_tag, allow_linking_libpython).with_context(
-            || {
-                if let Some(platform_tag) = platform_tag {
-                    format!("Error ensuring {platform_tag} compliance")
-                } else {
-                    "Error checking for manylinux/musllinux compliance".to_string()
-                }
-            },
-        )?;
+    let (policy, should_repair) = auditwheel_rs(
+        artifact,
+        target,
+        platform_tag,
+        allow_linking_libpython,
+        allowed_libs,
+    )
+    .with_context(|| {
+        if let Some(platform_tag) = platform_tag {
+            format!("Error ensuring {platform_tag} compliance")
+        } else {
+            "Error checking for manylinux/musllinux compliance".to_string()
+        }
+    })?;
     let external_libs = if should_repair {
         let sysroot = get_sysroot_path(target).unwrap_or_else(|_| PathBuf::from("/"));
         let ld_paths = artifact.linked_paths.iter().map(PathBuf::from).collect();
@@ -529,10 +539,30 @@ pub fn relpath(to: &Path, from: &Path) -> PathBuf {
 
 #[cfg(test)]
 mod test {
-    use crate::auditwheel::audit::relpath;
+    use crate::auditwheel::audit::{policy_is_satisfied, relpath, AuditWheelError};
+    use crate::auditwheel::Policy;
+    use goblin::elf::Elf;
     use pretty_assertions::assert_eq;
     use std::path::Path;
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_allowed_libs() {
+        // Any ELF file works since the linked libraries are passed separately
+        let exe = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::parse(&exe).unwrap();
+        let deps = ["libc.so.6".to_string(), "libfoo.so.1".to_string()];
+        let mut policy = Policy::from_name("manylinux_2_17").unwrap();
+        let result = policy_is_satisfied(&policy, &elf, "x86_64", &deps, &[], false);
+        assert!(matches!(
+            result,
+            Err(AuditWheelError::LinksForbiddenLibrariesError(_, libs)) if libs == ["libfoo.so.1"]
+        ));
+
+        policy.allow_libs(&["libfoo.so.1".to_string()]);
+        policy_is_satisfied(&policy, &elf, "x86_64", &deps, &[], false).unwrap();
+    }
+
     #[test]
     fn test_relpath() {
         let cases = [
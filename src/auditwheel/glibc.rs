@@ -0,0 +1,74 @@
+use fs_err as fs;
+use regex::bytes::Regex as BytesRegex;
+use regex::Regex;
+use std::process::Command;
+
+/// Where glibc usually lives, `/lib64` on RHEL based images such as the manylinux ones and
+/// `/lib/<triple>` on Debian based ones
+const LIBC_GLOBS: &[&str] = &[
+    "/lib64/libc.so.6",
+    "/usr/lib64/libc.so.6",
+    "/lib/*/libc.so.6",
+];
+
+/// Detects the glibc version of the machine we're building on, which is the newest glibc the
+/// compiled artifacts can require
+///
+/// Asks `getconf GNU_LIBC_VERSION` first and falls back to reading the version banner from
+/// `libc.so.6`. Returns `None` if neither worked, e.g. on a musl host.
+pub fn get_glibc_version() -> Option<(u16, u16)> {
+    let from_getconf = Command::new("getconf")
+        .arg("GNU_LIBC_VERSION")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| parse_getconf_glibc_version(&String::from_utf8_lossy(&output.stdout)));
+    from_getconf.or_else(|| {
+        LIBC_GLOBS
+            .iter()
+            .filter_map(|pattern| glob::glob(pattern).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .filter_map(|path| fs::read(path).ok())
+            .find_map(|content| parse_libc_banner(&content))
+    })
+}
+
+/// Parses the output of `getconf GNU_LIBC_VERSION`, e.g. `glibc 2.17`
+fn parse_getconf_glibc_version(output: &str) -> Option<(u16, u16)> {
+    let expr = Regex::new(r"^glibc (\d+)\.(\d+)").unwrap();
+    let capture = expr.captures(output.trim())?;
+    Some((capture[1].parse().ok()?, capture[2].parse().ok()?))
+}
+
+/// Finds the version in the banner that is embedded in `libc.so.6`, e.g.
+/// `GNU C Library (Debian GLIBC 2.36-9) stable release version 2.36.`
+fn parse_libc_banner(content: &[u8]) -> Option<(u16, u16)> {
+    let expr = BytesRegex::new(r"GNU C Library [^\x00\n]*?release version (\d+)\.(\d+)").unwrap();
+    let capture = expr.captures(content)?;
+    let major = std::str::from_utf8(&capture[1]).ok()?.parse().ok()?;
+    let minor = std::str::from_utf8(&capture[2]).ok()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_getconf_glibc_version, parse_libc_banner};
+
+    #[test]
+    fn test_parse_getconf_glibc_version() {
+        assert_eq!(parse_getconf_glibc_version("glibc 2.17\n"), Some((2, 17)));
+        assert_eq!(parse_getconf_glibc_version("glibc 2.36"), Some((2, 36)));
+        assert_eq!(parse_getconf_glibc_version("NPTL 2.17"), None);
+        assert_eq!(parse_getconf_glibc_version(""), None);
+    }
+
+    #[test]
+    fn test_parse_libc_banner() {
+        let content = b"\x7fELF\x00\x00GNU C Library (Debian GLIBC 2.36-9+deb12u4) stable release version 2.36.\n\x00more";
+        assert_eq!(parse_libc_banner(content), Some((2, 36)));
+        let content = b"\x00GNU C Library (GNU libc) stable release version 2.17, by Roland McGrath et al.\x00";
+        assert_eq!(parse_libc_banner(content), Some((2, 17)));
+        assert_eq!(parse_libc_banner(b"\x7fELF\x00musl"), None);
+    }
+}
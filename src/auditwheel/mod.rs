@@ -1,4 +1,5 @@
 mod audit;
+mod glibc;
 mod musllinux;
 pub mod patchelf;
 mod platform_tag;
@@ -6,6 +7,8 @@ mod policy;
 mod repair;
 
 pub use audit::*;
+pub(crate) use glibc::get_glibc_version;
 pub use platform_tag::PlatformTag;
 pub use policy::Policy;
-pub use repair::find_external_libs;
+pub use repair::{audit_wheel, find_external_libs, repair_wheel};
+pub(crate) use repair::{check_external_libs, eprint_external_libs, graft_external_libs};
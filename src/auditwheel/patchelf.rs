@@ -5,6 +5,15 @@ use std::process::Command;
 
 static MISSING_PATCHELF_ERROR: &str = "Failed to execute 'patchelf', did you install it? Hint: Try `pip install maturin[patchelf]` (or just `pip install patchelf`)";
 
+/// Whether the patchelf executable can be run
+pub fn is_installed() -> bool {
+    program_runs(OsStr::new("patchelf"))
+}
+
+fn program_runs(program: &OsStr) -> bool {
+    Command::new(program).arg("--version").output().is_ok()
+}
+
 /// Verify patchelf version
 pub fn verify_patchelf() -> Result<()> {
     let output = Command::new("patchelf")
@@ -111,3 +120,14 @@ pub fn get_rpath(file: impl AsRef<Path>) -> Result<Vec<String>> {
         Err(e) => bail!("Failed to parse ELF file at '{}': {}", file.display(), e),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::program_runs;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn missing_patchelf_is_detected() {
+        assert!(!program_runs(OsStr::new("maturin-test-missing-patchelf")));
+    }
+}
@@ -92,6 +92,12 @@ impl Policy {
             .cloned()
     }
 
+    /// Treats the given libraries as system libraries that may be linked, see
+    /// `[tool.maturin.auditwheel.allowed-libs]`
+    pub(crate) fn allow_libs(&mut self, libs: &[String]) {
+        self.lib_whitelist.extend(libs.iter().cloned());
+    }
+
     pub(crate) fn fixup_musl_libc_so_name(&mut self, target_arch: Arch) {
         // Fixup musl libc lib_whitelist
         if self.name.starts_with("musllinux") && self.lib_whitelist.remove("libc.so") {
@@ -1,8 +1,24 @@
-use super::audit::AuditWheelError;
+use super::audit::{get_policy_and_libs, relpath, AuditWheelError};
+use super::{patchelf, PlatformTag};
 use crate::auditwheel::Policy;
-use anyhow::Result;
-use lddtree::DependencyAnalyzer;
+use crate::build_context::hash_file;
+use crate::module_writer::{rebuild_record, ModuleWriter, PathWriter};
+use crate::{BuildArtifact, Target};
+use anyhow::{bail, Context, Result};
+use fs_err as fs;
+use fs_err::File;
+use goblin::elf::header::{
+    machine_to_str, EI_DATA, ELFDATA2LSB, EM_386, EM_AARCH64, EM_ARM, EM_PPC64, EM_RISCV, EM_S390,
+    EM_X86_64,
+};
+use goblin::elf::Elf;
+use ignore::WalkBuilder;
+use lddtree::{DependencyAnalyzer, Library};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
 
 /// Find external shared library dependencies
 #[allow(clippy::result_large_err)]
@@ -34,3 +50,538 @@ pub fn find_external_libs(
     }
     Ok(ext_libs)
 }
+
+/// Copies the external libraries into `libs_dir` of the wheel with hashed sonames and patches
+/// the artifacts to load them from there
+///
+/// `artifacts` are the artifacts on disk, each with the directory it has inside the wheel.
+/// They are patched in place, the grafted libraries are prepared in `temp_dir`.
+pub(crate) fn graft_external_libs(
+    writer: &mut impl ModuleWriter,
+    temp_dir: &Path,
+    libs_dir: &Path,
+    artifacts: &[(&Path, &Path)],
+    ext_libs: &[Vec<Library>],
+) -> Result<()> {
+    writer.add_directory(libs_dir)?;
+
+    let mut soname_map = BTreeMap::new();
+    let mut libs_copied = HashSet::new();
+    for lib in ext_libs.iter().flatten() {
+        let lib_path = lib.realpath.clone().with_context(|| {
+            format!(
+                "Cannot repair wheel, because required library {} could not be located.",
+                lib.path.display()
+            )
+        })?;
+        // Generate a new soname with a short hash
+        let short_hash = &hash_file(&lib_path)?[..8];
+        let (file_stem, file_ext) = lib.name.split_once('.').unwrap();
+        let new_soname = if !file_stem.ends_with(&format!("-{short_hash}")) {
+            format!("{file_stem}-{short_hash}.{file_ext}")
+        } else {
+            format!("{file_stem}.{file_ext}")
+        };
+
+        // Copy the original lib to a tmpdir and modify some of its properties
+        // for example soname and rpath
+        let dest_path = temp_dir.join(&new_soname);
+        fs::copy(&lib_path, &dest_path)?;
+        libs_copied.insert(lib_path);
+
+        // fs::copy copies permissions as well, and the original
+        // file may have been read-only
+        let mut perms = fs::metadata(&dest_path)?.permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        perms.set_readonly(false);
+        fs::set_permissions(&dest_path, perms)?;
+
+        patchelf::set_soname(&dest_path, &new_soname)?;
+        if !lib.rpath.is_empty() || !lib.runpath.is_empty() {
+            patchelf::set_rpath(&dest_path, &libs_dir)?;
+        }
+        soname_map.insert(
+            lib.name.clone(),
+            (new_soname.clone(), dest_path.clone(), lib.needed.clone()),
+        );
+    }
+
+    for ((artifact, _), artifact_ext_libs) in artifacts.iter().zip(ext_libs) {
+        let artifact_deps: HashSet<_> = artifact_ext_libs.iter().map(|lib| &lib.name).collect();
+        let replacements = soname_map
+            .iter()
+            .filter_map(|(k, v)| {
+                if artifact_deps.contains(k) {
+                    Some((k, v.0.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if !replacements.is_empty() {
+            patchelf::replace_needed(artifact, &replacements[..])?;
+        }
+    }
+
+    // we grafted in a bunch of libraries and modified their sonames, but
+    // they may have internal dependencies (DT_NEEDED) on one another, so
+    // we need to update those records so each now knows about the new
+    // name of the other.
+    for (new_soname, path, needed) in soname_map.values() {
+        let mut replacements = Vec::new();
+        for n in needed {
+            if soname_map.contains_key(n) {
+                replacements.push((n, soname_map[n].0.clone()));
+            }
+        }
+        if !replacements.is_empty() {
+            patchelf::replace_needed(path, &replacements[..])?;
+        }
+        writer.add_file_with_permissions(libs_dir.join(new_soname), path, 0o755)?;
+    }
+
+    eprintln!(
+        "🖨  Copied external shared libraries to package {} directory:",
+        libs_dir.display()
+    );
+    for lib_path in libs_copied {
+        eprintln!("    {}", lib_path.display());
+    }
+
+    for (artifact, artifact_dir) in artifacts {
+        let mut new_rpaths = patchelf::get_rpath(artifact)?;
+        // TODO: clean existing rpath entries if it's not pointed to a location within the wheel
+        // See https://github.com/pypa/auditwheel/blob/353c24250d66951d5ac7e60b97471a6da76c123f/src/auditwheel/repair.py#L160
+        let new_rpath = Path::new("$ORIGIN").join(relpath(libs_dir, artifact_dir));
+        new_rpaths.push(new_rpath.to_str().unwrap().to_string());
+        let new_rpath = new_rpaths.join(":");
+        patchelf::set_rpath(artifact, &new_rpath)?;
+    }
+    Ok(())
+}
+
+/// A wheel extracted to a temporary directory together with the audit results of its ELF files
+struct AuditedWheel {
+    /// Keeps the extracted wheel alive
+    _temp_dir: tempfile::TempDir,
+    unpacked: PathBuf,
+    /// The parts of the wheel filename, e.g. `["foo", "0.1.0", "cp39", "abi3", "linux_x86_64"]`
+    filename_parts: Vec<String>,
+    dist_info_dir: PathBuf,
+    target: Target,
+    /// The modification time and compression of the entries of the original wheel
+    entries: HashMap<String, (Option<DateTime>, CompressionMethod)>,
+    /// The ELF files, relative to the root of the wheel
+    artifacts: Vec<PathBuf>,
+    /// The external libraries of each of the `artifacts`
+    ext_libs: Vec<Vec<Library>>,
+    policy: Policy,
+}
+
+/// Extracts the wheel and runs the auditwheel analysis on all of its ELF files,
+/// without changing anything
+fn audit_wheel_files(
+    wheel_path: &Path,
+    platform_tag: Option<PlatformTag>,
+    allowed_libs: &[String],
+) -> Result<AuditedWheel> {
+    if !Target::from_target_triple(None)?.is_linux() {
+        bail!("Repairing wheels is only supported on linux");
+    }
+    let file_name = wheel_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".whl"))
+        .with_context(|| format!("{} is not a wheel", wheel_path.display()))?;
+    let parts: Vec<&str> = file_name.split('-').collect();
+    if !(5..=6).contains(&parts.len()) {
+        bail!("Invalid wheel filename {}", wheel_path.display());
+    }
+    let (name, version) = (parts[0], parts[1]);
+    let dist_info_dir = PathBuf::from(format!("{name}-{version}.dist-info"));
+    let scripts_dir = PathBuf::from(format!("{name}-{version}.data")).join("scripts");
+
+    let temp_dir = tempfile::tempdir()?;
+    let unpacked = temp_dir.path().join("wheel");
+    let mut archive = ZipArchive::new(File::open(wheel_path)?)
+        .with_context(|| format!("Failed to open {}", wheel_path.display()))?;
+    let mut entries = HashMap::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        entries.insert(
+            entry.name().to_string(),
+            (entry.last_modified(), entry.compression()),
+        );
+    }
+    archive
+        .extract(&unpacked)
+        .with_context(|| format!("Failed to extract {}", wheel_path.display()))?;
+
+    let mut artifacts = Vec::new();
+    for entry in WalkBuilder::new(&unpacked).standard_filters(false).build() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(&unpacked)?.to_path_buf();
+        let in_libs_dir = relative.components().any(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .is_some_and(|c| c.ends_with(".libs"))
+        });
+        if in_libs_dir || !entry.path().is_file() || !is_elf(entry.path())? {
+            continue;
+        }
+        artifacts.push(relative);
+    }
+    if artifacts.is_empty() {
+        bail!("{} doesn't contain any ELF files", wheel_path.display());
+    }
+    // The wheel may have been cross compiled, so we audit for the arch of its artifacts
+    let target = elf_target(&unpacked.join(&artifacts[0]))?;
+
+    let mut policies = Vec::new();
+    let mut ext_libs = Vec::new();
+    for relative in &artifacts {
+        let artifact = BuildArtifact {
+            path: unpacked.join(relative),
+            linked_paths: Vec::new(),
+        };
+        // Only binaries may link libpython, extension modules must not
+        let allow_linking_libpython = relative.starts_with(&scripts_dir);
+        let (policy, libs) = get_policy_and_libs(
+            &artifact,
+            platform_tag,
+            &target,
+            allow_linking_libpython,
+            allowed_libs,
+        )?;
+        policies.push(policy);
+        ext_libs.push(libs);
+    }
+    let policy = policies.into_iter().min_by_key(|p| p.priority).unwrap();
+    Ok(AuditedWheel {
+        _temp_dir: temp_dir,
+        unpacked,
+        filename_parts: parts.into_iter().map(ToString::to_string).collect(),
+        dist_info_dir,
+        target,
+        entries,
+        artifacts,
+        ext_libs,
+        policy,
+    })
+}
+
+/// Lists the external libraries with their resolved paths, or `not found` if they couldn't
+/// be resolved
+pub(crate) fn eprint_external_libs<'a>(libs: impl IntoIterator<Item = &'a Library>) {
+    for lib in libs {
+        if let Some(path) = lib.realpath.as_ref() {
+            eprintln!("    {} => {}", lib.name, path.display())
+        } else {
+            eprintln!("    {} => not found", lib.name)
+        };
+    }
+}
+
+/// Fails if any artifact requires external libraries that would have to be copied into the
+/// wheel, which `--auditwheel check` doesn't do
+pub(crate) fn check_external_libs(ext_libs: &[Vec<Library>]) -> Result<()> {
+    if ext_libs.iter().all(|libs| libs.is_empty()) {
+        return Ok(());
+    }
+    eprintln!("🖨️ Your library is not manylinux/musllinux compliant because it requires copying the following libraries:");
+    eprint_external_libs(ext_libs.iter().flatten());
+    bail!("Can not repair the wheel because `--auditwheel=check` is specified, re-run with `--auditwheel=repair` to copy the libraries.");
+}
+
+/// Runs the auditwheel analysis on an already built wheel without modifying it, failing if
+/// the wheel requires external libraries that `repair_wheel` would copy into it.
+///
+/// Without a `platform_tag`, the wheel must comply with any manylinux/musllinux policy.
+/// The `allowed_libs` are never considered external, see `[tool.maturin.auditwheel]`.
+/// Returns the highest policy the wheel complies with.
+pub fn audit_wheel(
+    wheel_path: &Path,
+    platform_tag: Option<PlatformTag>,
+    allowed_libs: &[String],
+) -> Result<Policy> {
+    let audited = audit_wheel_files(wheel_path, platform_tag, allowed_libs)?;
+    check_external_libs(&audited.ext_libs)?;
+    if !audited.policy.platform_tag().is_portable() {
+        bail!(
+            "{} isn't compliant with any manylinux/musllinux policy",
+            wheel_path.display()
+        );
+    }
+    Ok(audited.policy)
+}
+
+/// Runs the auditwheel repair on an already built wheel: the external libraries of its
+/// ELF files are grafted into the wheel and the wheel is retagged with the matched policy.
+///
+/// Without a `platform_tag`, the highest compatible manylinux/musllinux tag is used.
+/// The `allowed_libs` are never grafted, see `[tool.maturin.auditwheel]`.
+/// Returns the path of the repaired wheel in `out_dir`.
+pub fn repair_wheel(
+    wheel_path: &Path,
+    platform_tag: Option<PlatformTag>,
+    allowed_libs: &[String],
+    out_dir: &Path,
+) -> Result<PathBuf> {
+    let AuditedWheel {
+        _temp_dir,
+        unpacked,
+        filename_parts: parts,
+        dist_info_dir,
+        target,
+        entries,
+        artifacts,
+        ext_libs,
+        policy,
+    } = audit_wheel_files(wheel_path, platform_tag, allowed_libs)?;
+    let name = &parts[0];
+    let platform_tag = platform_tag.unwrap_or_else(|| policy.platform_tag());
+    if !platform_tag.is_portable() {
+        bail!(
+            "{} isn't compliant with any manylinux/musllinux policy",
+            wheel_path.display()
+        );
+    }
+
+    if ext_libs.iter().any(|libs| !libs.is_empty()) {
+        if !patchelf::is_installed() {
+            eprintln!("🖨️ {} is not manylinux/musllinux compliant because it requires copying the following libraries:", wheel_path.display());
+            eprint_external_libs(ext_libs.iter().flatten());
+            bail!(
+                "auditwheel repair needs patchelf to copy these libraries into the wheel. \
+                Install it with `pip install maturin[patchelf]` (or just `pip install patchelf`)."
+            );
+        }
+        patchelf::verify_patchelf()?;
+        let libs_dir = PathBuf::from(format!("{name}.libs"));
+        let artifact_paths: Vec<_> = artifacts.iter().map(|a| unpacked.join(a)).collect();
+        let artifact_dirs: Vec<_> = artifacts
+            .iter()
+            .map(|a| a.parent().unwrap_or(Path::new("")))
+            .collect();
+        let artifacts: Vec<_> = artifact_paths
+            .iter()
+            .map(PathBuf::as_path)
+            .zip(artifact_dirs)
+            .collect();
+        let libs_temp_dir = tempfile::tempdir()?;
+        let mut writer = PathWriter::from_path(&unpacked);
+        graft_external_libs(
+            &mut writer,
+            libs_temp_dir.path(),
+            &libs_dir,
+            &artifacts,
+            &ext_libs,
+        )?;
+    }
+
+    let arch = target.get_platform_arch()?;
+    let platform = std::iter::once(platform_tag.to_string())
+        .chain(platform_tag.aliases())
+        .map(|tag| format!("{tag}_{arch}"))
+        .collect::<Vec<_>>()
+        .join(".");
+    let wheel_file = unpacked.join(&dist_info_dir).join("WHEEL");
+    let wheel_contents = fs::read_to_string(&wheel_file)?;
+    fs::write(&wheel_file, retag_wheel_file(&wheel_contents, &platform))?;
+
+    let mut repaired_name = parts[..parts.len() - 1].to_vec();
+    repaired_name.push(platform);
+    fs::create_dir_all(out_dir)?;
+    let repaired = out_dir.join(format!("{}.whl", repaired_name.join("-")));
+    zip_dir(&unpacked, &repaired, &entries)?;
+    rebuild_record(&repaired)?;
+    Ok(repaired)
+}
+
+/// Replaces the platform of all `Tag` lines in a WHEEL file, `platform` may be a compressed
+/// tag set such as `manylinux_2_17_x86_64.manylinux2014_x86_64`
+fn retag_wheel_file(contents: &str, platform: &str) -> String {
+    let mut out = String::new();
+    let mut retagged = HashSet::new();
+    for line in contents.lines() {
+        match line
+            .strip_prefix("Tag: ")
+            .and_then(|tag| tag.rsplit_once('-'))
+        {
+            Some((python_abi, _)) => {
+                if !retagged.insert(python_abi) {
+                    continue;
+                }
+                for platform in platform.split('.') {
+                    out.push_str(&format!("Tag: {python_abi}-{platform}\n"));
+                }
+            }
+            None => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// The linux target an ELF file was compiled for, from its machine and the libc it links
+fn elf_target(path: &Path) -> Result<Target> {
+    let content = fs::read(path)?;
+    let elf = Elf::parse(&content)
+        .with_context(|| format!("Failed to parse {} as ELF file", path.display()))?;
+    let musl = elf.libraries.iter().any(|lib| lib.starts_with("libc.musl"));
+    let little_endian = elf.header.e_ident[EI_DATA] == ELFDATA2LSB;
+    let triple = match (elf.header.e_machine, musl) {
+        (EM_X86_64, false) => "x86_64-unknown-linux-gnu",
+        (EM_X86_64, true) => "x86_64-unknown-linux-musl",
+        (EM_386, false) => "i686-unknown-linux-gnu",
+        (EM_386, true) => "i686-unknown-linux-musl",
+        (EM_AARCH64, false) => "aarch64-unknown-linux-gnu",
+        (EM_AARCH64, true) => "aarch64-unknown-linux-musl",
+        (EM_ARM, false) => "armv7-unknown-linux-gnueabihf",
+        (EM_ARM, true) => "armv7-unknown-linux-musleabihf",
+        (EM_PPC64, _) if little_endian => "powerpc64le-unknown-linux-gnu",
+        (EM_PPC64, _) => "powerpc64-unknown-linux-gnu",
+        (EM_S390, _) => "s390x-unknown-linux-gnu",
+        (EM_RISCV, _) => "riscv64gc-unknown-linux-gnu",
+        (machine, _) => bail!(
+            "{} has an unsupported architecture ({})",
+            path.display(),
+            machine_to_str(machine)
+        ),
+    };
+    Target::from_target_triple(Some(triple.to_string()))
+}
+
+fn is_elf(path: &Path) -> Result<bool> {
+    let mut magic = [0; 4];
+    let mut file = File::open(path)?;
+    Ok(file.read_exact(&mut magic).is_ok() && magic == *b"\x7fELF")
+}
+
+/// Zips the contents of `dir` into a new wheel, keeping the unix permissions and the
+/// modification time and compression of the `entries` of the original wheel
+fn zip_dir(
+    dir: &Path,
+    wheel_path: &Path,
+    entries: &HashMap<String, (Option<DateTime>, CompressionMethod)>,
+) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(wheel_path)?);
+    let mut files = Vec::new();
+    for entry in WalkBuilder::new(dir).standard_filters(false).build() {
+        let entry = entry?;
+        if entry.path().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+    // Keep the .dist-info directory at the end, as recommended by the wheel specification
+    files.sort_by_key(|path| {
+        let relative = path.strip_prefix(dir).unwrap().to_path_buf();
+        let is_dist_info = relative
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .is_some_and(|c| c.ends_with(".dist-info"));
+        (is_dist_info, relative)
+    });
+    for path in files {
+        let relative = path.strip_prefix(dir)?;
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            fs::metadata(&path)?.permissions().mode()
+        };
+        #[cfg(not(unix))]
+        let mode = 0o644;
+        let mut options = SimpleFileOptions::default().unix_permissions(mode);
+        let name = relative.to_str().unwrap().replace('\\', "/");
+        if let Some((last_modified, compression)) = entries.get(&name) {
+            options = options.compression_method(*compression);
+            if let Some(last_modified) = last_modified {
+                options = options.last_modified_time(*last_modified);
+            }
+        }
+        zip.start_file(name, options)?;
+        zip.write_all(&fs::read(&path)?)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{elf_target, retag_wheel_file, zip_dir};
+    use crate::target::Arch;
+    use fs_err as fs;
+    use fs_err::File;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use zip::{CompressionMethod, DateTime, ZipArchive};
+
+    /// A 64-bit little endian ELF header of a shared library for `machine`
+    fn elf_header(machine: u16) -> Vec<u8> {
+        let mut header = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+        header.resize(16, 0);
+        header.extend(3u16.to_le_bytes()); // e_type: ET_DYN
+        header.extend(machine.to_le_bytes());
+        header.extend(1u32.to_le_bytes()); // e_version
+        header.extend([0; 24]); // e_entry, e_phoff, e_shoff
+        header.extend(0u32.to_le_bytes()); // e_flags
+        header.extend(64u16.to_le_bytes()); // e_ehsize
+        header.extend(56u16.to_le_bytes()); // e_phentsize
+        header.extend(0u16.to_le_bytes()); // e_phnum
+        header.extend(64u16.to_le_bytes()); // e_shentsize
+        header.extend([0; 4]); // e_shnum, e_shstrndx
+        header
+    }
+
+    #[test]
+    fn test_elf_target() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("lib.so");
+        fs::write(&path, elf_header(goblin::elf::header::EM_AARCH64)).unwrap();
+        let target = elf_target(&path).unwrap();
+        assert_eq!(target.target_arch(), Arch::Aarch64);
+        assert_eq!(target.get_platform_arch().unwrap(), "aarch64");
+        fs::write(&path, elf_header(goblin::elf::header::EM_X86_64)).unwrap();
+        assert_eq!(elf_target(&path).unwrap().target_arch(), Arch::X86_64);
+        fs::write(&path, elf_header(goblin::elf::header::EM_MIPS)).unwrap();
+        assert!(elf_target(&path).is_err());
+    }
+
+    #[test]
+    fn test_zip_dir_keeps_entries() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path().join("wheel");
+        fs::create_dir_all(dir.join("foo")).unwrap();
+        fs::write(dir.join("foo/__init__.py"), "").unwrap();
+        fs::write(dir.join("foo/new.so"), "").unwrap();
+        let last_modified = DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap();
+        let entries = HashMap::from([(
+            "foo/__init__.py".to_string(),
+            (Some(last_modified), CompressionMethod::Stored),
+        )]);
+        let wheel_path = tmp_dir.path().join("foo.whl");
+        zip_dir(&dir, &wheel_path, &entries).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&wheel_path).unwrap()).unwrap();
+        let kept = archive.by_name("foo/__init__.py").unwrap();
+        assert_eq!(kept.compression(), CompressionMethod::Stored);
+        assert_eq!(kept.last_modified(), Some(last_modified));
+        drop(kept);
+        let added = archive.by_name("foo/new.so").unwrap();
+        assert_eq!(added.compression(), CompressionMethod::Deflated);
+    }
+
+    #[test]
+    fn test_retag_wheel_file() {
+        let contents = "Wheel-Version: 1.0\nGenerator: maturin\nRoot-Is-Purelib: false\nTag: cp39-abi3-linux_x86_64\nTag: cp39-abi3-linux_i686\n";
+        assert_eq!(
+            retag_wheel_file(contents, "manylinux_2_17_x86_64.manylinux2014_x86_64"),
+            "Wheel-Version: 1.0\nGenerator: maturin\nRoot-Is-Purelib: false\n\
+            Tag: cp39-abi3-manylinux_2_17_x86_64\nTag: cp39-abi3-manylinux2014_x86_64\n"
+        );
+    }
+}
@@ -1,17 +1,25 @@
-use crate::auditwheel::{get_policy_and_libs, patchelf, relpath, AuditWheelMode};
+use crate::auditwheel::{
+    check_external_libs, eprint_external_libs, get_policy_and_libs, graft_external_libs, patchelf,
+    AuditWheelMode,
+};
 use crate::auditwheel::{PlatformTag, Policy};
 use crate::build_options::CargoOptions;
-use crate::compile::{warn_missing_py_init, CompileTarget};
+use crate::compile::{
+    debug_info_path, split_debug_info, warn_missing_py_init, CompileTarget, SplitDebuginfo,
+};
 use crate::module_writer::{
-    add_data, write_bin, write_bindings_module, write_cffi_module, write_python_part,
-    write_uniffi_module, write_wasm_launcher, WheelWriter,
+    add_data, first_wheel_difference, write_bin, write_bindings_module, write_cffi_module,
+    write_python_part, write_uniffi_module, write_wasm_launcher, write_workspace_member_module,
+    ExtraHash, ModuleWriter, WheelWriter,
 };
 use crate::project_layout::ProjectLayout;
+use crate::sbom::cyclonedx_sbom;
 use crate::source_distribution::source_distribution;
 use crate::target::{Arch, Os};
 use crate::{
-    compile, pyproject_toml::Format, BridgeModel, BuildArtifact, Metadata24, ModuleWriter,
-    PyProjectToml, PythonInterpreter, Target,
+    compile,
+    pyproject_toml::{Format, IncludeReadme},
+    BridgeModel, BuildArtifact, Metadata24, PyProjectToml, PythonInterpreter, Target,
 };
 use anyhow::{anyhow, bail, Context, Result};
 use cargo_metadata::CrateType;
@@ -24,10 +32,12 @@ use normpath::PathExt;
 use pep508_rs::Requirement;
 use platform_info::*;
 use sha2::{Digest, Sha256};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::HashSet;
 use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use tracing::instrument;
 
@@ -82,6 +92,53 @@ fn bin_wasi_helper(
     Ok(metadata24)
 }
 
+/// Removes the `[project.scripts]` and `[project.gui-scripts]` entries that name one of the
+/// binaries, e.g. `my-tool = "my-rust-bin"`, instead of a python callable.
+///
+/// Returns pairs of script file name and binary file name so that the binary can be installed
+/// a second time under the name of the script
+fn take_bin_scripts(metadata24: &mut Metadata24, bin_names: &[&str]) -> Vec<(String, String)> {
+    let mut bin_scripts = Vec::new();
+    for scripts in [&mut metadata24.scripts, &mut metadata24.gui_scripts] {
+        scripts.retain(|name, target| {
+            let bin_name = bin_names.iter().find(|bin_name| {
+                **bin_name == target || bin_name.strip_suffix(".exe") == Some(target.as_str())
+            });
+            match bin_name {
+                Some(bin_name) => {
+                    // Keep the executable suffix of the platform, i.e. `.exe` on windows
+                    let suffix = bin_name.strip_prefix(target.as_str()).unwrap_or_default();
+                    bin_scripts.push((format!("{name}{suffix}"), bin_name.to_string()));
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+    bin_scripts
+}
+
+/// Another package of the cargo workspace that is built into the wheel as a subpackage of the
+/// main module, configured with `tool.maturin.workspace-members`
+#[derive(Debug, Clone)]
+pub struct WorkspaceMember {
+    /// The name of the native module, i.e. of the lib target
+    pub module_name: String,
+    /// The name of the package
+    pub crate_name: String,
+    /// The path to the Cargo.toml of the package
+    pub manifest_path: PathBuf,
+    /// The lib target to build
+    pub compile_target: CompileTarget,
+}
+
+/// A compiled [WorkspaceMember] and the external libraries it links
+struct BuiltWorkspaceMember {
+    module_name: String,
+    artifact: BuildArtifact,
+    ext_libs: Vec<Library>,
+}
+
 /// Contains all the metadata required to build the crate
 #[derive(Clone)]
 pub struct BuildContext {
@@ -89,6 +146,8 @@ pub struct BuildContext {
     pub target: Target,
     /// List of Cargo targets to compile
     pub compile_targets: Vec<CompileTarget>,
+    /// Other packages of the workspace to build into the wheel (experimental)
+    pub workspace_members: Vec<WorkspaceMember>,
     /// Whether this project is pure rust or rust mixed with python
     pub project_layout: ProjectLayout,
     /// The path to pyproject.toml. Required for the source distribution
@@ -107,6 +166,14 @@ pub struct BuildContext {
     pub manifest_path: PathBuf,
     /// Directory for all generated artifacts
     pub target_dir: PathBuf,
+    /// Directory for maturin's intermediate files, defaults to locations inside `target_dir`
+    pub build_dir: Option<PathBuf>,
+    /// Overrides the python tag derived from the interpreter, e.g. `py3`
+    pub python_tag: Option<String>,
+    /// Overrides the ABI tag derived from the interpreter
+    pub abi_tag: Option<String>,
+    /// Overrides the interpreter's `SOABI` for the generic tags of unknown implementations
+    pub soabi: Option<String>,
     /// The directory to store the built wheels in. Defaults to a new "wheels"
     /// directory in the project's target directory
     pub out: PathBuf,
@@ -114,6 +181,36 @@ pub struct BuildContext {
     pub release: bool,
     /// Strip the library for minimum file size
     pub strip: bool,
+    /// Include the sources of all registry and git dependencies in the source distribution
+    pub vendor_crates: bool,
+    /// Directory to save the debug info split off the stripped artifacts in
+    pub emit_symbols: Option<PathBuf>,
+    /// Path to write the CycloneDX SBOM of the compiled crates to
+    pub emit_sbom: Option<PathBuf>,
+    /// Embed the CycloneDX SBOM of the compiled crates in the `.dist-info` of the wheels
+    pub embed_sbom: bool,
+    /// The `-C split-debuginfo` mode for the extension build
+    pub split_debuginfo: Option<SplitDebuginfo>,
+    /// Key to sign the wheels with, producing a detached `<wheel>.asc` signature
+    pub sign: Option<String>,
+    /// The command template used to create the signatures
+    pub sign_command: Vec<String>,
+    /// Maximum size of a wheel in MB, larger wheels are an error
+    pub max_wheel_size: Option<u64>,
+    /// File to write the pyo3 config of the interpreter that is being built for to
+    pub interpreter_config_file: Option<PathBuf>,
+    /// Link `bin` bindings against the static libpython of `PYO3_CONFIG_FILE`
+    pub static_libpython: bool,
+    /// Additional environment variables for the cargo build
+    pub env: Vec<(String, String)>,
+    /// Program to wrap rustc with, passed to cargo as `RUSTC_WRAPPER`
+    pub rustc_wrapper: Option<PathBuf>,
+    /// Only audit instead of failing when auditwheel repair needs patchelf but it's missing
+    pub no_auditwheel_patchelf: bool,
+    /// Algorithms for the supplementary `RECORD.<algorithm>` files of the wheels
+    pub extra_hashes: Vec<ExtraHash>,
+    /// Continue building for the remaining interpreters when the build for one fails
+    pub keep_going: bool,
     /// Checking the linked libraries for manylinux/musllinux compliance
     pub auditwheel: AuditWheelMode,
     /// When compiling for manylinux, use zig as linker to ensure glibc version compliance
@@ -139,6 +236,47 @@ pub struct BuildContext {
 /// they bind against (e.g. `cp37`).
 pub type BuiltWheelMetadata = (PathBuf, String);
 
+/// Returned by [BuildContext::build_wheels] with `--keep-going` when the build failed for some
+/// of the interpreters, while the wheels for the other interpreters were built nonetheless
+#[derive(Debug)]
+pub struct KeepGoingError {
+    /// The wheels that were built successfully
+    pub built: Vec<BuiltWheelMetadata>,
+    /// The interpreters for which the build failed, with the reason
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+impl KeepGoingError {
+    /// Returns the built wheels if nothing failed
+    fn into_result(
+        built: Vec<BuiltWheelMetadata>,
+        failed: Vec<(String, anyhow::Error)>,
+    ) -> Result<Vec<BuiltWheelMetadata>> {
+        if failed.is_empty() {
+            Ok(built)
+        } else {
+            Err(KeepGoingError { built, failed }.into())
+        }
+    }
+}
+
+impl fmt::Display for KeepGoingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Failed to build wheels for {} interpreter(s), {} wheel(s) were built successfully",
+            self.failed.len(),
+            self.built.len()
+        )?;
+        for (interpreter, err) in &self.failed {
+            writeln!(f, "  - {interpreter}: {err:#}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for KeepGoingError {}
+
 impl BuildContext {
     /// Checks which kind of bindings we have (pyo3/rust-cypthon or cffi or bin) and calls the
     /// correct builder.
@@ -146,13 +284,10 @@ impl BuildContext {
     pub fn build_wheels(&self) -> Result<Vec<BuiltWheelMetadata>> {
         use itertools::Itertools;
 
-        fs::create_dir_all(&self.out)
-            .context("Failed to create the target directory for the wheels")?;
-
         let wheels = match self.bridge() {
-            BridgeModel::Bin(None) => self.build_bin_wheel(None)?,
-            BridgeModel::Bin(Some(..)) => self.build_bin_wheels(&self.interpreter)?,
-            BridgeModel::Bindings { .. } => self.build_binding_wheels(&self.interpreter)?,
+            BridgeModel::Bin(None) => self.build_bin_wheel(None),
+            BridgeModel::Bin(Some(..)) => self.build_bin_wheels(&self.interpreter),
+            BridgeModel::Bindings { .. } => self.build_binding_wheels(&self.interpreter),
             BridgeModel::BindingsAbi3 { major, minor, .. } => {
                 let abi3_interps: Vec<_> = self
                     .interpreter
@@ -167,12 +302,19 @@ impl BuildContext {
                     .cloned()
                     .collect();
                 let mut built_wheels = Vec::new();
+                let mut failed = Vec::new();
                 if !abi3_interps.is_empty() {
-                    built_wheels.extend(self.build_binding_wheel_abi3(
-                        &abi3_interps,
-                        *major,
-                        *minor,
-                    )?);
+                    match self.build_binding_wheel_abi3(&abi3_interps, *major, *minor) {
+                        Ok(wheels) => built_wheels.extend(wheels),
+                        Err(err) if self.keep_going => {
+                            eprintln!(
+                                "💥 Failed to build the abi3 wheel, continuing with `--keep-going`"
+                            );
+                            failed
+                                .push((format!("abi3 ({})", abi3_interps.iter().join(", ")), err));
+                        }
+                        Err(err) => return Err(err),
+                    }
                 }
                 if !non_abi3_interps.is_empty() {
                     let interp_names: HashSet<_> = non_abi3_interps
@@ -183,15 +325,29 @@ impl BuildContext {
                         "⚠️ Warning: {} does not yet support abi3 so the build artifacts will be version-specific.",
                         interp_names.iter().join(", ")
                     );
-                    built_wheels.extend(self.build_binding_wheels(&non_abi3_interps)?);
+                    match self.build_binding_wheels(&non_abi3_interps) {
+                        Ok(wheels) => built_wheels.extend(wheels),
+                        Err(err) => {
+                            let err = err.downcast::<KeepGoingError>()?;
+                            built_wheels.extend(err.built);
+                            failed.extend(err.failed);
+                        }
+                    }
                 }
-                built_wheels
+                KeepGoingError::into_result(built_wheels, failed)
             }
-            BridgeModel::Cffi => self.build_cffi_wheel()?,
-            BridgeModel::UniFfi => self.build_uniffi_wheel()?,
+            BridgeModel::Cffi => self.build_cffi_wheel(),
+            BridgeModel::UniFfi => self.build_uniffi_wheel(),
         };
+        // With `--keep-going`, the SBOM is still written for the wheels that were built
+        if wheels
+            .as_ref()
+            .map_or_else(|err| err.is::<KeepGoingError>(), |_| true)
+        {
+            self.write_sbom()?;
+        }
 
-        Ok(wheels)
+        wheels
     }
 
     /// Bridge model
@@ -200,15 +356,72 @@ impl BuildContext {
         &self.compile_targets[0].bridge_model
     }
 
+    /// Whether the wheel should be marked as `Root-Is-Purelib: true` in the WHEEL file.
+    ///
+    /// This is opt-in through `[tool.maturin.purelib]` and only allowed for cffi wheels
+    /// without a native shared library, i.e. wheels for wasi that are tagged `py3-none-any`
+    pub fn root_is_purelib(&self) -> Result<bool> {
+        let purelib = self
+            .pyproject_toml
+            .as_ref()
+            .is_some_and(|pyproject| pyproject.purelib());
+        if !purelib {
+            return Ok(false);
+        }
+        if !matches!(self.bridge(), BridgeModel::Cffi) {
+            bail!(
+                "`tool.maturin.purelib` is only supported with cffi bindings, not with {}",
+                self.bridge()
+            );
+        }
+        if !self.target.is_wasi() {
+            bail!(
+                "`tool.maturin.purelib` is set, but the wheel for {} contains a platform specific \
+                shared library. Purelib wheels must be platform independent, which cffi wheels \
+                only are for wasi",
+                self.target.target_triple()
+            );
+        }
+        Ok(true)
+    }
+
     /// Builds a source distribution and returns the same metadata as [BuildContext::build_wheels]
     pub fn build_source_distribution(&self) -> Result<Option<BuiltWheelMetadata>> {
-        fs::create_dir_all(&self.out)
+        let out = expand_out_dir(
+            &self.out,
+            "the source distribution, use `--sdist-out` instead",
+            |name| (name == "version").then(|| self.metadata24.get_version_escaped()),
+        )?;
+        self.build_source_distribution_in(&out)
+    }
+
+    /// The directory to write the wheel with the given tag to, i.e. `--out` with the placeholders
+    /// replaced, which is created if it doesn't exist
+    fn wheel_dir(&self, tag: &str) -> Result<PathBuf> {
+        let mut tags = tag.splitn(3, '-');
+        let (python_tag, abi_tag, platform_tag) = (tags.next(), tags.next(), tags.next());
+        let wheel_dir = expand_out_dir(&self.out, "wheels", |name| match name {
+            "python_tag" => python_tag.map(str::to_string),
+            "abi_tag" => abi_tag.map(str::to_string),
+            "platform_tag" => platform_tag.map(str::to_string),
+            "version" => Some(self.metadata24.get_version_escaped()),
+            _ => None,
+        })?;
+        fs::create_dir_all(&wheel_dir)
+            .context("Failed to create the target directory for the wheels")?;
+        Ok(wheel_dir)
+    }
+
+    /// Like [BuildContext::build_source_distribution], but writes the source distribution to
+    /// `out` instead of the directory of the wheels
+    pub fn build_source_distribution_in(&self, out: &Path) -> Result<Option<BuiltWheelMetadata>> {
+        fs::create_dir_all(out)
             .context("Failed to create the target directory for the source distribution")?;
 
         match self.pyproject_toml.as_ref() {
             Some(pyproject) => {
                 let sdist_path =
-                    source_distribution(self, pyproject, self.excludes(Format::Sdist)?)
+                    source_distribution(self, pyproject, self.sdist_excludes(out)?, out)
                         .context("Failed to build source distribution")?;
                 Ok(Some((sdist_path, "source".to_string())))
             }
@@ -253,17 +466,96 @@ impl BuildContext {
 
         // only bin bindings allow linking to libpython, extension modules must not
         let allow_linking_libpython = self.bridge().is_bin();
-        if self.bridge().is_bin() && !musllinux.is_empty() {
-            return get_policy_and_libs(
+        let allowed_libs = self
+            .pyproject_toml
+            .as_ref()
+            .and_then(|x| x.auditwheel_allowed_libs())
+            .unwrap_or_default();
+        let (policy, libs) = if self.bridge().is_bin() && !musllinux.is_empty() {
+            get_policy_and_libs(
                 artifact,
                 Some(musllinux[0]),
                 &self.target,
                 allow_linking_libpython,
-            );
+                allowed_libs,
+            )?
+        } else {
+            let tag = others.first().or_else(|| musllinux.first()).copied();
+            if tag == Some(PlatformTag::Linux) && self.target.is_linux() {
+                eprintln!("🐍 Skipping auditwheel because the platform tag is `linux`");
+            }
+            get_policy_and_libs(
+                artifact,
+                tag,
+                &self.target,
+                allow_linking_libpython,
+                allowed_libs,
+            )?
+        };
+        if self.no_auditwheel_patchelf
+            && matches!(self.auditwheel, AuditWheelMode::Repair)
+            && !libs.is_empty()
+        {
+            return audit_without_patchelf(policy, libs, platform_tag, patchelf::is_installed());
+        }
+        // Fail before the wheel gets written, so that check mode never leaves a wheel behind.
+        // The binaries of a wheel are checked together in `build_bin_wheel` to list the
+        // libraries of all of them, like repair mode does
+        if matches!(self.auditwheel, AuditWheelMode::Check)
+            && !self.editable
+            && !self.bridge().is_bin()
+        {
+            check_external_libs(std::slice::from_ref(&libs))?;
+        }
+        Ok((policy, libs))
+    }
+
+    /// Saves the debug info split off the artifacts of a wheel into the `--emit-symbols`
+    /// directory, in a subdirectory named after the wheel
+    fn emit_symbols(&self, artifacts: &[&BuildArtifact], wheel_path: &Path) -> Result<()> {
+        let Some(symbols_dir) = &self.emit_symbols else {
+            return Ok(());
+        };
+        if self.editable {
+            return Ok(());
+        }
+        let wheel_symbols_dir = symbols_dir.join(wheel_path.file_stem().unwrap());
+        fs::create_dir_all(&wheel_symbols_dir)?;
+        for artifact in artifacts {
+            let debug_info = debug_info_path(&self.target, &artifact.path);
+            let dest = wheel_symbols_dir.join(debug_info.file_name().unwrap());
+            fs::copy(&debug_info, &dest).with_context(|| {
+                format!(
+                    "Failed to copy {} to {}",
+                    debug_info.display(),
+                    dest.display()
+                )
+            })?;
+        }
+        eprintln!("🔍 Saved debug symbols to {}", wheel_symbols_dir.display());
+        Ok(())
+    }
+
+    /// Errors if the wheel is larger than `--max-wheel-size`, or warns if it's over PyPI's limit
+    fn check_wheel_size(&self, wheel_path: &Path) -> Result<()> {
+        if self.editable {
+            return Ok(());
         }
+        let size = fs::metadata(wheel_path)?.len();
+        check_wheel_size(wheel_path, size, self.max_wheel_size)
+    }
 
-        let tag = others.first().or_else(|| musllinux.first()).copied();
-        get_policy_and_libs(artifact, tag, &self.target, allow_linking_libpython)
+    /// Creates a detached signature of the wheel if `--sign` was given
+    fn sign_wheel(&self, wheel_path: &Path) -> Result<()> {
+        let Some(key) = &self.sign else {
+            return Ok(());
+        };
+        if self.editable {
+            return Ok(());
+        }
+        let signature = sign_file(&self.sign_command, key, wheel_path)?;
+        eprintln!("🔏 Signed wheel to {}", signature.display());
+        Ok(())
     }
 
     /// Add library search paths in Cargo target directory rpath when building in editable mode
@@ -306,18 +598,15 @@ impl BuildContext {
             return Ok(());
         }
 
-        if matches!(self.auditwheel, AuditWheelMode::Check) {
+        if !patchelf::is_installed() {
             eprintln!("🖨️ Your library is not manylinux/musllinux compliant because it requires copying the following libraries:");
-            for lib in ext_libs.iter().flatten() {
-                if let Some(path) = lib.realpath.as_ref() {
-                    eprintln!("    {} => {}", lib.name, path.display())
-                } else {
-                    eprintln!("    {} => not found", lib.name)
-                };
-            }
-            bail!("Can not repair the wheel because `--auditwheel=check` is specified, re-run with `--auditwheel=repair` to copy the libraries.");
+            eprint_external_libs(ext_libs.iter().flatten());
+            bail!(
+                "auditwheel repair needs patchelf to copy these libraries into the wheel. \
+                Install it with `pip install maturin[patchelf]` (or just `pip install patchelf`), \
+                or pass `--no-auditwheel-patchelf` to build a `linux` tagged wheel without them."
+            );
         }
-
         patchelf::verify_patchelf()?;
 
         // Put external libs to ${module_name}.libs directory
@@ -330,123 +619,249 @@ impl BuildContext {
             .unwrap_or_else(|| self.module_name.clone().into());
         libs_dir.push(".libs");
         let libs_dir = PathBuf::from(libs_dir);
-        writer.add_directory(&libs_dir)?;
 
-        let temp_dir = tempfile::tempdir()?;
-        let mut soname_map = BTreeMap::new();
-        let mut libs_copied = HashSet::new();
-        for lib in ext_libs.iter().flatten() {
-            let lib_path = lib.realpath.clone().with_context(|| {
-                format!(
-                    "Cannot repair wheel, because required library {} could not be located.",
-                    lib.path.display()
-                )
-            })?;
-            // Generate a new soname with a short hash
-            let short_hash = &hash_file(&lib_path)?[..8];
-            let (file_stem, file_ext) = lib.name.split_once('.').unwrap();
-            let new_soname = if !file_stem.ends_with(&format!("-{short_hash}")) {
-                format!("{file_stem}-{short_hash}.{file_ext}")
-            } else {
-                format!("{file_stem}.{file_ext}")
-            };
+        let artifact_dir = match self.bridge() {
+            // cffi bindings that contains '.' in the module name will be split into directories
+            BridgeModel::Cffi => self.module_name.split(".").collect::<PathBuf>(),
+            // For other bindings artifact .so file usually resides at ${module_name}/${module_name}.so
+            _ => PathBuf::from(&self.module_name),
+        };
+        let artifacts: Vec<_> = artifacts
+            .iter()
+            .map(|artifact| (artifact.path.as_path(), artifact_dir.as_path()))
+            .collect();
+        let temp_dir = self.temp_dir()?;
+        graft_external_libs(writer, temp_dir.path(), &libs_dir, &artifacts, ext_libs)
+    }
 
-            // Copy the original lib to a tmpdir and modify some of its properties
-            // for example soname and rpath
-            let dest_path = temp_dir.path().join(&new_soname);
-            fs::copy(&lib_path, &dest_path)?;
-            libs_copied.insert(lib_path);
-
-            // fs::copy copies permissions as well, and the original
-            // file may have been read-only
-            let mut perms = fs::metadata(&dest_path)?.permissions();
-            #[allow(clippy::permissions_set_readonly_false)]
-            perms.set_readonly(false);
-            fs::set_permissions(&dest_path, perms)?;
-
-            patchelf::set_soname(&dest_path, &new_soname)?;
-            if !lib.rpath.is_empty() || !lib.runpath.is_empty() {
-                patchelf::set_rpath(&dest_path, &libs_dir)?;
-            }
-            soname_map.insert(
-                lib.name.clone(),
-                (new_soname.clone(), dest_path.clone(), lib.needed.clone()),
+    /// Builds the wheels a second time and fails if they aren't byte-identical to `wheels`
+    ///
+    /// The crate itself is cleaned before the second pass so that it is recompiled instead of
+    /// reusing the cached artifacts, dependencies are reused. Timestamps come from
+    /// `SOURCE_DATE_EPOCH`, which therefore must be set.
+    pub fn check_reproducible(&self, wheels: &[BuiltWheelMetadata]) -> Result<()> {
+        if env::var_os("SOURCE_DATE_EPOCH").is_none() {
+            bail!(
+                "--require-reproducible needs SOURCE_DATE_EPOCH to be set, \
+                otherwise the wheels contain the time of the build"
             );
         }
+        let first_pass_dir = self.maturin_build_dir().join("reproducible");
+        if first_pass_dir.exists() {
+            fs::remove_dir_all(&first_pass_dir)?;
+        }
+        fs::create_dir_all(&first_pass_dir)?;
+        let mut first_pass = Vec::new();
+        for (wheel, _) in wheels {
+            let copy = first_pass_dir.join(wheel.file_name().unwrap());
+            fs::copy(wheel, &copy)?;
+            first_pass.push((wheel, copy));
+        }
 
-        for (artifact, artifact_ext_libs) in artifacts.iter().zip(ext_libs) {
-            let artifact_deps: HashSet<_> = artifact_ext_libs.iter().map(|lib| &lib.name).collect();
-            let replacements = soname_map
-                .iter()
-                .filter_map(|(k, v)| {
-                    if artifact_deps.contains(k) {
-                        Some((k, v.0.clone()))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-            if !replacements.is_empty() {
-                patchelf::replace_needed(&artifact.path, &replacements[..])?;
-            }
+        let package = self
+            .cargo_metadata
+            .packages
+            .iter()
+            .find(|package| package.manifest_path == self.manifest_path)
+            .map_or(self.crate_name.as_str(), |package| package.name.as_str());
+        let mut clean = std::process::Command::new("cargo");
+        clean
+            .args(["clean", "--quiet", "-p", package, "--manifest-path"])
+            .arg(&self.manifest_path)
+            .arg("--target-dir")
+            .arg(&self.target_dir);
+        if let Some(target) = &self.cargo_options.target {
+            clean.args(["--target", target]);
+        }
+        // Clean the profile the build used, cargo clean only cleans `dev` by default
+        if let Some(profile) = &self.cargo_options.profile {
+            clean.args(["--profile", profile]);
+        } else if self.release {
+            clean.arg("--release");
+        }
+        let status = clean
+            .status()
+            .context("Failed to run cargo clean before the second build")?;
+        if !status.success() {
+            bail!("cargo clean failed with {status}");
         }
 
-        // we grafted in a bunch of libraries and modified their sonames, but
-        // they may have internal dependencies (DT_NEEDED) on one another, so
-        // we need to update those records so each now knows about the new
-        // name of the other.
-        for (new_soname, path, needed) in soname_map.values() {
-            let mut replacements = Vec::new();
-            for n in needed {
-                if soname_map.contains_key(n) {
-                    replacements.push((n, soname_map[n].0.clone()));
-                }
+        // The second pass must not sign the wheels again or overwrite the sbom and symbols of
+        // the first one, the symbols still need to be split off so the wheels are comparable
+        let mut second_context = self.clone();
+        second_context.sign = None;
+        second_context.emit_sbom = None;
+        if second_context.emit_symbols.is_some() {
+            second_context.emit_symbols = Some(first_pass_dir.join("symbols"));
+        }
+        eprintln!("🔁 Building a second time to check reproducibility");
+        // With `--keep-going`, the same interpreters fail again and only the wheels built in
+        // the first pass are compared
+        let second_pass = match second_context.build_wheels() {
+            Ok(wheels) => wheels,
+            Err(err) => err.downcast::<KeepGoingError>()?.built,
+        };
+        for (wheel, copy) in first_pass {
+            if !second_pass.iter().any(|(path, _)| path == wheel) {
+                bail!("The second build didn't produce {}", wheel.display());
             }
-            if !replacements.is_empty() {
-                patchelf::replace_needed(path, &replacements[..])?;
+            if let Some(difference) = first_wheel_difference(&copy, wheel)? {
+                bail!(
+                    "{} is not reproducible: {}. The first build is kept at {}",
+                    wheel.display(),
+                    difference,
+                    copy.display()
+                );
             }
-            writer.add_file_with_permissions(libs_dir.join(new_soname), path, 0o755)?;
         }
+        fs::remove_dir_all(&first_pass_dir)?;
+        eprintln!("🔁 All wheels are byte-identical across two builds");
+        Ok(())
+    }
+
+    /// The directory for maturin's intermediate files, `--build-dir` or `<target_dir>/maturin`,
+    /// with a subdirectory per target so that builds for different targets don't overwrite
+    /// each other's files
+    pub(crate) fn maturin_build_dir(&self) -> PathBuf {
+        self.build_dir
+            .clone()
+            .unwrap_or_else(|| self.target_dir.join("maturin"))
+            .join(self.target.target_triple())
+    }
+
+    /// Creates a temporary directory, inside `--build-dir` if one was given
+    pub(crate) fn temp_dir(&self) -> Result<tempfile::TempDir> {
+        if self.build_dir.is_none() {
+            return Ok(tempfile::tempdir()?);
+        }
+        let build_dir = self.maturin_build_dir();
+        fs::create_dir_all(&build_dir)?;
+        Ok(tempfile::tempdir_in(build_dir)?)
+    }
 
-        eprintln!(
-            "🖨  Copied external shared libraries to package {} directory:",
-            libs_dir.display()
+    /// Copies a compiled artifact into the build directory, so that auditwheel repair and
+    /// splitting off the debug info don't edit cargo's output, which would fail on reruns
+    fn copy_artifact(&self, artifact_path: &Path) -> Result<PathBuf> {
+        // The profile directory (e.g. `release`) keeps the copies of different profiles apart
+        let maturin_build = self.maturin_build_dir().join(
+            artifact_path
+                .parent()
+                .unwrap()
+                .file_name()
+                .unwrap_or_default(),
         );
-        for lib_path in libs_copied {
-            eprintln!("    {}", lib_path.display());
+        fs::create_dir_all(&maturin_build)?;
+        let new_artifact_path = maturin_build.join(artifact_path.file_name().unwrap());
+        fs::copy(artifact_path, &new_artifact_path)?;
+        Ok(new_artifact_path)
+    }
+
+    fn add_pth(&self, writer: &mut WheelWriter) -> Result<()> {
+        if self.editable {
+            writer.add_pth(&self.project_layout, &self.metadata24)?;
         }
+        Ok(())
+    }
 
-        let artifact_dir = match self.bridge() {
-            // cffi bindings that contains '.' in the module name will be split into directories
-            BridgeModel::Cffi => self.module_name.split(".").collect::<PathBuf>(),
-            // For other bindings artifact .so file usually resides at ${module_name}/${module_name}.so
-            _ => PathBuf::from(&self.module_name),
+    /// Writes the `--emit-sbom` file
+    fn write_sbom(&self) -> Result<()> {
+        let Some(path) = &self.emit_sbom else {
+            return Ok(());
         };
-        for artifact in artifacts {
-            let mut new_rpaths = patchelf::get_rpath(&artifact.path)?;
-            // TODO: clean existing rpath entries if it's not pointed to a location within the wheel
-            // See https://github.com/pypa/auditwheel/blob/353c24250d66951d5ac7e60b97471a6da76c123f/src/auditwheel/repair.py#L160
-            let new_rpath = Path::new("$ORIGIN").join(relpath(&libs_dir, &artifact_dir));
-            new_rpaths.push(new_rpath.to_str().unwrap().to_string());
-            let new_rpath = new_rpaths.join(":");
-            patchelf::set_rpath(&artifact.path, &new_rpath)?;
+        let sbom = cyclonedx_sbom(&self.cargo_metadata, &self.manifest_path)?;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
         }
+        fs::write(path, sbom)?;
+        eprintln!("📋 Wrote the SBOM to {}", path.display());
         Ok(())
     }
 
-    fn add_pth(&self, writer: &mut WheelWriter) -> Result<()> {
-        if self.editable {
-            writer.add_pth(&self.project_layout, &self.metadata24)?;
+    /// Adds the SBOM to `.dist-info/sboms` with `--embed-sbom`, as proposed by PEP 770
+    fn add_sbom(&self, writer: &mut WheelWriter) -> Result<()> {
+        if !self.embed_sbom || self.editable {
+            return Ok(());
+        }
+        let sbom = cyclonedx_sbom(&self.cargo_metadata, &self.manifest_path)?;
+        let sboms_dir = self.metadata24.get_dist_info_dir().join("sboms");
+        writer.add_directory(&sboms_dir)?;
+        writer.add_bytes(
+            sboms_dir.join(format!("{}.cdx.json", self.crate_name)),
+            None,
+            sbom.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Adds the readme to the wheel with `tool.maturin.include-readme-in-wheel`
+    fn add_readme(&self, writer: &mut WheelWriter) -> Result<()> {
+        let Some(config) = self
+            .pyproject_toml
+            .as_ref()
+            .and_then(|x| x.include_readme_in_wheel())
+        else {
+            return Ok(());
+        };
+        // Editable installs use the python source directory instead
+        if self.editable && self.project_layout.python_module.is_some() {
+            return Ok(());
         }
+        let Some(path) = readme_wheel_path(
+            config,
+            &self.module_name,
+            self.bridge().is_bin(),
+            self.metadata24.description_content_type.as_deref(),
+        )?
+        else {
+            return Ok(());
+        };
+        let Some(readme) = &self.metadata24.description else {
+            eprintln!(
+                "⚠️  Warning: `include-readme-in-wheel` is set, but the project has no readme"
+            );
+            return Ok(());
+        };
+        writer.add_bytes(path, None, readme.as_bytes())?;
         Ok(())
     }
 
     fn excludes(&self, format: Format) -> Result<Override> {
-        let project_dir = match self.pyproject_toml_path.normalize() {
+        Ok(self
+            .excludes_builder(&self.project_dir()?, format)?
+            .build()?)
+    }
+
+    /// The excludes of the source distribution, which also ignore the source distributions in
+    /// `--out` and in the directory it's written to (`out`) so that we don't include them
+    fn sdist_excludes(&self, out: &Path) -> Result<Override> {
+        let project_dir = self.project_dir()?;
+        let mut excludes = self.excludes_builder(&project_dir, Format::Sdist)?;
+        for out_dir in [self.out.as_path(), out] {
+            if let Some(out_glob) = out_dir_glob(&project_dir, out_dir)? {
+                excludes.add(&format!(
+                    "{out_glob}/{}-*.tar.gz",
+                    self.metadata24.get_distribution_escaped()
+                ))?;
+            }
+        }
+        Ok(excludes.build()?)
+    }
+
+    /// The directory of pyproject.toml, or of Cargo.toml if there is none, which the globs in
+    /// pyproject.toml are relative to
+    fn project_dir(&self) -> Result<PathBuf> {
+        let project_file = match self.pyproject_toml_path.normalize() {
             Ok(pyproject_toml_path) => pyproject_toml_path.into_path_buf(),
             Err(_) => self.manifest_path.normalize()?.into_path_buf(),
         };
-        let mut excludes = OverrideBuilder::new(project_dir.parent().unwrap());
+        Ok(project_file.parent().unwrap().to_path_buf())
+    }
+
+    fn excludes_builder(&self, project_dir: &Path, format: Format) -> Result<OverrideBuilder> {
+        let mut excludes = OverrideBuilder::new(project_dir);
         if let Some(pyproject) = self.pyproject_toml.as_ref() {
             if let Some(glob_patterns) = &pyproject.exclude() {
                 for glob in glob_patterns
@@ -457,48 +872,58 @@ impl BuildContext {
                 }
             }
         }
-        // Ignore sdist output files so that we don't include them in the sdist
-        if matches!(format, Format::Sdist) {
-            let glob_pattern = format!(
-                "{}{}{}-*.tar.gz",
-                self.out.display(),
-                std::path::MAIN_SEPARATOR,
-                &self.metadata24.get_distribution_escaped(),
-            );
-            excludes.add(&glob_pattern)?;
+        Ok(excludes)
+    }
+
+    /// The files to store without compression in the wheel, from
+    /// `[tool.maturin.compression.store-globs]`
+    fn stored_files(&self) -> Result<Override> {
+        let mut stored = OverrideBuilder::new(self.project_dir()?);
+        if let Some(store_globs) = self.pyproject_toml.as_ref().and_then(|x| x.store_globs()) {
+            for glob in store_globs {
+                stored
+                    .add(glob)
+                    .with_context(|| format!("Invalid glob pattern in store-globs: {glob}"))?;
+            }
         }
-        Ok(excludes.build()?)
+        Ok(stored.build()?)
     }
 
     /// Returns the platform part of the tag for the wheel name
     pub fn get_platform_tag(&self, platform_tags: &[PlatformTag]) -> Result<String> {
+        self.get_interpreter_platform_tag(platform_tags, None)
+    }
+
+    /// Returns the platform part of the tag for a wheel built for `interpreter`
+    ///
+    /// On macOS the deployment target the interpreter was built for is used when
+    /// `MACOSX_DEPLOYMENT_TARGET` isn't set
+    pub(crate) fn get_interpreter_platform_tag(
+        &self,
+        platform_tags: &[PlatformTag],
+        interpreter: Option<&PythonInterpreter>,
+    ) -> Result<String> {
+        let target = &self.target;
         if let Ok(host_platform) = env::var("_PYTHON_HOST_PLATFORM") {
-            return Ok(host_platform.replace(['.', '-'], "_"));
+            match host_platform_tag(&host_platform, target, self.universal2) {
+                Some(tag) => return Ok(tag),
+                None => eprintln!(
+                    "⚠️  Warning: Ignoring `_PYTHON_HOST_PLATFORM={host_platform}` because it doesn't match the target {}",
+                    target.target_triple()
+                ),
+            }
         }
 
-        let target = &self.target;
         let tag = match (&target.target_os(), &target.target_arch()) {
             // Windows
             (Os::Windows, Arch::X86) => "win32".to_string(),
             (Os::Windows, Arch::X86_64) => "win_amd64".to_string(),
             (Os::Windows, Arch::Aarch64) => "win_arm64".to_string(),
             // Linux
-            (Os::Linux, _) => {
-                let arch = target.get_platform_arch()?;
-                let mut platform_tags = platform_tags.to_vec();
-                platform_tags.sort();
-                let mut tags = vec![];
-                for platform_tag in platform_tags {
-                    tags.push(format!("{platform_tag}_{arch}"));
-                    for alias in platform_tag.aliases() {
-                        tags.push(format!("{alias}_{arch}"));
-                    }
-                }
-                tags.join(".")
-            }
+            (Os::Linux, _) => linux_platform_tag(platform_tags, &target.get_platform_arch()?),
             // macOS
             (Os::Macos, Arch::X86_64) | (Os::Macos, Arch::Aarch64) => {
-                let ((x86_64_major, x86_64_minor), (arm64_major, arm64_minor)) = macosx_deployment_target(env::var("MACOSX_DEPLOYMENT_TARGET").ok().as_deref(), self.universal2)?;
+                let ((x86_64_major, x86_64_minor), (arm64_major, arm64_minor)) = macosx_deployment_target(env::var("MACOSX_DEPLOYMENT_TARGET").ok().or_else(|| interpreter.and_then(|interp| interp.macosx_deployment_target.clone())).as_deref(), self.universal2)?;
                 let x86_64_tag = if let Some(deployment_target) = self.pyproject_toml.as_ref().and_then(|x| x.target_config("x86_64-apple-darwin")).and_then(|config| config.macos_deployment_target.as_ref()) {
                     deployment_target.replace('.', "_")
                 } else {
@@ -519,6 +944,13 @@ impl BuildContext {
                     format!("macosx_{x86_64_tag}_x86_64")
                 }
             }
+            // iOS
+            (Os::Ios, Arch::Aarch64) | (Os::Ios, Arch::X86_64) => {
+                let simulator = target.target_triple().ends_with("-sim")
+                    || target.target_arch() == Arch::X86_64;
+                let deployment_target = env::var("IPHONEOS_DEPLOYMENT_TARGET").ok();
+                ios_platform_tag(deployment_target.as_deref(), target.target_arch(), simulator)?
+            }
             // FreeBSD
             (Os::FreeBsd, _)
             // NetBSD
@@ -547,12 +979,20 @@ impl BuildContext {
             }
             // Emscripten
             (Os::Emscripten, Arch::Wasm32) => {
-                let release = emscripten_version()?.replace(['.', '-'], "_");
-                format!("emscripten_{release}_wasm32")
+                let pyodide_abi_version = env::var("PYODIDE_ABI_VERSION").ok();
+                emscripten_platform_tag(pyodide_abi_version.as_deref(), emscripten_version)?
             }
             (Os::Wasi, Arch::Wasm32) => {
                 "any".to_string()
             }
+            // Redox is usually cross compiled, so we can't ask the host for the release
+            (Os::Redox, _) => {
+                format!("redox_{}", target.target_arch().machine())
+            }
+            // Unsupported os explicitly allowed with `--allow-unsupported-target`
+            (Os::Unknown, _) => {
+                format!("linux_{}", target.get_platform_arch()?)
+            }
             // osname_release_machine fallback for any POSIX system
             (_, _) => {
                 let info = PlatformInfo::new()
@@ -585,7 +1025,8 @@ impl BuildContext {
     /// Returns the tags for the WHEEL file for cffi wheels
     pub fn get_py3_tags(&self, platform_tags: &[PlatformTag]) -> Result<Vec<String>> {
         let tags = vec![format!(
-            "py3-none-{}",
+            "{}-none-{}",
+            self.python_tag.as_deref().unwrap_or("py3"),
             self.get_platform_tag(platform_tags)?
         )];
         Ok(tags)
@@ -597,7 +1038,8 @@ impl BuildContext {
         platform_tags: &[PlatformTag],
     ) -> Result<(String, Vec<String>)> {
         let tag = format!(
-            "py3-none-{platform}",
+            "{python_tag}-none-{platform}",
+            python_tag = self.python_tag.as_deref().unwrap_or("py3"),
             platform = self.get_platform_tag(platform_tags)?
         );
         let tags = self.get_py3_tags(platform_tags)?;
@@ -609,6 +1051,7 @@ impl BuildContext {
         artifact: BuildArtifact,
         platform_tags: &[PlatformTag],
         ext_libs: Vec<Library>,
+        members: &[BuiltWorkspaceMember],
         major: u8,
         min_minor: u8,
     ) -> Result<BuiltWheelMetadata> {
@@ -617,12 +1060,17 @@ impl BuildContext {
 
         let mut writer = WheelWriter::new(
             &tag,
-            &self.out,
+            &self.wheel_dir(&tag)?,
             &self.metadata24,
-            &[tag.clone()],
+            std::slice::from_ref(&tag),
+            false,
             self.excludes(Format::Wheel)?,
-        )?;
-        self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
+        )?
+        .with_jobs(self.cargo_options.jobs)
+        .with_stored(self.stored_files()?)
+        .with_extra_hashes(&self.extra_hashes);
+        let (artifacts, ext_libs) = with_workspace_members(&artifact, ext_libs, members);
+        self.add_external_libs(&mut writer, &artifacts, &ext_libs)?;
 
         write_bindings_module(
             &mut writer,
@@ -635,14 +1083,37 @@ impl BuildContext {
             self.pyproject_toml.as_ref(),
         )
         .context("Failed to add the files to the wheel")?;
+        for member in members {
+            write_workspace_member_module(
+                &mut writer,
+                &self.project_layout,
+                &member.module_name,
+                &member.artifact.path,
+                self.interpreter.first(),
+                true,
+                &self.target,
+                self.editable,
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to add the workspace member {} to the wheel",
+                    member.module_name
+                )
+            })?;
+        }
 
         self.add_pth(&mut writer)?;
+        self.add_sbom(&mut writer)?;
+        self.add_readme(&mut writer)?;
         add_data(
             &mut writer,
             &self.metadata24,
             self.project_layout.data.as_deref(),
         )?;
         let wheel_path = writer.finish()?;
+        self.emit_symbols(&artifacts, &wheel_path)?;
+        self.check_wheel_size(&wheel_path)?;
+        self.sign_wheel(&wheel_path)?;
         Ok((wheel_path, format!("cp{major}{min_minor}")))
     }
 
@@ -664,6 +1135,7 @@ impl BuildContext {
         )?;
         let (policy, external_libs) =
             self.auditwheel(&artifact, &self.platform_tag, python_interpreter)?;
+        let (policy, members) = self.build_workspace_members(python_interpreter, policy)?;
         let platform_tags = if self.platform_tag.is_empty() {
             vec![policy.platform_tag()]
         } else {
@@ -673,6 +1145,7 @@ impl BuildContext {
             artifact,
             &platform_tags,
             external_libs,
+            &members,
             major,
             min_minor,
         )?;
@@ -694,17 +1167,23 @@ impl BuildContext {
         artifact: BuildArtifact,
         platform_tags: &[PlatformTag],
         ext_libs: Vec<Library>,
+        members: &[BuiltWorkspaceMember],
     ) -> Result<BuiltWheelMetadata> {
         let tag = python_interpreter.get_tag(self, platform_tags)?;
 
         let mut writer = WheelWriter::new(
             &tag,
-            &self.out,
+            &self.wheel_dir(&tag)?,
             &self.metadata24,
-            &[tag.clone()],
+            std::slice::from_ref(&tag),
+            false,
             self.excludes(Format::Wheel)?,
-        )?;
-        self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
+        )?
+        .with_jobs(self.cargo_options.jobs)
+        .with_stored(self.stored_files()?)
+        .with_extra_hashes(&self.extra_hashes);
+        let (artifacts, ext_libs) = with_workspace_members(&artifact, ext_libs, members);
+        self.add_external_libs(&mut writer, &artifacts, &ext_libs)?;
 
         write_bindings_module(
             &mut writer,
@@ -717,14 +1196,37 @@ impl BuildContext {
             self.pyproject_toml.as_ref(),
         )
         .context("Failed to add the files to the wheel")?;
+        for member in members {
+            write_workspace_member_module(
+                &mut writer,
+                &self.project_layout,
+                &member.module_name,
+                &member.artifact.path,
+                Some(python_interpreter),
+                false,
+                &self.target,
+                self.editable,
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to add the workspace member {} to the wheel",
+                    member.module_name
+                )
+            })?;
+        }
 
         self.add_pth(&mut writer)?;
+        self.add_sbom(&mut writer)?;
+        self.add_readme(&mut writer)?;
         add_data(
             &mut writer,
             &self.metadata24,
             self.project_layout.data.as_deref(),
         )?;
         let wheel_path = writer.finish()?;
+        self.emit_symbols(&artifacts, &wheel_path)?;
+        self.check_wheel_size(&wheel_path)?;
+        self.sign_wheel(&wheel_path)?;
         Ok((
             wheel_path,
             format!("cp{}{}", python_interpreter.major, python_interpreter.minor),
@@ -742,14 +1244,15 @@ impl BuildContext {
         &self,
         interpreters: &[PythonInterpreter],
     ) -> Result<Vec<BuiltWheelMetadata>> {
-        let mut wheels = Vec::new();
-        for python_interpreter in interpreters {
+        self.build_for_each_interpreter(interpreters, |python_interpreter| {
             let artifact = self.compile_cdylib(
                 Some(python_interpreter),
                 Some(&self.project_layout.extension_name),
             )?;
             let (policy, external_libs) =
                 self.auditwheel(&artifact, &self.platform_tag, Some(python_interpreter))?;
+            let (policy, members) =
+                self.build_workspace_members(Some(python_interpreter), policy)?;
             let platform_tags = if self.platform_tag.is_empty() {
                 vec![policy.platform_tag()]
             } else {
@@ -760,6 +1263,7 @@ impl BuildContext {
                 artifact,
                 &platform_tags,
                 external_libs,
+                &members,
             )?;
             eprintln!(
                 "📦 Built wheel for {} {}.{}{} to {}",
@@ -770,10 +1274,8 @@ impl BuildContext {
                 wheel_path.display()
             );
 
-            wheels.push((wheel_path, tag));
-        }
-
-        Ok(wheels)
+            Ok(vec![(wheel_path, tag)])
+        })
     }
 
     /// Runs cargo build, extracts the cdylib from the output and returns the path to it
@@ -802,19 +1304,61 @@ impl BuildContext {
             let _ = warn_missing_py_init(&artifact.path, extension_name);
         }
 
-        if self.editable || matches!(self.auditwheel, AuditWheelMode::Skip) {
+        if self.editable
+            || (matches!(self.auditwheel, AuditWheelMode::Skip) && self.emit_symbols.is_none())
+        {
             return Ok(artifact);
         }
-        // auditwheel repair will edit the file, so we need to copy it to avoid errors in reruns
         let artifact_path = &artifact.path;
-        let maturin_build = artifact_path.parent().unwrap().join("maturin");
-        fs::create_dir_all(&maturin_build)?;
-        let new_artifact_path = maturin_build.join(artifact_path.file_name().unwrap());
-        fs::copy(artifact_path, &new_artifact_path)?;
+        let new_artifact_path = self.copy_artifact(artifact_path)?;
+        if self.emit_symbols.is_some() {
+            split_debug_info(&self.target, artifact_path, &new_artifact_path)?;
+        }
         artifact.path = new_artifact_path;
         Ok(artifact)
     }
 
+    /// Compiles and audits the `tool.maturin.workspace-members`, returning the least compatible
+    /// of their policies and `policy`
+    fn build_workspace_members(
+        &self,
+        python_interpreter: Option<&PythonInterpreter>,
+        mut policy: Policy,
+    ) -> Result<(Policy, Vec<BuiltWorkspaceMember>)> {
+        let mut members = Vec::with_capacity(self.workspace_members.len());
+        for member in &self.workspace_members {
+            eprintln!("🔗 Building workspace member {}", member.crate_name);
+            // The member is built like the main package, except for the cargo target and the
+            // features, which are specific to the main package
+            let mut context = self.clone();
+            context.workspace_members.clear();
+            context.compile_targets = vec![member.compile_target.clone()];
+            context.crate_name.clone_from(&member.crate_name);
+            context.module_name.clone_from(&member.module_name);
+            context.manifest_path.clone_from(&member.manifest_path);
+            context.cargo_options.manifest_path = Some(member.manifest_path.clone());
+            context.cargo_options.features.clear();
+            context.cargo_options.all_features = false;
+            context.cargo_options.no_default_features = false;
+            let artifact = context
+                .compile_cdylib(python_interpreter, Some(&member.module_name))
+                .with_context(|| {
+                    format!("Failed to build the workspace member {}", member.crate_name)
+                })?;
+            let (member_policy, ext_libs) =
+                self.auditwheel(&artifact, &self.platform_tag, python_interpreter)?;
+            if member_policy.priority < policy.priority {
+                policy = member_policy;
+            }
+            members.push(BuiltWorkspaceMember {
+                module_name: member.module_name.clone(),
+                artifact,
+                ext_libs,
+            });
+        }
+        Ok((policy, members))
+    }
+
     fn write_cffi_wheel(
         &self,
         artifact: BuildArtifact,
@@ -825,11 +1369,15 @@ impl BuildContext {
 
         let mut writer = WheelWriter::new(
             &tag,
-            &self.out,
+            &self.wheel_dir(&tag)?,
             &self.metadata24,
             &tags,
+            self.root_is_purelib()?,
             self.excludes(Format::Wheel)?,
-        )?;
+        )?
+        .with_jobs(self.cargo_options.jobs)
+        .with_stored(self.stored_files()?)
+        .with_extra_hashes(&self.extra_hashes);
         self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
 
         write_cffi_module(
@@ -843,15 +1391,21 @@ impl BuildContext {
             &self.interpreter[0].executable,
             self.editable,
             self.pyproject_toml.as_ref(),
+            &self.metadata24,
         )?;
 
         self.add_pth(&mut writer)?;
+        self.add_sbom(&mut writer)?;
+        self.add_readme(&mut writer)?;
         add_data(
             &mut writer,
             &self.metadata24,
             self.project_layout.data.as_deref(),
         )?;
         let wheel_path = writer.finish()?;
+        self.emit_symbols(&[&artifact], &wheel_path)?;
+        self.check_wheel_size(&wheel_path)?;
+        self.sign_wheel(&wheel_path)?;
         Ok((wheel_path, "py3".to_string()))
     }
 
@@ -896,18 +1450,23 @@ impl BuildContext {
 
         let mut writer = WheelWriter::new(
             &tag,
-            &self.out,
+            &self.wheel_dir(&tag)?,
             &self.metadata24,
             &tags,
+            false,
             self.excludes(Format::Wheel)?,
-        )?;
+        )?
+        .with_jobs(self.cargo_options.jobs)
+        .with_stored(self.stored_files()?)
+        .with_extra_hashes(&self.extra_hashes);
         self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
 
         write_uniffi_module(
             &mut writer,
             &self.project_layout,
             self.manifest_path.parent().unwrap(),
-            &self.target_dir,
+            &self.cargo_metadata,
+            &self.maturin_build_dir(),
             &self.module_name,
             &artifact.path,
             self.target.target_os(),
@@ -916,12 +1475,17 @@ impl BuildContext {
         )?;
 
         self.add_pth(&mut writer)?;
+        self.add_sbom(&mut writer)?;
+        self.add_readme(&mut writer)?;
         add_data(
             &mut writer,
             &self.metadata24,
             self.project_layout.data.as_deref(),
         )?;
         let wheel_path = writer.finish()?;
+        self.emit_symbols(&[&artifact], &wheel_path)?;
+        self.check_wheel_size(&wheel_path)?;
+        self.sign_wheel(&wheel_path)?;
         Ok((wheel_path, "py3".to_string()))
     }
 
@@ -959,10 +1523,6 @@ impl BuildContext {
             _ => unreachable!(),
         };
 
-        if !self.metadata24.scripts.is_empty() {
-            bail!("Defining scripts and working with a binary doesn't mix well");
-        }
-
         let mut artifacts_and_files = Vec::new();
         for artifact in artifacts {
             // I wouldn't know of any case where this would be the wrong (and neither do
@@ -986,19 +1546,50 @@ impl BuildContext {
             artifacts_and_files.push((artifact, bin_name))
         }
 
-        let metadata24 = if self.target.is_wasi() {
-            bin_wasi_helper(&artifacts_and_files, self.metadata24.clone())?
+        let mut metadata24 = self.metadata24.clone();
+        let bin_names: Vec<&str> = artifacts_and_files
+            .iter()
+            .map(|(_, bin_name)| bin_name.as_str())
+            .collect();
+        let bin_scripts = if self.target.is_wasi() {
+            Vec::new()
         } else {
-            self.metadata24.clone()
+            take_bin_scripts(&mut metadata24, &bin_names)
+        };
+        let python_scripts = metadata24
+            .scripts
+            .keys()
+            .chain(metadata24.gui_scripts.keys())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !python_scripts.is_empty()
+            && (self.target.is_wasi() || self.project_layout.python_module.is_none())
+        {
+            bail!(
+                "Defining scripts and working with a binary doesn't mix well: \
+                 {} must point to one of the binaries ({}) to be installed as its launcher",
+                python_scripts.join(", "),
+                bin_names.join(", ")
+            );
+        }
+
+        let metadata24 = if self.target.is_wasi() {
+            bin_wasi_helper(&artifacts_and_files, metadata24)?
+        } else {
+            metadata24
         };
 
         let mut writer = WheelWriter::new(
             &tag,
-            &self.out,
+            &self.wheel_dir(&tag)?,
             &metadata24,
             &tags,
+            false,
             self.excludes(Format::Wheel)?,
-        )?;
+        )?
+        .with_jobs(self.cargo_options.jobs)
+        .with_stored(self.stored_files()?)
+        .with_extra_hashes(&self.extra_hashes);
 
         if self.project_layout.python_module.is_some() && self.target.is_wasi() {
             // TODO: Can we have python code and the wasm launchers coexisting
@@ -1022,15 +1613,30 @@ impl BuildContext {
                 write_wasm_launcher(&mut writer, &self.metadata24, bin_name)?;
             }
         }
+        for (script_name, bin_name) in &bin_scripts {
+            if script_name == bin_name {
+                continue;
+            }
+            let (artifact, _) = artifacts_and_files
+                .iter()
+                .find(|(_, name)| name == bin_name)
+                .unwrap();
+            write_bin(&mut writer, &artifact.path, &self.metadata24, script_name)?;
+        }
         self.add_external_libs(&mut writer, &artifacts_ref, ext_libs)?;
 
         self.add_pth(&mut writer)?;
+        self.add_sbom(&mut writer)?;
+        self.add_readme(&mut writer)?;
         add_data(
             &mut writer,
             &self.metadata24,
             self.project_layout.data.as_deref(),
         )?;
         let wheel_path = writer.finish()?;
+        self.emit_symbols(&artifacts_ref, &wheel_path)?;
+        self.check_wheel_size(&wheel_path)?;
+        self.sign_wheel(&wheel_path)?;
         Ok((wheel_path, "py3".to_string()))
     }
 
@@ -1052,16 +1658,24 @@ impl BuildContext {
         let mut ext_libs = Vec::new();
         let mut artifact_paths = Vec::with_capacity(artifacts.len());
         for artifact in artifacts {
-            let artifact = artifact
+            let mut artifact = artifact
                 .get(&CrateType::Bin)
                 .cloned()
                 .ok_or_else(|| anyhow!("Cargo didn't build a binary"))?;
+            if self.emit_symbols.is_some() && !self.editable {
+                let new_artifact_path = self.copy_artifact(&artifact.path)?;
+                split_debug_info(&self.target, &artifact.path, &new_artifact_path)?;
+                artifact.path = new_artifact_path;
+            }
 
             let (policy, external_libs) = self.auditwheel(&artifact, &self.platform_tag, None)?;
             policies.push(policy);
             ext_libs.push(external_libs);
             artifact_paths.push(artifact);
         }
+        if matches!(self.auditwheel, AuditWheelMode::Check) && !self.editable {
+            check_external_libs(&ext_libs)?;
+        }
         let policy = policies.iter().min_by_key(|p| p.priority).unwrap();
         let platform_tags = if self.platform_tag.is_empty() {
             vec![policy.platform_tag()]
@@ -1087,15 +1701,52 @@ impl BuildContext {
     pub fn build_bin_wheels(
         &self,
         interpreters: &[PythonInterpreter],
+    ) -> Result<Vec<BuiltWheelMetadata>> {
+        self.build_for_each_interpreter(interpreters, |python_interpreter| {
+            self.build_bin_wheel(Some(python_interpreter))
+        })
+    }
+
+    /// Runs `build` for each interpreter. With `--keep-going`, failures are collected into a
+    /// [KeepGoingError] after trying all interpreters instead of aborting at the first one
+    fn build_for_each_interpreter(
+        &self,
+        interpreters: &[PythonInterpreter],
+        build: impl Fn(&PythonInterpreter) -> Result<Vec<BuiltWheelMetadata>>,
     ) -> Result<Vec<BuiltWheelMetadata>> {
         let mut wheels = Vec::new();
+        let mut failed = Vec::new();
         for python_interpreter in interpreters {
-            wheels.extend(self.build_bin_wheel(Some(python_interpreter))?);
+            match build(python_interpreter) {
+                Ok(built) => wheels.extend(built),
+                Err(err) if self.keep_going => {
+                    eprintln!(
+                        "💥 Failed to build wheel for {python_interpreter}, continuing with `--keep-going`"
+                    );
+                    failed.push((python_interpreter.to_string(), err));
+                }
+                Err(err) => return Err(err),
+            }
         }
-        Ok(wheels)
+        KeepGoingError::into_result(wheels, failed)
     }
 }
 
+/// The artifacts of a bindings wheel and the external libraries they link, main module first
+fn with_workspace_members<'a>(
+    artifact: &'a BuildArtifact,
+    ext_libs: Vec<Library>,
+    members: &'a [BuiltWorkspaceMember],
+) -> (Vec<&'a BuildArtifact>, Vec<Vec<Library>>) {
+    let mut artifacts = vec![artifact];
+    let mut libs = vec![ext_libs];
+    for member in members {
+        artifacts.push(&member.artifact);
+        libs.push(member.ext_libs.clone());
+    }
+    (artifacts, libs)
+}
+
 /// Calculate the sha256 of a file
 pub fn hash_file(path: impl AsRef<Path>) -> Result<String, io::Error> {
     let mut file = fs::File::open(path.as_ref())?;
@@ -1120,12 +1771,7 @@ fn macosx_deployment_target(
     let mut x86_64_ver = x86_64_default;
     let mut arm64_ver = arm64_default;
     if let Some(deploy_target) = deploy_target {
-        let err_ctx = "MACOSX_DEPLOYMENT_TARGET is invalid";
-        let mut parts = deploy_target.split('.');
-        let major = parts.next().context(err_ctx)?;
-        let major: u16 = major.parse().context(err_ctx)?;
-        let minor = parts.next().context(err_ctx)?;
-        let minor: u16 = minor.parse().context(err_ctx)?;
+        let (major, minor) = parse_macosx_version(deploy_target)?;
         if (major, minor) > x86_64_default {
             x86_64_ver = (major, minor);
         }
@@ -1139,6 +1785,34 @@ fn macosx_deployment_target(
     ))
 }
 
+/// Parses a `MACOSX_DEPLOYMENT_TARGET` value such as `10.9` or `11` into `(major, minor)`
+fn parse_macosx_version(deploy_target: &str) -> Result<(u16, u16)> {
+    let err_ctx = "MACOSX_DEPLOYMENT_TARGET is invalid";
+    let mut parts = deploy_target.split('.');
+    let major = parts.next().context(err_ctx)?;
+    let major: u16 = major.parse().context(err_ctx)?;
+    let minor: u16 = match parts.next() {
+        Some(minor) => minor.parse().context(err_ctx)?,
+        None => 0,
+    };
+    Ok((major, minor))
+}
+
+/// The `MACOSX_DEPLOYMENT_TARGET` used for `target` when neither the environment nor
+/// `pyproject.toml` set one: rustc's default, raised to the macOS version the interpreter
+/// was built for since extension modules can't run on anything older than their interpreter
+pub(crate) fn default_macosx_deployment_target(
+    target: &str,
+    interpreter: Option<&PythonInterpreter>,
+) -> (u16, u16) {
+    let rustc_default = rustc_macosx_target_version(target);
+    interpreter
+        .and_then(|interp| interp.macosx_deployment_target.as_deref())
+        .and_then(|version| parse_macosx_version(version).ok())
+        .filter(|version| *version > rustc_default)
+        .unwrap_or(rustc_default)
+}
+
 #[inline]
 fn python_macosx_target_version(version: (u16, u16)) -> (u16, u16) {
     let (major, minor) = version;
@@ -1218,6 +1892,291 @@ pub(crate) fn rustc_macosx_target_version(target: &str) -> (u16, u16) {
     rustc_target_version().unwrap_or(fallback_version)
 }
 
+/// The default `tool.maturin.sign-command`, an ASCII armored detached signature made with gpg
+pub(crate) const DEFAULT_SIGN_COMMAND: &[&str] = &[
+    "gpg",
+    "--batch",
+    "--yes",
+    "--armor",
+    "--detach-sign",
+    "--local-user",
+    "{key}",
+    "--output",
+    "{signature}",
+    "{wheel}",
+];
+
+/// PyPI's default upload limit in MB, projects can request a higher limit
+pub const PYPI_MAX_WHEEL_SIZE: u64 = 100;
+
+/// The placeholders `--out` can contain, they're replaced with the values of each wheel
+pub(crate) const OUT_DIR_PLACEHOLDERS: &[&str] =
+    &["python_tag", "abi_tag", "platform_tag", "version"];
+
+/// Replaces the placeholders such as `{platform_tag}` in the `--out` directory with the values
+/// of the file written to it, erroring for unknown placeholders and those without a value for
+/// the file (`what`)
+pub(crate) fn expand_out_dir(
+    out: &Path,
+    what: &str,
+    value: impl Fn(&str) -> Option<String>,
+) -> Result<PathBuf> {
+    let Some(mut rest) = out.to_str() else {
+        return Ok(out.to_path_buf());
+    };
+    let mut expanded = String::new();
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        let name = &rest[start + 1..end];
+        if !OUT_DIR_PLACEHOLDERS.contains(&name) {
+            bail!(
+                "Unknown placeholder `{{{name}}}` in `--out {}`, the available placeholders are {}",
+                out.display(),
+                OUT_DIR_PLACEHOLDERS
+                    .iter()
+                    .map(|placeholder| format!("`{{{placeholder}}}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        let Some(value) = value(name) else {
+            bail!(
+                "The placeholder `{{{name}}}` in `--out {}` can't be used for {what}",
+                out.display()
+            );
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+/// The glob matching the output directory `out` relative to `project_dir`, with the placeholders
+/// of `--out` matching any directory name, or `None` if it's outside of the project
+///
+/// Directories that don't exist yet can't contain files of the project either.
+pub(crate) fn out_dir_glob(project_dir: &Path, out: &Path) -> Result<Option<String>> {
+    let out = expand_out_dir(out, "the excludes", |_| Some("*".to_string()))?;
+    // Only the part before the first placeholder exists on disk
+    let templated = out
+        .components()
+        .position(|component| component.as_os_str().to_string_lossy().contains('*'))
+        .unwrap_or(out.components().count());
+    let fixed: PathBuf = out.components().take(templated).collect();
+    let fixed = if fixed.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        fixed.as_path()
+    };
+    let Ok(fixed) = fixed.normalize() else {
+        return Ok(None);
+    };
+    let Ok(relative) = fixed.as_path().strip_prefix(project_dir) else {
+        return Ok(None);
+    };
+    let mut glob_pattern = String::new();
+    for component in relative
+        .components()
+        .chain(out.components().skip(templated))
+    {
+        glob_pattern.push('/');
+        glob_pattern.push_str(&component.as_os_str().to_string_lossy());
+    }
+    Ok(Some(glob_pattern))
+}
+
+/// Degrades auditwheel repair to only auditing when patchelf isn't installed and
+/// `--no-auditwheel-patchelf` was given: the external libraries aren't copied into the wheel,
+/// which is therefore tagged `linux` unless a portable platform tag was requested explicitly
+fn audit_without_patchelf(
+    policy: Policy,
+    libs: Vec<Library>,
+    platform_tag: &[PlatformTag],
+    patchelf_installed: bool,
+) -> Result<(Policy, Vec<Library>)> {
+    if patchelf_installed || libs.is_empty() {
+        return Ok((policy, libs));
+    }
+    eprintln!("⚠️  Warning: patchelf is not installed, so the following libraries required by your library can't be copied into the wheel:");
+    eprint_external_libs(&libs);
+    if let Some(tag) = platform_tag.iter().find(|tag| tag.is_portable()) {
+        bail!(
+            "Can not build a {tag} compliant wheel without copying the libraries, \
+            install patchelf or build without `--compatibility {tag}`"
+        );
+    }
+    eprintln!("⚠️  Warning: Tagging the wheel as `linux` instead of {policy} because of `--no-auditwheel-patchelf`");
+    Ok((Policy::default(), Vec::new()))
+}
+
+/// `_PYTHON_HOST_PLATFORM` as the platform part of the wheel tag
+///
+/// On macOS it's ignored if it names another arch than the one we build for, e.g.
+/// `macosx-10.9-universal2` from the environment of a universal2 interpreter when only
+/// `--target x86_64-apple-darwin` is built.
+fn host_platform_tag(host_platform: &str, target: &Target, universal2: bool) -> Option<String> {
+    if target.is_macos() {
+        let arch = if universal2 {
+            "universal2"
+        } else if target.target_arch() == Arch::Aarch64 {
+            "arm64"
+        } else {
+            "x86_64"
+        };
+        if host_platform.rsplit('-').next() != Some(arch) {
+            return None;
+        }
+    }
+    Some(host_platform.replace(['.', '-'], "_"))
+}
+
+/// Where `tool.maturin.include-readme-in-wheel` puts the readme in the wheel, by default
+/// `README.<ext>` in the top level package with the extension matching the content type
+fn readme_wheel_path(
+    config: &IncludeReadme,
+    module_name: &str,
+    is_bin: bool,
+    content_type: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    match config {
+        IncludeReadme::Enabled(false) => Ok(None),
+        IncludeReadme::Enabled(true) => {
+            if is_bin {
+                bail!(
+                    "Wheels with binaries have no python package to put the readme in, \
+                    please set `include-readme-in-wheel` to a path instead"
+                );
+            }
+            let content_type = content_type.unwrap_or_default();
+            let extension = if content_type.starts_with("text/markdown") {
+                "md"
+            } else if content_type.starts_with("text/x-rst") {
+                "rst"
+            } else {
+                "txt"
+            };
+            let package = module_name.split('.').next().unwrap_or(module_name);
+            Ok(Some(Path::new(package).join(format!("README.{extension}"))))
+        }
+        IncludeReadme::Path(path) => {
+            if path.as_os_str().is_empty()
+                || !path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+            {
+                bail!(
+                    "`include-readme-in-wheel` must be a relative path inside the wheel, got `{}`",
+                    path.display()
+                );
+            }
+            Ok(Some(path.clone()))
+        }
+    }
+}
+
+fn check_wheel_size(wheel_path: &Path, size: u64, max_wheel_size: Option<u64>) -> Result<()> {
+    let size_mb = size as f64 / 1_000_000.0;
+    if let Some(limit) = max_wheel_size {
+        if size > limit * 1_000_000 {
+            bail!(
+                "{} is {size_mb:.1} MB, which exceeds the limit of {limit} MB",
+                wheel_path.display()
+            );
+        }
+    } else if size > PYPI_MAX_WHEEL_SIZE * 1_000_000 {
+        eprintln!(
+            "⚠️  Warning: {} is {size_mb:.1} MB, which exceeds PyPI's default upload limit of {PYPI_MAX_WHEEL_SIZE} MB",
+            wheel_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Replaces the `{name}` placeholders in an argument of a configured command with their values,
+/// leaving unknown placeholders as they are
+pub(crate) fn expand_command_arg(arg: &str, values: &[(&str, &OsStr)]) -> OsString {
+    // Arguments that are only a placeholder keep paths that aren't valid UTF-8 intact
+    let placeholder = arg.strip_prefix('{').and_then(|arg| arg.strip_suffix('}'));
+    if let Some((_, value)) = values.iter().find(|(name, _)| placeholder == Some(*name)) {
+        return value.to_os_string();
+    }
+    let mut expanded = String::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        match values
+            .iter()
+            .find(|(name, _)| *name == &rest[start + 1..end])
+        {
+            Some((_, value)) => expanded.push_str(&value.to_string_lossy()),
+            None => expanded.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded.into()
+}
+
+/// Runs the sign command template for `file`, returning the path of the `<file>.asc` signature
+fn sign_file(command_template: &[String], key: &str, file: &Path) -> Result<PathBuf> {
+    use std::process::Command;
+
+    let mut signature = file.as_os_str().to_owned();
+    signature.push(".asc");
+    let signature = PathBuf::from(signature);
+
+    let (program, args) = command_template
+        .split_first()
+        .context("`tool.maturin.sign-command` must not be empty")?;
+    let values = [
+        ("key", OsStr::new(key)),
+        ("wheel", file.as_os_str()),
+        ("signature", signature.as_os_str()),
+    ];
+    let args: Vec<OsString> = args
+        .iter()
+        .map(|arg| expand_command_arg(arg, &values))
+        .collect();
+    let status = Command::new(program)
+        .args(&args)
+        .status()
+        .with_context(|| format!("Failed to run the sign command `{program}`"))?;
+    if !status.success() {
+        bail!(
+            "Signing {} with `{program}` failed: {status}",
+            file.display()
+        );
+    }
+    if !signature.is_file() {
+        bail!(
+            "The sign command `{program}` didn't create the signature {}",
+            signature.display()
+        );
+    }
+    Ok(signature)
+}
+
+/// The compressed tag set of a linux wheel, e.g. `manylinux_2_17_armv7l.manylinux2014_armv7l`
+fn linux_platform_tag(platform_tags: &[PlatformTag], arch: &str) -> String {
+    let mut platform_tags = platform_tags.to_vec();
+    platform_tags.sort();
+    let mut tags = vec![];
+    for platform_tag in platform_tags {
+        tags.push(format!("{platform_tag}_{arch}"));
+        for alias in platform_tag.aliases() {
+            tags.push(format!("{alias}_{arch}"));
+        }
+    }
+    tags.join(".")
+}
+
 /// Emscripten version
 fn emscripten_version() -> Result<String> {
     let os_version = env::var("MATURIN_EMSCRIPTEN_VERSION");
@@ -1228,6 +2187,59 @@ fn emscripten_version() -> Result<String> {
     Ok(release)
 }
 
+/// The platform tag for emscripten wheels
+///
+/// Pyodide since 0.28 tags wheels with its ABI version, e.g. `pyodide_2025_0_wasm32`, which
+/// `pyodide build` exports as `PYODIDE_ABI_VERSION`. Otherwise we use the emscripten version
+/// as older pyodide versions do, e.g. `emscripten_3_1_58_wasm32`.
+fn emscripten_platform_tag(
+    pyodide_abi_version: Option<&str>,
+    emscripten_version: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    Ok(match pyodide_abi_version {
+        Some(abi_version) => format!("pyodide_{}_wasm32", abi_version.replace(['.', '-'], "_")),
+        None => {
+            let release = emscripten_version()?.replace(['.', '-'], "_");
+            format!("emscripten_{release}_wasm32")
+        }
+    })
+}
+
+/// The PEP 730 platform tag for iOS, e.g. `ios_13_0_arm64_iphoneos`
+///
+/// Devices and the simulator are separate platforms, so there is one wheel per arch and sdk
+/// instead of a fat binary. The minimum version defaults to 13.0, the oldest release CPython
+/// supports.
+fn ios_platform_tag(
+    deployment_target: Option<&str>,
+    arch: Arch,
+    simulator: bool,
+) -> Result<String> {
+    let (major, minor) = match deployment_target {
+        Some(version) => {
+            let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+            let parse = |x: &str| {
+                x.parse::<u16>().with_context(|| {
+                    format!("Invalid IPHONEOS_DEPLOYMENT_TARGET `{version}`, expected e.g. `13.0`")
+                })
+            };
+            (parse(major)?, parse(minor)?)
+        }
+        None => (13, 0),
+    };
+    let arch = match arch {
+        Arch::Aarch64 => "arm64",
+        Arch::X86_64 => "x86_64",
+        _ => bail!("Unsupported architecture {arch} for iOS"),
+    };
+    let sdk = if simulator {
+        "iphonesimulator"
+    } else {
+        "iphoneos"
+    };
+    Ok(format!("ios_{major}_{minor}_{arch}_{sdk}"))
+}
+
 fn emcc_version() -> Result<String> {
     use regex::bytes::Regex;
     use std::process::Command;
@@ -1246,8 +2258,27 @@ fn emcc_version() -> Result<String> {
 
 #[cfg(test)]
 mod test {
-    use super::macosx_deployment_target;
+    use super::{
+        audit_without_patchelf, check_wheel_size, default_macosx_deployment_target,
+        emscripten_platform_tag, expand_command_arg, expand_out_dir, host_platform_tag,
+        ios_platform_tag, linux_platform_tag, macosx_deployment_target, out_dir_glob,
+        readme_wheel_path, rustc_macosx_target_version, sign_file, take_bin_scripts,
+        KeepGoingError,
+    };
+    use crate::auditwheel::{PlatformTag, Policy};
+    use crate::pyproject_toml::IncludeReadme;
+    use crate::python_interpreter::{InterpreterConfig, InterpreterKind};
+    use crate::target::Arch;
+    use crate::Metadata24;
+    use crate::{PythonInterpreter, Target};
+    use fs_err as fs;
+    use normpath::PathExt;
+    use pep440_rs::Version;
     use pretty_assertions::assert_eq;
+    use std::ffi::OsStr;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::str::FromStr;
 
     #[test]
     fn test_macosx_deployment_target() {
@@ -1284,4 +2315,395 @@ mod test {
             ((11, 0), (11, 0))
         );
     }
+
+    #[test]
+    fn test_default_macosx_deployment_target_from_interpreter() {
+        let target = Target::from_target_triple(Some("aarch64-apple-darwin".to_string())).unwrap();
+        let config = InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, 12), "")
+            .unwrap()
+            .clone();
+        let mut interpreter = PythonInterpreter::from_config(config);
+        let rustc_default = rustc_macosx_target_version("aarch64-apple-darwin");
+        assert_eq!(
+            default_macosx_deployment_target("aarch64-apple-darwin", Some(&interpreter)),
+            rustc_default
+        );
+        // Mocked sysconfig `MACOSX_DEPLOYMENT_TARGET` of an interpreter built for macOS 14
+        interpreter.macosx_deployment_target = Some("14.0".to_string());
+        assert_eq!(
+            default_macosx_deployment_target("aarch64-apple-darwin", Some(&interpreter)),
+            (14, 0)
+        );
+        assert_eq!(
+            default_macosx_deployment_target("aarch64-apple-darwin", None),
+            rustc_default
+        );
+        // Older interpreters never lower the target below rustc's default
+        interpreter.macosx_deployment_target = Some("10.9".to_string());
+        assert_eq!(
+            default_macosx_deployment_target("aarch64-apple-darwin", Some(&interpreter)),
+            rustc_default
+        );
+        interpreter.macosx_deployment_target = Some("15".to_string());
+        assert_eq!(
+            default_macosx_deployment_target("aarch64-apple-darwin", Some(&interpreter)),
+            (15, 0)
+        );
+        assert_eq!(
+            macosx_deployment_target(Some("15"), false).unwrap(),
+            ((15, 0), (15, 0))
+        );
+    }
+
+    #[test]
+    fn test_audit_without_patchelf() {
+        let manylinux = Policy::from_name("manylinux_2_17").unwrap();
+        let libs = vec![lddtree::Library {
+            name: "libfoo.so.1".to_string(),
+            path: PathBuf::from("/usr/lib/libfoo.so.1"),
+            realpath: Some(PathBuf::from("/usr/lib/libfoo.so.1")),
+            needed: Vec::new(),
+            rpath: Vec::new(),
+            runpath: Vec::new(),
+        }];
+
+        // With patchelf the libraries are repaired as usual
+        let (policy, kept) =
+            audit_without_patchelf(manylinux.clone(), libs.clone(), &[], true).unwrap();
+        assert_eq!(policy.name, "manylinux_2_17");
+        assert_eq!(kept.len(), 1);
+
+        // Simulated missing patchelf: nothing to copy and no manylinux tag
+        let (policy, kept) =
+            audit_without_patchelf(manylinux.clone(), libs.clone(), &[], false).unwrap();
+        assert_eq!(policy.name, "linux");
+        assert!(kept.is_empty());
+
+        // An explicitly requested manylinux tag can't be honored
+        let err = audit_without_patchelf(
+            manylinux,
+            libs,
+            &[PlatformTag::Manylinux { x: 2, y: 17 }],
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("install patchelf"), "{err}");
+    }
+
+    #[test]
+    fn test_out_dir_glob() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let project_dir = tmp_dir.path().normalize().unwrap().into_path_buf();
+        fs::create_dir_all(project_dir.join("dist")).unwrap();
+        assert_eq!(
+            out_dir_glob(&project_dir, &project_dir.join("dist/{version}")).unwrap(),
+            Some("/dist/*".to_string())
+        );
+        assert_eq!(
+            out_dir_glob(&project_dir, &project_dir.join("{version}/sdist")).unwrap(),
+            Some("/*/sdist".to_string())
+        );
+        assert_eq!(
+            out_dir_glob(&project_dir, &project_dir.join("dist")).unwrap(),
+            Some("/dist".to_string())
+        );
+        // Doesn't exist
+        assert_eq!(
+            out_dir_glob(&project_dir, &project_dir.join("target/{version}")).unwrap(),
+            None
+        );
+        // Outside of the project
+        assert_eq!(
+            out_dir_glob(&project_dir.join("dist"), &project_dir).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expand_out_dir() {
+        let value = |name: &str| match name {
+            "python_tag" => Some("cp312".to_string()),
+            "platform_tag" => Some("manylinux_2_17_x86_64".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_out_dir(
+                Path::new("dist/{python_tag}/{platform_tag}"),
+                "wheels",
+                value
+            )
+            .unwrap(),
+            Path::new("dist/cp312/manylinux_2_17_x86_64")
+        );
+        assert_eq!(
+            expand_out_dir(Path::new("dist/{python_tag"), "wheels", value).unwrap(),
+            Path::new("dist/{python_tag")
+        );
+        let err = expand_out_dir(Path::new("dist/{os}"), "wheels", value)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Unknown placeholder `{os}` in `--out dist/{os}`"));
+        assert!(err.contains("`{python_tag}`, `{abi_tag}`, `{platform_tag}`, `{version}`"));
+        let err = expand_out_dir(
+            Path::new("dist/{abi_tag}"),
+            "the source distribution",
+            value,
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "The placeholder `{abi_tag}` in `--out dist/{abi_tag}` can't be used for the source distribution"
+        );
+    }
+
+    #[test]
+    fn test_arm_linux_platform_tag() {
+        let armv6 =
+            Target::from_target_triple(Some("arm-unknown-linux-gnueabihf".to_string())).unwrap();
+        assert_eq!(armv6.get_python_arch(), "armv6l");
+        // There is no manylinux policy for armv6l
+        assert_eq!(armv6.get_minimum_manylinux_tag(), PlatformTag::Linux);
+        assert!(!armv6.supports_platform_tag(&PlatformTag::manylinux2014()));
+        assert_eq!(
+            linux_platform_tag(&[PlatformTag::Linux], armv6.get_python_arch()),
+            "linux_armv6l"
+        );
+
+        for triple in [
+            "armv7-unknown-linux-gnueabihf",
+            "thumbv7neon-unknown-linux-gnueabihf",
+        ] {
+            let armv7 = Target::from_target_triple(Some(triple.to_string())).unwrap();
+            assert_eq!(armv7.get_python_arch(), "armv7l");
+            assert_eq!(
+                armv7.get_minimum_manylinux_tag(),
+                PlatformTag::manylinux2014()
+            );
+            assert!(armv7.supports_platform_tag(&PlatformTag::manylinux2014()));
+        }
+        assert_eq!(
+            linux_platform_tag(&[PlatformTag::Linux], "armv7l"),
+            "linux_armv7l"
+        );
+        assert_eq!(
+            linux_platform_tag(&[PlatformTag::manylinux2014()], "armv7l"),
+            "manylinux_2_17_armv7l.manylinux2014_armv7l"
+        );
+        assert_eq!(
+            linux_platform_tag(&[PlatformTag::Musllinux { x: 1, y: 2 }], "armv6l"),
+            "musllinux_1_2_armv6l"
+        );
+    }
+
+    #[test]
+    fn test_host_platform_tag() {
+        let x86_64 = Target::from_target_triple(Some("x86_64-apple-darwin".to_string())).unwrap();
+        assert_eq!(
+            host_platform_tag("macosx-10.9-x86_64", &x86_64, false).as_deref(),
+            Some("macosx_10_9_x86_64")
+        );
+        // The environment of a universal2 interpreter, but only x86_64 is built
+        assert_eq!(
+            host_platform_tag("macosx-10.9-universal2", &x86_64, false),
+            None
+        );
+        assert_eq!(
+            host_platform_tag("macosx-10.9-universal2", &x86_64, true).as_deref(),
+            Some("macosx_10_9_universal2")
+        );
+        assert_eq!(host_platform_tag("macosx-11.0-arm64", &x86_64, false), None);
+        let linux =
+            Target::from_target_triple(Some("x86_64-unknown-linux-gnu".to_string())).unwrap();
+        assert_eq!(
+            host_platform_tag("linux-aarch64", &linux, false).as_deref(),
+            Some("linux_aarch64")
+        );
+    }
+
+    #[test]
+    fn test_emscripten_platform_tag() {
+        assert_eq!(
+            emscripten_platform_tag(None, || Ok("3.1.58".to_string())).unwrap(),
+            "emscripten_3_1_58_wasm32"
+        );
+        // The pyodide ABI version takes precedence and emcc isn't needed
+        assert_eq!(
+            emscripten_platform_tag(Some("2025_0"), || unreachable!()).unwrap(),
+            "pyodide_2025_0_wasm32"
+        );
+        assert_eq!(
+            emscripten_platform_tag(Some("2024.0"), || unreachable!()).unwrap(),
+            "pyodide_2024_0_wasm32"
+        );
+    }
+
+    #[test]
+    fn test_ios_platform_tag() {
+        assert_eq!(
+            ios_platform_tag(None, Arch::Aarch64, false).unwrap(),
+            "ios_13_0_arm64_iphoneos"
+        );
+        assert_eq!(
+            ios_platform_tag(Some("14.2"), Arch::Aarch64, true).unwrap(),
+            "ios_14_2_arm64_iphonesimulator"
+        );
+        assert_eq!(
+            ios_platform_tag(Some("15"), Arch::X86_64, true).unwrap(),
+            "ios_15_0_x86_64_iphonesimulator"
+        );
+        assert!(ios_platform_tag(Some("latest"), Arch::Aarch64, false).is_err());
+    }
+
+    #[test]
+    fn test_check_wheel_size() {
+        let wheel = std::path::Path::new("foo-0.1.0-py3-none-any.whl");
+        assert!(check_wheel_size(wheel, 50_000_000, None).is_ok());
+        // Over PyPI's limit only warns without an explicit limit
+        assert!(check_wheel_size(wheel, 150_000_000, None).is_ok());
+        assert!(check_wheel_size(wheel, 150_000_000, Some(200)).is_ok());
+        let err = check_wheel_size(wheel, 12_345_678, Some(10)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "foo-0.1.0-py3-none-any.whl is 12.3 MB, which exceeds the limit of 10 MB"
+        );
+    }
+
+    #[test]
+    fn test_readme_wheel_path() {
+        let markdown = Some("text/markdown; charset=UTF-8; variant=GFM");
+        assert_eq!(
+            readme_wheel_path(&IncludeReadme::Enabled(true), "pkg._ext", false, markdown).unwrap(),
+            Some(PathBuf::from("pkg/README.md"))
+        );
+        assert_eq!(
+            readme_wheel_path(&IncludeReadme::Enabled(true), "pkg", false, None).unwrap(),
+            Some(PathBuf::from("pkg/README.txt"))
+        );
+        assert_eq!(
+            readme_wheel_path(&IncludeReadme::Enabled(false), "pkg", false, markdown).unwrap(),
+            None
+        );
+        assert!(readme_wheel_path(&IncludeReadme::Enabled(true), "cli", true, markdown).is_err());
+
+        let path = IncludeReadme::Path(PathBuf::from("pkg/docs/README.md"));
+        assert_eq!(
+            readme_wheel_path(&path, "cli", true, markdown).unwrap(),
+            Some(PathBuf::from("pkg/docs/README.md"))
+        );
+        for invalid in ["../README.md", "/README.md", ""] {
+            let path = IncludeReadme::Path(PathBuf::from(invalid));
+            assert!(readme_wheel_path(&path, "pkg", false, markdown).is_err());
+        }
+    }
+
+    #[test]
+    fn test_expand_command_arg() {
+        let values = [
+            ("key", OsStr::new("mykey")),
+            ("wheel", OsStr::new("dist/foo.whl")),
+        ];
+        assert_eq!(expand_command_arg("{wheel}", &values), "dist/foo.whl");
+        assert_eq!(
+            expand_command_arg("--user={key}:{wheel}", &values),
+            "--user=mykey:dist/foo.whl"
+        );
+        assert_eq!(expand_command_arg("{other} {key", &values), "{other} {key");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sign_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let wheel = tmp_dir.path().join("foo-1.0-py3-none-any.whl");
+        fs_err::write(&wheel, "wheel").unwrap();
+        let command: Vec<String> = [
+            "sh",
+            "-c",
+            "echo \"$0 $1\" > \"${2#--output=}\"",
+            "--local-user={key}",
+            "{wheel}",
+            "--output={signature}",
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        let signature = sign_file(&command, "mykey", &wheel).unwrap();
+        assert_eq!(
+            signature,
+            tmp_dir.path().join("foo-1.0-py3-none-any.whl.asc")
+        );
+        assert_eq!(
+            fs_err::read_to_string(&signature).unwrap(),
+            format!("--local-user=mykey {}\n", wheel.display())
+        );
+
+        let failing: Vec<String> = vec!["false".to_string()];
+        assert!(sign_file(&failing, "mykey", &wheel).is_err());
+        assert!(sign_file(&[], "mykey", &wheel).is_err());
+    }
+
+    #[test]
+    fn test_keep_going_error() {
+        let built = vec![(
+            PathBuf::from("foo-1.0-cp39-cp39-linux_x86_64.whl"),
+            "cp39".to_string(),
+        )];
+        assert_eq!(
+            KeepGoingError::into_result(built.clone(), Vec::new()).unwrap(),
+            built
+        );
+
+        let failed = vec![(
+            "CPython 3.10 at /usr/bin/python3.10".to_string(),
+            anyhow::anyhow!("Failed to build").context("Failed to build a native library"),
+        )];
+        let err = KeepGoingError::into_result(built.clone(), failed).unwrap_err();
+        let err = err.downcast::<KeepGoingError>().unwrap();
+        assert_eq!(err.built, built);
+        assert_eq!(
+            err.to_string(),
+            "Failed to build wheels for 1 interpreter(s), 1 wheel(s) were built successfully\n  \
+            - CPython 3.10 at /usr/bin/python3.10: Failed to build a native library: Failed to build\n"
+        );
+    }
+
+    #[test]
+    fn test_take_bin_scripts() {
+        let mut metadata24 =
+            Metadata24::new("my-tool".to_string(), Version::from_str("1.0.0").unwrap());
+        metadata24
+            .scripts
+            .insert("my-tool".to_string(), "my-rust-bin".to_string());
+        metadata24
+            .scripts
+            .insert("helper".to_string(), "my_tool.cli:main".to_string());
+        metadata24
+            .gui_scripts
+            .insert("my-tool-gui".to_string(), "my-rust-gui".to_string());
+
+        let bin_scripts = take_bin_scripts(&mut metadata24, &["my-rust-bin", "my-rust-gui"]);
+        assert_eq!(
+            bin_scripts,
+            [
+                ("my-tool".to_string(), "my-rust-bin".to_string()),
+                ("my-tool-gui".to_string(), "my-rust-gui".to_string()),
+            ]
+        );
+        assert_eq!(metadata24.scripts.len(), 1);
+        assert_eq!(metadata24.scripts["helper"], "my_tool.cli:main");
+        assert!(metadata24.gui_scripts.is_empty());
+
+        // The binary's `.exe` suffix is kept on windows
+        let mut metadata24 =
+            Metadata24::new("my-tool".to_string(), Version::from_str("1.0.0").unwrap());
+        metadata24
+            .scripts
+            .insert("my-tool".to_string(), "my-rust-bin".to_string());
+        let bin_scripts = take_bin_scripts(&mut metadata24, &["my-rust-bin.exe"]);
+        assert_eq!(
+            bin_scripts,
+            [("my-tool.exe".to_string(), "my-rust-bin.exe".to_string())]
+        );
+    }
 }
@@ -1,20 +1,27 @@
-use crate::auditwheel::{AuditWheelMode, PlatformTag};
-use crate::compile::{CompileTarget, LIB_CRATE_TYPES};
+use crate::auditwheel::{get_glibc_version, AuditWheelMode, PlatformTag};
+use crate::build_context::{expand_out_dir, WorkspaceMember, DEFAULT_SIGN_COMMAND};
+use crate::compile::{supports_split_debug_info, CompileTarget, SplitDebuginfo, LIB_CRATE_TYPES};
 use crate::cross_compile::{find_sysconfigdata, parse_sysconfigdata};
-use crate::project_layout::ProjectResolver;
+use crate::module_writer::ExtraHash;
+use crate::project_layout::{find_sdist_cargo_lock, ProjectResolver};
 use crate::pyproject_toml::ToolMaturin;
-use crate::python_interpreter::{InterpreterConfig, InterpreterKind};
+use crate::python_interpreter::{
+    find_pyenv_interpreter, InterpreterConfig, InterpreterKind, LockedInterpreter,
+};
 use crate::{Bindings, BridgeModel, BuildContext, PythonInterpreter, Target};
 use anyhow::{bail, format_err, Context, Result};
 use cargo_metadata::{CrateType, PackageId, TargetKind};
 use cargo_metadata::{Metadata, Node};
 use cargo_options::heading;
+use fs_err as fs;
+use itertools::Itertools;
 use pep440_rs::VersionSpecifiers;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::ops::{Deref, DerefMut};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use tracing::{debug, instrument};
 
 // This is used for BridgeModel::Bindings("pyo3-ffi") and BridgeModel::Bindings("pyo3").
@@ -139,7 +146,9 @@ pub struct BuildOptions {
     /// Wheels with the native `linux` tag will be rejected by pypi,
     /// unless they are separately validated by `auditwheel`.
     ///
-    /// The default is the lowest compatible `manylinux` tag, or plain `linux` if nothing matched
+    /// The default is the lowest compatible `manylinux` tag, or plain `linux` if nothing matched.
+    /// When building in a container whose glibc is not newer than what rustc requires,
+    /// e.g. the manylinux2014 images, the default is the `manylinux` tag of that glibc.
     ///
     /// This option is ignored on all non-linux platforms
     #[arg(
@@ -153,6 +162,14 @@ pub struct BuildOptions {
 
     /// The python versions to build wheels for, given as the executables of
     /// interpreters such as `python3.9` or `/usr/bin/python3.8`.
+    ///
+    /// Use `auto:3.12` to pick the python 3.12 found on the host and `conda:myenv` for the
+    /// python of the conda environment `myenv`. Glob patterns such as
+    /// `/opt/pythons/cp3*/bin/python3` are expanded to all matching interpreters.
+    ///
+    /// Defaults to the comma or space separated interpreters in the `MATURIN_INTERPRETERS`
+    /// environment variable, then to the first installed version of a pyenv `.python-version`
+    /// file in the project if no virtualenv is active, or to `python3`
     #[arg(short, long, num_args = 0.., action = clap::ArgAction::Append)]
     pub interpreter: Vec<PathBuf>,
 
@@ -160,15 +177,144 @@ pub struct BuildOptions {
     #[arg(short = 'f', long, conflicts_with = "interpreter")]
     pub find_interpreter: bool,
 
+    /// Don't restrict the python interpreters to the `requires-python` of pyproject.toml,
+    /// e.g. to test a build with an interpreter that's not supported yet
+    #[arg(long)]
+    pub ignore_requires_python: bool,
+
     /// Which kind of bindings to use.
     #[arg(short, long, value_parser = ["pyo3", "pyo3-ffi", "cffi", "uniffi", "bin"])]
     pub bindings: Option<String>,
 
+    /// Continue building wheels for the remaining interpreters if the build for one fails,
+    /// reporting all failures at the end
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Write the configuration of the discovered interpreter, including the directory of
+    /// libpython, to this pyo3 config file and pass it to the build as `PYO3_CONFIG_FILE`,
+    /// instead of letting pyo3 query the interpreter again
+    ///
+    /// When building for several interpreters, the file contains the configuration of the
+    /// interpreter that is currently being built for
+    #[arg(long, value_name = "FILE")]
+    pub write_interpreter_config: Option<PathBuf>,
+
+    /// Record the resolved interpreters with their paths, versions and ABIs in this JSON file,
+    /// to check later builds against it with `--interpreter-lock`
+    #[arg(long, value_name = "FILE")]
+    pub write_interpreter_lock: Option<PathBuf>,
+
+    /// Fail if the resolved interpreters differ from the ones recorded in this file with
+    /// `--write-interpreter-lock`, e.g. to ensure a build matrix uses the same interpreters
+    #[arg(long, value_name = "FILE")]
+    pub interpreter_lock: Option<PathBuf>,
+
+    /// Build for an operating system maturin doesn't support, tagging the wheel as linux.
+    ///
+    /// This is a best-effort escape hatch, the resulting wheels are unlikely to be installable
+    /// with standard tools
+    #[arg(long)]
+    pub allow_unsupported_target: bool,
+
+    /// Override the python tag of the wheel, e.g. `py3` for a library that doesn't depend on
+    /// the python version. The ABI tag then defaults to `none`.
+    ///
+    /// All wheels get the same tag, so only one interpreter can be built for at a time
+    #[arg(long, value_parser = parse_python_tag)]
+    pub python_tag: Option<String>,
+
+    /// Override the ABI tag of the wheel, e.g. `pypy38_pp73`.
+    ///
+    /// By default it's derived from the interpreter's `EXT_SUFFIX`, which doesn't work for
+    /// some exotic interpreters
+    #[arg(long)]
+    pub abi_tag: Option<String>,
+
+    /// Override the `SOABI` reported by the interpreter, e.g. `pyston-23-x86_64-linux-gnu`.
+    ///
+    /// Only used for the generic tags of python implementations maturin doesn't know, such as
+    /// Pyston, whose ABI tag is derived from `SOABI`
+    #[arg(long, value_parser = parse_soabi)]
+    pub soabi: Option<String>,
+
     /// The directory to store the built wheels in. Defaults to a new "wheels"
-    /// directory in the project's target directory
+    /// directory in the project's target directory.
+    ///
+    /// The placeholders `{python_tag}`, `{abi_tag}`, `{platform_tag}` and `{version}` are
+    /// replaced with the values of each wheel, e.g. `dist/{python_tag}/{platform_tag}`
     #[arg(short, long)]
     pub out: Option<PathBuf>,
 
+    /// The directory for maturin's intermediate files, such as generated pyo3 config files
+    /// and the library copies used for auditwheel repair, in a subdirectory per target.
+    /// Defaults to `maturin` inside cargo's target directory
+    #[arg(long)]
+    pub build_dir: Option<PathBuf>,
+
+    /// Save the debug info split off the stripped libraries and binaries into this directory,
+    /// in a subdirectory named after each wheel. Requires `--strip`.
+    ///
+    /// Uses objcopy for ELF targets and the `.pdb` files of the MSVC linker on windows
+    #[arg(long, value_name = "DIR")]
+    pub emit_symbols: Option<PathBuf>,
+
+    /// Write a CycloneDX JSON SBOM listing the name, version and license of the crates compiled
+    /// into the wheel to this path
+    #[arg(long, value_name = "PATH")]
+    pub emit_sbom: Option<PathBuf>,
+
+    /// Embed the CycloneDX JSON SBOM of the compiled crates in the `.dist-info/sboms` directory
+    /// of the wheel
+    #[arg(long)]
+    pub embed_sbom: bool,
+
+    /// Pass `-C split-debuginfo` to rustc for the extension build, e.g. `unpacked` to skip
+    /// the slow `dsymutil` step on macOS.
+    ///
+    /// This only changes how rustc emits the debug info, it's independent of `--strip`
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub split_debuginfo: Option<SplitDebuginfo>,
+
+    /// Sign each wheel with the given key, writing a detached `<wheel>.asc` signature next to it.
+    ///
+    /// Uses gpg by default, the command can be changed with `tool.maturin.sign-command`
+    /// in pyproject.toml
+    #[arg(long, value_name = "KEY")]
+    pub sign: Option<String>,
+
+    /// Strip even when building with a debug profile such as `dev`, where `--strip` is
+    /// ignored by default to keep the symbols of debug builds
+    #[arg(long)]
+    pub force_strip: bool,
+
+    /// Fail if a wheel is larger than the given size in MB.
+    ///
+    /// Wheels over PyPI's default upload limit of 100 MB are always warned about,
+    /// `maturin publish` to PyPI or TestPyPI fails for them unless a different limit is given
+    #[arg(long, value_name = "MB")]
+    pub max_wheel_size: Option<u64>,
+
+    /// Fail if the git working tree has modified or untracked files, to ensure the built
+    /// packages correspond to committed source
+    #[arg(long)]
+    pub require_clean_worktree: bool,
+
+    /// Set an environment variable for the cargo build only, e.g. to configure build scripts.
+    /// Can be given multiple times
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var, action = clap::ArgAction::Append)]
+    pub env: Vec<(String, String)>,
+
+    /// Wrap rustc with the given program, e.g. `sccache`, like `RUSTC_WRAPPER`
+    #[arg(long, value_name = "PATH")]
+    pub rustc_wrapper: Option<PathBuf>,
+
+    /// Also write the hashes of all files in the wheel with this algorithm to a
+    /// `RECORD.<algorithm>` file next to RECORD, which always uses sha256.
+    /// Can be given multiple times
+    #[arg(long, value_name = "ALGORITHM", action = clap::ArgAction::Append)]
+    pub extra_hashes: Vec<ExtraHash>,
+
     /// Audit wheel for manylinux compliance
     #[arg(long, conflicts_with = "skip_auditwheel")]
     pub auditwheel: Option<AuditWheelMode>,
@@ -177,6 +323,11 @@ pub struct BuildOptions {
     #[arg(long, hide = true)]
     pub skip_auditwheel: bool,
 
+    /// When auditwheel repair would have to copy external libraries but patchelf isn't
+    /// installed, report them and build a `linux` tagged wheel without them instead of failing
+    #[arg(long)]
+    pub no_auditwheel_patchelf: bool,
+
     /// For manylinux targets, use zig to ensure compliance for the chosen manylinux version
     ///
     /// Default to manylinux2014/manylinux_2_17 if you do not specify an `--compatibility`
@@ -299,6 +450,10 @@ impl BuildOptions {
                             runnable: false,
                             implementation_name: interpreter_kind.to_string().to_ascii_lowercase(),
                             soabi: soabi.cloned(),
+                            macosx_deployment_target: sysconfig_data
+                                .get("MACOSX_DEPLOYMENT_TARGET")
+                                .cloned(),
+                            lib_dir: None,
                         });
                     } else {
                         if interpreter.is_empty() && !self.find_interpreter {
@@ -396,6 +551,8 @@ impl BuildOptions {
                             runnable: false,
                             implementation_name: "cpython".to_string(),
                             soabi: None,
+                            macosx_deployment_target: None,
+                            lib_dir: None,
                         }])
                     } else if let Some(config_file) = env::var_os("PYO3_CONFIG_FILE") {
                         let interpreter_config =
@@ -422,6 +579,8 @@ impl BuildOptions {
                                 runnable: false,
                                 implementation_name: "cpython".to_string(),
                                 soabi: None,
+                                macosx_deployment_target: None,
+                                lib_dir: None,
                             })
                         }
                         Ok(found_interpreters)
@@ -451,6 +610,8 @@ impl BuildOptions {
                             runnable: false,
                             implementation_name: "cpython".to_string(),
                             soabi: None,
+                            macosx_deployment_target: None,
+                            lib_dir: None,
                         }])
                     } else if target.cross_compiling() {
                         let mut interps = Vec::with_capacity(found_interpreters.len());
@@ -504,6 +665,8 @@ pub struct BuildContextBuilder {
     strip: bool,
     editable: bool,
     sdist_only: bool,
+    vendor_crates: bool,
+    locked_sdist: bool,
 }
 
 impl BuildContextBuilder {
@@ -514,6 +677,8 @@ impl BuildContextBuilder {
             strip: false,
             editable: false,
             sdist_only: false,
+            vendor_crates: false,
+            locked_sdist: false,
         }
     }
 
@@ -537,14 +702,39 @@ impl BuildContextBuilder {
         self
     }
 
+    pub fn vendor_crates(mut self, vendor_crates: bool) -> Self {
+        self.vendor_crates = vendor_crates;
+        self
+    }
+
+    /// Build with `--locked` if this is a source distribution that contains a `Cargo.lock`
+    pub fn locked_sdist(mut self, locked_sdist: bool) -> Self {
+        self.locked_sdist = locked_sdist;
+        self
+    }
+
     pub fn build(self) -> Result<BuildContext> {
         let Self {
-            build_options,
+            mut build_options,
             release,
             strip,
             editable,
             sdist_only,
+            vendor_crates,
+            locked_sdist,
         } = self;
+        if locked_sdist && !build_options.cargo.locked && !build_options.cargo.frozen {
+            // Checked before running `cargo metadata`, which would update an outdated lockfile
+            if let Some(cargo_lock) =
+                find_sdist_cargo_lock(build_options.manifest_path.clone(), &build_options.cargo)?
+            {
+                eprintln!(
+                    "🔒 Building from a source distribution with {}, using `--locked`",
+                    cargo_lock.display()
+                );
+                build_options.cargo.locked = true;
+            }
+        }
         let ProjectResolver {
             project_layout,
             cargo_toml_path,
@@ -561,6 +751,9 @@ impl BuildContextBuilder {
             build_options.cargo.clone(),
         )?;
         let pyproject = pyproject_toml.as_ref();
+        if build_options.require_clean_worktree {
+            require_clean_worktree(&project_layout.project_root)?;
+        }
 
         let bridge = find_bridge(
             &cargo_metadata,
@@ -613,19 +806,29 @@ impl BuildContextBuilder {
             target_triple = Some("aarch64-apple-darwin".to_string());
         }
 
-        let mut target = Target::from_target_triple(target_triple)?;
+        let mut target = Target::from_target_triple_with_options(
+            target_triple,
+            build_options.allow_unsupported_target,
+        )?;
         if !target.user_specified && !universal2 {
             if let Some(interpreter) = build_options.interpreter.first() {
                 if let Some(detected_target) =
                     crate::target::detect_arch_from_python(interpreter, &target)
                 {
-                    target = Target::from_target_triple(Some(detected_target))?;
+                    target = Target::from_target_triple_with_options(
+                        Some(detected_target),
+                        build_options.allow_unsupported_target,
+                    )?;
                 }
             }
         }
 
         let wheel_dir = match build_options.out {
-            Some(ref dir) => dir.clone(),
+            Some(ref dir) => {
+                // Only checks the placeholders, they're replaced for each wheel
+                expand_out_dir(dir, "wheels", |_| Some(String::new()))?;
+                dir.clone()
+            }
             None => PathBuf::from(&cargo_metadata.target_directory).join("wheels"),
         };
 
@@ -634,11 +837,17 @@ impl BuildContextBuilder {
             // We don't need a python interpreter to build sdist only
             Vec::new()
         } else {
+            let requires_python = if build_options.ignore_requires_python {
+                None
+            } else {
+                metadata24.requires_python.as_ref()
+            };
             resolve_interpreters(
                 &build_options,
+                &project_layout.project_root,
                 &bridge,
                 &target,
-                metadata24.requires_python.as_ref(),
+                requires_python,
                 generate_import_lib,
             )?
         };
@@ -652,7 +861,32 @@ impl BuildContextBuilder {
             }
         }
 
-        let strip = pyproject.map(|x| x.strip()).unwrap_or_default() || strip;
+        let strip_requested = pyproject.map(|x| x.strip()).unwrap_or_default() || strip;
+        let strip_ignored = strip_requested
+            && !build_options.force_strip
+            && is_debug_profile(cargo_options.profile.as_deref(), release);
+        if strip_ignored {
+            eprintln!(
+                "⚠️  Warning: Not stripping the debug build, use `--force-strip` to strip anyway"
+            );
+        }
+        let strip = strip_requested && !strip_ignored;
+        if build_options.emit_symbols.is_some() {
+            if strip_ignored {
+                bail!(
+                    "`--emit-symbols` needs the stripped artifacts, but `--strip` is ignored for \
+                    the debug build, use `--force-strip` to strip anyway"
+                );
+            }
+            if !strip {
+                bail!("`--emit-symbols` only works together with `--strip`");
+            }
+            if !supports_split_debug_info(&target) {
+                bail!(
+                    "`--emit-symbols` is not supported for {target}, only for ELF and MSVC targets"
+                );
+            }
+        }
         let skip_auditwheel = pyproject.map(|x| x.skip_auditwheel()).unwrap_or_default()
             || build_options.skip_auditwheel;
         let auditwheel = build_options
@@ -678,7 +912,7 @@ impl BuildContextBuilder {
                 .or(if use_zig {
                     if target.is_musl_libc() {
                         // Zig bundles musl 1.2
-                        Some(PlatformTag::Musllinux { x: 1, y: 2 })
+                        Some(default_musllinux_tag(&target))
                     } else {
                         // With zig we can compile to any glibc version that we want, so we pick the lowest
                         // one supported by the rust compiler
@@ -687,9 +921,9 @@ impl BuildContextBuilder {
                 } else {
                     // Defaults to musllinux_1_2 for musl target if it's not bin bindings
                     if target.is_musl_libc() && !bridge.is_bin() {
-                        Some(PlatformTag::Musllinux { x: 1, y: 2 })
+                        Some(default_musllinux_tag(&target))
                     } else {
-                        None
+                        default_manylinux_tag(&target, get_glibc_version)
                     }
                 });
             if let Some(platform_tag) = compatibility {
@@ -704,12 +938,36 @@ impl BuildContextBuilder {
         for platform_tag in &platform_tags {
             if !platform_tag.is_supported() {
                 eprintln!("⚠️  Warning: {platform_tag} is unsupported by the Rust compiler.");
-            } else if platform_tag.is_musllinux() && !target.is_musl_libc() {
+            } else if target.is_linux()
+                && !target.supports_platform_tag(platform_tag)
+                // Statically linked musl binaries are manylinux compatible too
+                && !(bridge.is_bin() && target.is_musl_libc() && platform_tag.is_manylinux())
+            {
                 eprintln!("⚠️  Warning: {target} is not compatible with {platform_tag}.");
             }
         }
 
         validate_bridge_type(&bridge, &target, &platform_tags)?;
+        if let Some(python_tag) = &build_options.python_tag {
+            check_python_tag(&bridge, python_tag, &interpreter)?;
+        }
+        if build_options.write_interpreter_config.is_some() {
+            check_write_interpreter_config(&bridge)?;
+        }
+        if build_options.soabi.is_some()
+            && !interpreter.iter().any(PythonInterpreter::uses_generic_tag)
+        {
+            eprintln!(
+                "⚠️  Warning: `--soabi` is ignored, it only applies to python implementations with generic wheel tags"
+            );
+        }
+
+        let static_libpython = if pyproject.is_some_and(|x| x.static_libpython()) {
+            pyproject_toml_maturin_options.push("static-libpython");
+            check_static_libpython(&bridge, &target)?
+        } else {
+            false
+        };
 
         // linux tag can not be mixed with manylinux and musllinux tags
         if platform_tags.len() > 1 && platform_tags.iter().any(|tag| !tag.is_portable()) {
@@ -735,11 +993,51 @@ impl BuildContextBuilder {
         if compile_targets.is_empty() {
             bail!("No Cargo targets to build, please check your bindings configuration in pyproject.toml.");
         }
+        let workspace_members = match pyproject.and_then(|x| x.workspace_members()) {
+            Some(members) => resolve_workspace_members(
+                &cargo_metadata,
+                members,
+                &compile_targets[0].bridge_model,
+                &project_layout.extension_name,
+            )?,
+            None => Vec::new(),
+        };
+
+        let sign_command = pyproject
+            .and_then(|x| x.sign_command())
+            .map(<[String]>::to_vec)
+            .unwrap_or_else(|| {
+                DEFAULT_SIGN_COMMAND
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            });
+
+        let mut top_level_modules: Vec<&str> = project_layout
+            .python_packages
+            .iter()
+            .map(|package| package.strip_suffix(".py").unwrap_or(package))
+            .collect();
+        top_level_modules.extend(module_name.split('.').next());
+        top_level_modules.extend(
+            project_layout
+                .python_module
+                .as_ref()
+                .and_then(|module| module.file_name()?.to_str()),
+        );
+        for (name, target) in metadata24.mismatched_script_targets(&top_level_modules) {
+            eprintln!(
+                "⚠️  Warning: The script `{name}` points to `{target}`, but the wheel only contains \
+                the top level modules {}",
+                top_level_modules.join(", ")
+            );
+        }
 
         let crate_name = cargo_toml.package.name;
         Ok(BuildContext {
             target,
             compile_targets,
+            workspace_members,
             project_layout,
             pyproject_toml_path,
             pyproject_toml,
@@ -748,9 +1046,28 @@ impl BuildContextBuilder {
             module_name,
             manifest_path: cargo_toml_path,
             target_dir,
+            build_dir: build_options.build_dir.clone(),
+            emit_symbols: build_options.emit_symbols.clone(),
+            emit_sbom: build_options.emit_sbom.clone(),
+            embed_sbom: build_options.embed_sbom,
+            split_debuginfo: build_options.split_debuginfo,
+            sign: build_options.sign.clone(),
+            sign_command,
+            max_wheel_size: build_options.max_wheel_size,
+            interpreter_config_file: build_options.write_interpreter_config.clone(),
+            static_libpython,
+            env: build_options.env.clone(),
+            rustc_wrapper: build_options.rustc_wrapper.clone(),
+            extra_hashes: build_options.extra_hashes.clone(),
+            no_auditwheel_patchelf: build_options.no_auditwheel_patchelf,
+            keep_going: build_options.keep_going,
+            python_tag: build_options.python_tag.clone(),
+            abi_tag: build_options.abi_tag.clone(),
+            soabi: build_options.soabi.clone(),
             out: wheel_dir,
             release,
             strip,
+            vendor_crates,
             auditwheel,
             #[cfg(feature = "zig")]
             zig: build_options.zig,
@@ -766,11 +1083,30 @@ impl BuildContextBuilder {
 
 fn resolve_interpreters(
     build_options: &BuildOptions,
+    project_root: &Path,
     bridge: &BridgeModel,
     target: &Target,
     requires_python: Option<&VersionSpecifiers>,
     generate_import_lib: bool,
 ) -> Result<Vec<PythonInterpreter>, anyhow::Error> {
+    // `MATURIN_INTERPRETERS` is used like `-i`, but explicit cli options take precedence
+    let env_interpreter = match env::var(INTERPRETERS_ENV_VAR) {
+        Ok(value) if build_options.interpreter.is_empty() && !build_options.find_interpreter => {
+            parse_interpreters_env(&value)
+        }
+        _ => Vec::new(),
+    };
+    let env_build_options;
+    let build_options = if env_interpreter.is_empty() {
+        build_options
+    } else {
+        debug!("Using the interpreters from `{INTERPRETERS_ENV_VAR}`: {env_interpreter:?}");
+        env_build_options = BuildOptions {
+            interpreter: env_interpreter,
+            ..build_options.clone()
+        };
+        &env_build_options
+    };
     let interpreter = if build_options.find_interpreter {
         // Auto-detect interpreters
         build_options.find_interpreters(
@@ -788,19 +1124,143 @@ fn resolve_interpreters(
                     Some(python) => vec![python.into()],
                     None => vec![target.get_python()],
                 }
+            } else if let Some(pyenv_interpreter) =
+                find_pyenv_interpreter(&env::current_dir()?, project_root)?
+            {
+                vec![pyenv_interpreter]
             } else {
                 vec![target.get_python()]
             }
         } else {
-            // XXX: False positive clippy warning
-            #[allow(clippy::redundant_clone)]
-            build_options.interpreter.clone()
+            let interpreter = expand_interpreter_globs(&build_options.interpreter)?;
+            let interpreter = resolve_conda_interpreters(&interpreter, target, bridge)?;
+            resolve_auto_interpreters(&interpreter, target, bridge)?
         };
         build_options.find_interpreters(bridge, &interpreter, target, None, generate_import_lib)?
     };
+    check_interpreter_pointer_width(&interpreter, target)?;
+    let locked: Vec<_> = interpreter
+        .iter()
+        .map(PythonInterpreter::to_locked)
+        .collect();
+    if let Some(lock_file) = &build_options.interpreter_lock {
+        check_interpreter_lock(lock_file, &locked)?;
+    }
+    if let Some(lock_file) = &build_options.write_interpreter_lock {
+        fs::write(lock_file, serde_json::to_string_pretty(&locked)?)?;
+        eprintln!("🔒 Wrote interpreter lock to {}", lock_file.display());
+    }
     Ok(interpreter)
 }
 
+/// Environment variable with the interpreters to build for when `-i` isn't given
+const INTERPRETERS_ENV_VAR: &str = "MATURIN_INTERPRETERS";
+
+/// Parses the value of `MATURIN_INTERPRETERS`, a comma or whitespace separated list of
+/// interpreters in the same format as `-i`, e.g. `python3.11, python3.12` or `auto:3.13`
+fn parse_interpreters_env(value: &str) -> Vec<PathBuf> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|interpreter| !interpreter.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Errors if the resolved interpreters differ from the ones in the lockfile written by
+/// `--write-interpreter-lock`, listing the missing and the unexpected interpreters
+fn check_interpreter_lock(lock_file: &Path, resolved: &[LockedInterpreter]) -> Result<()> {
+    let locked: Vec<LockedInterpreter> = serde_json::from_str(&fs::read_to_string(lock_file)?)
+        .with_context(|| {
+            format!(
+                "Failed to parse the interpreter lock {}",
+                lock_file.display()
+            )
+        })?;
+    let missing: Vec<_> = locked.iter().filter(|x| !resolved.contains(x)).collect();
+    let unexpected: Vec<_> = resolved.iter().filter(|x| !locked.contains(x)).collect();
+    if missing.is_empty() && unexpected.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!(
+        "The python interpreters differ from the ones locked in {}",
+        lock_file.display()
+    );
+    for interpreter in missing {
+        message.push_str(&format!("\n  - missing or changed: {interpreter}"));
+    }
+    for interpreter in unexpected {
+        message.push_str(&format!("\n  + not in the lock: {interpreter}"));
+    }
+    bail!(message)
+}
+
+/// Errors if an interpreter is e.g. a 32-bit python while we're building for a 64-bit target,
+/// since the resulting wheel can't be imported by it
+///
+/// Cross compiling is skipped, the interpreters are then only used for their configuration.
+fn check_interpreter_pointer_width(
+    interpreters: &[PythonInterpreter],
+    target: &Target,
+) -> Result<()> {
+    if target.cross_compiling() {
+        return Ok(());
+    }
+    for interpreter in interpreters {
+        let Some(pointer_width) = interpreter.config.pointer_width else {
+            continue;
+        };
+        if interpreter.runnable && pointer_width != target.pointer_width() {
+            bail!(
+                "{} at {} is a {}-bit interpreter, but the target {} is {}-bit. \
+                Use a {}-bit python or pass a matching `--target`",
+                interpreter,
+                interpreter.executable.display(),
+                pointer_width,
+                target.target_triple(),
+                target.pointer_width(),
+                target.pointer_width(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The musllinux tag to use for a musl target when no compatibility was requested, warning when
+/// there is no musllinux policy for the architecture
+fn default_musllinux_tag(target: &Target) -> PlatformTag {
+    let platform_tag = target.get_default_musllinux_tag();
+    if platform_tag == PlatformTag::Linux {
+        eprintln!(
+            "⚠️  Warning: There is no musllinux policy for {}, falling back to the linux tag. \
+            You won't be able to upload those wheels to PyPI.",
+            target.target_arch()
+        );
+    }
+    platform_tag
+}
+
+/// The manylinux tag to use when no compatibility was requested and we're building natively for
+/// a glibc target, e.g. in a manylinux container
+///
+/// If the glibc we're building against is not newer than what rustc requires anyway, it's the
+/// effective floor and we use its tag. Otherwise we return `None` and let auditwheel pick the tag
+/// from the symbols that the artifacts actually use.
+fn default_manylinux_tag(
+    target: &Target,
+    detect_glibc: impl FnOnce() -> Option<(u16, u16)>,
+) -> Option<PlatformTag> {
+    if !target.is_linux() || target.is_musl_libc() || target.cross_compiling() {
+        return None;
+    }
+    let (x, y) = detect_glibc()?;
+    let platform_tag = PlatformTag::Manylinux { x, y };
+    if platform_tag > target.get_minimum_manylinux_tag() {
+        return None;
+    }
+    eprintln!("🔍 Detected glibc {x}.{y}, defaulting to {platform_tag}");
+    Some(platform_tag)
+}
+
 /// Checks for bridge/platform type edge cases
 fn validate_bridge_type(
     bridge: &BridgeModel,
@@ -810,7 +1270,10 @@ fn validate_bridge_type(
     match bridge {
         BridgeModel::Bin(None) => {
             // Only support two different kind of platform tags when compiling to musl target without any binding crates
-            if platform_tags.iter().any(|tag| tag.is_musllinux()) && !target.is_musl_libc() {
+            if platform_tags
+                .iter()
+                .any(|tag| tag.is_musllinux() && !target.supports_platform_tag(tag))
+            {
                 bail!(
                     "Cannot mix musllinux and manylinux platform tags when compiling to {}",
                     target.target_triple()
@@ -849,6 +1312,104 @@ fn validate_bridge_type(
     Ok(())
 }
 
+/// Fails if `git status` reports modified or untracked files, for `--require-clean-worktree`
+fn require_clean_worktree(project_root: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(project_root)
+        .output()
+        .context("Failed to run `git status` for `--require-clean-worktree`, is git installed?")?;
+    if !output.status.success() {
+        bail!(
+            "`--require-clean-worktree` needs a git repository, but `git status` failed in {}: {}",
+            project_root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let status = String::from_utf8_lossy(&output.stdout);
+    if !status.trim().is_empty() {
+        bail!(
+            "The git working tree isn't clean, please commit or stash your changes \
+            (`--require-clean-worktree`):\n{}",
+            status.trim_end()
+        );
+    }
+    Ok(())
+}
+
+/// Finds the cdylib targets of the `tool.maturin.workspace-members` packages
+fn resolve_workspace_members(
+    cargo_metadata: &Metadata,
+    members: &[String],
+    bridge: &BridgeModel,
+    extension_name: &str,
+) -> Result<Vec<WorkspaceMember>> {
+    if members.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !matches!(
+        bridge,
+        BridgeModel::Bindings { .. } | BridgeModel::BindingsAbi3 { .. }
+    ) {
+        bail!("`tool.maturin.workspace-members` is only supported for pyo3 bindings");
+    }
+    eprintln!(
+        "⚠️  Warning: `tool.maturin.workspace-members` is experimental and may change without a \
+        major version bump"
+    );
+    let root_pkg = cargo_metadata.root_package().context(
+        "`tool.maturin.workspace-members` needs the manifest of the main package, but the \
+        manifest is a virtual workspace root. Point `tool.maturin.manifest-path` at the \
+        Cargo.toml of the package with the bindings instead",
+    )?;
+    let workspace_packages = cargo_metadata.workspace_packages();
+    members
+        .iter()
+        .map(|name| {
+            let package = workspace_packages
+                .iter()
+                .find(|package| &package.name == name)
+                .with_context(|| {
+                    format!(
+                        "The workspace member `{name}` from `tool.maturin.workspace-members` \
+                        isn't a package of the cargo workspace"
+                    )
+                })?;
+            if package.id == root_pkg.id {
+                bail!(
+                    "The workspace member `{name}` from `tool.maturin.workspace-members` is the \
+                    main package, which is always built"
+                );
+            }
+            let target = package
+                .targets
+                .iter()
+                .find(|target| target.crate_types.contains(&CrateType::CDyLib))
+                .with_context(|| {
+                    format!(
+                        "The workspace member `{name}` has no lib target with \
+                        `crate-type = [\"cdylib\"]`"
+                    )
+                })?;
+            if target.name == extension_name {
+                bail!(
+                    "The workspace member `{name}` has the same module name `{extension_name}` as \
+                    the main package"
+                );
+            }
+            Ok(WorkspaceMember {
+                module_name: target.name.clone(),
+                crate_name: package.name.clone(),
+                manifest_path: package.manifest_path.clone().into_std_path_buf(),
+                compile_target: CompileTarget {
+                    target: target.clone(),
+                    bridge_model: bridge.clone(),
+                },
+            })
+        })
+        .collect()
+}
+
 fn filter_cargo_targets(
     cargo_metadata: &Metadata,
     bridge: BridgeModel,
@@ -864,21 +1425,18 @@ fn filter_cargo_targets(
     let mut targets: Vec<_> = root_pkg
         .targets
         .iter()
-        .filter(|target| match bridge {
-            BridgeModel::Bin(_) => {
-                let is_bin = target.is_bin();
-                if target.required_features.is_empty() {
-                    is_bin
-                } else {
-                    // Check all required features are enabled for this bin target
-                    is_bin
-                        && target
-                            .required_features
-                            .iter()
-                            .all(|f| resolved_features.contains(f))
-                }
-            }
-            _ => target.crate_types.contains(&CrateType::CDyLib),
+        .filter(|target| {
+            let is_candidate = match bridge {
+                BridgeModel::Bin(_) => target.is_bin(),
+                _ => target.crate_types.contains(&CrateType::CDyLib),
+            };
+            // Check all required features are enabled for this target, cargo refuses to build
+            // it otherwise
+            is_candidate
+                && target
+                    .required_features
+                    .iter()
+                    .all(|f| resolved_features.contains(f))
         })
         .map(|target| CompileTarget {
             target: target.clone(),
@@ -886,6 +1444,19 @@ fn filter_cargo_targets(
         })
         .collect();
     if targets.is_empty() && !bridge.is_bin() {
+        // `dylib` is a rust dynamic library, which python can't load as an extension module
+        if let Some(target) = root_pkg
+            .targets
+            .iter()
+            .find(|target| target.crate_types == [CrateType::DyLib])
+        {
+            bail!(
+                "The lib target `{}` has `crate-type = [\"dylib\"]`, which builds a rust dynamic \
+                library that python can't load. Python extension modules need `cdylib` instead, \
+                please change your `Cargo.toml` to:\n\n[lib]\ncrate-type = [\"cdylib\"]",
+                target.name
+            );
+        }
         // No `crate-type = ["cdylib"]` in `Cargo.toml`
         // Let's try compile one of the target with `--crate-type cdylib`
         let lib_target = root_pkg.targets.iter().find(|target| {
@@ -904,6 +1475,11 @@ fn filter_cargo_targets(
 
     // Filter targets by config_targets
     if let Some(config_targets) = config_targets {
+        for config_target in config_targets {
+            if config_target.kind.is_none() {
+                check_unambiguous_target(root_pkg, &config_target.name)?;
+            }
+        }
         targets.retain(|CompileTarget { target, .. }| {
             config_targets.iter().any(|config_target| {
                 let name_eq = config_target.name == target.name;
@@ -933,6 +1509,227 @@ fn filter_cargo_targets(
     Ok(targets)
 }
 
+/// Fails if a `tool.maturin.targets` entry without `kind` names both a bin and a library target,
+/// e.g. a `src/main.rs` and a `src/lib.rs` of a package whose name doesn't contain a dash
+fn check_unambiguous_target(package: &cargo_metadata::Package, name: &str) -> Result<()> {
+    let kinds: Vec<&str> = package
+        .targets
+        .iter()
+        .filter(|target| target.name == name)
+        .filter_map(|target| {
+            if target.is_bin() {
+                Some("bin")
+            } else if target.crate_types.contains(&CrateType::CDyLib) {
+                Some("cdylib")
+            } else if target
+                .crate_types
+                .iter()
+                .any(|crate_type| LIB_CRATE_TYPES.contains(crate_type))
+            {
+                Some("lib")
+            } else {
+                None
+            }
+        })
+        .unique()
+        .collect();
+    if kinds.len() > 1 {
+        bail!(
+            "The target `{name}` in `tool.maturin.targets` is ambiguous, the package has {} \
+            targets with this name. Please set its kind, e.g. `{{ name = \"{name}\", kind = \"{}\" }}`",
+            kinds.join(" and "),
+            kinds[0]
+        );
+    }
+    Ok(())
+}
+
+/// Whether the cargo profile is one of the built-in profiles with debug info, `dev` or `test`
+fn is_debug_profile(profile: Option<&str>, release: bool) -> bool {
+    match profile {
+        Some(profile) => matches!(profile, "dev" | "test"),
+        None => !release,
+    }
+}
+
+/// Parses a `--python-tag` such as `py3`, `cp39` or the compressed tag set `py2.py3`
+fn parse_python_tag(arg: &str) -> Result<String> {
+    let valid = |tag: &str| {
+        tag.starts_with(|c: char| c.is_ascii_lowercase())
+            && tag
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    };
+    if !arg.split('.').all(valid) {
+        bail!("Invalid python tag `{arg}`, expected e.g. `py3`, `cp39` or `py2.py3`");
+    }
+    Ok(arg.to_string())
+}
+
+/// Parses a `--soabi` such as `pyston-23-x86_64-linux-gnu`
+fn parse_soabi(arg: &str) -> Result<String> {
+    let valid = arg.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        bail!(
+            "Invalid soabi `{arg}`, expected letters, digits, `_`, `-` and `.` \
+            such as `pyston-23-x86_64-linux-gnu`"
+        );
+    }
+    Ok(arg.to_string())
+}
+
+/// Validates `[tool.maturin.static-libpython]`, returning whether the build links libpython
+/// statically
+///
+/// This requires `PYO3_CONFIG_FILE`, pyo3 only links libpython statically with `shared=false`.
+fn check_static_libpython(bridge: &BridgeModel, target: &Target) -> Result<bool> {
+    if !matches!(bridge, BridgeModel::Bin(Some(..))) {
+        eprintln!(
+            "⚠️  Warning: `tool.maturin.static-libpython` is ignored, it only applies to bin \
+            bindings that embed python, not to {bridge}"
+        );
+        return Ok(false);
+    }
+    if !target.is_linux() {
+        eprintln!(
+            "⚠️  Warning: `tool.maturin.static-libpython` is ignored, it's only supported on linux"
+        );
+        return Ok(false);
+    }
+    if !target.is_musl_libc() {
+        eprintln!(
+            "⚠️  Warning: The executable still links glibc dynamically with \
+            `tool.maturin.static-libpython`, consider a musl target for a portable executable"
+        );
+    }
+    let Some(config_file) = env::var_os("PYO3_CONFIG_FILE") else {
+        bail!(
+            "`tool.maturin.static-libpython` requires `PYO3_CONFIG_FILE` to point at the \
+            pyo3 config file of a static python build"
+        );
+    };
+    let config_file = PathBuf::from(config_file);
+    let config = fs::read_to_string(&config_file)?;
+    if let Some(problem) = static_libpython_problem(&config) {
+        eprintln!(
+            "⚠️  Warning: {problem} in the pyo3 config file {}, so the executable most likely \
+            needs a dynamic libpython for `tool.maturin.static-libpython`",
+            config_file.display()
+        );
+    }
+    Ok(true)
+}
+
+/// Explains why a pyo3 config file doesn't describe a static libpython, or returns `None` if it does
+fn static_libpython_problem(config: &str) -> Option<String> {
+    let values: HashMap<&str, &str> = config
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    // pyo3 defaults to a shared libpython
+    if values.get("shared") != Some(&"false") {
+        return Some("`shared` isn't `false`".to_string());
+    }
+    let Some(lib_name) = values.get("lib_name") else {
+        return Some("`lib_name` is missing".to_string());
+    };
+    if let Some(lib_dir) = values.get("lib_dir") {
+        let archive = Path::new(lib_dir).join(format!("lib{lib_name}.a"));
+        if !archive.is_file() {
+            return Some(format!("`{}` doesn't exist", archive.display()));
+        }
+    }
+    None
+}
+
+/// Errors when `--write-interpreter-config` would be ignored, since pyo3 only reads the config
+/// file for bindings that are built for a specific interpreter
+fn check_write_interpreter_config(bridge: &BridgeModel) -> Result<()> {
+    if env::var_os("PYO3_CONFIG_FILE").is_some() {
+        bail!(
+            "`--write-interpreter-config` can't be used together with `PYO3_CONFIG_FILE`, \
+            pyo3 already reads the interpreter configuration from that file"
+        );
+    }
+    let version_specific = matches!(
+        bridge,
+        BridgeModel::Bindings(..) | BridgeModel::Bin(Some(..))
+    );
+    if !version_specific || !(bridge.is_bindings("pyo3") || bridge.is_bindings("pyo3-ffi")) {
+        bail!(
+            "`--write-interpreter-config` only applies to pyo3 and pyo3-ffi bindings that are \
+            built for a specific interpreter, not to {bridge}"
+        );
+    }
+    Ok(())
+}
+
+/// Warns when `--python-tag` claims a broader or different compatibility than the built
+/// artifact has, and errors if it would give the wheels of several interpreters the same name
+fn check_python_tag(
+    bridge: &BridgeModel,
+    python_tag: &str,
+    interpreter: &[PythonInterpreter],
+) -> Result<()> {
+    // abi3 wheels keep their own tag, only the version specific wheels use `--python-tag`
+    let tagged: Vec<_> = match bridge {
+        BridgeModel::Bindings(..) | BridgeModel::Bin(Some(..)) => interpreter.iter().collect(),
+        BridgeModel::BindingsAbi3 { .. } => interpreter
+            .iter()
+            .filter(|interp| !interp.has_stable_api())
+            .collect(),
+        _ => Vec::new(),
+    };
+    if tagged.len() > 1 {
+        bail!(
+            "`--python-tag {python_tag}` would give the wheels for {} the same file name, \
+            build for one interpreter at a time instead",
+            tagged.iter().join(", ")
+        );
+    }
+    if let (BridgeModel::Bindings(..), [interp]) = (bridge, tagged.as_slice()) {
+        let cpython_tags: Vec<_> = python_tag
+            .split('.')
+            .filter_map(|tag| tag.strip_prefix("cp"))
+            .collect();
+        let interp_tag = format!("{}{}", interp.major, interp.minor);
+        if !cpython_tags.is_empty() && !cpython_tags.contains(&interp_tag.as_str()) {
+            eprintln!(
+                "⚠️  Warning: The python tag `{python_tag}` doesn't match {interp}, which the \
+                extension module is built for"
+            );
+        }
+    }
+    match bridge {
+        BridgeModel::Bindings(..) if python_tag.split('.').any(|tag| !tag.starts_with("cp")) => {
+            eprintln!(
+                "⚠️  Warning: The extension module is built for a specific python version, \
+                so it's most likely not importable by all interpreters matching the `{python_tag}` python tag"
+            );
+        }
+        BridgeModel::BindingsAbi3 { .. } => {
+            eprintln!("⚠️  Warning: `--python-tag` is ignored for abi3 wheels");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parses a `KEY=VALUE` argument of `--env`
+fn parse_env_var(arg: &str) -> Result<(String, String)> {
+    let (key, value) = arg
+        .split_once('=')
+        .with_context(|| format!("Expected `KEY=VALUE`, got `{arg}`"))?;
+    if key.is_empty() {
+        bail!("Expected `KEY=VALUE`, got an empty key in `{arg}`");
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
 /// pyo3 supports building abi3 wheels if the unstable-api feature is not selected
 fn has_abi3(deps: &HashMap<&str, &Node>) -> Result<Option<(u8, u8)>> {
     for &lib in PYO3_BINDING_CRATES.iter() {
@@ -1206,6 +2003,163 @@ fn find_single_python_interpreter(
     Ok(interpreter)
 }
 
+/// Expands `-i` entries containing glob patterns such as `/opt/pythons/cp3*/bin/python3`
+/// into the matching paths, in sorted order
+fn expand_interpreter_globs(interpreter: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::with_capacity(interpreter.len());
+    for interp in interpreter {
+        let pattern = interp.to_string_lossy();
+        if !pattern.contains(['*', '?', '[']) {
+            expanded.push(interp.clone());
+            continue;
+        }
+        let mut matches = glob::glob(&pattern)
+            .with_context(|| format!("Invalid interpreter glob `{pattern}`"))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            bail!("The interpreter glob `{pattern}` didn't match any files");
+        }
+        matches.sort();
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+/// Parses an `auto:<major>.<minor>` interpreter spec, returns `None` for anything else
+fn parse_auto_interpreter(interpreter: &Path) -> Option<Result<(usize, usize)>> {
+    let version = interpreter.to_str()?.strip_prefix("auto:")?;
+    let parsed = version
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+        .with_context(|| {
+            format!("Invalid interpreter `auto:{version}`, expected e.g. `auto:3.12`")
+        });
+    Some(parsed)
+}
+
+/// Replaces `auto:<major>.<minor>` in `-i` with the matching CPython found on the host
+///
+/// Unlike a bare version such as `3.12`, which may fall back to the bundled sysconfigs,
+/// this requires a runnable interpreter
+fn resolve_auto_interpreters(
+    interpreter: &[PathBuf],
+    target: &Target,
+    bridge: &BridgeModel,
+) -> Result<Vec<PathBuf>> {
+    let mut host_interpreters = None;
+    let mut resolved = Vec::with_capacity(interpreter.len());
+    for interp in interpreter {
+        let Some(version) = parse_auto_interpreter(interp) else {
+            resolved.push(interp.clone());
+            continue;
+        };
+        let (major, minor) = version?;
+        let host_interpreters = match &mut host_interpreters {
+            Some(found) => found,
+            None => host_interpreters.insert(
+                PythonInterpreter::find_all(target, bridge, None)
+                    .context("Finding python interpreters failed")?,
+            ),
+        };
+        let found = host_interpreters
+            .iter()
+            .find(|found| {
+                found.runnable
+                    && found.interpreter_kind == InterpreterKind::CPython
+                    && (found.major, found.minor) == (major, minor)
+            })
+            .with_context(|| {
+                format!(
+                    "Couldn't find a python {major}.{minor} interpreter for `{}`",
+                    interp.display()
+                )
+            })?;
+        resolved.push(found.executable.clone());
+    }
+    Ok(resolved)
+}
+
+/// Parses the output of `conda info -e` into the names and prefixes of the environments
+///
+/// Environments created with `--prefix` have no name and are skipped.
+fn parse_conda_envs(output: &str) -> Vec<(&str, PathBuf)> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(char::is_whitespace)?;
+            // The active environment is marked with a `*` and frozen ones with a `+`
+            let prefix = rest
+                .trim_start_matches(|c: char| c == '*' || c == '+' || c.is_whitespace())
+                .trim_end();
+            (!prefix.is_empty()).then(|| (name, PathBuf::from(prefix)))
+        })
+        .collect()
+}
+
+/// Replaces `conda:<env>` in `-i` with the python of the conda environment with that name
+fn resolve_conda_interpreters(
+    interpreter: &[PathBuf],
+    target: &Target,
+    bridge: &BridgeModel,
+) -> Result<Vec<PathBuf>> {
+    let mut conda_info = None;
+    let mut resolved = Vec::with_capacity(interpreter.len());
+    for interp in interpreter {
+        let Some(env_name) = interp.to_str().and_then(|x| x.strip_prefix("conda:")) else {
+            resolved.push(interp.clone());
+            continue;
+        };
+        let conda_info = match &mut conda_info {
+            Some(output) => output,
+            None => conda_info.insert(conda_env_list()?),
+        };
+        let (_, prefix) = parse_conda_envs(conda_info)
+            .into_iter()
+            .find(|(name, _)| *name == env_name)
+            .with_context(|| {
+                format!("There is no conda environment named `{env_name}` (from `conda info -e`)")
+            })?;
+        let executable = if target.is_windows() {
+            prefix.join("python.exe")
+        } else {
+            prefix.join("bin").join("python")
+        };
+        if PythonInterpreter::check_executable(&executable, target, bridge)?.is_none() {
+            bail!(
+                "The conda environment `{env_name}` has no working python interpreter at {}",
+                executable.display()
+            );
+        }
+        resolved.push(executable);
+    }
+    Ok(resolved)
+}
+
+/// Runs `conda info -e`, preferring the conda of the activated environment (`CONDA_EXE`)
+fn conda_env_list() -> Result<String> {
+    let conda = env::var_os("CONDA_EXE")
+        .filter(|conda| !conda.is_empty())
+        .unwrap_or_else(|| "conda".into());
+    let output = match Command::new(&conda).args(["info", "-e"]).output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail!(
+                "`-i conda:<env>` requires conda, but `{}` wasn't found",
+                conda.to_string_lossy()
+            )
+        }
+        Err(err) => return Err(err).context("Failed to run `conda info -e`"),
+    };
+    if !output.status.success() {
+        bail!(
+            "`conda info -e` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("`conda info -e` returned non-utf8 output")
+}
+
 /// Find python interpreters in host machine first,
 /// fallback to bundled sysconfig if not found in host machine
 fn find_interpreter(
@@ -1235,11 +2189,7 @@ fn find_interpreter(
 
     if found_interpreters.is_empty() {
         if interpreter.is_empty() {
-            if let Some(requires_python) = requires_python {
-                bail!("Couldn't find any python interpreters with version {}. Please specify at least one with -i", requires_python);
-            } else {
-                bail!("Couldn't find any python interpreters. Please specify at least one with -i");
-            }
+            return Err(no_interpreter_found(bridge, target, requires_python));
         } else {
             let interps_str = interpreter
                 .iter()
@@ -1270,15 +2220,58 @@ fn find_interpreter_in_host(
     };
 
     if interpreters.is_empty() {
-        if let Some(requires_python) = requires_python {
-            bail!("Couldn't find any python interpreters with {}. Please specify at least one with -i", requires_python);
-        } else {
-            bail!("Couldn't find any python interpreters. Please specify at least one with -i");
-        }
+        return Err(no_interpreter_found(bridge, target, requires_python));
     }
     Ok(interpreters)
 }
 
+/// The error for when the search for interpreters came up empty, listing the interpreters
+/// that `requires-python` excluded if there is one
+fn no_interpreter_found(
+    bridge: &BridgeModel,
+    target: &Target,
+    requires_python: Option<&VersionSpecifiers>,
+) -> anyhow::Error {
+    let Some(requires_python) = requires_python else {
+        return format_err!(
+            "Couldn't find any python interpreters. Please specify at least one with -i"
+        );
+    };
+    let excluded = PythonInterpreter::find_all(target, bridge, None).unwrap_or_default();
+    format_err!(requires_python_mismatch_message(requires_python, &excluded))
+}
+
+fn requires_python_mismatch_message(
+    requires_python: &VersionSpecifiers,
+    excluded: &[PythonInterpreter],
+) -> String {
+    let mut message =
+        format!("Couldn't find any python interpreters matching `requires-python = \"{requires_python}\"`. ");
+    if excluded.is_empty() {
+        message.push_str("No other python interpreters were found either. ");
+    } else {
+        let excluded = excluded
+            .iter()
+            .map(|interpreter| {
+                format!(
+                    "{} {}.{} at {}",
+                    interpreter.interpreter_kind,
+                    interpreter.major,
+                    interpreter.minor,
+                    interpreter.executable.display()
+                )
+            })
+            .join(", ");
+        message.push_str(&format!(
+            "The discovered interpreters {excluded} are excluded by `requires-python`. "
+        ));
+    }
+    message.push_str(
+        "Please specify a matching one with -i, or use `--ignore-requires-python` to build anyway",
+    );
+    message
+}
+
 /// Find python interpreters in the bundled sysconfig
 fn find_interpreter_in_sysconfig(
     bridge: &BridgeModel,
@@ -1504,13 +2497,50 @@ impl CargoOptions {
 
         args_from_pyproject
     }
+
+    /// Replace the `default` feature with the individual default features except for the
+    /// excluded ones, as configured with `tool.maturin.default-features-except`
+    pub(crate) fn exclude_default_features(
+        &mut self,
+        default_features: &[String],
+        excluded: &[String],
+    ) -> Result<()> {
+        if self.no_default_features || self.all_features {
+            eprintln!(
+                "⚠️  Warning: Ignoring `tool.maturin.default-features-except` because {} is set",
+                if self.all_features {
+                    "--all-features"
+                } else {
+                    "--no-default-features"
+                }
+            );
+            return Ok(());
+        }
+        for feature in excluded {
+            if !default_features.contains(feature) {
+                bail!(
+                    "`{}` in `tool.maturin.default-features-except` is not a default feature, the default features are: {}",
+                    feature,
+                    default_features.join(", ")
+                );
+            }
+        }
+        self.no_default_features = true;
+        for feature in default_features {
+            if !excluded.contains(feature) && !self.features.contains(feature) {
+                self.features.push(feature.clone());
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use cargo_metadata::MetadataCommand;
+    use fs_err as fs;
+    use indoc::indoc;
     use pretty_assertions::assert_eq;
-    use std::path::Path;
 
     use super::*;
 
@@ -1531,6 +2561,133 @@ mod test {
         ));
     }
 
+    #[test]
+    fn test_require_clean_worktree() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=maturin",
+                    "-c",
+                    "user.email=maturin@example.com",
+                ])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .current_dir(repo.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+        };
+        // Not a git repository
+        let err = require_clean_worktree(repo.path()).unwrap_err();
+        assert!(err.to_string().contains("needs a git repository"), "{err}");
+
+        git(&["init", "-q"]);
+        fs::write(repo.path().join("README.md"), "1").unwrap();
+        git(&["add", "README.md"]);
+        git(&["commit", "-q", "-m", "first"]);
+        require_clean_worktree(repo.path()).unwrap();
+
+        // Ignored files are fine, modified and untracked files are not
+        fs::write(
+            repo.path().join(".git").join("info").join("exclude"),
+            "target/\n",
+        )
+        .unwrap();
+        fs::create_dir(repo.path().join("target")).unwrap();
+        fs::write(repo.path().join("target").join("lib.so"), "").unwrap();
+        require_clean_worktree(repo.path()).unwrap();
+        fs::write(repo.path().join("README.md"), "2").unwrap();
+        let err = require_clean_worktree(repo.path()).unwrap_err();
+        assert!(err.to_string().contains(" M README.md"), "{err}");
+        git(&["commit", "-q", "-am", "second"]);
+        fs::write(repo.path().join("new.py"), "").unwrap();
+        let err = require_clean_worktree(repo.path()).unwrap_err();
+        assert!(err.to_string().contains("?? new.py"), "{err}");
+    }
+
+    #[test]
+    fn test_resolve_workspace_members() {
+        let metadata = MetadataCommand::new()
+            .manifest_path(Path::new("test-crates/pyo3-workspace-members").join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let bridge = find_bridge(&metadata, None).unwrap();
+
+        let members = resolve_workspace_members(
+            &metadata,
+            &["plugin-a".to_string()],
+            &bridge,
+            "pyo3_workspace_members",
+        )
+        .unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].module_name, "plugin_a");
+        assert_eq!(members[0].crate_name, "plugin-a");
+        assert!(members[0]
+            .manifest_path
+            .ends_with(Path::new("plugins/plugin-a/Cargo.toml")));
+
+        let err = resolve_workspace_members(
+            &metadata,
+            &["plugin-b".to_string()],
+            &bridge,
+            "pyo3_workspace_members",
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("isn't a package of the cargo workspace"));
+        let err = resolve_workspace_members(
+            &metadata,
+            &["pyo3-workspace-members".to_string()],
+            &bridge,
+            "pyo3_workspace_members",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("is the main package"));
+        let err = resolve_workspace_members(
+            &metadata,
+            &["plugin-a".to_string()],
+            &BridgeModel::Cffi,
+            "pyo3_workspace_members",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("only supported for pyo3 bindings"));
+    }
+
+    #[test]
+    fn test_resolve_workspace_members_virtual_root() {
+        let workspace = tempfile::tempdir().unwrap();
+        let member = workspace.path().join("member");
+        fs::create_dir_all(member.join("src")).unwrap();
+        fs::write(
+            workspace.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"member\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"member\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        fs::write(member.join("src").join("lib.rs"), "").unwrap();
+        let metadata = MetadataCommand::new()
+            .manifest_path(workspace.path().join("Cargo.toml"))
+            .no_deps()
+            .exec()
+            .unwrap();
+        let bridge = BridgeModel::Bindings(Bindings {
+            name: "pyo3".to_string(),
+            version: semver::Version::new(0, 23, 4),
+        });
+
+        let err = resolve_workspace_members(&metadata, &["member".to_string()], &bridge, "member")
+            .unwrap_err();
+        assert!(err.to_string().contains("virtual workspace root"), "{err}");
+    }
+
     #[test]
     fn test_find_bridge_pyo3_abi3() {
         let pyo3_pure = MetadataCommand::new()
@@ -1619,6 +2776,534 @@ mod test {
         ));
     }
 
+    #[test]
+    fn test_filter_cargo_targets_cdylib_required_features() {
+        let crate_dir = tempfile::tempdir().unwrap();
+        let crate_path = crate_dir.path();
+        fs::create_dir_all(crate_path.join("src")).unwrap();
+        fs::create_dir_all(crate_path.join("examples")).unwrap();
+        fs::write(crate_path.join("src/lib.rs"), "").unwrap();
+        fs::write(crate_path.join("examples/ext.rs"), "").unwrap();
+        fs::write(
+            crate_path.join("Cargo.toml"),
+            indoc!(
+                r#"
+                [package]
+                name = "gated"
+                version = "0.1.0"
+                edition = "2021"
+
+                [features]
+                ext = []
+
+                [[example]]
+                name = "ext"
+                crate-type = ["cdylib"]
+                required-features = ["ext"]
+                "#
+            ),
+        )
+        .unwrap();
+
+        let without_feature = MetadataCommand::new()
+            .manifest_path(crate_path.join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let targets = filter_cargo_targets(&without_feature, BridgeModel::Cffi, None).unwrap();
+        let names: Vec<_> = targets.iter().map(|t| t.target.name.as_str()).collect();
+        assert_eq!(names, ["gated"]);
+
+        let with_feature = MetadataCommand::new()
+            .manifest_path(crate_path.join("Cargo.toml"))
+            .features(cargo_metadata::CargoOpt::SomeFeatures(vec![
+                "ext".to_string()
+            ]))
+            .exec()
+            .unwrap();
+        let targets = filter_cargo_targets(&with_feature, BridgeModel::Cffi, None).unwrap();
+        let names: Vec<_> = targets.iter().map(|t| t.target.name.as_str()).collect();
+        assert_eq!(names, ["ext"]);
+    }
+
+    #[test]
+    fn test_filter_cargo_targets_dylib() {
+        let crate_dir = tempfile::tempdir().unwrap();
+        let crate_path = crate_dir.path();
+        fs::create_dir_all(crate_path.join("src")).unwrap();
+        fs::write(crate_path.join("src/lib.rs"), "").unwrap();
+        fs::write(
+            crate_path.join("Cargo.toml"),
+            indoc!(
+                r#"
+                [package]
+                name = "dynamic"
+                version = "0.1.0"
+                edition = "2021"
+
+                [lib]
+                crate-type = ["dylib"]
+                "#
+            ),
+        )
+        .unwrap();
+
+        let cargo_metadata = MetadataCommand::new()
+            .manifest_path(crate_path.join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let err = filter_cargo_targets(&cargo_metadata, BridgeModel::Cffi, None).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("`crate-type = [\"dylib\"]`"), "{message}");
+        assert!(
+            message.contains("[lib]\ncrate-type = [\"cdylib\"]"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_filter_cargo_targets_same_named_bin_and_cdylib() {
+        use crate::pyproject_toml::{CargoCrateType, CargoTarget};
+
+        let crate_dir = tempfile::tempdir().unwrap();
+        let crate_path = crate_dir.path();
+        fs::create_dir_all(crate_path.join("src")).unwrap();
+        fs::write(crate_path.join("src/lib.rs"), "").unwrap();
+        fs::write(crate_path.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(
+            crate_path.join("Cargo.toml"),
+            indoc!(
+                r#"
+                [package]
+                name = "both"
+                version = "0.1.0"
+                edition = "2021"
+
+                [lib]
+                crate-type = ["cdylib"]
+                "#
+            ),
+        )
+        .unwrap();
+        let cargo_metadata = MetadataCommand::new()
+            .manifest_path(crate_path.join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let config_target = |kind| CargoTarget {
+            name: "both".to_string(),
+            kind,
+        };
+
+        let err = filter_cargo_targets(
+            &cargo_metadata,
+            BridgeModel::Cffi,
+            Some(&[config_target(None)]),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("the package has cdylib and bin targets"),
+            "{err}"
+        );
+        assert!(err.contains("kind = \"cdylib\""), "{err}");
+
+        let targets = filter_cargo_targets(
+            &cargo_metadata,
+            BridgeModel::Bin(None),
+            Some(&[config_target(Some(CargoCrateType::Bin))]),
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 1);
+        assert!(targets[0].target.is_bin());
+
+        let targets = filter_cargo_targets(
+            &cargo_metadata,
+            BridgeModel::Cffi,
+            Some(&[config_target(Some(CargoCrateType::CDyLib))]),
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 1);
+        assert!(targets[0].target.crate_types.contains(&CrateType::CDyLib));
+    }
+
+    #[test]
+    fn test_is_debug_profile() {
+        assert!(is_debug_profile(None, false));
+        assert!(!is_debug_profile(None, true));
+        assert!(is_debug_profile(Some("dev"), false));
+        assert!(!is_debug_profile(Some("release"), false));
+        assert!(!is_debug_profile(Some("dist"), false));
+    }
+
+    #[test]
+    fn test_parse_auto_interpreter() {
+        assert_eq!(
+            parse_auto_interpreter(Path::new("auto:3.12"))
+                .unwrap()
+                .unwrap(),
+            (3, 12)
+        );
+        assert!(parse_auto_interpreter(Path::new("auto:3"))
+            .unwrap()
+            .is_err());
+        assert!(parse_auto_interpreter(Path::new("auto:pypy3.10"))
+            .unwrap()
+            .is_err());
+        assert!(parse_auto_interpreter(Path::new("python3.12")).is_none());
+        assert!(parse_auto_interpreter(Path::new("3.12")).is_none());
+    }
+
+    #[test]
+    fn test_check_interpreter_lock() {
+        let locked = |executable: &str, minor: usize| LockedInterpreter {
+            executable: PathBuf::from(executable),
+            environment_signature: format!("cpython-3.{minor}-64bit"),
+            soabi: Some(format!("cpython-3{minor}-x86_64-linux-gnu")),
+            config: InterpreterConfig {
+                major: 3,
+                minor,
+                interpreter_kind: InterpreterKind::CPython,
+                abiflags: String::new(),
+                ext_suffix: format!(".cpython-3{minor}-x86_64-linux-gnu.so"),
+                pointer_width: Some(64),
+                gil_disabled: false,
+            },
+        };
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let lock_file = tmp_dir.path().join("interpreters.lock");
+        let python312 = locked("/usr/bin/python3.12", 12);
+        let python313 = locked("/usr/bin/python3.13", 13);
+        fs::write(
+            &lock_file,
+            serde_json::to_string_pretty(std::slice::from_ref(&python312)).unwrap(),
+        )
+        .unwrap();
+
+        check_interpreter_lock(&lock_file, std::slice::from_ref(&python312)).unwrap();
+        let err = check_interpreter_lock(&lock_file, &[python312.clone(), python313.clone()])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("+ not in the lock: /usr/bin/python3.13 (cpython-313-x86_64-linux-gnu)")
+        );
+        assert!(!err.contains("missing"));
+        // An interpreter that changed version at the same path
+        let err = check_interpreter_lock(&lock_file, &[locked("/usr/bin/python3.12", 13)])
+            .unwrap_err()
+            .to_string();
+        assert!(err
+            .contains("- missing or changed: /usr/bin/python3.12 (cpython-312-x86_64-linux-gnu)"));
+        assert!(
+            err.contains("+ not in the lock: /usr/bin/python3.12 (cpython-313-x86_64-linux-gnu)")
+        );
+    }
+
+    #[test]
+    fn test_parse_conda_envs() {
+        let output = indoc! {r#"
+            # conda environments:
+            #
+            base                     /opt/conda
+            py312                 *  /opt/conda/envs/py312
+            frozen                * +  /opt/conda/envs/frozen
+            spaced                   /home/user/my envs/spaced
+                                     /tmp/prefix-env
+        "#};
+        assert_eq!(
+            parse_conda_envs(output),
+            [
+                ("base", PathBuf::from("/opt/conda")),
+                ("py312", PathBuf::from("/opt/conda/envs/py312")),
+                ("frozen", PathBuf::from("/opt/conda/envs/frozen")),
+                ("spaced", PathBuf::from("/home/user/my envs/spaced")),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_resolve_interpreters_abi3_above_host() {
+        // The abi3 minimum version is only a floor for the wheel tag, a build interpreter with a
+        // lower version must not be rejected
+        let bridge = BridgeModel::BindingsAbi3 {
+            bindings: Bindings {
+                name: "pyo3".to_string(),
+                version: semver::Version::new(0, 23, 4),
+            },
+            major: 3,
+            minor: 99,
+        };
+        let target = Target::from_target_triple(None).unwrap();
+        let interpreters = resolve_interpreters(
+            &BuildOptions::default(),
+            Path::new("."),
+            &bridge,
+            &target,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(!interpreters.is_empty());
+    }
+
+    #[test]
+    fn test_expand_interpreter_globs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        for version in ["cp312", "cp310", "cp311"] {
+            let bin = tmp_dir.path().join(version).join("bin");
+            fs::create_dir_all(&bin).unwrap();
+            fs::write(bin.join("python3"), "").unwrap();
+        }
+        let pattern = tmp_dir.path().join("cp3*").join("bin").join("python3");
+        let expanded =
+            expand_interpreter_globs(&[PathBuf::from("python3.9"), pattern.clone()]).unwrap();
+        assert_eq!(
+            expanded,
+            [
+                PathBuf::from("python3.9"),
+                tmp_dir.path().join("cp310/bin/python3"),
+                tmp_dir.path().join("cp311/bin/python3"),
+                tmp_dir.path().join("cp312/bin/python3"),
+            ]
+        );
+
+        let no_match = tmp_dir.path().join("pypy*").join("bin").join("python3");
+        assert!(expand_interpreter_globs(&[no_match]).is_err());
+    }
+
+    #[test]
+    fn test_parse_interpreters_env() {
+        assert_eq!(
+            parse_interpreters_env("python3.11,python3.12"),
+            [PathBuf::from("python3.11"), PathBuf::from("python3.12")]
+        );
+        assert_eq!(
+            parse_interpreters_env(" python3.11 , auto:3.13\n/usr/bin/python3 "),
+            [
+                PathBuf::from("python3.11"),
+                PathBuf::from("auto:3.13"),
+                PathBuf::from("/usr/bin/python3")
+            ]
+        );
+        assert!(parse_interpreters_env("").is_empty());
+        assert!(parse_interpreters_env(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_parse_python_tag() {
+        assert_eq!(parse_python_tag("py3").unwrap(), "py3");
+        assert_eq!(parse_python_tag("cp39").unwrap(), "cp39");
+        assert_eq!(parse_python_tag("py2.py3").unwrap(), "py2.py3");
+        assert!(parse_python_tag("").is_err());
+        assert!(parse_python_tag("py3-none").is_err());
+        assert!(parse_python_tag("Py3").is_err());
+        assert!(parse_python_tag("py3.").is_err());
+        assert!(parse_python_tag("3").is_err());
+    }
+
+    #[test]
+    fn test_check_write_interpreter_config() {
+        let pyo3 = Bindings {
+            name: "pyo3".to_string(),
+            version: semver::Version::new(0, 23, 4),
+        };
+        assert!(check_write_interpreter_config(&BridgeModel::Bindings(pyo3.clone())).is_ok());
+        let err = check_write_interpreter_config(&BridgeModel::BindingsAbi3 {
+            bindings: pyo3,
+            major: 3,
+            minor: 8,
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("only applies to pyo3 and pyo3-ffi bindings"));
+        assert!(check_write_interpreter_config(&BridgeModel::Cffi).is_err());
+    }
+
+    #[test]
+    fn test_check_python_tag() {
+        let target = Target::from_target_triple(None).unwrap();
+        let interpreter = |minor| {
+            let config =
+                InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, minor), "")
+                    .unwrap()
+                    .clone();
+            PythonInterpreter::from_config(config)
+        };
+        let bindings = BridgeModel::Bindings(Bindings {
+            name: "pyo3".to_string(),
+            version: semver::Version::new(0, 23, 4),
+        });
+        assert!(check_python_tag(&bindings, "cp312", &[interpreter(12)]).is_ok());
+        // Only warns about the mismatch
+        assert!(check_python_tag(&bindings, "cp39", &[interpreter(12)]).is_ok());
+        let err =
+            check_python_tag(&bindings, "cp312", &[interpreter(11), interpreter(12)]).unwrap_err();
+        assert!(err.to_string().contains("the same file name"), "{err}");
+
+        // The abi3 wheel has its own tag
+        let abi3 = BridgeModel::BindingsAbi3 {
+            bindings: Bindings {
+                name: "pyo3".to_string(),
+                version: semver::Version::new(0, 23, 4),
+            },
+            major: 3,
+            minor: 9,
+        };
+        assert!(check_python_tag(&abi3, "py3", &[interpreter(11), interpreter(12)]).is_ok());
+        assert!(check_python_tag(&BridgeModel::Cffi, "py3", &[interpreter(12)]).is_ok());
+    }
+
+    #[test]
+    fn test_static_libpython_problem() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        fs::write(tmp_dir.path().join("libpython3.12.a"), "").unwrap();
+        let lib_dir = tmp_dir.path().display();
+        let config = format!(
+            "implementation=CPython\nversion=3.12\nshared=false\nlib_name=python3.12\nlib_dir={lib_dir}\n"
+        );
+        assert_eq!(static_libpython_problem(&config), None);
+        assert_eq!(
+            static_libpython_problem(&config.replace("shared=false", "shared=true")).unwrap(),
+            "`shared` isn't `false`"
+        );
+        assert_eq!(
+            static_libpython_problem("implementation=CPython\nversion=3.12\n").unwrap(),
+            "`shared` isn't `false`"
+        );
+        assert_eq!(
+            static_libpython_problem("shared=false\n").unwrap(),
+            "`lib_name` is missing"
+        );
+        assert!(
+            static_libpython_problem(&config.replace("python3.12", "python3.13"))
+                .unwrap()
+                .contains("libpython3.13.a")
+        );
+    }
+
+    #[test]
+    fn test_parse_soabi() {
+        assert_eq!(
+            parse_soabi("pyston-23-x86_64-linux-gnu").unwrap(),
+            "pyston-23-x86_64-linux-gnu"
+        );
+        assert_eq!(parse_soabi("cpython-312.1").unwrap(), "cpython-312.1");
+        assert!(parse_soabi("").is_err());
+        assert!(parse_soabi("-pyston").is_err());
+        assert!(parse_soabi("pyston 23").is_err());
+        assert!(parse_soabi("pyston/23").is_err());
+    }
+
+    #[test]
+    fn test_default_manylinux_tag() {
+        let target = Target::from_target_triple(None).unwrap();
+        if !target.is_linux() || target.is_musl_libc() {
+            assert_eq!(default_manylinux_tag(&target, || Some((2, 17))), None);
+            return;
+        }
+        let floor = target.get_minimum_manylinux_tag();
+        let PlatformTag::Manylinux { x, y } = floor else {
+            unreachable!()
+        };
+        // A container at or below the rustc floor, e.g. manylinux2014
+        assert_eq!(default_manylinux_tag(&target, || Some((x, y))), Some(floor));
+        assert_eq!(
+            default_manylinux_tag(&target, || Some((2, 5))),
+            Some(PlatformTag::manylinux1())
+        );
+        // A newer host leaves the choice to auditwheel
+        assert_eq!(default_manylinux_tag(&target, || Some((2, 99))), None);
+        assert_eq!(default_manylinux_tag(&target, || None), None);
+    }
+
+    #[test]
+    fn test_requires_python_mismatch_message() {
+        let target = Target::from_target_triple(None).unwrap();
+        let config = InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, 12), "")
+            .unwrap()
+            .clone();
+        let mut interpreter = PythonInterpreter::from_config(config);
+        interpreter.executable = PathBuf::from("/usr/bin/python3.12");
+        let requires_python: VersionSpecifiers = ">=3.13".parse().unwrap();
+
+        let message = requires_python_mismatch_message(&requires_python, &[interpreter]);
+        assert!(message.contains(r#"`requires-python = ">=3.13"`"#));
+        assert!(message.contains("CPython 3.12 at /usr/bin/python3.12"));
+        assert!(message.contains("--ignore-requires-python"));
+
+        let message = requires_python_mismatch_message(&requires_python, &[]);
+        assert!(message.contains("No other python interpreters were found"));
+    }
+
+    #[test]
+    fn test_check_interpreter_pointer_width() {
+        let target = Target::from_target_triple(None).unwrap();
+        let mut config =
+            InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, 12), "")
+                .unwrap()
+                .clone();
+        let mut interpreter = PythonInterpreter::from_config(config.clone());
+        interpreter.runnable = true;
+        interpreter.executable = PathBuf::from("python3.12");
+        check_interpreter_pointer_width(&[interpreter.clone()], &target).unwrap();
+
+        config.pointer_width = Some(if target.pointer_width() == 64 { 32 } else { 64 });
+        interpreter.config = config;
+        let err = check_interpreter_pointer_width(&[interpreter.clone()], &target).unwrap_err();
+        assert!(err.to_string().contains(&format!(
+            "but the target {} is {}-bit",
+            target.target_triple(),
+            target.pointer_width()
+        )));
+
+        // Configurations that can't be run aren't checked
+        interpreter.runnable = false;
+        check_interpreter_pointer_width(&[interpreter], &target).unwrap();
+    }
+
+    #[test]
+    fn test_parse_env_var() {
+        assert_eq!(
+            parse_env_var("FOO=bar=baz").unwrap(),
+            ("FOO".to_string(), "bar=baz".to_string())
+        );
+        assert_eq!(
+            parse_env_var("FOO=").unwrap(),
+            ("FOO".to_string(), String::new())
+        );
+        assert!(parse_env_var("FOO").is_err());
+        assert!(parse_env_var("=bar").is_err());
+    }
+
+    #[test]
+    fn test_exclude_default_features() {
+        let defaults = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut cargo_options = CargoOptions {
+            features: vec!["d".to_string()],
+            ..Default::default()
+        };
+        cargo_options
+            .exclude_default_features(&defaults, &["b".to_string()])
+            .unwrap();
+        assert!(cargo_options.no_default_features);
+        assert_eq!(cargo_options.features, vec!["d", "a", "c"]);
+
+        let mut cargo_options = CargoOptions::default();
+        assert!(cargo_options
+            .exclude_default_features(&defaults, &["e".to_string()])
+            .is_err());
+
+        let mut cargo_options = CargoOptions {
+            all_features: true,
+            ..Default::default()
+        };
+        cargo_options
+            .exclude_default_features(&defaults, &["b".to_string()])
+            .unwrap();
+        assert!(!cargo_options.no_default_features);
+        assert!(cargo_options.features.is_empty());
+    }
+
     #[test]
     fn test_old_extra_feature_args() {
         let cargo_extra_args = CargoOptions {
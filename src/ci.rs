@@ -111,6 +111,10 @@ pub struct GenerateCI {
     /// Skip artifact attestation
     #[arg(long)]
     pub skip_attestation: bool,
+    /// Add a job that installs the sdist in a clean environment, to catch files
+    /// missing from it
+    #[arg(long)]
+    pub test_sdist: bool,
 }
 
 impl Default for GenerateCI {
@@ -128,6 +132,7 @@ impl Default for GenerateCI {
             pytest: false,
             zig: false,
             skip_attestation: false,
+            test_sdist: false,
         }
     }
 }
@@ -586,6 +591,51 @@ jobs:\n",
 ",
             );
             conf.push('\n');
+
+            if self.test_sdist {
+                needs.push("sdist-test".to_string());
+                conf.push_str(
+                    r#"  sdist-test:
+    runs-on: ubuntu-latest
+    needs: [sdist]
+    steps:
+"#,
+                );
+                if self.pytest {
+                    conf.push_str("      - uses: actions/checkout@v4\n");
+                }
+                conf.push_str(
+                    r#"      - uses: actions/download-artifact@v4
+        with:
+          name: wheels-sdist
+          path: dist
+      - uses: actions/setup-python@v5
+        with:
+          python-version: 3.x
+      - name: Install sdist
+        shell: bash
+        run: |
+          python -m venv .venv
+          source .venv/bin/activate
+          pip install dist/*.tar.gz
+"#,
+                );
+                if self.pytest {
+                    let mut chdir = String::new();
+                    if let Some(manifest_path) = self.manifest_path.as_ref() {
+                        if manifest_path != Path::new("Cargo.toml") {
+                            let parent = manifest_path.parent().unwrap();
+                            chdir = format!("cd {} && ", parent.display());
+                        }
+                    }
+                    conf.push_str(&format!(
+                        "          pip install pytest
+          {chdir}python -m pytest
+"
+                    ));
+                }
+                conf.push('\n');
+            }
         }
 
         conf.push_str(&format!(
@@ -1076,6 +1126,61 @@ mod tests {
         expected.assert_eq(&conf);
     }
 
+    #[test]
+    fn test_generate_github_test_sdist() {
+        let conf = GenerateCI {
+            test_sdist: true,
+            ..Default::default()
+        }
+        .generate_github(
+            "example",
+            &BridgeModel::Bindings(Bindings {
+                name: "pyo3".to_string(),
+                version: Version::new(0, 23, 0),
+            }),
+            true,
+        )
+        .unwrap();
+        let start = conf.find("  sdist-test:").unwrap();
+        let end = conf.find("  release:").unwrap();
+        // Jobs are indented by two spaces, which `expect!` would strip
+        let job = conf[start..end]
+            .lines()
+            .map(|line| line.strip_prefix("  ").unwrap_or(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let expected = expect![[r#"
+            sdist-test:
+              runs-on: ubuntu-latest
+              needs: [sdist]
+              steps:
+                - uses: actions/download-artifact@v4
+                  with:
+                    name: wheels-sdist
+                    path: dist
+                - uses: actions/setup-python@v5
+                  with:
+                    python-version: 3.x
+                - name: Install sdist
+                  shell: bash
+                  run: |
+                    python -m venv .venv
+                    source .venv/bin/activate
+                    pip install dist/*.tar.gz
+        "#]];
+        expected.assert_eq(&job);
+        assert!(conf.contains("needs: [linux, musllinux, windows, macos, sdist, sdist-test]"));
+
+        // Without a pyproject.toml there's no sdist to test
+        let conf = GenerateCI {
+            test_sdist: true,
+            ..Default::default()
+        }
+        .generate_github("example", &BridgeModel::Bin(None), false)
+        .unwrap();
+        assert!(!conf.contains("sdist-test"));
+    }
+
     #[test]
     fn test_generate_github_no_attestations() {
         let conf = GenerateCI {
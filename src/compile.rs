@@ -1,14 +1,16 @@
-use crate::target::RUST_1_64_0;
+use crate::target::{Os, RUST_1_64_0};
 #[cfg(feature = "zig")]
 use crate::PlatformTag;
 use crate::{BridgeModel, BuildContext, PythonInterpreter, Target};
 use anyhow::{anyhow, bail, Context, Result};
+use cargo_metadata::diagnostic::Diagnostic;
 use cargo_metadata::CrateType;
 use fat_macho::FatWriter;
 use fs_err::{self as fs, File};
 use normpath::PathExt;
 use std::collections::HashMap;
 use std::env;
+use std::ffi::{OsStr, OsString};
 use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
@@ -27,6 +29,31 @@ pub(crate) const LIB_CRATE_TYPES: [CrateType; 4] = [
     CrateType::StaticLib,
 ];
 
+/// How rustc emits the debug info, passed to the extension build as `-C split-debuginfo`
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDebuginfo {
+    /// Keep the debug info in the artifact, or in the object files on macOS
+    Off,
+    /// Collect the debug info in a single `.dSYM`/`.dwp`/`.pdb` file next to the artifact
+    Packed,
+    /// Leave the debug info in the object files or `.dwo` files, the fastest option
+    Unpacked,
+}
+
+impl SplitDebuginfo {
+    /// The value of the rustc codegen option
+    pub fn name(self) -> &'static str {
+        match self {
+            SplitDebuginfo::Off => "off",
+            SplitDebuginfo::Packed => "packed",
+            SplitDebuginfo::Unpacked => "unpacked",
+        }
+    }
+}
+
 /// A cargo target to build
 #[derive(Debug, Clone)]
 pub struct CompileTarget {
@@ -46,6 +73,15 @@ pub struct BuildArtifact {
     pub linked_paths: Vec<String>,
 }
 
+/// The result of [compile_with_diagnostics]
+#[derive(Debug)]
+pub struct CompileOutput {
+    /// The artifacts of each target like [compile()] returns them, or why the build failed
+    pub artifacts: Result<Vec<HashMap<CrateType, BuildArtifact>>>,
+    /// The errors and warnings reported by rustc in the order they were emitted
+    pub diagnostics: Vec<Diagnostic>,
+}
+
 /// Builds the rust crate into a native module (i.e. an .so or .dll) for a
 /// specific python version. Returns a mapping from crate type (e.g. cdylib)
 /// to artifact location.
@@ -54,29 +90,71 @@ pub fn compile(
     python_interpreter: Option<&PythonInterpreter>,
     targets: &[CompileTarget],
 ) -> Result<Vec<HashMap<CrateType, BuildArtifact>>> {
+    compile_collecting(context, python_interpreter, targets, None)
+}
+
+/// Like [compile()], but instead of printing the compiler messages, collects them as structured
+/// diagnostics with their spans, e.g. for showing them in an IDE
+///
+/// The diagnostics are also returned when the build fails.
+pub fn compile_with_diagnostics(
+    context: &BuildContext,
+    python_interpreter: Option<&PythonInterpreter>,
+    targets: &[CompileTarget],
+) -> CompileOutput {
+    let mut diagnostics = Vec::new();
+    let artifacts =
+        compile_collecting(context, python_interpreter, targets, Some(&mut diagnostics));
+    CompileOutput {
+        artifacts,
+        diagnostics,
+    }
+}
+
+/// Compiles the targets, collecting the compiler messages into `diagnostics` if given and
+/// printing them otherwise
+fn compile_collecting(
+    context: &BuildContext,
+    python_interpreter: Option<&PythonInterpreter>,
+    targets: &[CompileTarget],
+    diagnostics: Option<&mut Vec<Diagnostic>>,
+) -> Result<Vec<HashMap<CrateType, BuildArtifact>>> {
+    if let Some(wrapper) = rustc_wrapper(context) {
+        eprintln!("🔧 Using rustc wrapper {}", wrapper.to_string_lossy());
+    }
     if context.universal2 {
-        compile_universal2(context, python_interpreter, targets)
+        compile_universal2(context, python_interpreter, targets, diagnostics)
     } else {
-        compile_targets(context, python_interpreter, targets)
+        compile_targets(context, python_interpreter, targets, diagnostics)
     }
 }
 
 /// Build an universal2 wheel for macos which contains both an x86 and an aarch64 binary
+///
+/// Both binaries are built against the same interpreter, so e.g. a free-threaded interpreter
+/// gets the same `t` abi for both architectures.
 fn compile_universal2(
     context: &BuildContext,
     python_interpreter: Option<&PythonInterpreter>,
     targets: &[CompileTarget],
+    mut diagnostics: Option<&mut Vec<Diagnostic>>,
 ) -> Result<Vec<HashMap<CrateType, BuildArtifact>>> {
     let mut aarch64_context = context.clone();
     aarch64_context.target = Target::from_target_triple(Some("aarch64-apple-darwin".to_string()))?;
 
-    let aarch64_artifacts = compile_targets(&aarch64_context, python_interpreter, targets)
-        .context("Failed to build a aarch64 library through cargo")?;
+    let aarch64_artifacts = compile_targets(
+        &aarch64_context,
+        python_interpreter,
+        targets,
+        diagnostics.as_deref_mut(),
+    )
+    .context("Failed to build a aarch64 library through cargo")?;
     let mut x86_64_context = context.clone();
     x86_64_context.target = Target::from_target_triple(Some("x86_64-apple-darwin".to_string()))?;
 
-    let x86_64_artifacts = compile_targets(&x86_64_context, python_interpreter, targets)
-        .context("Failed to build a x86_64 library through cargo")?;
+    let x86_64_artifacts =
+        compile_targets(&x86_64_context, python_interpreter, targets, diagnostics)
+            .context("Failed to build a x86_64 library through cargo")?;
 
     let mut universal_artifacts = Vec::with_capacity(targets.len());
     for (bridge_model, (aarch64_artifact, x86_64_artifact)) in targets
@@ -143,11 +221,17 @@ fn compile_targets(
     context: &BuildContext,
     python_interpreter: Option<&PythonInterpreter>,
     targets: &[CompileTarget],
+    mut diagnostics: Option<&mut Vec<Diagnostic>>,
 ) -> Result<Vec<HashMap<CrateType, BuildArtifact>>> {
     let mut artifacts = Vec::with_capacity(targets.len());
     for target in targets {
-        let build_command = cargo_build_command(context, python_interpreter, target)?;
-        artifacts.push(compile_target(context, build_command)?);
+        let build_command =
+            cargo_build_command(context, python_interpreter, target, diagnostics.is_some())?;
+        artifacts.push(compile_target(
+            context,
+            build_command,
+            diagnostics.as_deref_mut(),
+        )?);
     }
     Ok(artifacts)
 }
@@ -156,11 +240,17 @@ fn cargo_build_command(
     context: &BuildContext,
     python_interpreter: Option<&PythonInterpreter>,
     compile_target: &CompileTarget,
+    json_diagnostics: bool,
 ) -> Result<Command> {
     let target = &context.target;
 
     let mut cargo_rustc: cargo_options::Rustc = context.cargo_options.clone().into();
-    cargo_rustc.message_format = vec!["json-render-diagnostics".to_string()];
+    // Usually cargo renders the diagnostics to stderr, unless we want to collect them
+    cargo_rustc.message_format = if json_diagnostics {
+        vec!["json".to_string()]
+    } else {
+        vec!["json-render-diagnostics".to_string()]
+    };
 
     // --release and --profile are conflicting options
     if context.release && cargo_rustc.profile.is_none() {
@@ -279,7 +369,31 @@ fn cargo_build_command(
         cargo_rustc.args.extend(emscripten_args);
     }
 
-    if context.strip {
+    if context.static_libpython {
+        // Extension modules loaded by the embedded interpreter need the python symbols, which
+        // aren't exported from the executable when libpython is linked statically
+        let static_libpython_args = [
+            "-C".to_string(),
+            "link-arg=-Wl,--export-dynamic".to_string(),
+        ];
+        debug!(
+            "Setting additional linker args for a static libpython: {:?}",
+            static_libpython_args
+        );
+        cargo_rustc.args.extend(static_libpython_args);
+    }
+
+    if let Some(split_debuginfo) = context.split_debuginfo {
+        // https://doc.rust-lang.org/rustc/codegen-options/index.html#split-debuginfo
+        cargo_rustc.args.extend([
+            "-C".to_string(),
+            format!("split-debuginfo={}", split_debuginfo.name()),
+        ]);
+    }
+
+    // With `--emit-symbols` the debug info is split off after the build instead,
+    // see [split_debug_info]
+    if context.strip && context.emit_symbols.is_none() {
         // https://doc.rust-lang.org/rustc/codegen-options/index.html#strip
         cargo_rustc
             .args
@@ -427,6 +541,13 @@ fn cargo_build_command(
 
             // rust-cpython, and legacy pyo3 versions
             build_command.env("PYTHON_SYS_EXECUTABLE", &interpreter.executable);
+
+            // Only set for version specific pyo3 bindings, see `check_write_interpreter_config`
+            if let Some(config_file) = &context.interpreter_config_file {
+                let config_file = write_pyo3_config(config_file, interpreter)?;
+                debug!("Setting PYO3_CONFIG_FILE to {}", config_file.display());
+                build_command.env("PYO3_CONFIG_FILE", config_file);
+            }
         } else if (bridge_model.is_bindings("pyo3")
             || bridge_model.is_bindings("pyo3-ffi")
             || (matches!(bridge_model, BridgeModel::BindingsAbi3 { .. })
@@ -434,26 +555,16 @@ fn cargo_build_command(
                     || interpreter.interpreter_kind.is_graalpy())))
             && env::var_os("PYO3_CONFIG_FILE").is_none()
         {
-            let pyo3_config = interpreter.pyo3_config_file();
-            let maturin_target_dir = context.target_dir.join("maturin");
-            let config_file = maturin_target_dir.join(format!(
-                "pyo3-config-{}-{}.{}.txt",
-                target_triple, interpreter.major, interpreter.minor
-            ));
-            fs::create_dir_all(&maturin_target_dir)?;
-            // We don't want to rewrite the file every time as that will make cargo
-            // trigger a rebuild of the project every time
-            let existing_pyo3_config = fs::read_to_string(&config_file).unwrap_or_default();
-            if pyo3_config != existing_pyo3_config {
-                fs::write(&config_file, pyo3_config).with_context(|| {
-                    format!(
-                        "Failed to create pyo3 config file at '{}'",
-                        config_file.display()
-                    )
-                })?;
-            }
-            let abs_config_file = config_file.normalize()?.into_path_buf();
-            build_command.env("PYO3_CONFIG_FILE", abs_config_file);
+            let config_file = match &context.interpreter_config_file {
+                Some(config_file) => config_file.clone(),
+                None => context.maturin_build_dir().join(format!(
+                    "pyo3-config-{}-{}.{}.txt",
+                    target_triple, interpreter.major, interpreter.minor
+                )),
+            };
+            let config_file = write_pyo3_config(&config_file, interpreter)?;
+            debug!("Setting PYO3_CONFIG_FILE to {}", config_file.display());
+            build_command.env("PYO3_CONFIG_FILE", config_file);
         }
     }
 
@@ -463,7 +574,7 @@ fn cargo_build_command(
 
     // Set default macOS deployment target version for non-editable builds
     if !context.editable && target.is_macos() && env::var_os("MACOSX_DEPLOYMENT_TARGET").is_none() {
-        use crate::build_context::rustc_macosx_target_version;
+        use crate::build_context::default_macosx_deployment_target;
 
         let target_config = context
             .pyproject_toml
@@ -478,20 +589,57 @@ fn cargo_build_command(
             );
             deployment_target.clone()
         } else {
-            let (major, minor) = rustc_macosx_target_version(target_triple);
-            eprintln!(
-                "💻 Using `MACOSX_DEPLOYMENT_TARGET={major}.{minor}` for {target_triple} by default"
-            );
+            let interpreter = python_interpreter.filter(|_| {
+                matches!(
+                    bridge_model,
+                    BridgeModel::Bindings(..) | BridgeModel::Bin(Some(..))
+                )
+            });
+            let (major, minor) = default_macosx_deployment_target(target_triple, interpreter);
+            match interpreter.and_then(|interp| interp.macosx_deployment_target.as_ref()) {
+                Some(interp_target) => eprintln!(
+                    "💻 Using `MACOSX_DEPLOYMENT_TARGET={major}.{minor}` for {target_triple} by default (interpreter built for {interp_target})"
+                ),
+                None => eprintln!(
+                    "💻 Using `MACOSX_DEPLOYMENT_TARGET={major}.{minor}` for {target_triple} by default"
+                ),
+            }
             format!("{major}.{minor}")
         };
         build_command.env("MACOSX_DEPLOYMENT_TARGET", deployment_target);
     }
+
+    if let Some(wrapper) = &context.rustc_wrapper {
+        build_command.env("RUSTC_WRAPPER", wrapper);
+    }
+
+    // Variables from `--env` take precedence over the ones set by maturin
+    build_command.envs(context.env.iter().map(|(key, value)| (key, value)));
     Ok(build_command)
 }
 
+/// The rustc wrapper cargo will use, from `--env`, `--rustc-wrapper`, `RUSTC_WRAPPER` or
+/// `CARGO_BUILD_RUSTC_WRAPPER` in that order. cargo treats an empty value as no wrapper.
+///
+/// Wrappers configured in `.cargo/config.toml` aren't detected.
+fn rustc_wrapper(context: &BuildContext) -> Option<OsString> {
+    let from_env_flag = context
+        .env
+        .iter()
+        .rev()
+        .find(|(key, _)| key == "RUSTC_WRAPPER")
+        .map(|(_, value)| OsString::from(value));
+    let wrapper = from_env_flag
+        .or_else(|| context.rustc_wrapper.clone().map(OsString::from))
+        .or_else(|| env::var_os("RUSTC_WRAPPER"))
+        .or_else(|| env::var_os("CARGO_BUILD_RUSTC_WRAPPER"))?;
+    (!wrapper.is_empty()).then_some(wrapper)
+}
+
 fn compile_target(
     context: &BuildContext,
     mut build_command: Command,
+    mut diagnostics: Option<&mut Vec<Diagnostic>>,
 ) -> Result<HashMap<CrateType, BuildArtifact>> {
     debug!("Running {:?}", build_command);
 
@@ -575,9 +723,10 @@ fn compile_target(
                     }
                 }
             }
-            cargo_metadata::Message::CompilerMessage(msg) => {
-                println!("{}", msg.message);
-            }
+            cargo_metadata::Message::CompilerMessage(msg) => match diagnostics.as_deref_mut() {
+                Some(diagnostics) => diagnostics.push(msg.message),
+                None => println!("{}", msg.message),
+            },
             _ => (),
         }
     }
@@ -602,8 +751,31 @@ fn compile_target(
     Ok(artifacts)
 }
 
+/// Writes the pyo3 config file for `interpreter` to `config_file`, returning its absolute path
+fn write_pyo3_config(config_file: &Path, interpreter: &PythonInterpreter) -> Result<PathBuf> {
+    let pyo3_config = interpreter.pyo3_config_file();
+    if let Some(dir) = config_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        fs::create_dir_all(dir)?;
+    }
+    // We don't want to rewrite the file every time as that will make cargo
+    // trigger a rebuild of the project every time
+    let existing_pyo3_config = fs::read_to_string(config_file).unwrap_or_default();
+    if pyo3_config != existing_pyo3_config {
+        fs::write(config_file, pyo3_config).with_context(|| {
+            format!(
+                "Failed to create pyo3 config file at '{}'",
+                config_file.display()
+            )
+        })?;
+    }
+    Ok(config_file.normalize()?.into_path_buf())
+}
+
 /// Checks that the native library contains a function called `PyInit_<module name>` and warns
-/// if it's missing.
+/// if it's missing, naming the `PyInit_` functions it found instead.
 ///
 /// That function is the python's entrypoint for loading native extensions, i.e. python will fail
 /// to import the module with error if it's missing or named incorrectly
@@ -611,71 +783,163 @@ fn compile_target(
 /// Currently the check is only run on linux, macOS and Windows
 #[instrument(skip_all)]
 pub fn warn_missing_py_init(artifact: &Path, module_name: &str) -> Result<()> {
-    let py_init = format!("PyInit_{module_name}");
     let mut fd = File::open(artifact)?;
     let mut buffer = Vec::new();
     fd.read_to_end(&mut buffer)?;
-    let mut found = false;
-    match goblin::Object::parse(&buffer)? {
+    let Some(py_inits) = find_py_init_symbols(&buffer)? else {
+        return Ok(());
+    };
+    if let Some(message) = missing_py_init_message(module_name, &py_inits) {
+        eprintln!("⚠️  Warning: {message}");
+    }
+    Ok(())
+}
+
+/// Returns the names of the exported `PyInit_` functions of a native library, without the
+/// prefix, or `None` for formats other than ELF, Mach-O and PE
+fn find_py_init_symbols(buffer: &[u8]) -> Result<Option<Vec<String>>> {
+    let mut names: Vec<String> = Vec::new();
+    match goblin::Object::parse(buffer)? {
         goblin::Object::Elf(elf) => {
             for dyn_sym in elf.dynsyms.iter() {
-                if py_init == elf.dynstrtab[dyn_sym.st_name] {
-                    found = true;
-                    break;
-                }
+                names.extend(elf.dynstrtab.get_at(dyn_sym.st_name).map(str::to_string));
             }
         }
         goblin::Object::Mach(mach) => {
             match mach {
                 goblin::mach::Mach::Binary(macho) => {
                     for sym in macho.exports()? {
-                        let sym_name = sym.name;
-                        if py_init == sym_name.strip_prefix('_').unwrap_or(&sym_name) {
-                            found = true;
-                            break;
-                        }
+                        names.push(sym.name.strip_prefix('_').unwrap_or(&sym.name).to_string());
                     }
-                    if !found {
-                        for sym in macho.symbols() {
-                            let (sym_name, _) = sym?;
-                            if py_init == sym_name.strip_prefix('_').unwrap_or(sym_name) {
-                                found = true;
-                                break;
-                            }
-                        }
+                    for sym in macho.symbols() {
+                        let (sym_name, _) = sym?;
+                        names.push(sym_name.strip_prefix('_').unwrap_or(sym_name).to_string());
                     }
                 }
                 goblin::mach::Mach::Fat(_) => {
                     // Ignore fat macho,
                     // we only generate them by combining thin binaries which is handled above
-                    found = true
+                    return Ok(None);
                 }
             }
         }
         goblin::Object::PE(pe) => {
-            for sym in &pe.exports {
-                if let Some(sym_name) = sym.name {
-                    if py_init == sym_name {
-                        found = true;
-                        break;
-                    }
-                }
-            }
+            names.extend(
+                pe.exports
+                    .iter()
+                    .filter_map(|sym| sym.name.map(str::to_string)),
+            );
         }
         _ => {
             // Currently, only linux, macOS and Windows are implemented
-            found = true
+            return Ok(None);
         }
     }
+    let mut py_inits: Vec<String> = names
+        .iter()
+        .filter_map(|name| name.strip_prefix("PyInit_"))
+        .map(str::to_string)
+        .collect();
+    py_inits.sort();
+    py_inits.dedup();
+    Ok(Some(py_inits))
+}
+
+/// The warning for a library that lacks `PyInit_<module name>`, given the names of the `PyInit_`
+/// functions it has
+fn missing_py_init_message(module_name: &str, py_inits: &[String]) -> Option<String> {
+    if py_inits.iter().any(|name| name == module_name) {
+        return None;
+    }
+    let mut message = format!(
+        "Couldn't find the symbol `PyInit_{module_name}` in the native library. \
+         Python will fail to import this module. "
+    );
+    match py_inits {
+        [] => message.push_str(&format!(
+            "If you're using pyo3, check that `#[pymodule]` uses `{module_name}` as module name"
+        )),
+        [found] => message.push_str(&format!(
+            "The library defines the module `{found}` instead, either rename the `#[pymodule]` \
+             to `{module_name}` or set `module-name` in `[tool.maturin]` to match it"
+        )),
+        found => message.push_str(&format!(
+            "The library defines the modules `{}` instead, check that `#[pymodule]` uses \
+             `{module_name}` as module name",
+            found.join("`, `")
+        )),
+    }
+    Some(message)
+}
 
-    if !found {
-        eprintln!(
-            "⚠️  Warning: Couldn't find the symbol `{py_init}` in the native library. \
-             Python will fail to import this module. \
-             If you're using pyo3, check that `#[pymodule]` uses `{module_name}` as module name"
-        )
+/// Whether `--emit-symbols` can split the debug info off the artifacts of this target, i.e. it
+/// either produces ELF files that objcopy can handle or `.pdb` files with the MSVC linker
+pub(crate) fn supports_split_debug_info(target: &Target) -> bool {
+    target.is_msvc()
+        || (target.is_unix()
+            && !matches!(
+                target.target_os(),
+                Os::Macos | Os::Ios | Os::Emscripten | Os::Wasi | Os::Aix
+            ))
+}
+
+/// The file holding the debug info split off `artifact` by [split_debug_info]
+pub(crate) fn debug_info_path(target: &Target, artifact: &Path) -> PathBuf {
+    if target.is_msvc() {
+        artifact.with_extension("pdb")
+    } else {
+        let mut file_name = artifact.file_name().unwrap().to_os_string();
+        file_name.push(".debug");
+        artifact.with_file_name(file_name)
     }
+}
 
+/// Splits the debug info off `artifact`, which is a copy of the cargo output at `original`, and
+/// puts it at [debug_info_path].
+///
+/// ELF files are stripped in place with objcopy (or `$OBJCOPY`), the build-id is kept and a
+/// debuglink to the `.debug` file is added. With MSVC, the linker has already written a `.pdb`
+/// next to the cargo output, which is copied over.
+pub(crate) fn split_debug_info(target: &Target, original: &Path, artifact: &Path) -> Result<()> {
+    let debug_info = debug_info_path(target, artifact);
+    if target.is_msvc() {
+        // cargo names the pdb after the crate, so `my-bin.exe` comes with `my_bin.pdb`
+        let stem = original.file_stem().unwrap().to_string_lossy();
+        let pdb = [stem.to_string(), stem.replace('-', "_")]
+            .into_iter()
+            .map(|stem| original.with_file_name(format!("{stem}.pdb")))
+            .find(|pdb| pdb.is_file())
+            .with_context(|| {
+                format!(
+                    "Couldn't find the .pdb file for {}, make sure it's built with debug info",
+                    original.display()
+                )
+            })?;
+        fs::copy(&pdb, &debug_info)?;
+        return Ok(());
+    }
+
+    let objcopy = env::var("OBJCOPY").unwrap_or_else(|_| "objcopy".to_string());
+    let run_objcopy = |args: &[&OsStr]| -> Result<()> {
+        let mut command = Command::new(&objcopy);
+        command.args(args);
+        debug!("Running {:?}", command);
+        let status = command.status().with_context(|| {
+            format!("Failed to run {objcopy}, which is required for `--emit-symbols`")
+        })?;
+        if !status.success() {
+            bail!("{objcopy} failed with {status}: `{command:?}`");
+        }
+        Ok(())
+    };
+    let mut debuglink = OsString::from("--add-gnu-debuglink=");
+    debuglink.push(&debug_info);
+    run_objcopy(&[
+        OsStr::new("--only-keep-debug"),
+        artifact.as_os_str(),
+        debug_info.as_os_str(),
+    ])?;
+    run_objcopy(&[OsStr::new("--strip-all"), &debuglink, artifact.as_os_str()])?;
     Ok(())
 }
 
@@ -697,3 +961,162 @@ fn pyo3_version(cargo_metadata: &cargo_metadata::Metadata) -> Option<(u64, u64,
         .or_else(|| packages.get("pyo3-ffi"))
         .map(|pkg| (pkg.version.major, pkg.version.minor, pkg.version.patch))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildOptions;
+    use clap::Parser;
+
+    #[test]
+    fn test_split_debuginfo_reaches_cargo() {
+        let build_options = BuildOptions::try_parse_from([
+            "build",
+            "--manifest-path",
+            "test-crates/hello-world/Cargo.toml",
+            "--split-debuginfo",
+            "unpacked",
+        ])
+        .unwrap();
+        let context = build_options
+            .into_build_context()
+            .release(false)
+            .strip(false)
+            .editable(false)
+            .build()
+            .unwrap();
+        let command =
+            cargo_build_command(&context, None, &context.compile_targets[0], false).unwrap();
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == [OsStr::new("-C"), OsStr::new("split-debuginfo=unpacked")]));
+
+        assert!(BuildOptions::try_parse_from(["build", "--split-debuginfo", "full"]).is_err());
+    }
+
+    #[test]
+    fn test_write_interpreter_config() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_file = tmp_dir.path().join("pyo3-config.txt");
+        let build_options = BuildOptions::try_parse_from([
+            OsStr::new("build"),
+            OsStr::new("--manifest-path"),
+            OsStr::new("test-crates/pyo3-mixed/Cargo.toml"),
+            OsStr::new("--interpreter"),
+            OsStr::new("python3"),
+            OsStr::new("--write-interpreter-config"),
+            config_file.as_os_str(),
+        ])
+        .unwrap();
+        let context = build_options
+            .into_build_context()
+            .release(false)
+            .strip(false)
+            .editable(false)
+            .build()
+            .unwrap();
+        let interpreter = &context.interpreter[0];
+        assert!(interpreter.runnable);
+        let command = cargo_build_command(
+            &context,
+            Some(interpreter),
+            &context.compile_targets[0],
+            false,
+        )
+        .unwrap();
+        let pyo3_config_file = command
+            .get_envs()
+            .find(|(key, _)| *key == "PYO3_CONFIG_FILE")
+            .and_then(|(_, value)| value)
+            .unwrap();
+        assert_eq!(
+            Path::new(pyo3_config_file),
+            config_file.normalize().unwrap().as_path()
+        );
+        let content = fs::read_to_string(&config_file).unwrap();
+        assert!(content.contains(&format!(
+            "version={}.{}",
+            interpreter.major, interpreter.minor
+        )));
+        assert!(content.contains("\nexecutable="), "{content}");
+        if let Some(lib_dir) = &interpreter.lib_dir {
+            assert!(content.contains(&format!("\nlib_dir={}", lib_dir.display())));
+        }
+    }
+
+    #[test]
+    fn test_locked_sdist_reaches_cargo() {
+        use fs_err as fs;
+
+        // An unpacked source distribution with a Cargo.lock
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"locked-sdist\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            root.join("pyproject.toml"),
+            "[build-system]\nrequires = [\"maturin>=1.0,<2.0\"]\nbuild-backend = \"maturin\"\n\n\
+             [tool.maturin]\nbindings = \"bin\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("PKG-INFO"),
+            "Metadata-Version: 2.4\nName: locked-sdist\nVersion: 0.1.0\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"locked-sdist\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let manifest_path = root.join("Cargo.toml");
+        for (locked_sdist, expected) in [(true, true), (false, false)] {
+            let build_options = BuildOptions::try_parse_from([
+                OsStr::new("build"),
+                OsStr::new("--manifest-path"),
+                manifest_path.as_os_str(),
+            ])
+            .unwrap();
+            let context = build_options
+                .into_build_context()
+                .release(true)
+                .strip(false)
+                .editable(false)
+                .locked_sdist(locked_sdist)
+                .build()
+                .unwrap();
+            let command =
+                cargo_build_command(&context, None, &context.compile_targets[0], false).unwrap();
+            let locked = command.get_args().any(|arg| arg == "--locked");
+            assert_eq!(locked, expected);
+        }
+    }
+
+    #[test]
+    fn test_missing_py_init_message() {
+        let found = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(missing_py_init_message("my_ext", &found(&["my_ext"])), None);
+        assert_eq!(
+            missing_py_init_message("my_ext", &found(&["other", "my_ext"])),
+            None
+        );
+
+        let message = missing_py_init_message("my_ext", &found(&["my_module"])).unwrap();
+        assert!(message.starts_with("Couldn't find the symbol `PyInit_my_ext`"));
+        assert!(message.contains("defines the module `my_module` instead"));
+        assert!(message.contains("set `module-name` in `[tool.maturin]`"));
+
+        let message = missing_py_init_message("my_ext", &found(&["a", "b"])).unwrap();
+        assert!(message.contains("defines the modules `a`, `b` instead"));
+
+        let message = missing_py_init_message("my_ext", &[]).unwrap();
+        assert!(message.contains("check that `#[pymodule]` uses `my_ext` as module name"));
+    }
+}
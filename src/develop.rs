@@ -190,6 +190,10 @@ pub struct DevelopOptions {
     /// Strip the library for minimum file size
     #[arg(long)]
     pub strip: bool,
+    /// Strip even when building with a debug profile such as `dev`, where `--strip` is
+    /// ignored by default to keep the symbols of debug builds
+    #[arg(long)]
+    pub force_strip: bool,
     /// Install extra requires aka. optional dependencies
     ///
     /// Use as `--extras=extra1,extra2`
@@ -205,12 +209,29 @@ pub struct DevelopOptions {
     /// Only works with mixed Rust/Python project layout
     #[arg(long)]
     pub skip_install: bool,
+    /// Install a regular wheel instead of an editable install
+    ///
+    /// The Python sources are copied into site-packages like with `pip install <wheel>`,
+    /// so changes to them need another `maturin develop`
+    #[arg(long, conflicts_with = "skip_install")]
+    pub no_editable: bool,
     /// Use a specific pip installation instead of the default one.
     ///
     /// This can be used to supply the path to a pip executable when the
     /// current virtualenv does not provide one.
     #[arg(long)]
     pub pip_path: Option<PathBuf>,
+    /// Extra arguments for installing the dependencies with pip or uv, e.g.
+    /// `--pip-args="--index-url https://example.com/simple"`
+    ///
+    /// They are not used for installing the built wheel itself
+    #[arg(
+        long,
+        value_delimiter = ' ',
+        allow_hyphen_values = true,
+        action = clap::ArgAction::Append
+    )]
+    pub pip_args: Vec<String>,
     /// `cargo rustc` options
     #[command(flatten)]
     pub cargo_options: CargoOptions,
@@ -226,6 +247,7 @@ fn install_dependencies(
     python: &Path,
     venv_dir: &Path,
     install_backend: &InstallBackend,
+    pip_args: &[String],
 ) -> Result<()> {
     if !build_context.metadata24.requires_dist.is_empty() {
         let mut extra_names = Vec::with_capacity(extras.len());
@@ -247,6 +269,7 @@ fn install_dependencies(
             pkg.marker = pkg.marker.simplify_extras(&extra_names);
             pkg.to_string()
         }));
+        args.extend(pip_args.iter().filter(|arg| !arg.is_empty()).cloned());
         let status = install_backend
             .make_command(python)
             .args(&args)
@@ -303,8 +326,10 @@ fn install_wheel(
             String::from_utf8_lossy(&output.stderr).trim(),
         );
     }
-    if let Err(err) = configure_as_editable(build_context, python, install_backend) {
-        eprintln!("⚠️ Warning: failed to set package as editable: {}", err);
+    if build_context.editable {
+        if let Err(err) = configure_as_editable(build_context, python, install_backend) {
+            eprintln!("⚠️ Warning: failed to set package as editable: {}", err);
+        }
     }
     Ok(())
 }
@@ -370,15 +395,25 @@ pub fn develop(develop_options: DevelopOptions, venv_dir: &Path) -> Result<()> {
         bindings,
         release,
         strip,
+        force_strip,
         extras,
         skip_install,
+        no_editable,
         pip_path,
+        pip_args,
         cargo_options,
         uv,
     } = develop_options;
     let mut target_triple = cargo_options.target.as_ref().map(|x| x.to_string());
     let target = Target::from_target_triple(cargo_options.target)?;
     let python = target.get_venv_python(venv_dir);
+    if !python.is_file() {
+        bail!(
+            "Couldn't find a python interpreter at {} in the environment at {}",
+            python.display(),
+            venv_dir.display()
+        );
+    }
 
     // check python platform and architecture
     if !target.user_specified {
@@ -394,10 +429,32 @@ pub fn develop(develop_options: DevelopOptions, venv_dir: &Path) -> Result<()> {
         platform_tag: vec![PlatformTag::Linux],
         interpreter: vec![python.clone()],
         find_interpreter: false,
+        ignore_requires_python: false,
         bindings,
+        keep_going: false,
+        allow_unsupported_target: false,
+        python_tag: None,
+        abi_tag: None,
+        soabi: None,
         out: Some(wheel_dir.path().to_path_buf()),
+        build_dir: None,
+        emit_symbols: None,
+        emit_sbom: None,
+        embed_sbom: false,
+        split_debuginfo: None,
+        sign: None,
+        force_strip,
+        max_wheel_size: None,
+        require_clean_worktree: false,
+        write_interpreter_config: None,
+        write_interpreter_lock: None,
+        interpreter_lock: None,
+        env: Vec::new(),
+        rustc_wrapper: None,
+        extra_hashes: Vec::new(),
         auditwheel: Some(AuditWheelMode::Skip),
         skip_auditwheel: false,
+        no_auditwheel_patchelf: false,
         #[cfg(feature = "zig")]
         zig: false,
         cargo: CargoOptions {
@@ -410,7 +467,7 @@ pub fn develop(develop_options: DevelopOptions, venv_dir: &Path) -> Result<()> {
         .into_build_context()
         .release(release)
         .strip(strip)
-        .editable(true)
+        .editable(!no_editable)
         .build()?;
 
     // Ensure that version information is present, https://github.com/PyO3/maturin/issues/2416
@@ -458,7 +515,14 @@ pub fn develop(develop_options: DevelopOptions, venv_dir: &Path) -> Result<()> {
         }
     };
 
-    install_dependencies(&build_context, &extras, &python, venv_dir, &install_backend)?;
+    install_dependencies(
+        &build_context,
+        &extras,
+        &python,
+        venv_dir,
+        &install_backend,
+        &pip_args,
+    )?;
 
     let wheels = build_context.build_wheels()?;
     if !skip_install {
@@ -484,7 +548,28 @@ pub fn develop(develop_options: DevelopOptions, venv_dir: &Path) -> Result<()> {
 mod test {
     use std::path::PathBuf;
 
-    use super::parse_direct_url_path;
+    use clap::Parser;
+
+    use super::{parse_direct_url_path, DevelopOptions};
+
+    #[test]
+    fn test_pip_args() {
+        let options = DevelopOptions::try_parse_from([
+            "develop",
+            "--pip-args=--index-url https://example.com/simple",
+            "--pip-args",
+            "--no-cache-dir",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.pip_args,
+            [
+                "--index-url",
+                "https://example.com/simple",
+                "--no-cache-dir"
+            ]
+        );
+    }
 
     #[test]
     #[cfg(not(target_os = "windows"))]
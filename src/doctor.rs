@@ -0,0 +1,212 @@
+use crate::auditwheel::patchelf;
+use crate::project_layout::resolve_project_layout;
+use crate::{BridgeModel, PythonInterpreter, Target};
+use anyhow::{bail, Result};
+use std::env;
+use std::fmt;
+use std::path::Path;
+
+/// The outcome of a single `maturin doctor` check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "✅"),
+            CheckStatus::Warn => write!(f, "⚠️ "),
+            CheckStatus::Fail => write!(f, "❌"),
+        }
+    }
+}
+
+/// A single line of the `maturin doctor` checklist
+#[derive(Debug, Clone)]
+struct Check {
+    status: CheckStatus,
+    name: &'static str,
+    detail: String,
+}
+
+impl Check {
+    fn new(status: CheckStatus, name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            name,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.status, self.name, self.detail)
+    }
+}
+
+/// Checks the rust toolchain, returning the host target if rustc could be queried
+fn check_rustc() -> (Check, Option<Target>) {
+    match Target::from_target_triple(None) {
+        Ok(target) => {
+            let check = Check::new(
+                CheckStatus::Pass,
+                "rustc",
+                format!(
+                    "{} (host {})",
+                    target.rustc_version.semver, target.rustc_version.host
+                ),
+            );
+            (check, Some(target))
+        }
+        Err(err) => (
+            Check::new(CheckStatus::Fail, "rustc", format!("{err:#}")),
+            None,
+        ),
+    }
+}
+
+fn check_interpreters(target: Option<&Target>) -> Check {
+    let Some(target) = target else {
+        return Check::new(
+            CheckStatus::Fail,
+            "python",
+            "Can't search for interpreters without a working rustc",
+        );
+    };
+    // We don't know the targeted bindings yet, so we use the most lenient
+    match PythonInterpreter::find_all(target, &BridgeModel::Cffi, None) {
+        Ok(found) if found.is_empty() => Check::new(
+            CheckStatus::Warn,
+            "python",
+            "No python interpreter found, you can only build abi3 wheels and binaries",
+        ),
+        Ok(found) => Check::new(
+            CheckStatus::Pass,
+            "python",
+            found
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Err(err) => Check::new(CheckStatus::Fail, "python", format!("{err:#}")),
+    }
+}
+
+#[cfg(feature = "zig")]
+fn check_zig() -> Check {
+    match cargo_zigbuild::Zig::find_zig() {
+        Ok((zig_cmd, zig_args)) => {
+            let zig = if zig_args.is_empty() {
+                zig_cmd.display().to_string()
+            } else {
+                format!("{} {}", zig_cmd.display(), zig_args.join(" "))
+            };
+            Check::new(CheckStatus::Pass, "zig", format!("found `{zig}`"))
+        }
+        Err(_) => Check::new(
+            CheckStatus::Warn,
+            "zig",
+            "Not found, `--zig` won't work. Hint: Try `pip install ziglang`",
+        ),
+    }
+}
+
+#[cfg(feature = "xwin")]
+fn check_xwin() -> Check {
+    Check::new(
+        CheckStatus::Pass,
+        "xwin",
+        "Cross compiling to windows msvc targets is supported",
+    )
+}
+
+fn check_patchelf() -> Check {
+    if patchelf::is_installed() {
+        Check::new(CheckStatus::Pass, "patchelf", "found")
+    } else {
+        Check::new(
+            CheckStatus::Warn,
+            "patchelf",
+            "Not found, wheels with external shared libraries can't be repaired. \
+            Hint: Try `pip install maturin[patchelf]`",
+        )
+    }
+}
+
+/// Checks whether the current directory `dir` looks like the root of a maturin project
+fn check_project(dir: &Path) -> Check {
+    if !dir.join("pyproject.toml").is_file() && !dir.join("Cargo.toml").is_file() {
+        return Check::new(
+            CheckStatus::Warn,
+            "project",
+            format!(
+                "{} has neither a pyproject.toml nor a Cargo.toml",
+                dir.display()
+            ),
+        );
+    }
+    match resolve_project_layout(None) {
+        Ok(layout) => Check::new(
+            CheckStatus::Pass,
+            "project",
+            format!("found the `{}` module", layout.extension_name),
+        ),
+        Err(err) => Check::new(CheckStatus::Fail, "project", format!("{err:#}")),
+    }
+}
+
+/// Prints a checklist of the build environment: the rust toolchain, the python interpreters,
+/// the optional cross compilation tools and the project in the current directory
+///
+/// This only reuses the discovery that the other commands do and doesn't change anything.
+/// Fails if any of the checks failed.
+pub fn doctor() -> Result<()> {
+    let (rustc, target) = check_rustc();
+    let mut checks = vec![rustc, check_interpreters(target.as_ref())];
+    #[cfg(feature = "zig")]
+    checks.push(check_zig());
+    #[cfg(feature = "xwin")]
+    checks.push(check_xwin());
+    if cfg!(target_os = "linux") {
+        checks.push(check_patchelf());
+    }
+    checks.push(check_project(&env::current_dir()?));
+
+    eprintln!("🩺 maturin {}", env!("CARGO_PKG_VERSION"));
+    for check in &checks {
+        eprintln!("{check}");
+    }
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        bail!("{} of {} checks failed", failed, checks.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_project, check_rustc, CheckStatus};
+    use std::path::Path;
+
+    #[test]
+    fn test_check_rustc() {
+        let (check, target) = check_rustc();
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.detail.contains("host"));
+        assert!(target.is_some());
+    }
+
+    #[test]
+    fn test_check_project_without_manifest() {
+        let check = check_project(Path::new("src"));
+        assert_eq!(check.status, CheckStatus::Warn);
+    }
+}
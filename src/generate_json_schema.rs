@@ -32,13 +32,14 @@ pub struct GenerateJsonSchemaOptions {
 /// Generate the JSON schema for the `pyproject.toml` file.
 pub fn generate_json_schema(args: GenerateJsonSchemaOptions) -> Result<()> {
     let schema = schema_for!(ToolMaturin);
-    let schema_string = serde_json::to_string_pretty(&schema).unwrap();
+    // End the file with a newline like the other files in the repository
+    let schema_string = serde_json::to_string_pretty(&schema).unwrap() + "\n";
     let filename = "maturin.schema.json";
     let schema_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(filename);
 
     match args.mode {
         Mode::DryRun => {
-            println!("{schema_string}");
+            print!("{schema_string}");
         }
         Mode::Check => {
             let current = fs::read_to_string(schema_path)?;
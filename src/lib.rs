@@ -24,25 +24,34 @@
 #![deny(missing_docs)]
 
 pub use crate::bridge::{Bindings, BridgeModel};
-pub use crate::build_context::{BuildContext, BuiltWheelMetadata};
+pub use crate::build_context::{
+    BuildContext, BuiltWheelMetadata, KeepGoingError, PYPI_MAX_WHEEL_SIZE,
+};
 pub use crate::build_options::{BuildOptions, CargoOptions};
 pub use crate::cargo_toml::CargoToml;
-pub use crate::compile::{compile, BuildArtifact};
+pub use crate::compile::{
+    compile, compile_with_diagnostics, BuildArtifact, CompileOutput, SplitDebuginfo,
+};
 pub use crate::develop::{develop, DevelopOptions};
+pub use crate::doctor::doctor;
 #[cfg(feature = "schemars")]
 pub use crate::generate_json_schema::{generate_json_schema, GenerateJsonSchemaOptions, Mode};
 pub use crate::metadata::{Metadata24, WheelMetadata};
 pub use crate::module_writer::{
-    write_dist_info, ModuleWriter, PathWriter, SDistWriter, WheelWriter,
+    rebuild_record, write_dist_archive, write_dist_index, write_dist_info, ExtraHash, ModuleWriter,
+    PathWriter, SDistWriter, WheelWriter,
 };
 #[cfg(feature = "scaffolding")]
-pub use crate::new_project::{init_project, new_project, GenerateProjectOptions};
+pub use crate::new_project::{init_project, new_project, GenerateProjectOptions, LayoutPreset};
+pub use crate::project_layout::{resolve_project_layout, resolve_project_metadata, ProjectLayout};
 pub use crate::pyproject_toml::PyProjectToml;
 pub use crate::python_interpreter::PythonInterpreter;
 pub use crate::target::Target;
 #[cfg(feature = "upload")]
-pub use crate::upload::{upload, upload_ui, PublishOpt, Registry, UploadError};
-pub use auditwheel::PlatformTag;
+pub use crate::upload::{
+    upload, upload_ui, validate_wheel_filename_for_pypi, PublishOpt, Registry, UploadError,
+};
+pub use auditwheel::{audit_wheel, repair_wheel, AuditWheelMode, PlatformTag};
 
 mod auditwheel;
 mod bridge;
@@ -55,6 +64,7 @@ pub mod ci;
 mod compile;
 mod cross_compile;
 mod develop;
+mod doctor;
 mod generate_json_schema;
 mod metadata;
 mod module_writer;
@@ -63,6 +73,7 @@ mod new_project;
 mod project_layout;
 pub mod pyproject_toml;
 mod python_interpreter;
+mod sbom;
 mod source_distribution;
 mod target;
 #[cfg(feature = "upload")]
@@ -10,16 +10,19 @@ use cargo_zigbuild::Zig;
 #[cfg(feature = "cli-completion")]
 use clap::CommandFactory;
 use clap::{Parser, Subcommand};
-#[cfg(feature = "scaffolding")]
-use maturin::{ci::GenerateCI, init_project, new_project, GenerateProjectOptions};
+use fs_err as fs;
 use maturin::{
-    develop, write_dist_info, BridgeModel, BuildOptions, CargoOptions, DevelopOptions, PathWriter,
-    PlatformTag, PythonInterpreter, Target,
+    audit_wheel, develop, doctor, rebuild_record, repair_wheel, resolve_project_metadata,
+    write_dist_archive, write_dist_index, write_dist_info, AuditWheelMode, BridgeModel,
+    BuildOptions, CargoOptions, DevelopOptions, KeepGoingError, PathWriter, PlatformTag,
+    PyProjectToml, PythonInterpreter, Target,
 };
+#[cfg(feature = "scaffolding")]
+use maturin::{ci::GenerateCI, init_project, new_project, GenerateProjectOptions};
 #[cfg(feature = "schemars")]
 use maturin::{generate_json_schema, GenerateJsonSchemaOptions};
 #[cfg(feature = "upload")]
-use maturin::{upload_ui, PublishOpt};
+use maturin::{upload_ui, PublishOpt, PYPI_MAX_WHEEL_SIZE};
 use std::env;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -70,6 +73,38 @@ enum Command {
         /// Build a source distribution
         #[arg(long)]
         sdist: bool,
+        /// Vendor all crates.io and git dependencies into the source distribution for offline builds
+        #[arg(long, requires = "sdist")]
+        vendor_crates: bool,
+        /// The directory to store the source distribution in, defaults to the directory of
+        /// the wheels (`--out`)
+        #[arg(long, requires = "sdist")]
+        sdist_out: Option<PathBuf>,
+        /// Write the wheel's runtime dependencies (`Requires-Dist`) to the given file
+        /// as a pip requirements file
+        #[arg(long, value_name = "PATH")]
+        emit_requirements: Option<PathBuf>,
+        /// Also collect the built wheels and the source distribution into a single zip
+        /// archive with a `manifest.json`, e.g. to pass them between CI jobs
+        #[arg(long, value_name = "PATH")]
+        out_archive: Option<PathBuf>,
+        /// Write an `index.html` linking the built wheels and the source distribution to the
+        /// output directory, for `pip install --find-links <out>/index.html`
+        #[arg(long)]
+        emit_index: bool,
+        /// Write the `cargo metadata` maturin resolved the project with to the given file
+        /// as JSON, e.g. to compare the resolved dependencies and features with plain cargo
+        #[arg(long, value_name = "PATH")]
+        emit_cargo_metadata: Option<PathBuf>,
+        /// Build the wheels twice and fail if they aren't byte-identical, naming the first
+        /// differing file. Requires SOURCE_DATE_EPOCH to be set. This recompiles the crate,
+        /// so it's meant for CI checks rather than regular builds
+        #[arg(long)]
+        require_reproducible: bool,
+        /// Print the version of the package to stdout and exit without building, e.g. for
+        /// release scripts
+        #[arg(long)]
+        print_metadata_version: bool,
         #[command(flatten)]
         build: BuildOptions,
     },
@@ -86,6 +121,9 @@ enum Command {
         /// Don't build a source distribution
         #[arg(long = "no-sdist")]
         no_sdist: bool,
+        /// Vendor all crates.io and git dependencies into the source distribution for offline builds
+        #[arg(long, conflicts_with = "no_sdist")]
+        vendor_crates: bool,
         #[command(flatten)]
         publish: PublishOpt,
         #[command(flatten)]
@@ -97,6 +135,9 @@ enum Command {
         #[arg(long)]
         target: Option<String>,
     },
+    #[command(name = "doctor")]
+    /// Check the rust toolchain, python interpreters and build tools maturin would use
+    Doctor,
     #[command(name = "develop", alias = "dev")]
     /// Install the crate as module in the current virtualenv
     Develop(DevelopOptions),
@@ -114,6 +155,46 @@ enum Command {
         /// directory in the project's target directory
         #[arg(short, long)]
         out: Option<PathBuf>,
+        /// Vendor all crates.io and git dependencies into the source distribution for offline builds
+        #[arg(long)]
+        vendor_crates: bool,
+        /// Append a suffix such as `.dev20240101` or `+nightly` to the version of the source
+        /// distribution, e.g. to upload nightly builds next to releases. The result must be a
+        /// valid PEP 440 version
+        #[arg(long, value_name = "SUFFIX")]
+        version_suffix: Option<String>,
+    },
+    /// Recompute the RECORD of wheels whose files were modified after they were built
+    ///
+    /// The hashes and sizes of all files are updated and the wheels are rewritten in place.
+    #[command(name = "rebuild-record")]
+    RebuildRecord {
+        /// The wheels to fix
+        #[arg(value_name = "WHEEL", required = true)]
+        wheels: Vec<PathBuf>,
+    },
+    /// Repair an already built wheel for manylinux/musllinux compliance
+    ///
+    /// Copies the external shared libraries into the wheel and retags it, like `maturin build`
+    /// does after compiling. This is useful when building and repairing run in different jobs.
+    /// The `allowed-libs` of `[tool.maturin.auditwheel]` are read from the `pyproject.toml`
+    /// in the working directory, if there is one.
+    #[command(name = "repair")]
+    Repair {
+        /// The wheel to repair
+        #[arg(value_name = "WHEEL")]
+        wheel: PathBuf,
+        /// The manylinux/musllinux platform tag to repair for, e.g. `manylinux_2_28`.
+        /// Defaults to the highest compatible tag
+        #[arg(long, alias = "manylinux")]
+        compatibility: Option<PlatformTag>,
+        /// The directory to write the repaired wheel to
+        #[arg(short, long, default_value = "wheelhouse")]
+        out: PathBuf,
+        /// Whether to repair the wheel, or only check it for external libraries that would
+        /// have to be copied into it, leaving the wheel untouched
+        #[arg(long, value_name = "AUDITWHEEL", default_value = "repair")]
+        auditwheel: AuditWheelMode,
     },
     /// Create a new cargo project in an existing directory
     #[cfg(feature = "scaffolding")]
@@ -188,6 +269,9 @@ enum Pep517Command {
         /// Strip the library for minimum file size
         #[arg(long)]
         strip: bool,
+        /// Allow updating the `Cargo.lock` of a source distribution
+        #[arg(long)]
+        no_locked: bool,
     },
     #[command(name = "build-wheel")]
     /// Implementation of build_wheel
@@ -202,6 +286,12 @@ enum Pep517Command {
         /// Build editable wheels
         #[arg(long)]
         editable: bool,
+        /// Allow updating the `Cargo.lock` of a source distribution
+        ///
+        /// Wheels built from a source distribution that contains a `Cargo.lock` use `--locked`
+        /// by default, so cargo fails instead of silently changing the dependencies
+        #[arg(long)]
+        no_locked: bool,
     },
     /// The implementation of build_sdist
     #[command(name = "write-sdist")]
@@ -271,13 +361,16 @@ fn pep517(subcommand: Pep517Command) -> Result<()> {
             build_options,
             metadata_directory,
             strip,
+            no_locked,
         } => {
             assert_eq!(build_options.interpreter.len(), 1);
+            // `cargo metadata` would otherwise update the lockfile before `build-wheel` checks it
             let context = build_options
                 .into_build_context()
                 .release(true)
                 .strip(strip)
                 .editable(false)
+                .locked_sdist(!no_locked)
                 .build()?;
 
             // Since afaik all other PEP 517 backends also return linux tagged wheels, we do so too
@@ -295,19 +388,23 @@ fn pep517(subcommand: Pep517Command) -> Result<()> {
             };
 
             let mut writer = PathWriter::from_path(metadata_directory);
-            write_dist_info(&mut writer, &context.metadata24, &tags)?;
+            let root_is_purelib = context.root_is_purelib()?;
+            write_dist_info(&mut writer, &context.metadata24, &tags, root_is_purelib)?;
             println!("{}", context.metadata24.get_dist_info_dir().display());
         }
         Pep517Command::BuildWheel {
             build_options,
             strip,
             editable,
+            no_locked,
         } => {
+            // `tool.maturin.profile` takes precedence over the release default
             let build_context = build_options
                 .into_build_context()
                 .release(true)
                 .strip(strip)
                 .editable(editable)
+                .locked_sdist(!no_locked)
                 .build()?;
             let wheels = build_context.build_wheels()?;
             assert_eq!(wheels.len(), 1);
@@ -376,34 +473,108 @@ fn run() -> Result<()> {
             release,
             strip,
             sdist,
+            vendor_crates,
+            sdist_out,
+            emit_requirements,
+            out_archive,
+            emit_index,
+            emit_cargo_metadata,
+            require_reproducible,
+            print_metadata_version,
         } => {
+            if print_metadata_version {
+                let metadata24 =
+                    resolve_project_metadata(build.manifest_path.clone(), build.cargo)?;
+                println!("{}", metadata24.version);
+                return Ok(());
+            }
             let build_context = build
                 .into_build_context()
                 .release(release)
                 .strip(strip)
                 .editable(false)
+                .vendor_crates(vendor_crates)
                 .build()?;
-            if sdist {
-                build_context
-                    .build_source_distribution()?
-                    .context("Failed to build source distribution, pyproject.toml not found")?;
+            // Written before building so it's available when the build fails
+            if let Some(path) = emit_cargo_metadata {
+                let metadata = serde_json::to_string_pretty(&build_context.cargo_metadata)?;
+                fs::write(&path, metadata)?;
+                eprintln!("📝 Wrote cargo metadata to {}", path.display());
+            }
+            let sdist = if sdist {
+                let sdist = match sdist_out {
+                    Some(sdist_out) => build_context.build_source_distribution_in(&sdist_out)?,
+                    None => build_context.build_source_distribution()?,
+                }
+                .context("Failed to build source distribution, pyproject.toml not found")?;
+                Some(sdist)
+            } else {
+                None
+            };
+            // With `--keep-going`, the outputs below are still written for the wheels that
+            // were built before reporting the failures
+            let (wheels, failures) = match build_context.build_wheels() {
+                Ok(wheels) => {
+                    assert!(!wheels.is_empty());
+                    (wheels, None)
+                }
+                Err(err) => {
+                    let err = err.downcast::<KeepGoingError>()?;
+                    (err.built.clone(), Some(err))
+                }
+            };
+            if require_reproducible {
+                build_context.check_reproducible(&wheels)?;
+            }
+            let dists: Vec<_> = wheels
+                .iter()
+                .chain(sdist.as_ref())
+                .map(|(path, _)| path.clone())
+                .collect();
+            if let Some(archive) = out_archive {
+                write_dist_archive(&archive, &dists)?;
+                eprintln!(
+                    "📦 Collected {} files into {}",
+                    dists.len(),
+                    archive.display()
+                );
+            }
+            if emit_index {
+                let index = write_dist_index(&dists)?;
+                eprintln!(
+                    "📝 Wrote an index of {} files to {}",
+                    dists.len(),
+                    index.display()
+                );
+            }
+            if let Some(path) = emit_requirements {
+                fs::write(&path, build_context.metadata24.to_requirements_txt())?;
+                eprintln!("📝 Wrote requirements to {}", path.display());
+            }
+            if let Some(failures) = failures {
+                return Err(failures.into());
             }
-            let wheels = build_context.build_wheels()?;
-            assert!(!wheels.is_empty());
         }
         #[cfg(feature = "upload")]
         Command::Publish {
-            build,
+            mut build,
             mut publish,
             debug,
             no_strip,
             no_sdist,
+            vendor_crates,
         } => {
+            // PyPI would reject larger wheels after spending the time on the upload, private
+            // registries may have other limits or none at all
+            if publish.targets_pypi()? {
+                build.max_wheel_size.get_or_insert(PYPI_MAX_WHEEL_SIZE);
+            }
             let build_context = build
                 .into_build_context()
                 .release(!debug)
                 .strip(!no_strip)
                 .editable(false)
+                .vendor_crates(vendor_crates)
                 .build()?;
 
             if !build_context.release {
@@ -436,12 +607,18 @@ fn run() -> Result<()> {
                 eprintln!(" - {interpreter}");
             }
         }
+        Command::Doctor => doctor()?,
         Command::Develop(develop_options) => {
             let target = Target::from_target_triple(develop_options.cargo_options.target.clone())?;
             let venv_dir = detect_venv(&target)?;
             develop(develop_options, &venv_dir)?;
         }
-        Command::SDist { manifest_path, out } => {
+        Command::SDist {
+            manifest_path,
+            out,
+            vendor_crates,
+            version_suffix,
+        } => {
             let build_options = BuildOptions {
                 out,
                 cargo: CargoOptions {
@@ -453,17 +630,57 @@ fn run() -> Result<()> {
                 },
                 ..Default::default()
             };
-            let build_context = build_options
+            let mut build_context = build_options
                 .into_build_context()
                 .release(false)
                 .strip(false)
                 .editable(false)
                 .sdist_only(true)
+                .vendor_crates(vendor_crates)
                 .build()?;
+            if let Some(suffix) = version_suffix {
+                build_context.metadata24.append_version_suffix(&suffix)?;
+            }
             build_context
                 .build_source_distribution()?
                 .context("Failed to build source distribution, pyproject.toml not found")?;
         }
+        Command::RebuildRecord { wheels } => {
+            for wheel in wheels {
+                rebuild_record(&wheel)?;
+                eprintln!("📝 Rebuilt RECORD of {}", wheel.display());
+            }
+        }
+        Command::Repair {
+            wheel,
+            compatibility,
+            out,
+            auditwheel,
+        } => {
+            let pyproject_file = env::current_dir()?.join("pyproject.toml");
+            let pyproject = if pyproject_file.is_file() {
+                Some(PyProjectToml::new(&pyproject_file)?)
+            } else {
+                None
+            };
+            let allowed_libs = pyproject
+                .as_ref()
+                .and_then(|pyproject| pyproject.auditwheel_allowed_libs())
+                .unwrap_or_default();
+            match auditwheel {
+                AuditWheelMode::Repair => {
+                    let repaired = repair_wheel(&wheel, compatibility, allowed_libs, &out)?;
+                    eprintln!("📦 Repaired wheel to {}", repaired.display());
+                }
+                AuditWheelMode::Check => {
+                    let policy = audit_wheel(&wheel, compatibility, allowed_libs)?;
+                    eprintln!("✅ {} is {} compliant", wheel.display(), policy);
+                }
+                AuditWheelMode::Skip => {
+                    bail!("`maturin repair` can't skip auditwheel, use `--auditwheel check` or `--auditwheel repair`")
+                }
+            }
+        }
         Command::Pep517(subcommand) => pep517(subcommand)?,
         #[cfg(feature = "scaffolding")]
         Command::InitProject { path, options } => init_project(path, options)?,
@@ -1,10 +1,12 @@
+use crate::pyproject_toml::{FeatureExtra, VersionSource};
 use crate::PyProjectToml;
 use anyhow::{bail, format_err, Context, Result};
 use fs_err as fs;
 use indexmap::IndexMap;
 use pep440_rs::{Version, VersionSpecifiers};
 use pep508_rs::{
-    ExtraName, ExtraOperator, MarkerExpression, MarkerTree, MarkerValueExtra, Requirement,
+    ExtraName, ExtraOperator, MarkerExpression, MarkerTree, MarkerValueExtra, PackageName,
+    Requirement, VerbatimUrl,
 };
 use pyproject_toml::License;
 use regex::Regex;
@@ -12,6 +14,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Write as _;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::str;
 use std::str::FromStr;
 
@@ -56,12 +59,18 @@ pub struct Metadata24 {
     // https://peps.python.org/pep-0639/#license-file-multiple-use
     pub license_files: Vec<PathBuf>,
     pub classifiers: Vec<String>,
+    /// In declaration order: `project.dependencies` first, then the `optional-dependencies`
+    /// of each extra in the order the extras are declared. The order is not sorted so that the
+    /// METADATA matches pyproject.toml. The dependencies of the `tool.maturin.extras` of enabled
+    /// cargo features come last, sorted by cargo feature name since that table is unordered.
+    /// The order never depends on hashing, so repeated builds write identical METADATA
     pub requires_dist: Vec<Requirement>,
     pub provides_dist: Vec<String>,
     pub obsoletes_dist: Vec<String>,
     pub requires_python: Option<VersionSpecifiers>,
     pub requires_external: Vec<String>,
     pub project_url: IndexMap<String, String>,
+    /// In the order the extras are declared in `project.optional-dependencies`
     pub provides_extra: Vec<String>,
     pub scripts: IndexMap<String, String>,
     pub gui_scripts: IndexMap<String, String>,
@@ -160,6 +169,17 @@ impl Metadata24 {
                 self.version = version.clone();
             }
 
+            if pyproject_toml.version_source() == VersionSource::Scm {
+                if dynamic.contains("version") {
+                    self.version = scm_version(pyproject_dir)?;
+                } else {
+                    eprintln!(
+                        "⚠️  Warning: `tool.maturin.version.source` is ignored because \
+                        `version` is not listed in `project.dynamic`"
+                    );
+                }
+            }
+
             if let Some(description) = &project.description {
                 self.summary = Some(description.clone());
             }
@@ -341,6 +361,131 @@ impl Metadata24 {
                 self.entry_points.clone_from(entry_points);
             }
         }
+
+        if let Some(classifiers_file) = pyproject_toml.classifiers_file() {
+            pyproject_toml.require_dynamic("classifiers", "classifiers-file")?;
+            self.merge_classifiers_file(&pyproject_dir.join(classifiers_file))?;
+        }
+        let mut dependencies = Vec::new();
+        if let Some(tool_dependencies) = pyproject_toml.dependencies() {
+            pyproject_toml.require_dynamic("dependencies", "dependencies")?;
+            for dependency in tool_dependencies {
+                dependencies.push(Requirement::from_str(dependency).with_context(|| {
+                    format!("Invalid dependency `{dependency}` in `tool.maturin.dependencies`")
+                })?);
+            }
+        }
+        if let Some(overrides) = pyproject_toml.dependency_overrides() {
+            // The dependencies of `[tool.maturin.extras]` are only added once the enabled
+            // cargo features are known, but they can be overridden too
+            let feature_extra_dependencies: Vec<_> = pyproject_toml
+                .extras()
+                .into_iter()
+                .flat_map(HashMap::values)
+                .flat_map(|feature_extra| &feature_extra.dependencies)
+                .filter_map(|dependency| Requirement::<VerbatimUrl>::from_str(dependency).ok())
+                .map(|dependency| dependency.name)
+                .collect();
+            apply_dependency_overrides(
+                &mut dependencies,
+                overrides,
+                &feature_extra_dependencies,
+                &self.requires_dist,
+            )?;
+        }
+        // The dependencies come before the ones of the extras
+        self.requires_dist.splice(0..0, dependencies);
+        Ok(())
+    }
+
+    /// Adds the extras of `[tool.maturin.extras]` whose cargo feature is enabled as
+    /// `Provides-Extra` with their `Requires-Dist; extra == '...'` dependencies
+    ///
+    /// Several features can add to the same extra. `[tool.maturin.dependency-overrides]` apply
+    /// to the dependencies like to the ones of `tool.maturin.dependencies`.
+    pub(crate) fn add_feature_extras(
+        &mut self,
+        extras: &HashMap<String, FeatureExtra>,
+        enabled_features: &[String],
+        overrides: Option<&HashMap<String, String>>,
+    ) -> Result<()> {
+        let overrides = match overrides {
+            Some(overrides) => parse_dependency_overrides(overrides)?,
+            None => Vec::new(),
+        };
+        let mut extras: Vec<_> = extras
+            .iter()
+            .filter(|(feature, _)| enabled_features.contains(feature))
+            .collect();
+        extras.sort_by_key(|(feature, _)| *feature);
+        for (feature, feature_extra) in extras {
+            let extra = feature_extra.extra.as_ref().unwrap_or(feature);
+            let extra_name = ExtraName::new(extra.clone()).with_context(|| {
+                format!("invalid extra name `{extra}` for the cargo feature `{feature}`")
+            })?;
+            if !self
+                .provides_extra
+                .iter()
+                .any(|existing| ExtraName::new(existing.clone()).as_ref() == Ok(&extra_name))
+            {
+                self.provides_extra.push(extra.clone());
+            }
+            for dependency in &feature_extra.dependencies {
+                let mut dependency = Requirement::from_str(dependency).with_context(|| {
+                    format!(
+                        "Invalid dependency `{dependency}` of the extra `{extra}` \
+                        in `tool.maturin.extras`"
+                    )
+                })?;
+                let extra_marker = MarkerExpression::Extra {
+                    operator: ExtraOperator::Equal,
+                    name: MarkerValueExtra::Extra(extra_name.clone()),
+                };
+                dependency.marker.and(MarkerTree::expression(extra_marker));
+                if let Some((_, overridden)) = overrides
+                    .iter()
+                    .find(|(_, overridden)| overridden.name == dependency.name)
+                {
+                    dependency
+                        .version_or_url
+                        .clone_from(&overridden.version_or_url);
+                }
+                self.requires_dist.push(dependency);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the classifiers from a file with one classifier per line, skipping empty lines,
+    /// `#` comments and classifiers that are already present
+    fn merge_classifiers_file(&mut self, path: &Path) -> Result<()> {
+        let contents = fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read the classifiers file specified in pyproject.toml, which should be at {}",
+                path.display()
+            )
+        })?;
+        for line in contents.lines() {
+            let classifier = line.trim();
+            if classifier.is_empty() || classifier.starts_with('#') {
+                continue;
+            }
+            let prefix = classifier.split(" :: ").next().unwrap_or_default();
+            if !CLASSIFIER_PREFIXES.contains(&prefix) {
+                eprintln!(
+                    "⚠️  Warning: Unknown classifier `{classifier}` in {}, \
+                    see https://pypi.org/classifiers/ for the valid ones",
+                    path.display()
+                );
+            }
+            if !self
+                .classifiers
+                .iter()
+                .any(|existing| existing == classifier)
+            {
+                self.classifiers.push(classifier.to_string());
+            }
+        }
         Ok(())
     }
 
@@ -548,6 +693,33 @@ impl Metadata24 {
         Ok(out)
     }
 
+    /// Returns the `[project.scripts]` and `[project.gui-scripts]` entries as `(name, target)`
+    /// whose target module isn't one of the top level `modules` of the wheel, so they would
+    /// fail with an `ImportError` after installing
+    pub(crate) fn mismatched_script_targets<'a>(
+        &'a self,
+        modules: &[&str],
+    ) -> Vec<(&'a str, &'a str)> {
+        self.scripts
+            .iter()
+            .chain(&self.gui_scripts)
+            .filter(|(_, target)| {
+                let module = target.split(':').next().unwrap_or_default();
+                let top_level = module.split('.').next().unwrap_or_default().trim();
+                !modules.contains(&top_level)
+            })
+            .map(|(name, target)| (name.as_str(), target.as_str()))
+            .collect()
+    }
+
+    /// Writes `Requires-Dist` as a pip requirements file, one requirement per line
+    pub fn to_requirements_txt(&self) -> String {
+        self.requires_dist
+            .iter()
+            .map(|requirement| format!("{requirement}\n"))
+            .collect()
+    }
+
     /// Returns the distribution name according to PEP 427, Section "Escaping
     /// and Unicode"
     pub fn get_distribution_escaped(&self) -> String {
@@ -561,6 +733,16 @@ impl Metadata24 {
         self.version.to_string().replace('-', "_")
     }
 
+    /// Appends a suffix such as `.dev20240101` or `+nightly` to the version, failing if the
+    /// result isn't a valid PEP 440 version
+    pub fn append_version_suffix(&mut self, suffix: &str) -> Result<()> {
+        let version = format!("{}{}", self.version, suffix);
+        self.version = Version::from_str(&version).map_err(|err| {
+            format_err!("Appending `{suffix}` gives the invalid version `{version}`: {err}")
+        })?;
+        Ok(())
+    }
+
     /// Returns the name of the .dist-info directory as defined in the wheel specification
     pub fn get_dist_info_dir(&self) -> PathBuf {
         PathBuf::from(format!(
@@ -580,6 +762,76 @@ impl Metadata24 {
     }
 }
 
+/// Replaces the version specifier or URL of the dependencies named in
+/// `[tool.maturin.dependency-overrides]`, keeping their extras and markers
+///
+/// Only the dynamic dependencies from `tool.maturin` can be overridden, overriding one of the
+/// `static_dependencies` from `project` is an error.
+fn apply_dependency_overrides(
+    dependencies: &mut [Requirement],
+    overrides: &HashMap<String, String>,
+    feature_extra_dependencies: &[PackageName],
+    static_dependencies: &[Requirement],
+) -> Result<()> {
+    for (name, overridden) in parse_dependency_overrides(overrides)? {
+        let mut found = false;
+        for dependency in dependencies.iter_mut() {
+            if dependency.name == overridden.name {
+                dependency
+                    .version_or_url
+                    .clone_from(&overridden.version_or_url);
+                found = true;
+            }
+        }
+        if found || feature_extra_dependencies.contains(&overridden.name) {
+            continue;
+        }
+        if static_dependencies
+            .iter()
+            .any(|dependency| dependency.name == overridden.name)
+        {
+            bail!(
+                "The dependency override for `{name}` in pyproject.toml would change the \
+                static metadata in `project`, list `dependencies` in `project.dynamic` and \
+                declare them in `tool.maturin.dependencies` instead"
+            );
+        }
+        eprintln!(
+            "⚠️  Warning: The dependency override for `{name}` in pyproject.toml \
+            doesn't match any dependency"
+        );
+    }
+    Ok(())
+}
+
+/// Parses `[tool.maturin.dependency-overrides]` into requirements with the overridden version
+/// specifier or URL, sorted by name
+fn parse_dependency_overrides(
+    overrides: &HashMap<String, String>,
+) -> Result<Vec<(&String, Requirement)>> {
+    let mut overrides: Vec<_> = overrides.iter().collect();
+    overrides.sort();
+    overrides
+        .into_iter()
+        .map(|(name, specifier)| {
+            let overridden =
+                Requirement::from_str(&format!("{name} {specifier}")).with_context(|| {
+                    format!(
+                    "Invalid dependency override `{name} = \"{specifier}\"` in pyproject.toml, \
+                    expected a PEP 508 version specifier such as `>=1.2` or `@ <url>`"
+                )
+                })?;
+            if !overridden.extras.is_empty() || !overridden.marker.is_true() {
+                bail!(
+                    "Invalid dependency override `{name} = \"{specifier}\"` in pyproject.toml, \
+                    only the version specifier or URL can be overridden"
+                );
+            }
+            Ok((name, overridden))
+        })
+        .collect()
+}
+
 /// Escape email addresses with display name if necessary
 /// according to RFC 822 Section 3.3. "specials".
 fn escape_email_with_display_name(display_name: &str, email: &str) -> String {
@@ -597,6 +849,92 @@ fn escape_email_with_display_name(display_name: &str, email: &str) -> String {
     format!("{display_name} <{email}>")
 }
 
+/// The top level categories of the trove classifiers at https://pypi.org/classifiers/
+const CLASSIFIER_PREFIXES: [&str; 11] = [
+    "Development Status",
+    "Environment",
+    "Framework",
+    "Intended Audience",
+    "License",
+    "Natural Language",
+    "Operating System",
+    "Private",
+    "Programming Language",
+    "Topic",
+    "Typing",
+];
+
+/// Computes a PEP 440 version from the latest git tag, similar to setuptools-scm
+///
+/// * `1.2.3` on the commit tagged `v1.2.3` or `1.2.3`
+/// * `1.2.3.post4+g1a2b3c4` four commits after that tag
+/// * `0.0.0+g1a2b3c4` if there is no tag at all
+///
+/// Uncommitted changes add `.dirty` to the local version. Source distributions don't contain the
+/// git history, so when building from one the version is read from `PKG-INFO` instead.
+fn scm_version(project_dir: &Path) -> Result<Version> {
+    let pkg_info = project_dir.join("PKG-INFO");
+    if pkg_info.is_file() {
+        let contents = fs::read_to_string(&pkg_info)?;
+        let version = contents
+            .lines()
+            .find_map(|line| line.strip_prefix("Version: "))
+            .with_context(|| format!("No version in {}", pkg_info.display()))?;
+        return Version::from_str(version.trim()).map_err(|err| format_err!(err));
+    }
+
+    let output = Command::new("git")
+        .args(["describe", "--tags", "--long", "--dirty", "--always"])
+        .current_dir(project_dir)
+        .output()
+        .context("Failed to run `git describe`, is git installed?")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to determine the version from git in {}: {}",
+            project_dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let describe = str::from_utf8(&output.stdout)
+        .context("`git describe` returned non-utf8 output")?
+        .trim();
+
+    let (describe, dirty) = match describe.strip_suffix("-dirty") {
+        Some(describe) => (describe, true),
+        None => (describe, false),
+    };
+    let expr = Regex::new(r"^(.+)-(\d+)-g([0-9a-f]+)$").unwrap();
+    let (base, distance, sha) = match expr.captures(describe) {
+        Some(captures) => {
+            let tag = captures.get(1).unwrap().as_str();
+            let base = Version::from_str(tag.strip_prefix('v').unwrap_or(tag))
+                .map_err(|err| format_err!("git tag `{tag}` is not a valid version: {err}"))?;
+            let distance: u64 = captures[2].parse()?;
+            (base, distance, captures[3].to_string())
+        }
+        // Without any tag `--always` gives us the abbreviated commit hash
+        None => (Version::new([0, 0, 0]), 0, describe.to_string()),
+    };
+
+    let mut version = base.to_string();
+    if distance > 0 {
+        write!(version, ".post{distance}").unwrap();
+    }
+    // The commit hash is prefixed with `g` like in `git describe` so it's never mistaken for a
+    // number, which would get normalized
+    let mut local = Vec::new();
+    if distance > 0 || describe == sha {
+        local.push(format!("g{sha}"));
+    }
+    if dirty {
+        local.push("dirty".to_string());
+    }
+    if !local.is_empty() {
+        write!(version, "+{}", local.join(".")).unwrap();
+    }
+    Version::from_str(&version).map_err(|err| format_err!(err))
+}
+
 /// Fold long header field according to RFC 5322 section 2.2.3
 /// https://datatracker.ietf.org/doc/html/rfc5322#section-2.2.3
 fn fold_header(text: &str) -> String {
@@ -625,7 +963,7 @@ mod test {
     use super::*;
     use cargo_metadata::MetadataCommand;
     use expect_test::{expect, Expect};
-    use indoc::indoc;
+    use indoc::{formatdoc, indoc};
     use pretty_assertions::assert_eq;
 
     fn assert_metadata_from_cargo_toml(
@@ -741,6 +1079,218 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_merge_classifiers_file() {
+        let project_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            project_dir.path().join("classifiers.txt"),
+            indoc! {"
+                # Shared across the monorepo
+                Programming Language :: Rust
+
+                Topic :: Software Development :: Libraries
+                Not A Category :: Foo
+            "},
+        )
+        .unwrap();
+        let pyproject_toml: PyProjectToml = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [project]
+            name = "shared"
+            version = "0.1.0"
+            dynamic = ["classifiers"]
+
+            [tool.maturin]
+            classifiers-file = "classifiers.txt"
+        "#})
+        .unwrap();
+        let mut metadata = Metadata24::new("shared".to_string(), Version::new([0, 1, 0]));
+        metadata.classifiers = vec!["Programming Language :: Rust".to_string()];
+        metadata
+            .merge_pyproject_toml(project_dir.path(), &pyproject_toml)
+            .unwrap();
+        assert_eq!(
+            metadata.classifiers,
+            [
+                "Programming Language :: Rust",
+                "Topic :: Software Development :: Libraries",
+                "Not A Category :: Foo",
+            ]
+        );
+
+        fs::remove_file(project_dir.path().join("classifiers.txt")).unwrap();
+        let err = metadata
+            .merge_pyproject_toml(project_dir.path(), &pyproject_toml)
+            .unwrap_err();
+        assert!(err.to_string().contains("classifiers file"));
+
+        // Static classifiers must not be changed
+        for dynamic in ["", r#"dynamic = ["classifiers"]"#] {
+            let pyproject_toml: PyProjectToml = toml::from_str(&formatdoc! {r#"
+                [build-system]
+                requires = ["maturin>=1.0,<2.0"]
+                build-backend = "maturin"
+
+                [project]
+                name = "shared"
+                version = "0.1.0"
+                classifiers = ["Programming Language :: Rust"]
+                {dynamic}
+
+                [tool.maturin]
+                classifiers-file = "classifiers.txt"
+            "#})
+            .unwrap();
+            let err = metadata
+                .merge_pyproject_toml(project_dir.path(), &pyproject_toml)
+                .unwrap_err();
+            assert!(err.to_string().contains("project.dynamic"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_dependency_overrides() {
+        let pyproject_toml: PyProjectToml = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [project]
+            name = "app"
+            version = "0.1.0"
+            dynamic = ["dependencies"]
+
+            [project.optional-dependencies]
+            plugins = ["shared-plugins>=0.4"]
+
+            [tool.maturin]
+            dependencies = [
+                "Shared.Core[fast] @ file:///workspace/shared-core ; python_version >= '3.9'",
+                "requests>=2",
+            ]
+
+            [tool.maturin.dependency-overrides]
+            shared-core = ">=1.2"
+        "#})
+        .unwrap();
+        let mut metadata = Metadata24::new("app".to_string(), Version::new([0, 1, 0]));
+        metadata
+            .merge_pyproject_toml("/not/exist", &pyproject_toml)
+            .unwrap();
+        let requires_dist: Vec<_> = metadata
+            .requires_dist
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            requires_dist,
+            [
+                "shared-core[fast]>=1.2 ; python_full_version >= '3.9'",
+                "requests>=2",
+                "shared-plugins>=0.4 ; extra == 'plugins'",
+            ]
+        );
+
+        // Static dependencies must not be changed
+        let pyproject_toml: PyProjectToml = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [project]
+            name = "app"
+            version = "0.1.0"
+            dependencies = ["shared-core @ file:///workspace/shared-core"]
+
+            [tool.maturin.dependency-overrides]
+            shared-core = ">=1.2"
+        "#})
+        .unwrap();
+        let err = metadata
+            .merge_pyproject_toml("/not/exist", &pyproject_toml)
+            .unwrap_err();
+        assert!(err.to_string().contains("project.dynamic"), "{err}");
+
+        for invalid in [r#""==1.0 ; sys_platform == 'win32'""#, r#""=>1.0""#] {
+            let pyproject_toml: PyProjectToml = toml::from_str(&format!(
+                indoc! {r#"
+                    [build-system]
+                    requires = ["maturin>=1.0,<2.0"]
+                    build-backend = "maturin"
+
+                    [project]
+                    name = "app"
+                    version = "0.1.0"
+                    dynamic = ["dependencies"]
+
+                    [tool.maturin]
+                    dependencies = ["requests>=2"]
+
+                    [tool.maturin.dependency-overrides]
+                    requests = {}
+                "#},
+                invalid
+            ))
+            .unwrap();
+            let err = metadata
+                .merge_pyproject_toml("/not/exist", &pyproject_toml)
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid dependency override"),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_requires_dist_declaration_order() {
+        let pyproject_toml: PyProjectToml = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [project]
+            name = "ordering"
+            version = "0.1.0"
+            dependencies = ["zeta", "alpha>=1", "mu"]
+
+            [project.optional-dependencies]
+            z = ["omega", "beta"]
+            a = ["gamma"]
+            m = ["delta"]
+        "#})
+        .unwrap();
+        let metadata_contents = || {
+            let mut metadata = Metadata24::new("ordering".to_string(), Version::new([0, 1, 0]));
+            metadata
+                .merge_pyproject_toml(Path::new("."), &pyproject_toml)
+                .unwrap();
+            assert_eq!(metadata.provides_extra, ["z", "a", "m"]);
+            metadata.to_file_contents().unwrap()
+        };
+        let first = metadata_contents();
+        assert_eq!(first, metadata_contents());
+        let requires_dist: Vec<_> = first
+            .lines()
+            .filter_map(|line| line.strip_prefix("Requires-Dist: "))
+            .collect();
+        assert_eq!(
+            requires_dist,
+            [
+                "zeta",
+                "alpha>=1",
+                "mu",
+                "omega ; extra == 'z'",
+                "beta ; extra == 'z'",
+                "gamma ; extra == 'a'",
+                "delta ; extra == 'm'",
+            ]
+        );
+    }
+
     #[test]
     fn test_merge_metadata_from_pyproject_toml() {
         let manifest_dir = PathBuf::from("test-crates").join("pyo3-pure");
@@ -811,6 +1361,96 @@ mod test {
         assert_eq!(metadata.scripts["get_42"], "pyo3_mixed_py_subdir:get_42");
     }
 
+    #[test]
+    fn test_merge_metadata_custom_entry_point_group() {
+        let project_dir = tempfile::tempdir().unwrap();
+        let pyproject_toml_path = project_dir.path().join("pyproject.toml");
+        fs::write(
+            &pyproject_toml_path,
+            indoc!(
+                r#"
+                [build-system]
+                requires = ["maturin>=1.0,<2.0"]
+                build-backend = "maturin"
+
+                [project]
+                name = "pytest-plugin"
+                version = "0.1.0"
+
+                [project.entry-points."pytest11"]
+                myplugin = "pytest_plugin.plugin"
+                "#
+            ),
+        )
+        .unwrap();
+        let pyproject_toml = PyProjectToml::new(&pyproject_toml_path).unwrap();
+        let mut metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        metadata
+            .merge_pyproject_toml(project_dir.path(), &pyproject_toml)
+            .unwrap();
+        assert_eq!(
+            metadata.entry_points["pytest11"]["myplugin"],
+            "pytest_plugin.plugin"
+        );
+
+        let out_dir = tempfile::tempdir().unwrap();
+        let mut writer = crate::module_writer::PathWriter::from_path(out_dir.path());
+        crate::module_writer::write_dist_info(
+            &mut writer,
+            &metadata,
+            &["py3-none-any".to_string()],
+            false,
+        )
+        .unwrap();
+        let entry_points = fs::read_to_string(
+            out_dir
+                .path()
+                .join(metadata.get_dist_info_dir())
+                .join("entry_points.txt"),
+        )
+        .unwrap();
+        assert_eq!(entry_points, "[pytest11]\nmyplugin=pytest_plugin.plugin\n");
+    }
+
+    #[test]
+    fn test_mismatched_script_targets() {
+        let mut metadata = Metadata24::new("foo".to_string(), Version::new([1, 0]));
+        metadata
+            .scripts
+            .insert("ok".to_string(), "my_project:main".to_string());
+        metadata
+            .scripts
+            .insert("nested".to_string(), "my_project.cli:app.run".to_string());
+        metadata
+            .scripts
+            .insert("typo".to_string(), "my_projcet:main".to_string());
+        metadata
+            .gui_scripts
+            .insert("gui".to_string(), "helpers.gui:main".to_string());
+        assert_eq!(
+            metadata.mismatched_script_targets(&["my_project", "helpers"]),
+            [("typo", "my_projcet:main")]
+        );
+        assert_eq!(
+            metadata.mismatched_script_targets(&["my_project"]),
+            [("typo", "my_projcet:main"), ("gui", "helpers.gui:main")]
+        );
+    }
+
+    #[test]
+    fn test_to_requirements_txt() {
+        let mut metadata = Metadata24::new("foo".to_string(), Version::new([1, 0]));
+        assert_eq!(metadata.to_requirements_txt(), "");
+        metadata.requires_dist = vec![
+            Requirement::from_str("cffi>=1.0").unwrap(),
+            Requirement::from_str("pytest; extra == 'test'").unwrap(),
+        ];
+        assert_eq!(
+            metadata.to_requirements_txt(),
+            "cffi>=1.0\npytest ; extra == 'test'\n"
+        );
+    }
+
     #[test]
     fn test_implicit_readme() {
         let manifest_dir = PathBuf::from("test-crates").join("pyo3-mixed");
@@ -892,4 +1532,82 @@ mod test {
             assert_eq!(result, expected);
         }
     }
+
+    #[test]
+    fn test_append_version_suffix() {
+        let mut metadata = Metadata24::new("foo".to_string(), Version::new([1, 2, 3]));
+        metadata.append_version_suffix(".dev20240101").unwrap();
+        assert_eq!(metadata.version.to_string(), "1.2.3.dev20240101");
+        metadata.append_version_suffix("+nightly.1").unwrap();
+        assert_eq!(metadata.version.to_string(), "1.2.3.dev20240101+nightly.1");
+        assert_eq!(
+            metadata.get_version_escaped(),
+            "1.2.3.dev20240101+nightly.1"
+        );
+
+        let err = metadata.append_version_suffix("+again").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("1.2.3.dev20240101+nightly.1+again"));
+        assert!(Metadata24::new("foo".to_string(), Version::new([1]))
+            .append_version_suffix("-nightly!")
+            .is_err());
+        // Nothing changes on error
+        assert_eq!(metadata.version.to_string(), "1.2.3.dev20240101+nightly.1");
+    }
+
+    #[test]
+    fn test_scm_version() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=maturin",
+                    "-c",
+                    "user.email=maturin@example.com",
+                ])
+                .args(["-c", "commit.gpgsign=false", "-c", "tag.gpgsign=false"])
+                .args(args)
+                .current_dir(repo.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "-q"]);
+        fs::write(repo.path().join("README.md"), "1").unwrap();
+        git(&["add", "README.md"]);
+        git(&["commit", "-q", "-m", "first"]);
+        let sha = git(&["rev-parse", "--short", "HEAD"]);
+        assert_eq!(
+            scm_version(repo.path()).unwrap().to_string(),
+            format!("0.0.0+g{sha}")
+        );
+
+        git(&["tag", "v1.2.3"]);
+        assert_eq!(scm_version(repo.path()).unwrap().to_string(), "1.2.3");
+
+        fs::write(repo.path().join("README.md"), "2").unwrap();
+        assert_eq!(scm_version(repo.path()).unwrap().to_string(), "1.2.3+dirty");
+
+        git(&["commit", "-q", "-am", "second"]);
+        let sha = git(&["rev-parse", "--short", "HEAD"]);
+        assert_eq!(
+            scm_version(repo.path()).unwrap().to_string(),
+            format!("1.2.3.post1+g{sha}")
+        );
+
+        // Source distributions don't have the git history
+        let sdist = tempfile::tempdir().unwrap();
+        fs::write(
+            sdist.path().join("PKG-INFO"),
+            "Metadata-Version: 2.4\nName: foo\nVersion: 1.2.3.post1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            scm_version(sdist.path()).unwrap().to_string(),
+            "1.2.3.post1"
+        );
+    }
 }
@@ -1,8 +1,10 @@
 //! The wheel format is (mostly) specified in PEP 427
+use crate::build_context::expand_command_arg;
 use crate::project_layout::ProjectLayout;
 use crate::target::Os;
 use crate::{
-    pyproject_toml::Format, BridgeModel, Metadata24, PyProjectToml, PythonInterpreter, Target,
+    pyproject_toml::{Format, GenerateStubsConfig},
+    BridgeModel, Metadata24, PyProjectToml, PythonInterpreter, Target,
 };
 use anyhow::{anyhow, bail, Context, Result};
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
@@ -21,21 +23,24 @@ use indexmap::IndexMap;
 use itertools::Itertools;
 use normpath::PathExt as _;
 use same_file::is_same_file;
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fmt::Write as _;
 use std::io;
 use std::io::{Read, Write};
+use std::num::NonZeroUsize;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tempfile::{tempdir, TempDir};
 use tracing::{debug, instrument};
-use zip::{self, DateTime, ZipWriter};
+use zip::write::SimpleFileOptions;
+use zip::{self, DateTime, ZipArchive, ZipWriter};
 
 /// Allows writing the module to a wheel or add it directly to the virtualenv
 pub trait ModuleWriter {
@@ -111,7 +116,7 @@ impl PathWriter {
                     anyhow!("Expected `python` to be a python interpreter inside a virtualenv ಠ_ಠ")
                 })?;
 
-        let base_path = interpreter.get_venv_site_package(venv_dir, target);
+        let base_path = interpreter.get_venv_site_package(venv_dir, target)?;
 
         Ok(PathWriter {
             base_path,
@@ -227,14 +232,63 @@ impl ModuleWriter for PathWriter {
     }
 }
 
+/// The number of bytes of pending files after which they are compressed and written to the wheel,
+/// so that large wheels aren't held in memory as a whole
+const PENDING_BYTES_LIMIT: usize = 64 * 1024 * 1024;
+
 /// A glorified zip builder, mostly useful for writing the record file of a wheel
 pub struct WheelWriter {
     zip: ZipWriter<File>,
+    /// Files are buffered and compressed in parallel once they reach `pending_limit` bytes or
+    /// when the wheel is finished
+    pending: Vec<(String, SimpleFileOptions, Vec<u8>)>,
+    pending_bytes: usize,
+    pending_limit: usize,
+    jobs: usize,
     record: Vec<(String, String, usize)>,
+    /// The `extra_hashes` digests of each file in `record`
+    extra_digests: Vec<Vec<String>>,
     record_file: PathBuf,
     wheel_path: PathBuf,
     file_tracker: FileTracker,
     excludes: Override,
+    /// Files matching these globs are stored without compression
+    stored: Override,
+    /// Algorithms for the supplementary `RECORD.<algorithm>` files
+    extra_hashes: Vec<ExtraHash>,
+    /// The modification time of all members, from `SOURCE_DATE_EPOCH` by default
+    mtime: Option<DateTime>,
+}
+
+/// A hash algorithm for a supplementary hashes file written next to RECORD, which itself
+/// always uses sha256 as mandated by the wheel spec
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtraHash {
+    /// BLAKE3, written to `RECORD.blake3`
+    Blake3,
+    /// SHA-512, written to `RECORD.sha512`
+    Sha512,
+}
+
+impl ExtraHash {
+    /// The name used for the file extension and as the prefix of the hashes
+    pub fn name(self) -> &'static str {
+        match self {
+            ExtraHash::Blake3 => "blake3",
+            ExtraHash::Sha512 => "sha512",
+        }
+    }
+
+    /// Hashes `bytes`, encoded as urlsafe base64 without padding like the RECORD hashes
+    pub fn digest(self, bytes: &[u8]) -> String {
+        match self {
+            ExtraHash::Blake3 => URL_SAFE_NO_PAD.encode(blake3::hash(bytes).as_bytes()),
+            ExtraHash::Sha512 => URL_SAFE_NO_PAD.encode(Sha512::digest(bytes)),
+        }
+    }
 }
 
 impl ModuleWriter for WheelWriter {
@@ -250,7 +304,8 @@ impl ModuleWriter for WheelWriter {
         permissions: u32,
     ) -> Result<()> {
         let target = target.as_ref();
-        if self.exclude(target) {
+        // Patterns are relative to pyproject.toml, which isn't necessarily the root of the wheel
+        if self.exclude(target) || source.is_some_and(|source| self.exclude(source)) {
             return Ok(());
         }
 
@@ -259,30 +314,39 @@ impl ModuleWriter for WheelWriter {
             return Ok(());
         }
 
+        let stored = self.stored.matched(target, false).is_whitelist();
+
         // The zip standard mandates using unix style paths
         let target = target.to_str().unwrap().replace('\\', "/");
 
         // Unlike users which can use the develop subcommand, the tests have to go through
         // packing a zip which pip than has to unpack. This makes this 2-3 times faster
-        let compression_method = if cfg!(feature = "faster-tests") {
+        let compression_method = if cfg!(feature = "faster-tests") || stored {
             zip::CompressionMethod::Stored
         } else {
             zip::CompressionMethod::Deflated
         };
 
-        let mut options = zip::write::SimpleFileOptions::default()
+        let mut options = SimpleFileOptions::default()
             .unix_permissions(permissions)
             .compression_method(compression_method);
-        let mtime = self.mtime().ok();
-        if let Some(mtime) = mtime {
+        if let Some(mtime) = self.mtime {
             options = options.last_modified_time(mtime);
         }
 
-        self.zip.start_file(target.clone(), options)?;
-        self.zip.write_all(bytes)?;
-
         let hash = URL_SAFE_NO_PAD.encode(Sha256::digest(bytes));
-        self.record.push((target, hash, bytes.len()));
+        self.record.push((target.clone(), hash, bytes.len()));
+        self.extra_digests.push(
+            self.extra_hashes
+                .iter()
+                .map(|extra_hash| extra_hash.digest(bytes))
+                .collect(),
+        );
+        self.pending.push((target, options, bytes.to_vec()));
+        self.pending_bytes += bytes.len();
+        if self.pending_bytes >= self.pending_limit {
+            self.flush_pending()?;
+        }
 
         Ok(())
     }
@@ -297,6 +361,7 @@ impl WheelWriter {
         wheel_dir: &Path,
         metadata24: &Metadata24,
         tags: &[String],
+        root_is_purelib: bool,
         excludes: Override,
     ) -> Result<WheelWriter> {
         let wheel_path = wheel_dir.join(format!(
@@ -308,20 +373,87 @@ impl WheelWriter {
 
         let file = File::create(&wheel_path)?;
 
+        let jobs = std::thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1);
         let mut builder = WheelWriter {
             zip: ZipWriter::new(file),
+            pending: Vec::new(),
+            pending_bytes: 0,
+            pending_limit: PENDING_BYTES_LIMIT,
+            jobs,
             record: Vec::new(),
+            extra_digests: Vec::new(),
             record_file: metadata24.get_dist_info_dir().join("RECORD"),
             wheel_path,
             file_tracker: FileTracker::default(),
             excludes,
+            stored: Override::empty(),
+            extra_hashes: Vec::new(),
+            mtime: Self::source_date_epoch().ok(),
         };
 
-        write_dist_info(&mut builder, metadata24, tags)?;
+        write_dist_info(&mut builder, metadata24, tags, root_is_purelib)?;
 
         Ok(builder)
     }
 
+    /// Sets the number of threads used to compress the files, defaults to the number of CPUs
+    pub fn with_jobs(mut self, jobs: Option<usize>) -> Self {
+        if let Some(jobs) = jobs {
+            self.jobs = jobs.max(1);
+        }
+        self
+    }
+
+    /// Stores the files matching `stored` without compression, e.g. already compressed assets
+    pub fn with_stored(mut self, stored: Override) -> Self {
+        self.stored = stored;
+        self
+    }
+
+    /// Writes a `RECORD.<algorithm>` file with the hashes of all members for each algorithm
+    pub fn with_extra_hashes(mut self, extra_hashes: &[ExtraHash]) -> Self {
+        self.extra_hashes = extra_hashes.iter().copied().unique().collect();
+        // Only the dist-info files of `new` were added so far, which are all still pending
+        debug_assert_eq!(self.pending.len(), self.record.len());
+        self.extra_digests = self
+            .pending
+            .iter()
+            .map(|(_, _, bytes)| {
+                self.extra_hashes
+                    .iter()
+                    .map(|extra_hash| extra_hash.digest(bytes))
+                    .collect()
+            })
+            .collect();
+        self
+    }
+
+    /// Sets the modification time of all members instead of reading it from `SOURCE_DATE_EPOCH`
+    pub fn with_mtime(mut self, mtime: DateTime) -> Self {
+        self.mtime = Some(mtime);
+        // The dist-info files of `new` are still pending, so they can be updated too
+        for (_, options, _) in &mut self.pending {
+            *options = options.last_modified_time(mtime);
+        }
+        self
+    }
+
+    /// Compresses the pending files in parallel and appends them to the wheel in the order they
+    /// were added
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        self.pending_bytes = 0;
+        // Each file is compressed into an archive of its own, which is then copied over without
+        // recompressing, so the output doesn't depend on the number of jobs
+        for compressed in compress_in_parallel(&pending, self.jobs)? {
+            let mut archive = ZipArchive::new(io::Cursor::new(compressed))?;
+            self.zip.raw_copy_file(archive.by_index(0)?)?;
+        }
+        Ok(())
+    }
+
     /// Add a pth file to wheel root for editable installs
     pub fn add_pth(
         &mut self,
@@ -353,12 +485,12 @@ impl WheelWriter {
 
     /// Returns `true` if the given path should be excluded
     fn exclude(&self, path: impl AsRef<Path>) -> bool {
-        self.excludes.matched(path.as_ref(), false).is_whitelist()
+        is_excluded(&self.excludes, path.as_ref())
     }
 
     /// Returns a DateTime representing the value SOURCE_DATE_EPOCH environment variable
     /// Note that the earliest timestamp a zip file can represent is 1980-01-01
-    fn mtime(&self) -> Result<DateTime> {
+    fn source_date_epoch() -> Result<DateTime> {
         let epoch: i64 = env::var("SOURCE_DATE_EPOCH")?.parse()?;
         let dt = time::OffsetDateTime::from_unix_timestamp(epoch)?;
         let min_dt = time::Date::from_calendar_date(1980, time::Month::January, 1)
@@ -379,29 +511,346 @@ impl WheelWriter {
             zip::CompressionMethod::Deflated
         };
 
-        let mut options =
-            zip::write::SimpleFileOptions::default().compression_method(compression_method);
-        let mtime = self.mtime().ok();
-        if let Some(mtime) = mtime {
+        let mut options = SimpleFileOptions::default().compression_method(compression_method);
+        if let Some(mtime) = self.mtime {
             options = options.last_modified_time(mtime);
         }
 
         let record_filename = self.record_file.to_str().unwrap().replace('\\', "/");
-        debug!("Adding {}", record_filename);
-        self.zip.start_file(&record_filename, options)?;
-        for (filename, hash, len) in self.record {
-            self.zip
-                .write_all(format!("{filename},sha256={hash},{len}\n").as_bytes())?;
+        // The supplementary hash files cover all other members and are listed in RECORD
+        let mut extra_files = Vec::new();
+        for (index, extra_hash) in self.extra_hashes.iter().enumerate() {
+            let mut contents = String::new();
+            for ((filename, _, len), digests) in self.record.iter().zip(&self.extra_digests) {
+                writeln!(
+                    contents,
+                    "{filename},{}={},{len}",
+                    extra_hash.name(),
+                    digests[index]
+                )
+                .unwrap();
+            }
+            extra_files.push((format!("{record_filename}.{}", extra_hash.name()), contents));
         }
-        // Write the record for the RECORD file itself
-        self.zip
-            .write_all(format!("{record_filename},,\n").as_bytes())?;
+        for (filename, contents) in extra_files {
+            debug!("Adding {}", filename);
+            let hash = URL_SAFE_NO_PAD.encode(Sha256::digest(contents.as_bytes()));
+            self.record.push((filename.clone(), hash, contents.len()));
+            self.pending
+                .push((filename, options, contents.into_bytes()));
+        }
+
+        debug!("Adding {}", record_filename);
+        let record = record_contents(&self.record, &record_filename);
+        self.pending
+            .push((record_filename, options, record.into_bytes()));
+
+        self.flush_pending()?;
 
         self.zip.finish()?;
         Ok(self.wheel_path)
     }
 }
 
+/// Formats the lines of a RECORD file, the entry for RECORD itself has neither hash nor size
+fn record_contents(record: &[(String, String, usize)], record_filename: &str) -> String {
+    let mut contents = String::new();
+    for (filename, hash, len) in record {
+        writeln!(contents, "{filename},sha256={hash},{len}").unwrap();
+    }
+    // Write the record for the RECORD file itself
+    writeln!(contents, "{record_filename},,").unwrap();
+    contents
+}
+
+/// Recomputes the RECORD of an existing wheel and rewrites the wheel in place
+///
+/// This is meant for wheels whose files were modified after they were built, e.g. by distro
+/// patches. All other members are copied over unchanged, signature files next to RECORD are
+/// dropped since they can't be valid anymore.
+pub fn rebuild_record(wheel_path: &Path) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(wheel_path)?)
+        .with_context(|| format!("Failed to read wheel at {}", wheel_path.display()))?;
+
+    let record_files: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.ends_with(".dist-info/RECORD") && name.matches('/').count() == 1)
+        .map(ToString::to_string)
+        .collect();
+    let record_filename = match record_files.as_slice() {
+        [record_filename] => record_filename.clone(),
+        [] => bail!(
+            "{} doesn't contain a .dist-info/RECORD",
+            wheel_path.display()
+        ),
+        _ => bail!(
+            "{} contains more than one .dist-info/RECORD: {}",
+            wheel_path.display(),
+            record_files.join(", ")
+        ),
+    };
+    let signature_files = [
+        format!("{record_filename}.jws"),
+        format!("{record_filename}.p7s"),
+    ];
+    let extra_hash_files: Vec<String> = [ExtraHash::Blake3, ExtraHash::Sha512]
+        .iter()
+        .map(|extra_hash| format!("{record_filename}.{}", extra_hash.name()))
+        .collect();
+
+    let temp_file = tempfile::NamedTempFile::new_in(
+        wheel_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new(".")),
+    )?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let mut record = Vec::new();
+    let mut record_options = SimpleFileOptions::default();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        if name == record_filename {
+            record_options = record_options
+                .compression_method(file.compression())
+                .last_modified_time(file.last_modified().unwrap_or_default());
+            if let Some(mode) = file.unix_mode() {
+                record_options = record_options.unix_permissions(mode);
+            }
+            continue;
+        }
+        if signature_files.contains(&name) {
+            eprintln!(
+                "⚠️  Warning: Dropping {name}, the signature is invalid after rebuilding RECORD"
+            );
+            continue;
+        }
+        if extra_hash_files.contains(&name) {
+            eprintln!("⚠️  Warning: Dropping {name}, the hashes are stale after rebuilding RECORD");
+            continue;
+        }
+        if !file.is_dir() {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let hash = URL_SAFE_NO_PAD.encode(Sha256::digest(&bytes));
+            record.push((name, hash, bytes.len()));
+        }
+        drop(file);
+        zip.raw_copy_file(archive.by_index_raw(index)?)?;
+    }
+
+    zip.start_file(record_filename.as_str(), record_options)?;
+    zip.write_all(record_contents(&record, &record_filename).as_bytes())?;
+    zip.finish()?;
+
+    fs::set_permissions(temp_file.path(), fs::metadata(wheel_path)?.permissions())?;
+    temp_file
+        .persist(wheel_path)
+        .with_context(|| format!("Failed to replace {}", wheel_path.display()))?;
+    Ok(())
+}
+
+/// Returns `true` if the path or one of its parent directories matches an exclude pattern, so
+/// that e.g. `**/__pycache__` also drops the files inside of such directories
+///
+/// Absolute paths are only matched up to the directory the patterns are relative to.
+fn is_excluded(excludes: &Override, path: &Path) -> bool {
+    if excludes.is_empty() {
+        return false;
+    }
+    path.ancestors()
+        .take_while(|ancestor| {
+            !ancestor.as_os_str().is_empty()
+                && (path.is_relative() || ancestor.starts_with(excludes.path()))
+                && *ancestor != excludes.path()
+        })
+        .enumerate()
+        .any(|(index, ancestor)| excludes.matched(ancestor, index > 0).is_whitelist())
+}
+
+/// Collects built wheels and source distributions into a single zip archive, e.g. to move them
+/// between CI jobs as one artifact
+///
+/// The archive contains the files next to each other and a `manifest.json` listing their names,
+/// sizes and sha256 hashes. It is deterministic: entries are sorted, stored uncompressed since
+/// wheels and sdists are already compressed, and get a fixed timestamp.
+pub fn write_dist_archive(archive_path: &Path, dists: &[PathBuf]) -> Result<()> {
+    let mut dists = dists.to_vec();
+    dists.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644);
+    let mut zip = ZipWriter::new(File::create(archive_path)?);
+    let mut manifest = Vec::new();
+    for dist in &dists {
+        let filename = dist
+            .file_name()
+            .and_then(OsStr::to_str)
+            .with_context(|| format!("Invalid file name {}", dist.display()))?;
+        let bytes = fs::read(dist)?;
+        manifest.push(serde_json::json!({
+            "filename": filename,
+            "size": bytes.len(),
+            "sha256": format!("{:x}", Sha256::digest(&bytes)),
+        }));
+        zip.start_file(filename, options)?;
+        zip.write_all(&bytes)?;
+    }
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes an `index.html` listing built wheels and source distributions, so that the directory
+/// can be used with `pip install --find-links <dir>/index.html`
+///
+/// The index is written to the closest directory containing all files and links them by their
+/// relative path with a `#sha256=` fragment as in PEP 503. Returns the path of the index.
+pub fn write_dist_index(dists: &[PathBuf]) -> Result<PathBuf> {
+    let mut dists = dists
+        .iter()
+        .map(|dist| Ok(dist.normalize()?.into_path_buf()))
+        .collect::<Result<Vec<_>>>()?;
+    dists.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    let mut index_dir = dists
+        .first()
+        .and_then(|dist| dist.parent())
+        .context("No files to write an index for")?
+        .to_path_buf();
+    while !dists.iter().all(|dist| dist.starts_with(&index_dir)) {
+        index_dir = index_dir
+            .parent()
+            .context("The files don't have a common parent directory")?
+            .to_path_buf();
+    }
+
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n  <body>\n");
+    for dist in &dists {
+        let href = dist
+            .strip_prefix(&index_dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .join("/");
+        let filename = dist.file_name().unwrap_or_default().to_string_lossy();
+        let sha256 = format!("{:x}", Sha256::digest(fs::read(dist)?));
+        writeln!(
+            html,
+            "    <a href=\"{href}#sha256={sha256}\">{filename}</a><br/>"
+        )?;
+    }
+    html.push_str("  </body>\n</html>\n");
+    let index = index_dir.join("index.html");
+    fs::write(&index, html)?;
+    Ok(index)
+}
+
+/// Compares two wheels and describes the first archive member that differs between them, or
+/// returns `None` if both files are byte-identical
+///
+/// Members are compared in the order of the first wheel by name, content, permissions and
+/// timestamp, so the description points at the file that made the build non-reproducible.
+pub fn first_wheel_difference(first: &Path, second: &Path) -> Result<Option<String>> {
+    if fs::read(first)? == fs::read(second)? {
+        return Ok(None);
+    }
+    let mut first_zip = ZipArchive::new(File::open(first)?)
+        .with_context(|| format!("Failed to open {}", first.display()))?;
+    let mut second_zip = ZipArchive::new(File::open(second)?)
+        .with_context(|| format!("Failed to open {}", second.display()))?;
+    for index in 0..first_zip.len().max(second_zip.len()) {
+        let (mut first_file, mut second_file) =
+            match (first_zip.by_index(index), second_zip.by_index(index)) {
+                (Ok(first_file), Ok(second_file)) => (first_file, second_file),
+                (Ok(file), Err(_)) => {
+                    return Ok(Some(format!(
+                        "{} is missing in the second build",
+                        file.name()
+                    )))
+                }
+                (Err(_), Ok(file)) => {
+                    return Ok(Some(format!(
+                        "{} is missing in the first build",
+                        file.name()
+                    )))
+                }
+                (Err(err), Err(_)) => return Err(err.into()),
+            };
+        let name = first_file.name().to_string();
+        if name != second_file.name() {
+            return Ok(Some(format!(
+                "member {} is {} in the first build but {} in the second build",
+                index,
+                name,
+                second_file.name()
+            )));
+        }
+        let mut first_bytes = Vec::new();
+        first_file.read_to_end(&mut first_bytes)?;
+        let mut second_bytes = Vec::new();
+        second_file.read_to_end(&mut second_bytes)?;
+        if first_bytes != second_bytes {
+            return Ok(Some(format!("{name} has different contents")));
+        }
+        if first_file.unix_mode() != second_file.unix_mode() {
+            return Ok(Some(format!("{name} has different permissions")));
+        }
+        if first_file.last_modified() != second_file.last_modified() {
+            return Ok(Some(format!("{name} has a different timestamp")));
+        }
+        if first_file.compression() != second_file.compression() {
+            return Ok(Some(format!("{name} uses a different compression")));
+        }
+    }
+    Ok(Some(
+        "the archives differ outside of their members".to_string(),
+    ))
+}
+
+/// Compresses each file into a single file zip archive using up to `jobs` threads,
+/// returning the archives in the order of the files
+fn compress_in_parallel(
+    files: &[(String, SimpleFileOptions, Vec<u8>)],
+    jobs: usize,
+) -> io::Result<Vec<Vec<u8>>> {
+    let next = AtomicUsize::new(0);
+    let mut compressed = std::thread::scope(|scope| {
+        let workers = (0..jobs.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut compressed = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((name, options, bytes)) = files.get(index) else {
+                            break;
+                        };
+                        let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+                        zip.start_file(name.as_str(), *options)?;
+                        zip.write_all(bytes)?;
+                        compressed.push((index, zip.finish()?.into_inner()));
+                    }
+                    Ok::<_, io::Error>(compressed)
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("A compression thread panicked")))
+            })
+            .collect::<io::Result<Vec<_>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    compressed.sort_by_key(|(index, _)| *index);
+    Ok(compressed.into_iter().map(|(_, bytes)| bytes).collect())
+}
+
 /// Creates a .tar.gz archive containing the source distribution
 pub struct SDistWriter {
     tar: tar::Builder<GzEncoder<Vec<u8>>>,
@@ -497,9 +946,16 @@ impl SDistWriter {
         })
     }
 
+    /// Sets whether symlinks are replaced by the files they point to (the default) or stored as
+    /// symlinks in the archive
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.tar.follow_symlinks(follow_symlinks);
+        self
+    }
+
     /// Returns `true` if the given path should be excluded
     fn exclude(&self, path: impl AsRef<Path>) -> bool {
-        self.excludes.matched(path.as_ref(), false).is_whitelist()
+        is_excluded(&self.excludes, path.as_ref())
     }
 
     /// Finished the .tar.gz archive
@@ -567,11 +1023,11 @@ impl FileTracker {
     }
 }
 
-fn wheel_file(tags: &[String]) -> Result<String> {
+fn wheel_file(tags: &[String], root_is_purelib: bool) -> Result<String> {
     let mut wheel_file = format!(
         "Wheel-Version: 1.0
 Generator: {name} ({version})
-Root-Is-Purelib: false
+Root-Is-Purelib: {root_is_purelib}
 ",
         name = env!("CARGO_PKG_NAME"),
         version = env!("CARGO_PKG_VERSION"),
@@ -667,23 +1123,14 @@ fn cffi_header(crate_dir: &Path, target_dir: &Path, tempdir: &TempDir) -> Result
     }
 }
 
-/// Returns the content of what will become ffi.py by invoking cbindgen and cffi
-///
-/// Checks if user has provided their own header at `target/header.h`, otherwise
-/// we run cbindgen to generate one. Installs cffi if it's missing and we're inside a virtualenv
+/// Returns the content of what will become ffi.py by invoking cffi on the header from
+/// [cffi_header]. Installs cffi if it's missing and we're inside a virtualenv
 ///
 /// We're using the cffi recompiler, which reads the header, translates them into instructions
 /// how to load the shared library without the header and then writes those instructions to a
 /// file called `ffi.py`. This `ffi.py` will expose an object called `ffi`. This object is used
 /// in `__init__.py` to load the shared library into a module called `lib`.
-pub fn generate_cffi_declarations(
-    crate_dir: &Path,
-    target_dir: &Path,
-    python: &Path,
-) -> Result<String> {
-    let tempdir = tempdir()?;
-    let header = cffi_header(crate_dir, target_dir, &tempdir)?;
-
+fn generate_cffi_declarations(header: &Path, python: &Path, tempdir: TempDir) -> Result<String> {
     let ffi_py = tempdir.as_ref().join("ffi.py");
 
     // Using raw strings is important because on windows there are path like
@@ -815,21 +1262,14 @@ fn unpack_big_archive(target: &Target, artifact: &Path, temp_dir_path: &Path) ->
     Ok(unpacked_artifact)
 }
 
-/// Copies the shared library into the module, which is the only extra file needed with bindings
-#[allow(clippy::too_many_arguments)]
-#[instrument(skip_all)]
-pub fn write_bindings_module(
-    writer: &mut impl ModuleWriter,
-    project_layout: &ProjectLayout,
-    artifact: &Path,
+/// The file name of the native module, e.g. `my_module.abi3.so`
+fn bindings_so_filename(
+    ext_name: &str,
     python_interpreter: Option<&PythonInterpreter>,
     is_abi3: bool,
     target: &Target,
-    editable: bool,
-    pyproject_toml: Option<&PyProjectToml>,
-) -> Result<()> {
-    let ext_name = &project_layout.extension_name;
-    let so_filename = if is_abi3 {
+) -> String {
+    if is_abi3 {
         if target.is_unix() {
             format!("{ext_name}.abi3.so")
         } else {
@@ -845,7 +1285,24 @@ pub fn write_bindings_module(
         let python_interpreter =
             python_interpreter.expect("A python interpreter is required for non-abi3 build");
         python_interpreter.get_library_name(ext_name)
-    };
+    }
+}
+
+/// Copies the shared library into the module, which is the only extra file needed with bindings
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all)]
+pub fn write_bindings_module(
+    writer: &mut impl ModuleWriter,
+    project_layout: &ProjectLayout,
+    artifact: &Path,
+    python_interpreter: Option<&PythonInterpreter>,
+    is_abi3: bool,
+    target: &Target,
+    editable: bool,
+    pyproject_toml: Option<&PyProjectToml>,
+) -> Result<()> {
+    let ext_name = &project_layout.extension_name;
+    let so_filename = bindings_so_filename(ext_name, python_interpreter, is_abi3, target);
 
     let artifact_is_big_ar =
         target.is_aix() && artifact.extension().unwrap_or(OsStr::new(" ")) == OsStr::new("a");
@@ -873,6 +1330,21 @@ pub fn write_bindings_module(
         write_python_part(writer, project_layout, pyproject_toml)
             .context("Failed to add the python module to the package")?;
     }
+    let type_stub = project_layout.rust_module.join(format!("{ext_name}.pyi"));
+    let generated_stub = match pyproject_toml.and_then(|x| x.generate_stubs()) {
+        Some(_) if type_stub.exists() => {
+            eprintln!(
+                "⚠️  Warning: Found type stub file at {ext_name}.pyi, \
+                not running the `tool.maturin.generate-stubs` command"
+            );
+            None
+        }
+        // Editable installs of mixed projects import the python source directly, where we don't
+        // want to write generated files
+        Some(_) if editable && project_layout.python_module.is_some() => None,
+        Some(config) => Some(generate_stubs(config, ext_name, artifact)?),
+        None => None,
+    };
     if let Some(python_module) = &project_layout.python_module {
         if editable {
             let target = project_layout.rust_module.join(&so_filename);
@@ -893,6 +1365,9 @@ pub fn write_bindings_module(
                 .strip_prefix(python_module.parent().unwrap())
                 .unwrap();
             writer.add_file_with_permissions(relative.join(&so_filename), artifact, 0o755)?;
+            if let Some(stub) = &generated_stub {
+                writer.add_bytes(relative.join(format!("{ext_name}.pyi")), None, stub)?;
+            }
         }
     } else {
         let module = PathBuf::from(ext_name);
@@ -910,8 +1385,10 @@ if hasattr({ext_name}, "__all__"):
             )
             .as_bytes(),
         )?;
-        let type_stub = project_layout.rust_module.join(format!("{ext_name}.pyi"));
-        if type_stub.exists() {
+        if let Some(stub) = &generated_stub {
+            writer.add_bytes(module.join("__init__.pyi"), None, stub)?;
+            writer.add_bytes(module.join("py.typed"), None, b"")?;
+        } else if type_stub.exists() {
             eprintln!("📖 Found type stub file at {ext_name}.pyi");
             writer.add_file(module.join("__init__.pyi"), type_stub)?;
             writer.add_bytes(module.join("py.typed"), None, b"")?;
@@ -922,6 +1399,107 @@ if hasattr({ext_name}, "__all__"):
     Ok(())
 }
 
+/// Adds the native module of a `tool.maturin.workspace-members` package as a subpackage of the
+/// main module, i.e. `<main module>/<member>/__init__.py` reexporting `<main module>/<member>/<member>.so`
+#[allow(clippy::too_many_arguments)]
+pub fn write_workspace_member_module(
+    writer: &mut impl ModuleWriter,
+    project_layout: &ProjectLayout,
+    member_name: &str,
+    artifact: &Path,
+    python_interpreter: Option<&PythonInterpreter>,
+    is_abi3: bool,
+    target: &Target,
+    editable: bool,
+) -> Result<()> {
+    let so_filename = bindings_so_filename(member_name, python_interpreter, is_abi3, target);
+    let init_py = format!(
+        r#"from .{member_name} import *
+
+__doc__ = {member_name}.__doc__
+if hasattr({member_name}, "__all__"):
+    __all__ = {member_name}.__all__"#
+    );
+    if editable && project_layout.python_module.is_some() {
+        let package = project_layout.rust_module.join(member_name);
+        fs::create_dir_all(&package)?;
+        fs::write(package.join("__init__.py"), init_py)?;
+        let target = package.join(&so_filename);
+        // Remove existing so file to avoid triggering SIGSEV in running process
+        let _ = fs::remove_file(&target);
+        fs::copy(artifact, &target)?;
+        return Ok(());
+    }
+    let package = match &project_layout.python_module {
+        Some(python_module) => project_layout
+            .rust_module
+            .strip_prefix(python_module.parent().unwrap())
+            .unwrap()
+            .join(member_name),
+        None => Path::new(&project_layout.extension_name).join(member_name),
+    };
+    writer.add_directory(&package)?;
+    writer.add_bytes(package.join("__init__.py"), None, init_py.as_bytes())?;
+    writer.add_file_with_permissions(package.join(so_filename), artifact, 0o755)?;
+    Ok(())
+}
+
+/// Runs the `tool.maturin.generate-stubs` command and returns the generated type stub
+fn generate_stubs(
+    config: &GenerateStubsConfig,
+    ext_name: &str,
+    artifact: &Path,
+) -> Result<Vec<u8>> {
+    let (program, args) = config
+        .command
+        .split_first()
+        .context("`tool.maturin.generate-stubs.command` must not be empty")?;
+    let temp_dir = tempdir()?;
+    let output = temp_dir.path().join(format!("{ext_name}.pyi"));
+    let writes_output = args.iter().any(|arg| arg.contains("{output}"));
+    let values = [
+        ("module", OsStr::new(ext_name)),
+        ("artifact", artifact.as_os_str()),
+        ("output", output.as_os_str()),
+    ];
+    let args: Vec<OsString> = args
+        .iter()
+        .map(|arg| expand_command_arg(arg, &values))
+        .collect();
+    eprintln!("📝 Generating type stub for {ext_name} with `{program}`");
+    let result = match Command::new(program)
+        .args(&args)
+        .stderr(Stdio::inherit())
+        .output()
+    {
+        Ok(result) => result,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => bail!(
+            "The stub generator `{program}` from `tool.maturin.generate-stubs` was not found, \
+            please install it or remove `tool.maturin.generate-stubs` from pyproject.toml"
+        ),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to run the stub generator `{program}`"))
+        }
+    };
+    if !result.status.success() {
+        bail!(
+            "Generating the type stub for {ext_name} with `{program}` failed: {}",
+            result.status
+        );
+    }
+    if writes_output {
+        fs::read(&output).with_context(|| {
+            format!(
+                "The stub generator `{program}` didn't write the type stub to {}",
+                output.display()
+            )
+        })
+    } else {
+        Ok(result.stdout)
+    }
+}
+
 /// Creates the cffi module with the shared library, the cffi declarations and the cffi loader
 #[allow(clippy::too_many_arguments)]
 pub fn write_cffi_module(
@@ -935,8 +1513,26 @@ pub fn write_cffi_module(
     python: &Path,
     editable: bool,
     pyproject_toml: Option<&PyProjectToml>,
+    metadata24: &Metadata24,
 ) -> Result<()> {
-    let cffi_declarations = generate_cffi_declarations(crate_dir, target_dir, python)?;
+    let tempdir = tempdir()?;
+    let header = cffi_header(crate_dir, target_dir, &tempdir)?;
+    if let Some(header_name) = pyproject_toml.and_then(|x| x.cffi_header()) {
+        if header_name.is_absolute()
+            || header_name
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            bail!(
+                "`tool.maturin.cffi.header` must be a relative path without `..`, got {}",
+                header_name.display()
+            );
+        }
+        let target = metadata24.get_data_dir().join("headers").join(header_name);
+        debug!("Adding the cffi header as {}", target.display());
+        writer.add_file(target, &header)?;
+    }
+    let cffi_declarations = generate_cffi_declarations(&header, python, tempdir)?;
 
     if !editable {
         write_python_part(writer, project_layout, pyproject_toml)
@@ -1070,37 +1666,129 @@ fn uniffi_bindgen_command(crate_dir: &Path) -> Result<Command> {
     Ok(command)
 }
 
+/// The crate directories of the local dependencies that are uniffi components themselves, their
+/// scaffolding is linked into the cdylib so we need to generate their bindings too
+///
+/// Only the packages the root package actually depends on are considered, not every local
+/// package of the workspace.
+fn uniffi_dependency_dirs(cargo_metadata: &cargo_metadata::Metadata) -> Result<Vec<PathBuf>> {
+    let resolve = cargo_metadata
+        .resolve
+        .as_ref()
+        .context("Expected cargo to return metadata with resolve")?;
+    let root = cargo_metadata
+        .root_package()
+        .context("Expected cargo to return metadata with root_package")?;
+    let mut seen = HashSet::from([&root.id]);
+    let mut stack = vec![&root.id];
+    let mut dirs = Vec::new();
+    while let Some(id) = stack.pop() {
+        let Some(node) = resolve.nodes.iter().find(|node| &node.id == id) else {
+            continue;
+        };
+        for dependency in &node.dependencies {
+            if !seen.insert(dependency) {
+                continue;
+            }
+            let Some(package) = cargo_metadata
+                .packages
+                .iter()
+                .find(|package| &package.id == dependency)
+            else {
+                continue;
+            };
+            // Only local packages can be uniffi components of this project
+            if package.source.is_some() {
+                continue;
+            }
+            if package.dependencies.iter().any(|dep| dep.name == "uniffi") {
+                if let Some(dir) = package.manifest_path.parent() {
+                    dirs.push(dir.as_std_path().to_path_buf());
+                }
+            }
+            stack.push(dependency);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Returns the name of the `namespace` declared in a UDL file
+fn udl_namespace(udl: &str) -> Option<&str> {
+    udl.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("//"))
+        .find_map(|line| line.strip_prefix("namespace "))
+        .and_then(|rest| rest.split(|c: char| c.is_whitespace() || c == '{').next())
+        .filter(|name| !name.is_empty())
+}
+
+/// Each uniffi namespace becomes a python module in the same package, so two UDL files with the
+/// same namespace would overwrite each other's bindings
+fn check_udl_namespaces(udls: &[PathBuf]) -> Result<()> {
+    let mut seen: HashMap<String, &Path> = HashMap::new();
+    for udl in udls {
+        let content = fs::read_to_string(udl)?;
+        let namespace = udl_namespace(&content)
+            .with_context(|| format!("Couldn't find the namespace in {}", udl.display()))?;
+        if let Some(previous) = seen.insert(namespace.to_string(), udl) {
+            bail!(
+                "The UDL files {} and {} both define the uniffi namespace `{}`, \
+                their python bindings would overwrite each other",
+                previous.display(),
+                udl.display(),
+                namespace
+            );
+        }
+    }
+    Ok(())
+}
+
+fn glob_udls(crate_dir: &Path) -> Result<Vec<PathBuf>> {
+    let pattern = crate_dir.join("src").join("*.udl");
+    Ok(glob::glob(pattern.to_str().unwrap())?
+        .map(|p| p.unwrap())
+        .collect::<Vec<_>>())
+}
+
 fn generate_uniffi_bindings(
     crate_dir: &Path,
-    target_dir: &Path,
+    cargo_metadata: &cargo_metadata::Metadata,
+    build_dir: &Path,
     module_name: &str,
     target_os: Os,
     artifact: &Path,
 ) -> Result<UniFfiBindings> {
+    let binding_dir = build_dir.join("uniffi").join(module_name);
+    // We package every python file in there, so we must not pick up stale bindings
+    if binding_dir.exists() {
+        fs::remove_dir_all(&binding_dir)?;
+    }
+    fs::create_dir_all(&binding_dir)?;
     // `binding_dir` must use absolute path because we chdir to `crate_dir`
     // when running uniffi-bindgen
-    let binding_dir = target_dir
-        .normalize()?
-        .join("maturin")
-        .join("uniffi")
-        .join(module_name)
-        .into_path_buf();
-    fs::create_dir_all(&binding_dir)?;
+    let binding_dir = binding_dir.normalize()?.into_path_buf();
 
-    let pattern = crate_dir.join("src").join("*.udl");
-    let udls = glob::glob(pattern.to_str().unwrap())?
-        .map(|p| p.unwrap())
-        .collect::<Vec<_>>();
-    let is_library = if udls.is_empty() {
-        true
-    } else if udls.len() > 1 {
+    let udls = glob_udls(crate_dir)?;
+    if udls.len() > 1 {
         bail!(
-            "Multiple UDL files found in {}",
+            "Multiple UDL files found in {}, uniffi only supports one namespace per crate. \
+            Please move the other namespaces into their own crates",
             crate_dir.join("src").display()
         );
+    }
+    // With other uniffi components in the dependencies, only library mode generates the bindings
+    // for all of them, one module per namespace
+    let dependency_dirs = if udls.is_empty() {
+        Vec::new()
     } else {
-        false
+        uniffi_dependency_dirs(cargo_metadata)?
     };
+    let mut all_udls = udls.clone();
+    for dir in &dependency_dirs {
+        all_udls.extend(glob_udls(dir)?);
+    }
+    check_udl_namespaces(&all_udls)?;
+    let is_library = udls.is_empty() || !dependency_dirs.is_empty();
 
     let mut cmd = uniffi_bindgen_command(crate_dir)?;
     cmd.args([
@@ -1169,6 +1857,7 @@ fn generate_uniffi_bindings(
                 .to_string_lossy()
                 .to_string()
         })
+        .sorted()
         .collect_vec();
 
     Ok(UniFfiBindings {
@@ -1184,7 +1873,8 @@ pub fn write_uniffi_module(
     writer: &mut impl ModuleWriter,
     project_layout: &ProjectLayout,
     crate_dir: &Path,
-    target_dir: &Path,
+    cargo_metadata: &cargo_metadata::Metadata,
+    build_dir: &Path,
     module_name: &str,
     artifact: &Path,
     target_os: Os,
@@ -1195,7 +1885,14 @@ pub fn write_uniffi_module(
         names: binding_names,
         cdylib,
         path: binding_dir,
-    } = generate_uniffi_bindings(crate_dir, target_dir, module_name, target_os, artifact)?;
+    } = generate_uniffi_bindings(
+        crate_dir,
+        cargo_metadata,
+        build_dir,
+        module_name,
+        target_os,
+        artifact,
+    )?;
 
     let py_init = binding_names
         .iter()
@@ -1354,7 +2051,7 @@ pub fn write_python_part(
     }
     for package in &project_layout.python_packages {
         let package_path = python_dir.join(package);
-        if python_packages.iter().any(|p| *p == package_path) {
+        if python_packages.contains(&package_path) {
             continue;
         }
         python_packages.push(package_path);
@@ -1398,26 +2095,19 @@ pub fn write_python_part(
     if let Some(pyproject) = pyproject_toml {
         // FIXME: in src-layout pyproject.toml isn't located directly in python dir
         let pyproject_dir = python_dir;
-        if let Some(glob_patterns) = pyproject.include() {
-            for pattern in glob_patterns
-                .iter()
-                .filter_map(|glob_pattern| glob_pattern.targets(Format::Wheel))
-            {
-                eprintln!("📦 Including files matching \"{pattern}\"");
-                for source in glob::glob(&pyproject_dir.join(pattern).to_string_lossy())
-                    .with_context(|| format!("Invalid glob pattern: {pattern}"))?
-                    .filter_map(Result::ok)
-                {
-                    let target = source.strip_prefix(pyproject_dir)?.to_path_buf();
-                    if source.is_dir() {
-                        writer.add_directory(target)?;
-                    } else {
-                        #[cfg(unix)]
-                        let mode = source.metadata()?.permissions().mode();
-                        #[cfg(not(unix))]
-                        let mode = 0o644;
-                        writer.add_file_with_permissions(target, source, mode)?;
-                    }
+        for (pattern, sources) in pyproject.resolved_includes(pyproject_dir, Format::Wheel)? {
+            eprintln!("📦 Including files matching \"{pattern}\"");
+            warn_unmatched_include(pattern, &sources, Format::Wheel, pyproject_dir);
+            for source in sources {
+                let target = source.strip_prefix(pyproject_dir)?.to_path_buf();
+                if source.is_dir() {
+                    writer.add_directory(target)?;
+                } else {
+                    #[cfg(unix)]
+                    let mode = source.metadata()?.permissions().mode();
+                    #[cfg(not(unix))]
+                    let mode = 0o644;
+                    writer.add_file_with_permissions(target, source, mode)?;
                 }
             }
         }
@@ -1426,11 +2116,28 @@ pub fn write_python_part(
     Ok(())
 }
 
+/// Warns about a `[tool.maturin.include]` pattern that matched nothing, which usually means
+/// it's relative to the wrong directory or restricted to the other format
+pub(crate) fn warn_unmatched_include(
+    pattern: &str,
+    sources: &[PathBuf],
+    format: Format,
+    base_dir: &Path,
+) {
+    if sources.is_empty() {
+        eprintln!(
+            "⚠️  Warning: The {format} include pattern \"{pattern}\" didn't match any files in {}",
+            base_dir.display()
+        );
+    }
+}
+
 /// Creates the .dist-info directory and fills it with all metadata files except RECORD
 pub fn write_dist_info(
     writer: &mut impl ModuleWriter,
     metadata24: &Metadata24,
     tags: &[String],
+    root_is_purelib: bool,
 ) -> Result<()> {
     let dist_info_dir = metadata24.get_dist_info_dir();
 
@@ -1445,7 +2152,7 @@ pub fn write_dist_info(
     writer.add_bytes(
         dist_info_dir.join("WHEEL"),
         None,
-        wheel_file(tags)?.as_bytes(),
+        wheel_file(tags, root_is_purelib)?.as_bytes(),
     )?;
 
     let mut entry_points = String::new();
@@ -1521,6 +2228,20 @@ pub fn add_data(
                         .get_data_dir()
                         .join(file.path().strip_prefix(data).unwrap());
 
+                    if dir_name == "scripts" && file.path().is_file() {
+                        let script = fs::read(file.path())?;
+                        if let Some(script) = rewrite_python_shebang(&script) {
+                            debug!("Rewriting the shebang of {}", file.path().display());
+                            writer.add_bytes_with_permissions(
+                                relative,
+                                Some(file.path()),
+                                &script,
+                                mode,
+                            )?;
+                            continue;
+                        }
+                    }
+
                     if file.path_is_symlink() {
                         // Copy the actual file contents, not the link, so that you can create a
                         // data directory by joining different data sources
@@ -1546,6 +2267,42 @@ pub fn add_data(
     Ok(())
 }
 
+/// Replaces a python shebang such as `#!/usr/bin/env python3` in a script of the data
+/// directory with `#!python` (or `#!pythonw`), which installers replace with the interpreter of
+/// the environment the wheel is installed into
+///
+/// Returns `None` if the script has no python shebang or already uses the placeholder.
+///
+/// See https://packaging.python.org/en/latest/specifications/binary-distribution-format/#recommended-installer-features
+fn rewrite_python_shebang(script: &[u8]) -> Option<Vec<u8>> {
+    let first_line_end = script
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .unwrap_or(script.len());
+    let first_line = str::from_utf8(&script[..first_line_end]).ok()?;
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?;
+    if program.rsplit('/').next() == Some("env") {
+        // Skip options such as `-S`
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    let version = program.rsplit('/').next()?.strip_prefix("python")?;
+    let (version, placeholder) = match version.strip_suffix('w') {
+        Some(version) => (version, "#!pythonw"),
+        None => (version, "#!python"),
+    };
+    if !version.chars().all(|c| c.is_ascii_digit() || c == '.')
+        || first_line.trim_end() == placeholder
+    {
+        return None;
+    }
+    // Keep the line ending of the script
+    let shebang_end = first_line_end - (first_line.len() - first_line.trim_end_matches('\r').len());
+    let mut rewritten = placeholder.as_bytes().to_vec();
+    rewritten.extend_from_slice(&script[shebang_end..]);
+    Some(rewritten)
+}
+
 #[cfg(test)]
 mod tests {
     use ignore::overrides::OverrideBuilder;
@@ -1553,6 +2310,53 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_uniffi_dependency_dirs() {
+        let cargo_metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path("test-crates/uniffi-multiple-udl/Cargo.toml")
+            .exec()
+            .unwrap();
+        let dirs = uniffi_dependency_dirs(&cargo_metadata).unwrap();
+        assert_eq!(dirs.len(), 1);
+        assert!(dirs[0].ends_with("uniffi-multiple-udl/strings"));
+
+        // The root package isn't its own dependency
+        let cargo_metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path("test-crates/uniffi-multiple-udl/strings/Cargo.toml")
+            .exec()
+            .unwrap();
+        assert!(uniffi_dependency_dirs(&cargo_metadata).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_udl_namespace() {
+        assert_eq!(
+            udl_namespace("namespace math {\n  u32 add(u32 a, u32 b);\n};\n"),
+            Some("math")
+        );
+        assert_eq!(
+            udl_namespace("// namespace commented {};\n\nnamespace strings{};\n"),
+            Some("strings")
+        );
+        assert_eq!(udl_namespace("interface Foo {};\n"), None);
+    }
+
+    #[test]
+    fn test_check_udl_namespaces_collision() {
+        let tmp_dir = tempdir().unwrap();
+        let first = tmp_dir.path().join("first.udl");
+        let second = tmp_dir.path().join("second.udl");
+        fs::write(&first, "namespace math {};\n").unwrap();
+        fs::write(&second, "namespace strings {};\n").unwrap();
+        check_udl_namespaces(&[first.clone(), second.clone()]).unwrap();
+
+        fs::write(&second, "namespace math {};\n").unwrap();
+        let err = check_udl_namespaces(&[first, second]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("both define the uniffi namespace `math`"));
+    }
+
     #[test]
     // The mechanism is the same for wheel_writer
     fn sdist_writer_excludes() -> Result<(), Box<dyn std::error::Error>> {
@@ -1586,4 +2390,465 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn sdist_writer_follow_symlinks() -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        let tmp_dir = TempDir::new()?;
+        let shared = tmp_dir.path().join("shared.txt");
+        fs::write(&shared, "shared")?;
+        let link = tmp_dir.path().join("link.txt");
+        std::os::unix::fs::symlink("shared.txt", &link)?;
+
+        for follow_symlinks in [true, false] {
+            let out_dir = TempDir::new()?;
+            let mut writer = SDistWriter::new(&out_dir, &metadata, Override::empty())?
+                .with_follow_symlinks(follow_symlinks);
+            writer.add_file("dummy-1.0/link.txt", &link)?;
+            let sdist = writer.finish()?;
+
+            let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(File::open(sdist)?));
+            let mut entries = archive.entries()?;
+            let mut entry = entries.next().unwrap()?;
+            assert_eq!(entry.path()?, Path::new("dummy-1.0/link.txt"));
+            if follow_symlinks {
+                assert!(entry.header().entry_type().is_file());
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                assert_eq!(contents, "shared");
+            } else {
+                assert!(entry.header().entry_type().is_symlink());
+                assert_eq!(entry.link_name()?.unwrap(), Path::new("shared.txt"));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_python_shebang() {
+        let rewrite = |script: &str| {
+            rewrite_python_shebang(script.as_bytes()).map(|x| String::from_utf8(x).unwrap())
+        };
+        let body = "\nimport sys\n";
+        for shebang in [
+            "#!/usr/bin/env python",
+            "#!/usr/bin/env python3",
+            "#!/usr/bin/env -S python3.12 -u",
+            "#!/usr/bin/python3.12",
+            "#! /opt/python/bin/python",
+            "#!python3",
+        ] {
+            assert_eq!(
+                rewrite(&format!("{shebang}{body}")).as_deref(),
+                Some("#!python\nimport sys\n"),
+                "{shebang}"
+            );
+        }
+        assert_eq!(
+            rewrite("#!/usr/bin/env pythonw\r\nimport sys\r\n").as_deref(),
+            Some("#!pythonw\r\nimport sys\r\n")
+        );
+        assert_eq!(rewrite("#!/usr/bin/python3").as_deref(), Some("#!python"));
+        // Already the placeholder, other interpreters and no shebang
+        assert_eq!(rewrite("#!python\nimport sys\n"), None);
+        assert_eq!(rewrite("#!/bin/bash\necho hi\n"), None);
+        assert_eq!(rewrite("#!/usr/bin/env pythonista\n"), None);
+        assert_eq!(rewrite("import sys\n"), None);
+    }
+
+    #[test]
+    fn wheel_writer_parallel_compression_keeps_order() -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        let tags = ["py3-none-any".to_string()];
+        let files: Vec<(String, Vec<u8>)> = (0..20)
+            .map(|i| {
+                (
+                    format!("dummy/file{i}.py"),
+                    i.to_string().repeat(i * 1000).into(),
+                )
+            })
+            .collect();
+
+        let mut wheels = Vec::new();
+        let mut archives = Vec::new();
+        // The last one flushes the pending files several times while adding them
+        for (jobs, pending_limit) in [(1, usize::MAX), (4, usize::MAX), (4, 30_000)] {
+            let tmp_dir = TempDir::new()?;
+            let mut writer = WheelWriter::new(
+                "py3-none-any",
+                tmp_dir.path(),
+                &metadata,
+                &tags,
+                false,
+                Override::empty(),
+            )?
+            .with_jobs(Some(jobs))
+            .with_extra_hashes(&[ExtraHash::Sha512])
+            // Fixed timestamps, so that the archives can be compared byte by byte
+            .with_mtime(DateTime::default());
+            writer.pending_limit = pending_limit;
+            for (name, bytes) in &files {
+                writer.add_bytes(name, None, bytes)?;
+            }
+            let wheel_path = writer.finish()?;
+            archives.push(fs::read(&wheel_path)?);
+
+            let mut archive = ZipArchive::new(fs::File::open(&wheel_path)?)?;
+            let mut entries = Vec::new();
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                entries.push((file.name().to_string(), contents));
+            }
+            wheels.push(entries);
+        }
+        assert_eq!(wheels[0], wheels[1]);
+        assert!(archives[0] == archives[1] && archives[1] == archives[2]);
+        let names: Vec<_> = wheels[0].iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names.last(), Some(&"dummy-1.0.dist-info/RECORD"));
+        let file_names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names
+            .windows(file_names.len())
+            .any(|window| window == file_names));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "faster-tests"))]
+    fn wheel_writer_stores_matching_files() -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        let tags = ["py3-none-any".to_string()];
+        let tmp_dir = TempDir::new()?;
+        let mut stored = OverrideBuilder::new(&tmp_dir);
+        stored.add("*.onnx")?;
+        let mut writer = WheelWriter::new(
+            "py3-none-any",
+            tmp_dir.path(),
+            &metadata,
+            &tags,
+            false,
+            Override::empty(),
+        )?
+        .with_stored(stored.build()?);
+        writer.add_bytes("dummy/__init__.py", None, "x = 1\n".repeat(100).as_bytes())?;
+        writer.add_bytes("dummy/models/model.onnx", None, &[0; 1000])?;
+        let wheel_path = writer.finish()?;
+
+        let mut archive = ZipArchive::new(fs::File::open(&wheel_path)?)?;
+        assert_eq!(
+            archive.by_name("dummy/models/model.onnx")?.compression(),
+            zip::CompressionMethod::Stored
+        );
+        assert_eq!(
+            archive.by_name("dummy/__init__.py")?.compression(),
+            zip::CompressionMethod::Deflated
+        );
+        assert_eq!(
+            archive.by_name("dummy-1.0.dist-info/RECORD")?.compression(),
+            zip::CompressionMethod::Deflated
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dist_index_links_relative_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        fs::create_dir_all(tmp_dir.path().join("cp312"))?;
+        let wheel = tmp_dir
+            .path()
+            .join("cp312")
+            .join("foo-0.1.0-cp312-cp312-linux_x86_64.whl");
+        let sdist = tmp_dir.path().join("foo-0.1.0.tar.gz");
+        fs::write(&wheel, b"wheel")?;
+        fs::write(&sdist, b"sdist")?;
+
+        let index = write_dist_index(&[wheel, sdist])?;
+        assert_eq!(index, tmp_dir.path().normalize()?.join("index.html"));
+        let html = fs::read_to_string(&index)?;
+        assert!(html.contains(
+            "<a href=\"cp312/foo-0.1.0-cp312-cp312-linux_x86_64.whl#sha256=\
+            ba59926159d2aa256eb8739b8da7e2b574b960e1202c6d624cbe981cef996c91\">\
+            foo-0.1.0-cp312-cp312-linux_x86_64.whl</a><br/>"
+        ));
+        assert!(html.contains("<a href=\"foo-0.1.0.tar.gz#sha256="));
+        Ok(())
+    }
+
+    #[test]
+    fn dist_archive_is_deterministic() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        let wheel = tmp_dir.path().join("foo-0.1.0-py3-none-any.whl");
+        let sdist = tmp_dir.path().join("foo-0.1.0.tar.gz");
+        fs::write(&wheel, b"wheel")?;
+        fs::write(&sdist, b"sdist")?;
+
+        let first = tmp_dir.path().join("first.zip");
+        let second = tmp_dir.path().join("second.zip");
+        write_dist_archive(&first, &[wheel.clone(), sdist.clone()])?;
+        write_dist_archive(&second, &[sdist, wheel])?;
+        assert_eq!(fs::read(&first)?, fs::read(&second)?);
+
+        let mut archive = ZipArchive::new(File::open(&first)?)?;
+        assert_eq!(
+            archive.file_names().sorted().collect::<Vec<_>>(),
+            [
+                "foo-0.1.0-py3-none-any.whl",
+                "foo-0.1.0.tar.gz",
+                "manifest.json"
+            ]
+        );
+        let mut manifest = String::new();
+        archive
+            .by_name("manifest.json")?
+            .read_to_string(&mut manifest)?;
+        let manifest: serde_json::Value = serde_json::from_str(&manifest)?;
+        assert_eq!(manifest[0]["filename"], "foo-0.1.0-py3-none-any.whl");
+        assert_eq!(manifest[0]["size"], 5);
+        assert_eq!(
+            manifest[0]["sha256"],
+            "ba59926159d2aa256eb8739b8da7e2b574b960e1202c6d624cbe981cef996c91"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wheel_difference_names_first_member() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        let write_zip = |name: &str, files: &[(&str, &[u8])]| -> Result<PathBuf> {
+            let path = tmp_dir.path().join(name);
+            let options = SimpleFileOptions::default().last_modified_time(DateTime::default());
+            let mut zip = ZipWriter::new(File::create(&path)?);
+            for (file_name, bytes) in files {
+                zip.start_file(*file_name, options)?;
+                zip.write_all(bytes)?;
+            }
+            zip.finish()?;
+            Ok(path)
+        };
+        let first = write_zip(
+            "first.whl",
+            &[("foo/__init__.py", b""), ("foo/lib.so", b"a")],
+        )?;
+        let same = write_zip(
+            "same.whl",
+            &[("foo/__init__.py", b""), ("foo/lib.so", b"a")],
+        )?;
+        let changed = write_zip(
+            "changed.whl",
+            &[("foo/__init__.py", b""), ("foo/lib.so", b"b")],
+        )?;
+        let shorter = write_zip("shorter.whl", &[("foo/__init__.py", b"")])?;
+
+        assert_eq!(first_wheel_difference(&first, &same)?, None);
+        assert_eq!(
+            first_wheel_difference(&first, &changed)?.as_deref(),
+            Some("foo/lib.so has different contents")
+        );
+        assert_eq!(
+            first_wheel_difference(&first, &shorter)?.as_deref(),
+            Some("foo/lib.so is missing in the second build")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wheel_writer_excludes_directories() -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        let tags = ["py3-none-any".to_string()];
+        let tmp_dir = TempDir::new()?;
+        let mut excludes = OverrideBuilder::new(tmp_dir.path());
+        excludes.add("**/__pycache__")?;
+        excludes.add("dummy/tests/")?;
+        excludes.add("python/dummy/*.txt")?;
+        let mut writer = WheelWriter::new(
+            "py3-none-any",
+            tmp_dir.path(),
+            &metadata,
+            &tags,
+            false,
+            excludes.build()?,
+        )?;
+        writer.add_bytes("dummy/__init__.py", None, b"")?;
+        writer.add_bytes("dummy/__pycache__/__init__.cpython-312.pyc", None, b"")?;
+        writer.add_bytes("dummy/sub/__pycache__/mod.cpython-312.pyc", None, b"")?;
+        writer.add_bytes("dummy/tests/test_dummy.py", None, b"")?;
+        writer.add_bytes("dummy/tests.py", None, b"")?;
+        // With `python-source = "python"` the patterns match the source relative to
+        // pyproject.toml
+        let source = tmp_dir.path().join("python/dummy/notes.txt");
+        writer.add_bytes("dummy/notes.txt", Some(&source), b"")?;
+        let wheel_path = writer.finish()?;
+
+        let archive = ZipArchive::new(File::open(&wheel_path)?)?;
+        assert_eq!(
+            archive.file_names().sorted().collect::<Vec<_>>(),
+            [
+                "dummy-1.0.dist-info/METADATA",
+                "dummy-1.0.dist-info/RECORD",
+                "dummy-1.0.dist-info/WHEEL",
+                "dummy/__init__.py",
+                "dummy/tests.py",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wheel_writer_extra_hashes() -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        let tags = ["py3-none-any".to_string()];
+        let tmp_dir = TempDir::new()?;
+        let mut writer = WheelWriter::new(
+            "py3-none-any",
+            tmp_dir.path(),
+            &metadata,
+            &tags,
+            false,
+            Override::empty(),
+        )?
+        .with_extra_hashes(&[ExtraHash::Blake3, ExtraHash::Blake3]);
+        writer.add_bytes("dummy/__init__.py", None, b"print('hello')\n")?;
+        writer.add_bytes("dummy/data.txt", None, b"data")?;
+        let wheel_path = writer.finish()?;
+
+        let mut archive = ZipArchive::new(fs::File::open(&wheel_path)?)?;
+        let mut blake3_record = String::new();
+        archive
+            .by_name("dummy-1.0.dist-info/RECORD.blake3")?
+            .read_to_string(&mut blake3_record)?;
+        let lines: Vec<&str> = blake3_record.lines().collect();
+        // Everything but RECORD and the supplementary file itself
+        assert_eq!(lines.len(), archive.len() - 2);
+        for line in lines {
+            let (name, rest) = line.split_once(',').unwrap();
+            let (hash, len) = rest.rsplit_once(',').unwrap();
+            let mut bytes = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut bytes)?;
+            let expected = URL_SAFE_NO_PAD.encode(blake3::hash(&bytes).as_bytes());
+            assert_eq!(hash, format!("blake3={expected}"), "{name}");
+            assert_eq!(len, bytes.len().to_string(), "{name}");
+        }
+
+        // The spec-mandated RECORD stays sha256 and covers the supplementary file
+        let mut record = String::new();
+        archive
+            .by_name("dummy-1.0.dist-info/RECORD")?
+            .read_to_string(&mut record)?;
+        let blake3_hash = URL_SAFE_NO_PAD.encode(Sha256::digest(blake3_record.as_bytes()));
+        assert!(record.contains(&format!(
+            "dummy-1.0.dist-info/RECORD.blake3,sha256={blake3_hash},{}",
+            blake3_record.len()
+        )));
+        assert_eq!(record.lines().count(), archive.len());
+        Ok(())
+    }
+
+    #[test]
+    fn rebuild_record_after_modification() -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = Metadata24::new("dummy".to_string(), Version::new([1, 0]));
+        let tags = ["py3-none-any".to_string()];
+        let tmp_dir = TempDir::new()?;
+        let mut writer = WheelWriter::new(
+            "py3-none-any",
+            tmp_dir.path(),
+            &metadata,
+            &tags,
+            false,
+            Override::empty(),
+        )?;
+        writer.add_bytes("dummy/__init__.py", None, b"print('original')\n")?;
+        writer.add_bytes("dummy/data.txt", None, b"unchanged")?;
+        let wheel_path = writer.finish()?;
+
+        // Patch a file the way a distro would, leaving RECORD stale
+        let patched = b"print('patched')\n".as_slice();
+        let mut archive = ZipArchive::new(fs::File::open(&wheel_path)?)?;
+        let patched_path = tmp_dir.path().join("patched.whl");
+        let mut zip = ZipWriter::new(fs::File::create(&patched_path)?);
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if file.name() == "dummy/__init__.py" {
+                zip.start_file(file.name(), SimpleFileOptions::default())?;
+                zip.write_all(patched)?;
+            } else {
+                zip.raw_copy_file(file)?;
+            }
+        }
+        zip.finish()?;
+
+        rebuild_record(&patched_path)?;
+
+        let mut archive = ZipArchive::new(fs::File::open(&patched_path)?)?;
+        let mut record = String::new();
+        archive
+            .by_name("dummy-1.0.dist-info/RECORD")?
+            .read_to_string(&mut record)?;
+        let lines: Vec<&str> = record.lines().collect();
+        let patched_hash = URL_SAFE_NO_PAD.encode(Sha256::digest(patched));
+        assert!(lines.contains(&format!("dummy/__init__.py,sha256={patched_hash},17").as_str()));
+        let unchanged_hash = URL_SAFE_NO_PAD.encode(Sha256::digest(b"unchanged"));
+        assert!(lines.contains(&format!("dummy/data.txt,sha256={unchanged_hash},9").as_str()));
+        assert_eq!(lines.last(), Some(&"dummy-1.0.dist-info/RECORD,,"));
+        // Every file in the wheel, including RECORD itself, is listed exactly once
+        assert_eq!(lines.len(), archive.len());
+        let mut contents = String::new();
+        archive
+            .by_name("dummy/__init__.py")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents.as_bytes(), patched);
+        Ok(())
+    }
+
+    #[test]
+    fn wheel_file_root_is_purelib() -> Result<()> {
+        let tags = ["py3-none-any".to_string()];
+        assert!(wheel_file(&tags, false)?.contains("Root-Is-Purelib: false\n"));
+        assert!(wheel_file(&tags, true)?.contains("Root-Is-Purelib: true\n"));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn generate_stubs_from_command() -> Result<()> {
+        let artifact = Path::new("/tmp/my_module.so");
+        let from_stdout = GenerateStubsConfig {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo \"# stub for $0 from $1\"".to_string(),
+                "{module}".to_string(),
+                "{artifact}".to_string(),
+            ],
+        };
+        let stub = generate_stubs(&from_stdout, "my_module", artifact)?;
+        assert_eq!(stub, b"# stub for my_module from /tmp/my_module.so\n");
+
+        let from_file = GenerateStubsConfig {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo 'def f() -> int: ...' > \"${0#--output=}\"".to_string(),
+                "--output={output}".to_string(),
+            ],
+        };
+        let stub = generate_stubs(&from_file, "my_module", artifact)?;
+        assert_eq!(stub, b"def f() -> int: ...\n");
+        Ok(())
+    }
+
+    #[test]
+    fn generate_stubs_missing_generator() {
+        let config = GenerateStubsConfig {
+            command: vec!["maturin-nonexistent-stubgen".to_string()],
+        };
+        let err = generate_stubs(&config, "my_module", Path::new("my_module.so")).unwrap_err();
+        assert!(err.to_string().contains(
+            "The stub generator `maturin-nonexistent-stubgen` from `tool.maturin.generate-stubs` was not found"
+        ));
+
+        let empty = GenerateStubsConfig { command: vec![] };
+        assert!(generate_stubs(&empty, "my_module", Path::new("my_module.so")).is_err());
+    }
 }
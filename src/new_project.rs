@@ -16,6 +16,27 @@ enum ProjectLayout {
     PureRust,
 }
 
+/// The directory structure of the generated project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LayoutPreset {
+    /// Pure Rust project with only a `Cargo.toml` and `src/`
+    Pure,
+    /// Rust in `src/` and the python package in `python/`
+    Mixed,
+    /// Python first layout with the python package in `src/` and the Rust crate in `rust/`
+    Src,
+}
+
+impl From<LayoutPreset> for ProjectLayout {
+    fn from(preset: LayoutPreset) -> Self {
+        match preset {
+            LayoutPreset::Pure => ProjectLayout::PureRust,
+            LayoutPreset::Mixed => ProjectLayout::Mixed { src: false },
+            LayoutPreset::Src => ProjectLayout::Mixed { src: true },
+        }
+    }
+}
+
 struct ProjectGenerator<'a> {
     env: Environment<'a>,
     project_name: String,
@@ -171,6 +192,9 @@ pub struct GenerateProjectOptions {
     /// Use Python first src layout for mixed Rust/Python project
     #[arg(long)]
     src: bool,
+    /// The project layout, an alternative to `--mixed` and `--src`
+    #[arg(long, value_enum, conflicts_with_all = ["mixed", "src"])]
+    layout: Option<LayoutPreset>,
     /// Which kind of bindings to use
     #[arg(
         short,
@@ -232,7 +256,12 @@ fn generate_project(
 
         validate_name(temp.as_str()).map_err(|e| anyhow::anyhow!(e))?
     };
-    let bindings_items = if options.mixed {
+    let layout = match options.layout {
+        Some(preset) => preset.into(),
+        None if options.mixed => ProjectLayout::Mixed { src: options.src },
+        None => ProjectLayout::PureRust,
+    };
+    let bindings_items = if matches!(layout, ProjectLayout::Mixed { .. }) {
         vec!["pyo3", "cffi", "uniffi"]
     } else {
         vec!["pyo3", "cffi", "uniffi", "bin"]
@@ -252,15 +281,59 @@ fn generate_project(
         bindings_items[selection].to_string()
     };
 
-    let layout = if options.mixed {
-        ProjectLayout::Mixed { src: options.src }
-    } else {
-        ProjectLayout::PureRust
-    };
     let generator = ProjectGenerator::new(name, layout, bindings, overwrite)?;
     generator.generate(project_path)
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    fn generate(layout: &str) -> tempfile::TempDir {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let options = GenerateProjectOptions::try_parse_from([
+            "new",
+            "--name",
+            "my-project",
+            "--bindings",
+            "pyo3",
+            "--layout",
+            layout,
+        ])
+        .unwrap();
+        generate_project(tmp_dir.path(), options, true).unwrap();
+        tmp_dir
+    }
+
+    #[test]
+    fn test_layout_presets() {
+        let pure = generate("pure");
+        assert!(pure.path().join("src/lib.rs").is_file());
+        assert!(!pure.path().join("python").exists());
+
+        let mixed = generate("mixed");
+        assert!(mixed.path().join("src/lib.rs").is_file());
+        assert!(mixed.path().join("python/my_project/__init__.py").is_file());
+        let pyproject = fs::read_to_string(mixed.path().join("pyproject.toml")).unwrap();
+        assert!(pyproject.contains("python-source = \"python\""));
+
+        let src = generate("src");
+        assert!(src.path().join("rust/Cargo.toml").is_file());
+        assert!(src.path().join("rust/src/lib.rs").is_file());
+        assert!(src.path().join("src/my_project/__init__.py").is_file());
+        let pyproject = fs::read_to_string(src.path().join("pyproject.toml")).unwrap();
+        assert!(!pyproject.contains("python-source"));
+    }
+
+    #[test]
+    fn test_layout_conflicts_with_mixed() {
+        assert!(
+            GenerateProjectOptions::try_parse_from(["new", "--mixed", "--layout", "pure"]).is_err()
+        );
+    }
+}
+
 mod package_name_validations {
     // based on: https://github.com/pypi/warehouse/blob/8f79d90a310f0243ab15f52c41de093708a61dfd/warehouse/packaging/models.py#L211C9-L214C10
     pub fn pypi_check_name(name: &str) -> anyhow::Result<()> {
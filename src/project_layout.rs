@@ -6,7 +6,7 @@ use normpath::PathExt as _;
 use std::collections::HashSet;
 use std::env;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use tracing::{debug, instrument};
 
 const PYPROJECT_TOML: &str = "pyproject.toml";
@@ -63,8 +63,9 @@ impl ProjectResolver {
         cargo_manifest_path: Option<PathBuf>,
         mut cargo_options: CargoOptions,
     ) -> Result<Self> {
-        let (manifest_file, pyproject_file) =
+        let (manifest_file, pyproject_file, manifest_metadata) =
             Self::resolve_manifest_paths(cargo_manifest_path, &cargo_options)?;
+        let manifest_metadata_args = extract_cargo_metadata_args(&cargo_options)?;
         if !manifest_file.is_file() {
             bail!(
                 "{} is not the path to a Cargo.toml",
@@ -101,19 +102,70 @@ impl ProjectResolver {
         let pyproject = pyproject_toml.as_ref();
         let tool_maturin = pyproject.and_then(|p| p.maturin());
 
-        let pyproject_toml_maturin_options = if let Some(tool_maturin) = tool_maturin {
+        let mut pyproject_toml_maturin_options = if let Some(tool_maturin) = tool_maturin {
             cargo_options.merge_with_pyproject_toml(tool_maturin.clone())
         } else {
             Vec::new()
         };
+        let manifest_metadata = match pyproject.and_then(|x| x.default_features_except()) {
+            Some(excluded) => {
+                // The default features as cargo resolved them, with optional dependencies
+                // that are only enabled through `dep:` removed as they aren't features
+                let manifest_metadata = match manifest_metadata {
+                    Some(cargo_metadata) => cargo_metadata,
+                    None => Self::resolve_cargo_metadata(&manifest_file, &cargo_options)?,
+                };
+                let default_features: Vec<String> = manifest_metadata
+                    .root_package()
+                    .and_then(|package| package.features.get("default"))
+                    .map(|features| {
+                        features
+                            .iter()
+                            .filter(|feature| !feature.starts_with("dep:"))
+                            .cloned()
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                cargo_options.exclude_default_features(&default_features, excluded)?;
+                pyproject_toml_maturin_options.push("default-features-except");
+                Some(manifest_metadata)
+            }
+            None => manifest_metadata,
+        };
 
-        let cargo_metadata = Self::resolve_cargo_metadata(&manifest_file, &cargo_options)?;
+        let cargo_metadata = match manifest_metadata {
+            // Avoid running `cargo metadata` twice, unless pyproject.toml changed its arguments
+            Some(cargo_metadata)
+                if extract_cargo_metadata_args(&cargo_options)? == manifest_metadata_args =>
+            {
+                debug!("Reusing the cargo metadata of the manifest path lookup");
+                cargo_metadata
+            }
+            _ => Self::resolve_cargo_metadata(&manifest_file, &cargo_options)?,
+        };
 
         let mut metadata24 = Metadata24::from_cargo_toml(manifest_dir, &cargo_metadata)
             .context("Failed to parse Cargo.toml into python metadata")?;
         if let Some(pyproject) = pyproject {
             let pyproject_dir = pyproject_file.parent().unwrap();
             metadata24.merge_pyproject_toml(pyproject_dir, pyproject)?;
+            if let Some(extras) = pyproject.extras() {
+                pyproject.require_dynamic("optional-dependencies", "extras")?;
+                let enabled_features = cargo_metadata
+                    .resolve
+                    .as_ref()
+                    .zip(cargo_metadata.root_package())
+                    .and_then(|(resolve, root_pkg)| {
+                        resolve.nodes.iter().find(|node| node.id == root_pkg.id)
+                    })
+                    .map(|node| node.features.clone())
+                    .unwrap_or_default();
+                metadata24.add_feature_extras(
+                    extras,
+                    &enabled_features,
+                    pyproject.dependency_overrides(),
+                )?;
+            }
         }
 
         let crate_name = &cargo_toml.package.name;
@@ -133,6 +185,10 @@ impl ProjectResolver {
                 .map(|project| project.name.as_str()))
             .unwrap_or(crate_name)
             .to_owned();
+        let module_name = match pyproject.and_then(|x| x.module_path()) {
+            Some(module_path) => apply_module_path(&module_name, module_path)?,
+            None => module_name,
+        };
 
         let project_root = if pyproject_file.is_file() {
             pyproject_file.parent().unwrap_or(manifest_dir)
@@ -197,6 +253,16 @@ impl ProjectResolver {
             data,
             custom_python_source,
         )?;
+        if let Some(module_path) = pyproject.and_then(|x| x.module_path()) {
+            if project_layout.python_module.is_none() {
+                bail!(
+                    "`tool.maturin.module-path` is set to `{}`, but there is no python package at {}. \
+                    The module path must point into the python package of a mixed rust/python project",
+                    module_path.display(),
+                    project_layout.python_dir.join(module_path).display()
+                );
+            }
+        }
         Ok(Self {
             project_layout,
             cargo_toml_path: manifest_file,
@@ -211,11 +277,12 @@ impl ProjectResolver {
         })
     }
 
-    /// Get cargo manifest file path and pyproject.toml path
+    /// Get cargo manifest file path and pyproject.toml path, and the `cargo metadata` of the
+    /// manifest if it had to be resolved to find the pyproject.toml
     fn resolve_manifest_paths(
         cargo_manifest_path: Option<PathBuf>,
         cargo_options: &CargoOptions,
-    ) -> Result<(PathBuf, PathBuf)> {
+    ) -> Result<(PathBuf, PathBuf, Option<Metadata>)> {
         // use command line argument if specified
         if let Some(path) = cargo_manifest_path {
             let path = path
@@ -226,8 +293,9 @@ impl ProjectResolver {
                 "Using cargo manifest path from command line argument: {:?}",
                 path
             );
-            let workspace_root = Self::resolve_cargo_metadata(&path, cargo_options)?.workspace_root;
-            let workspace_parent = workspace_root.parent().unwrap_or(&workspace_root);
+            let cargo_metadata = Self::resolve_cargo_metadata(&path, cargo_options)?;
+            let workspace_root = &cargo_metadata.workspace_root;
+            let workspace_parent = workspace_root.parent().unwrap_or(workspace_root);
             for parent in path.ancestors().skip(1) {
                 // Allow looking outside to the parent directory of Cargo workspace root
                 if !dunce::simplified(parent).starts_with(workspace_parent) {
@@ -236,18 +304,19 @@ impl ProjectResolver {
                 let pyproject_file = parent.join(PYPROJECT_TOML);
                 if pyproject_file.is_file() {
                     debug!("Found pyproject.toml at {:?}", pyproject_file);
-                    return Ok((path, pyproject_file));
+                    return Ok((path, pyproject_file, Some(cargo_metadata)));
                 }
             }
             let pyproject_file = path.parent().unwrap().join(PYPROJECT_TOML);
             debug!("Trying pyproject.toml at {:?}", pyproject_file);
-            return Ok((path, pyproject_file));
+            return Ok((path, pyproject_file, Some(cargo_metadata)));
         }
         // check `manifest-path` option in pyproject.toml
         let current_dir = env::current_dir()
             .context("Failed to detect current directory ಠ_ಠ")?
             .normalize()?
             .into_path_buf();
+        let current_dir = Self::find_project_root(&current_dir)?;
         let pyproject_file = current_dir.join(PYPROJECT_TOML);
         if pyproject_file.is_file() {
             debug!(
@@ -264,6 +333,7 @@ impl ProjectResolver {
                         })?
                         .into_path_buf(),
                     pyproject_file,
+                    None,
                 ));
             } else {
                 // Detect src layout:
@@ -284,7 +354,7 @@ impl ProjectResolver {
                     debug!("Python first src-layout detected");
                     if pyproject.python_source().is_some() {
                         // python source directory is specified in pyproject.toml
-                        return Ok((path, pyproject_file));
+                        return Ok((path, pyproject_file, None));
                     } else if let Some(project_name) = pyproject.project_name() {
                         // Check if python source directory in `src/<project_name>`
                         let import_name = project_name.replace('-', "_");
@@ -300,7 +370,7 @@ impl ProjectResolver {
                                 .insert(current_dir.join("src").join(package).join("__init__.py"));
                         }
                         if package_init.iter().any(|x| x.is_file()) {
-                            return Ok((path, pyproject_file));
+                            return Ok((path, pyproject_file, None));
                         }
                     }
                 }
@@ -313,7 +383,7 @@ impl ProjectResolver {
                 "Using cargo manifest path from working directory: {:?}",
                 path
             );
-            Ok((path, current_dir.join(PYPROJECT_TOML)))
+            Ok((path, current_dir.join(PYPROJECT_TOML), None))
         } else {
             Err(format_err!(
                 "Can't find {} (in {})",
@@ -323,6 +393,37 @@ impl ProjectResolver {
         }
     }
 
+    /// Returns the working directory if it contains a `Cargo.toml` or a `pyproject.toml`,
+    /// otherwise the first parent directory that contains both or a `pyproject.toml` with a
+    /// `[tool.maturin]` section
+    fn find_project_root(current_dir: &Path) -> Result<PathBuf> {
+        if current_dir.join("Cargo.toml").is_file() || current_dir.join(PYPROJECT_TOML).is_file() {
+            return Ok(current_dir.to_path_buf());
+        }
+        for dir in current_dir.ancestors().skip(1) {
+            let pyproject_file = dir.join(PYPROJECT_TOML);
+            if !pyproject_file.is_file() {
+                continue;
+            }
+            let is_maturin_project = dir.join("Cargo.toml").is_file()
+                || PyProjectToml::new(&pyproject_file)
+                    .map(|pyproject| pyproject.maturin().is_some())
+                    .unwrap_or(false);
+            if is_maturin_project {
+                debug!("Found project root in parent directory {:?}", dir);
+                return Ok(dir.to_path_buf());
+            }
+        }
+        bail!(
+            "Can't find a Cargo.toml or pyproject.toml, searched in:\n{}",
+            current_dir
+                .ancestors()
+                .map(|dir| format!("  {}", dir.display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+
     #[instrument(skip_all)]
     fn resolve_cargo_metadata(
         manifest_path: &Path,
@@ -356,6 +457,112 @@ impl ProjectResolver {
     }
 }
 
+/// Resolves the [ProjectLayout] of a project without building it.
+///
+/// This only reads `Cargo.toml`, `pyproject.toml` and the output of `cargo metadata`, it doesn't
+/// look for a python interpreter or compile anything. With `None`, the manifest is searched for
+/// starting from the current directory, the same way `maturin build` does.
+pub fn resolve_project_layout(manifest_path: Option<PathBuf>) -> Result<ProjectLayout> {
+    let resolver = ProjectResolver::resolve(manifest_path, CargoOptions::default())?;
+    Ok(resolver.project_layout)
+}
+
+/// Resolves the python package metadata of a project without building it, including a dynamic
+/// version from `Cargo.toml`
+///
+/// Like [resolve_project_layout], this doesn't look for a python interpreter or compile anything.
+pub fn resolve_project_metadata(
+    manifest_path: Option<PathBuf>,
+    cargo_options: CargoOptions,
+) -> Result<Metadata24> {
+    let resolver = ProjectResolver::resolve(manifest_path, cargo_options)?;
+    Ok(resolver.metadata24)
+}
+
+/// Finds the `Cargo.lock` of a project that was unpacked from a source distribution
+///
+/// Source distributions are recognized by the `PKG-INFO` next to `pyproject.toml`, the lockfile
+/// is the first `Cargo.lock` from the manifest directory up to the root of the source
+/// distribution. Returns `None` when not building from a source distribution or when it has
+/// no `Cargo.lock`. This doesn't run `cargo metadata`, which could update the lockfile.
+pub(crate) fn find_sdist_cargo_lock(
+    manifest_path: Option<PathBuf>,
+    cargo_options: &CargoOptions,
+) -> Result<Option<PathBuf>> {
+    let manifest_file = match manifest_path {
+        Some(path) => path
+            .normalize()
+            .with_context(|| format!("failed to normalize manifest path `{}`", path.display()))?
+            .into_path_buf(),
+        None => ProjectResolver::resolve_manifest_paths(None, cargo_options)?.0,
+    };
+    let manifest_dir = manifest_file.parent().unwrap_or(&manifest_file);
+    let Some(sdist_root) = manifest_dir
+        .ancestors()
+        .find(|dir| dir.join("PKG-INFO").is_file() && dir.join(PYPROJECT_TOML).is_file())
+    else {
+        return Ok(None);
+    };
+    Ok(manifest_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(sdist_root))
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|cargo_lock| cargo_lock.is_file()))
+}
+
+/// Moves the extension module into the directory given by `[tool.maturin.module-path]`,
+/// e.g. `foo/_native` turns the module name `ext` into `foo._native.ext`
+fn apply_module_path(module_name: &str, module_path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in module_path.components() {
+        match component {
+            Component::Normal(part) => {
+                let part = part.to_str().with_context(|| {
+                    format!("module-path `{}` is not valid utf-8", module_path.display())
+                })?;
+                if !is_python_identifier(part) {
+                    bail!(
+                        "module-path `{}` is invalid, `{}` is not a valid python identifier",
+                        module_path.display(),
+                        part
+                    );
+                }
+                parts.push(part);
+            }
+            Component::CurDir => {}
+            _ => bail!(
+                "module-path `{}` must be a relative path inside the python package",
+                module_path.display()
+            ),
+        }
+    }
+    if parts.is_empty() {
+        bail!("module-path must not be empty");
+    }
+    let package = parts.join(".");
+    match module_name.rsplit_once('.') {
+        Some((parent, extension_name)) => {
+            if parent != package {
+                bail!(
+                    "module-name `{}` places the extension in `{}`, which is inconsistent with \
+                    module-path `{}`",
+                    module_name,
+                    parent,
+                    module_path.display()
+                );
+            }
+            Ok(format!("{package}.{extension_name}"))
+        }
+        None => Ok(format!("{package}.{module_name}")),
+    }
+}
+
+fn is_python_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+}
+
 impl ProjectLayout {
     /// Checks whether a python module exists besides Cargo.toml with the right name
     fn determine(
@@ -404,6 +611,27 @@ impl ProjectLayout {
             None
         };
 
+        let python_packages = python_packages
+            .into_iter()
+            .map(|package| {
+                let package_path = python_root.join(&package);
+                if package_path.exists() {
+                    return Ok(package);
+                }
+                // Single file modules can be listed by their module name
+                let module_file = format!("{package}.py");
+                if python_root.join(&module_file).is_file() {
+                    return Ok(module_file);
+                }
+                bail!(
+                    "The python package `{}` listed in `tool.maturin.python-packages` was not found, \
+                    expected a directory or a `.py` file at {}",
+                    package,
+                    package_path.display()
+                );
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         if python_module.is_dir() {
             eprintln!("🍹 Building a mixed python/rust project");
 
@@ -438,3 +666,254 @@ impl ProjectLayout {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fs_err as fs;
+    use indoc::indoc;
+
+    #[test]
+    fn test_find_sdist_cargo_lock() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path().normalize().unwrap().into_path_buf();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        fs::write(root.join(PYPROJECT_TOML), "").unwrap();
+        let manifest_path = Some(root.join("Cargo.toml"));
+        let cargo_options = CargoOptions::default();
+
+        // A checkout isn't a source distribution
+        fs::write(root.join("Cargo.lock"), "version = 3\n").unwrap();
+        assert_eq!(
+            find_sdist_cargo_lock(manifest_path.clone(), &cargo_options).unwrap(),
+            None
+        );
+        fs::remove_file(root.join("Cargo.lock")).unwrap();
+        fs::write(
+            root.join("PKG-INFO"),
+            "Metadata-Version: 2.4\nName: foo\nVersion: 0.1.0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_sdist_cargo_lock(manifest_path.clone(), &cargo_options).unwrap(),
+            None
+        );
+        fs::write(root.join("Cargo.lock"), "version = 3\n").unwrap();
+        assert_eq!(
+            find_sdist_cargo_lock(manifest_path, &cargo_options).unwrap(),
+            Some(root.join("Cargo.lock"))
+        );
+    }
+
+    #[test]
+    fn test_find_project_root() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path();
+        let nested = root.join("project").join("python").join("pkg");
+        fs::create_dir_all(&nested).unwrap();
+        assert!(ProjectResolver::find_project_root(&nested).is_err());
+
+        let project = root.join("project");
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        fs::write(project.join(PYPROJECT_TOML), "").unwrap();
+        assert_eq!(
+            ProjectResolver::find_project_root(&nested).unwrap(),
+            project
+        );
+        // The working directory wins if it has either file
+        fs::write(nested.join(PYPROJECT_TOML), "").unwrap();
+        assert_eq!(ProjectResolver::find_project_root(&nested).unwrap(), nested);
+    }
+
+    #[test]
+    fn test_resolve_project_layout() {
+        let layout =
+            resolve_project_layout(Some(PathBuf::from("test-crates/pyo3-mixed/Cargo.toml")))
+                .unwrap();
+        let project_root = env::current_dir()
+            .unwrap()
+            .join("test-crates/pyo3-mixed")
+            .normalize()
+            .unwrap()
+            .into_path_buf();
+        assert_eq!(layout.project_root, project_root);
+        assert_eq!(layout.python_dir, project_root);
+        assert_eq!(layout.python_module, Some(project_root.join("pyo3_mixed")));
+        assert_eq!(layout.rust_module, project_root.join("pyo3_mixed"));
+        assert_eq!(layout.extension_name, "pyo3_mixed");
+    }
+
+    #[test]
+    fn test_resolve_project_metadata() {
+        let metadata24 = resolve_project_metadata(
+            Some(PathBuf::from("test-crates/pyo3-mixed/Cargo.toml")),
+            CargoOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(metadata24.name, "pyo3-mixed");
+        assert_eq!(metadata24.version.to_string(), "2.1.5");
+    }
+
+    #[test]
+    fn test_resolve_project_metadata_feature_extras() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            indoc!(
+                r#"
+                [package]
+                name = "feature-extras"
+                version = "0.1.0"
+                edition = "2021"
+
+                [lib]
+                crate-type = ["cdylib"]
+
+                [features]
+                matplotlib = []
+                numpy = []
+                plotting = []
+                "#
+            ),
+        )
+        .unwrap();
+        fs::write(
+            root.join("pyproject.toml"),
+            indoc!(
+                r#"
+                [build-system]
+                requires = ["maturin>=1.0,<2.0"]
+                build-backend = "maturin"
+
+                [project]
+                name = "feature-extras"
+                version = "0.1.0"
+                dynamic = ["optional-dependencies"]
+
+                [tool.maturin.extras]
+                numpy = { dependencies = ["numpy>=1.20"] }
+                plotting = { extra = "plot", dependencies = ["seaborn ; python_version >= '3.9'"] }
+                matplotlib = { extra = "plot", dependencies = ["matplotlib"] }
+
+                [tool.maturin.dependency-overrides]
+                numpy = ">=2"
+                "#
+            ),
+        )
+        .unwrap();
+        let requires_dist = |metadata24: &Metadata24| {
+            metadata24
+                .requires_dist
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let metadata24 =
+            resolve_project_metadata(Some(root.join("Cargo.toml")), CargoOptions::default())
+                .unwrap();
+        assert!(metadata24.provides_extra.is_empty());
+        assert!(requires_dist(&metadata24).is_empty());
+
+        let cargo_options = CargoOptions {
+            features: vec![
+                "numpy".to_string(),
+                "plotting".to_string(),
+                "matplotlib".to_string(),
+            ],
+            ..Default::default()
+        };
+        let metadata24 =
+            resolve_project_metadata(Some(root.join("Cargo.toml")), cargo_options).unwrap();
+        assert_eq!(metadata24.provides_extra, ["plot", "numpy"]);
+        assert_eq!(
+            requires_dist(&metadata24),
+            [
+                "matplotlib ; extra == 'plot'",
+                "numpy>=2 ; extra == 'numpy'",
+                "seaborn ; python_full_version >= '3.9' and extra == 'plot'",
+            ]
+        );
+
+        // Static optional dependencies must not be changed
+        let pyproject_toml = fs::read_to_string(root.join("pyproject.toml")).unwrap();
+        fs::write(
+            root.join("pyproject.toml"),
+            pyproject_toml.replace(
+                r#"dynamic = ["optional-dependencies"]"#,
+                "optional-dependencies = { plot = [\"matplotlib\"] }",
+            ),
+        )
+        .unwrap();
+        let err = resolve_project_metadata(Some(root.join("Cargo.toml")), CargoOptions::default())
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("project.dynamic"), "{err:#}");
+    }
+
+    #[test]
+    fn test_determine_python_packages() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("my_project")).unwrap();
+        fs::create_dir_all(root.join("my_project-stubs")).unwrap();
+
+        let layout = ProjectLayout::determine(
+            root,
+            "my_project",
+            root.to_path_buf(),
+            vec!["my_project-stubs".to_string()],
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(layout.python_packages, ["my_project-stubs"]);
+
+        // Single file modules, by file or module name
+        fs::write(root.join("helper.py"), "").unwrap();
+        for package in ["helper.py", "helper"] {
+            let layout = ProjectLayout::determine(
+                root,
+                "my_project",
+                root.to_path_buf(),
+                vec![package.to_string()],
+                None,
+                false,
+            )
+            .unwrap();
+            assert_eq!(layout.python_packages, ["helper.py"]);
+        }
+
+        let err = ProjectLayout::determine(
+            root,
+            "my_project",
+            root.to_path_buf(),
+            vec!["missing".to_string()],
+            None,
+            false,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("`missing` listed in `tool.maturin.python-packages` was not found"));
+    }
+
+    #[test]
+    fn test_apply_module_path() {
+        assert_eq!(
+            apply_module_path("ext", Path::new("pkg/_native")).unwrap(),
+            "pkg._native.ext"
+        );
+        assert_eq!(
+            apply_module_path("pkg._native.ext", Path::new("./pkg/_native")).unwrap(),
+            "pkg._native.ext"
+        );
+        assert!(apply_module_path("pkg.ext", Path::new("pkg/_native")).is_err());
+        assert!(apply_module_path("ext", Path::new("../pkg")).is_err());
+        assert!(apply_module_path("ext", Path::new("/pkg")).is_err());
+        assert!(apply_module_path("ext", Path::new("pkg/my-native")).is_err());
+        assert!(apply_module_path("ext", Path::new("")).is_err());
+    }
+}
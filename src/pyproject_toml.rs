@@ -2,13 +2,14 @@
 
 use crate::auditwheel::AuditWheelMode;
 use crate::PlatformTag;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use fs_err as fs;
 use pep440_rs::Version;
 use pep508_rs::VersionOrUrl;
 use pyproject_toml::{BuildSystem, Project};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
@@ -33,6 +34,15 @@ pub enum Format {
     Wheel,
 }
 
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Sdist => write!(f, "sdist"),
+            Format::Wheel => write!(f, "wheel"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(untagged)]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
@@ -151,6 +161,41 @@ pub struct TargetConfig {
     pub macos_deployment_target: Option<String>,
 }
 
+/// cffi bindings configuration
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CffiConfig {
+    /// Install the header of the C API into the wheel's `headers` data directory under this
+    /// relative path, so that C projects can build against the installed package
+    pub header: Option<PathBuf>,
+}
+
+/// Type stub generation configuration
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct GenerateStubsConfig {
+    /// Command that generates the `.pyi` stub of the native module.
+    /// `{module}`, `{artifact}` and `{output}` in the arguments, including inside of arguments
+    /// such as `--output={output}`, are replaced by the module name, the path of the compiled
+    /// extension and the path the stub should be written to.
+    /// Without `{output}`, the stub is read from stdout
+    pub command: Vec<String>,
+}
+
+/// A python extra that is added to the metadata when a cargo feature is enabled
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct FeatureExtra {
+    /// Name of the extra, defaults to the name of the cargo feature
+    pub extra: Option<String>,
+    /// PEP 508 requirements of the extra, e.g. `numpy>=1.20`
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
 /// Source distribution generator
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
 #[serde(rename_all = "kebab-case")]
@@ -163,6 +208,74 @@ pub enum SdistGenerator {
     Git,
 }
 
+/// Source of the project version when it's listed in `project.dynamic`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum VersionSource {
+    /// Use the version from Cargo.toml
+    #[default]
+    Cargo,
+    /// Derive the version from the latest git tag with `git describe`
+    Scm,
+}
+
+/// Version configuration
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct VersionConfig {
+    /// Where to get the version from
+    #[serde(default)]
+    pub source: VersionSource,
+}
+
+/// The `[tool.maturin.auditwheel]` option, either just the mode or a table
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum AuditWheelConfig {
+    /// Audit wheel mode
+    Mode(AuditWheelMode),
+    /// Audit wheel mode and additional settings
+    Table(AuditWheelTable),
+}
+
+/// The `[tool.maturin.include-readme-in-wheel]` option, either a switch or the path in the wheel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum IncludeReadme {
+    /// Whether to add the readme as `README.<ext>` to the top level python package
+    Enabled(bool),
+    /// Where to add the readme, relative to the root of the wheel
+    Path(PathBuf),
+}
+
+/// The table form of `[tool.maturin.auditwheel]`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AuditWheelTable {
+    /// Audit wheel mode
+    pub mode: Option<AuditWheelMode>,
+    /// Additional libraries, by soname, that are treated as system libraries: they may be
+    /// linked without failing the audit and aren't bundled into the wheel by repair
+    #[serde(default)]
+    pub allowed_libs: Vec<String>,
+}
+
+/// Wheel compression configuration
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CompressionConfig {
+    /// Files in the wheel matching these glob patterns are stored without compression,
+    /// e.g. assets that are already compressed
+    #[serde(default)]
+    pub store_globs: Vec<String>,
+}
+
 /// The `[tool.maturin]` section of a pyproject.toml
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "kebab-case")]
@@ -171,6 +284,8 @@ pub struct ToolMaturin {
     // maturin specific options
     /// Module name, accepts setuptools style import name like `foo.bar`
     pub module_name: Option<String>,
+    /// Directory inside the python package to place the compiled extension in, e.g. `foo/_native`
+    pub module_path: Option<PathBuf>,
     /// Include files matching the given glob pattern(s)
     pub include: Option<Vec<GlobPattern>>,
     /// Exclude files matching the given glob pattern(s)
@@ -180,25 +295,69 @@ pub struct ToolMaturin {
     /// Platform compatibility
     #[serde(alias = "manylinux")]
     pub compatibility: Option<PlatformTag>,
-    /// Audit wheel mode
-    pub auditwheel: Option<AuditWheelMode>,
+    /// Audit wheel mode, or a table with `mode` and `allowed-libs`
+    pub auditwheel: Option<AuditWheelConfig>,
     /// Skip audit wheel
     #[serde(default)]
     pub skip_auditwheel: bool,
     /// Strip the final binary
     #[serde(default)]
     pub strip: bool,
+    /// Mark cffi wheels as `Root-Is-Purelib: true`, only allowed for platform independent
+    /// (`py3-none-any`) wheels for wasi, which don't contain a native shared library
+    #[serde(default)]
+    pub purelib: bool,
+    /// Link `bin` bindings that embed python against the static libpython of the pyo3 config
+    /// file in `PYO3_CONFIG_FILE`, e.g. for portable musl executables
+    #[serde(default)]
+    pub static_libpython: bool,
+    /// Whether symlinks are replaced by the files they point to in the source distribution,
+    /// defaults to `true`. With `false`, they are stored as symlinks
+    pub sdist_follow_symlinks: Option<bool>,
+    /// cffi bindings configuration
+    pub cffi: Option<CffiConfig>,
+    /// A file with the trove classifiers, one per line, relative to pyproject.toml. Requires
+    /// `classifiers` to be listed in `project.dynamic`
+    pub classifiers_file: Option<PathBuf>,
+    /// The dependencies of the project when `dependencies` is listed in `project.dynamic`,
+    /// before `dependency-overrides` are applied
+    pub dependencies: Option<Vec<String>>,
+    /// Replace the version specifier or URL of dependencies in the wheel metadata, e.g. to
+    /// publish a path dependency used during development as `>=1.2`. Applies to `dependencies`
+    /// and `extras`, the static dependencies in `project` can't be overridden
+    pub dependency_overrides: Option<HashMap<String, String>>,
+    /// Python extras keyed by the cargo feature that adds them to the wheel metadata when it's
+    /// enabled. Requires `optional-dependencies` to be listed in `project.dynamic`
+    pub extras: Option<HashMap<String, FeatureExtra>>,
+    /// Add the readme of the project metadata to the wheel as a file, e.g. to display it at
+    /// runtime
+    pub include_readme_in_wheel: Option<IncludeReadme>,
+    /// Generate a `.pyi` type stub for the native module with an external command
+    pub generate_stubs: Option<GenerateStubsConfig>,
     /// Source distribution generator
     #[serde(default)]
     pub sdist_generator: SdistGenerator,
+    /// Wheel compression configuration
+    pub compression: Option<CompressionConfig>,
+    /// Command used by `--sign` to create a detached signature of each wheel.
+    /// `{key}`, `{wheel}` and `{signature}` in the arguments, including inside of arguments such
+    /// as `--output={signature}`, are replaced by the signing key, the path of the wheel and the
+    /// path of the signature file
+    pub sign_command: Option<Vec<String>>,
+    /// Dynamic version configuration
+    pub version: Option<VersionConfig>,
     /// The directory with python module, contains `<module_name>/__init__.py`
     pub python_source: Option<PathBuf>,
-    /// Python packages to include
+    /// Additional top level python packages to include, as directories or single file modules
+    /// (`foo.py`) in the python source directory
     pub python_packages: Option<Vec<String>>,
     /// Path to the wheel directory, defaults to `<module_name>.data`
     pub data: Option<PathBuf>,
     /// Cargo compile targets
     pub targets: Option<Vec<CargoTarget>>,
+    /// Other packages of the cargo workspace to build and add to the wheel as subpackages of the
+    /// main module (experimental)
+    pub workspace_members: Option<Vec<String>>,
     /// Target configuration
     #[serde(default, rename = "target")]
     pub target_config: HashMap<String, TargetConfig>,
@@ -211,6 +370,8 @@ pub struct ToolMaturin {
     pub all_features: Option<bool>,
     /// Do not activate the `default` feature
     pub no_default_features: Option<bool>,
+    /// Activate the `default` features except for the listed ones
+    pub default_features_except: Option<Vec<String>>,
     /// Path to Cargo.toml
     pub manifest_path: Option<PathBuf>,
     /// Require Cargo.lock and cache are up to date
@@ -274,11 +435,108 @@ impl PyProjectToml {
         self.maturin()?.module_name.as_deref()
     }
 
+    /// Returns the value of `[tool.maturin.default-features-except]` in pyproject.toml
+    pub fn default_features_except(&self) -> Option<&[String]> {
+        self.maturin()?.default_features_except.as_deref()
+    }
+
+    /// Returns the value of `[tool.maturin.cffi.header]` in pyproject.toml
+    pub fn cffi_header(&self) -> Option<&Path> {
+        self.maturin()?.cffi.as_ref()?.header.as_deref()
+    }
+
+    /// Errors unless `field` is listed in `project.dynamic` without a static value, since the
+    /// `tool.maturin.<setting>` option changes it and static metadata must be used as is
+    pub fn require_dynamic(&self, field: &str, setting: &str) -> Result<()> {
+        // Without a `[project]` table, the metadata isn't static
+        let Some(project) = &self.project else {
+            return Ok(());
+        };
+        let dynamic = project
+            .dynamic
+            .as_ref()
+            .is_some_and(|dynamic| dynamic.iter().any(|dynamic| dynamic == field));
+        if !dynamic {
+            bail!(
+                "`tool.maturin.{setting}` changes `{field}`, which must therefore be listed \
+                in `project.dynamic` in pyproject.toml"
+            );
+        }
+        let has_static_value = match field {
+            "classifiers" => project.classifiers.is_some(),
+            "dependencies" => project.dependencies.is_some(),
+            "optional-dependencies" => project.optional_dependencies.is_some(),
+            _ => false,
+        };
+        if has_static_value {
+            bail!(
+                "`project.{field}` must not be set when `{field}` is listed in \
+                `project.dynamic` in pyproject.toml"
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the value of `[tool.maturin.classifiers-file]` in pyproject.toml
+    pub fn classifiers_file(&self) -> Option<&Path> {
+        self.maturin()?.classifiers_file.as_deref()
+    }
+
+    /// Returns the value of `[tool.maturin.dependencies]` in pyproject.toml
+    pub fn dependencies(&self) -> Option<&[String]> {
+        self.maturin()?.dependencies.as_deref()
+    }
+
+    /// Returns the value of `[tool.maturin.dependency-overrides]` in pyproject.toml
+    pub fn dependency_overrides(&self) -> Option<&HashMap<String, String>> {
+        self.maturin()?.dependency_overrides.as_ref()
+    }
+
+    /// Returns the value of `[tool.maturin.extras]` in pyproject.toml
+    pub fn extras(&self) -> Option<&HashMap<String, FeatureExtra>> {
+        self.maturin()?.extras.as_ref()
+    }
+
+    /// Returns the value of `[tool.maturin.include-readme-in-wheel]` in pyproject.toml
+    pub fn include_readme_in_wheel(&self) -> Option<&IncludeReadme> {
+        self.maturin()?.include_readme_in_wheel.as_ref()
+    }
+
+    /// Returns the value of `[tool.maturin.module-path]` in pyproject.toml
+    pub fn module_path(&self) -> Option<&Path> {
+        self.maturin()?.module_path.as_deref()
+    }
+
     /// Returns the value of `[tool.maturin.include]` in pyproject.toml
     pub fn include(&self) -> Option<&[GlobPattern]> {
         self.maturin()?.include.as_ref().map(AsRef::as_ref)
     }
 
+    /// Resolves the `[tool.maturin.include]` patterns that target `format` relative to `base_dir`,
+    /// returning each pattern with the files and directories it matched.
+    ///
+    /// Patterns that don't match anything are returned with an empty list.
+    pub fn resolved_includes(
+        &self,
+        base_dir: &Path,
+        format: Format,
+    ) -> Result<Vec<(&str, Vec<PathBuf>)>> {
+        let Some(glob_patterns) = self.include() else {
+            return Ok(Vec::new());
+        };
+        glob_patterns
+            .iter()
+            .filter_map(|glob_pattern| glob_pattern.targets(format))
+            .map(|pattern| {
+                let matches = glob::glob(&base_dir.join(pattern).to_string_lossy())
+                    .with_context(|| format!("Invalid glob pattern: {pattern}"))?
+                    .filter_map(Result::ok)
+                    .collect();
+                Ok((pattern, matches))
+            })
+            .collect()
+    }
+
     /// Returns the value of `[tool.maturin.exclude]` in pyproject.toml
     pub fn exclude(&self) -> Option<&[GlobPattern]> {
         self.maturin()?.exclude.as_ref().map(AsRef::as_ref)
@@ -296,9 +554,18 @@ impl PyProjectToml {
 
     /// Returns the value of `[tool.maturin.auditwheel]` in pyproject.toml
     pub fn auditwheel(&self) -> Option<AuditWheelMode> {
-        self.maturin()
-            .map(|maturin| maturin.auditwheel)
-            .unwrap_or_default()
+        match self.maturin()?.auditwheel.as_ref()? {
+            AuditWheelConfig::Mode(mode) => Some(*mode),
+            AuditWheelConfig::Table(table) => table.mode,
+        }
+    }
+
+    /// Returns the value of `[tool.maturin.auditwheel.allowed-libs]` in pyproject.toml
+    pub fn auditwheel_allowed_libs(&self) -> Option<&[String]> {
+        match self.maturin()?.auditwheel.as_ref()? {
+            AuditWheelConfig::Mode(_) => None,
+            AuditWheelConfig::Table(table) => Some(&table.allowed_libs),
+        }
     }
 
     /// Returns the value of `[tool.maturin.skip-auditwheel]` in pyproject.toml
@@ -315,6 +582,43 @@ impl PyProjectToml {
             .unwrap_or_default()
     }
 
+    /// Returns the value of `[tool.maturin.purelib]` in pyproject.toml
+    pub fn purelib(&self) -> bool {
+        self.maturin()
+            .map(|maturin| maturin.purelib)
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of `[tool.maturin.static-libpython]` in pyproject.toml
+    pub fn static_libpython(&self) -> bool {
+        self.maturin()
+            .map(|maturin| maturin.static_libpython)
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of `[tool.maturin.sdist-follow-symlinks]` in pyproject.toml,
+    /// defaulting to `true`
+    pub fn sdist_follow_symlinks(&self) -> bool {
+        self.maturin()
+            .and_then(|maturin| maturin.sdist_follow_symlinks)
+            .unwrap_or(true)
+    }
+
+    /// Returns the value of `[tool.maturin.generate-stubs]` in pyproject.toml
+    pub fn generate_stubs(&self) -> Option<&GenerateStubsConfig> {
+        self.maturin()?.generate_stubs.as_ref()
+    }
+
+    /// Returns the value of `[tool.maturin.sign-command]` in pyproject.toml
+    pub fn sign_command(&self) -> Option<&[String]> {
+        self.maturin()?.sign_command.as_deref()
+    }
+
+    /// Returns the value of `[tool.maturin.compression.store-globs]` in pyproject.toml
+    pub fn store_globs(&self) -> Option<&[String]> {
+        Some(self.maturin()?.compression.as_ref()?.store_globs.as_slice())
+    }
+
     /// Returns the value of `[tool.maturin.sdist-generator]` in pyproject.toml
     pub fn sdist_generator(&self) -> SdistGenerator {
         self.maturin()
@@ -322,6 +626,14 @@ impl PyProjectToml {
             .unwrap_or_default()
     }
 
+    /// Returns the value of `[tool.maturin.version.source]` in pyproject.toml
+    pub fn version_source(&self) -> VersionSource {
+        self.maturin()
+            .and_then(|maturin| maturin.version.as_ref())
+            .map(|version| version.source)
+            .unwrap_or_default()
+    }
+
     /// Returns the value of `[tool.maturin.python-source]` in pyproject.toml
     pub fn python_source(&self) -> Option<&Path> {
         self.maturin()
@@ -344,6 +656,11 @@ impl PyProjectToml {
         self.maturin().and_then(|maturin| maturin.targets.clone())
     }
 
+    /// Returns the value of `[tool.maturin.workspace-members]` in pyproject.toml
+    pub fn workspace_members(&self) -> Option<&[String]> {
+        self.maturin()?.workspace_members.as_deref()
+    }
+
     /// Returns the value of `[tool.maturin.target.<target>]` in pyproject.toml
     pub fn target_config(&self, target: &str) -> Option<&TargetConfig> {
         self.maturin()
@@ -455,6 +772,7 @@ impl PyProjectToml {
 #[cfg(test)]
 mod tests {
     use crate::{
+        auditwheel::AuditWheelMode,
         pyproject_toml::{Format, Formats, GlobPattern, ToolMaturin},
         PyProjectToml,
     };
@@ -526,6 +844,103 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolved_includes() {
+        let tmp_dir = TempDir::new().unwrap();
+        let pyproject_file = tmp_dir.path().join("pyproject.toml");
+        fs::write(
+            &pyproject_file,
+            indoc!(
+                r#"
+                [build-system]
+                requires = ["maturin"]
+                build-backend = "maturin"
+
+                [tool.maturin]
+                include = [
+                    "data/*.txt",
+                    { path = "docs/*.md", format = "sdist" },
+                    { path = "missing/*", format = ["sdist", "wheel"] },
+                ]
+                "#
+            ),
+        )
+        .unwrap();
+        fs::create_dir_all(tmp_dir.path().join("data")).unwrap();
+        fs::write(tmp_dir.path().join("data/a.txt"), "").unwrap();
+        fs::write(tmp_dir.path().join("data/b.bin"), "").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("docs")).unwrap();
+        fs::write(tmp_dir.path().join("docs/index.md"), "").unwrap();
+
+        let pyproject = PyProjectToml::new(&pyproject_file).unwrap();
+        let wheel = pyproject
+            .resolved_includes(tmp_dir.path(), Format::Wheel)
+            .unwrap();
+        assert_eq!(
+            wheel,
+            [
+                ("data/*.txt", vec![tmp_dir.path().join("data/a.txt")]),
+                ("missing/*", vec![]),
+            ]
+        );
+        let sdist = pyproject
+            .resolved_includes(tmp_dir.path(), Format::Sdist)
+            .unwrap();
+        assert_eq!(
+            sdist,
+            [
+                ("data/*.txt", vec![tmp_dir.path().join("data/a.txt")]),
+                ("docs/*.md", vec![tmp_dir.path().join("docs/index.md")]),
+                ("missing/*", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_auditwheel_table() {
+        let tmp_dir = TempDir::new().unwrap();
+        let pyproject_file = tmp_dir.path().join("pyproject.toml");
+        fs::write(
+            &pyproject_file,
+            indoc!(
+                r#"
+                [build-system]
+                requires = ["maturin"]
+                build-backend = "maturin"
+
+                [tool.maturin.auditwheel]
+                mode = "check"
+                allowed-libs = ["libfoo.so.1"]
+                "#
+            ),
+        )
+        .unwrap();
+        let pyproject = PyProjectToml::new(&pyproject_file).unwrap();
+        assert_eq!(pyproject.auditwheel(), Some(AuditWheelMode::Check));
+        assert_eq!(
+            pyproject.auditwheel_allowed_libs(),
+            Some(&["libfoo.so.1".to_string()][..])
+        );
+
+        fs::write(
+            &pyproject_file,
+            indoc!(
+                r#"
+                [build-system]
+                requires = ["maturin"]
+                build-backend = "maturin"
+
+                [tool.maturin]
+                auditwheel = "skip"
+                "#
+            ),
+        )
+        .unwrap();
+        let pyproject = PyProjectToml::new(&pyproject_file).unwrap();
+        assert_eq!(pyproject.auditwheel(), Some(AuditWheelMode::Skip));
+        assert_eq!(pyproject.auditwheel_allowed_libs(), None);
+    }
+
     #[test]
     fn test_warn_missing_maturin_version() {
         let with_constraint = PyProjectToml::new("test-crates/pyo3-pure/pyproject.toml").unwrap();
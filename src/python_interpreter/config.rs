@@ -6,7 +6,7 @@ use crate::target::{Arch, Os};
 use crate::Target;
 use anyhow::{format_err, Context, Result};
 use fs_err as fs;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Write as _;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
@@ -15,7 +15,7 @@ const PYPY_ABI_TAG: &str = "pp73";
 const GRAALPY_ABI_TAG: &str = "graalpy230_310_native";
 
 /// Some of the sysconfigdata of Python interpreter we care about
-#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct InterpreterConfig {
     /// Python's major version
     pub major: usize,
@@ -310,10 +310,15 @@ impl InterpreterConfig {
         })?;
         let implementation = implementation.unwrap_or_else(|| "cpython".to_string());
         let interpreter_kind = implementation.parse().map_err(|e| format_err!("{}", e))?;
+        let gil_disabled = build_flags
+            .map(|flags| flags.contains("Py_GIL_DISABLED"))
+            .unwrap_or(false);
+        // pyo3 config files have no abiflags, the free-threaded build uses `t`
+        let default_abiflags = if gil_disabled { "t" } else { "" };
         let abi_tag = match interpreter_kind {
             InterpreterKind::CPython => {
                 if (major, minor) >= (3, 8) {
-                    abi_tag.unwrap_or_else(|| format!("{major}{minor}"))
+                    abi_tag.unwrap_or_else(|| format!("{major}{minor}{default_abiflags}"))
                 } else {
                     abi_tag.unwrap_or_else(|| format!("{major}{minor}m"))
                 }
@@ -373,14 +378,11 @@ impl InterpreterConfig {
         } else {
             ext_suffix.context("missing value for ext_suffix")?
         };
-        let gil_disabled = build_flags
-            .map(|flags| flags.contains("Py_GIL_DISABLED"))
-            .unwrap_or(false);
         Ok(Self {
             major,
             minor,
             interpreter_kind,
-            abiflags: abiflags.unwrap_or_default(),
+            abiflags: abiflags.unwrap_or_else(|| default_abiflags.to_string()),
             ext_suffix,
             pointer_width,
             gil_disabled,
@@ -816,6 +818,22 @@ mod test {
         expected.assert_eq(&config_file);
     }
 
+    #[test]
+    fn test_pyo3_config_file_round_trip() {
+        let target =
+            Target::from_target_triple(Some("x86_64-unknown-linux-gnu".to_string())).unwrap();
+        let tmp_dir = tempfile::tempdir().unwrap();
+        for minor in [10, 12] {
+            let sysconfig =
+                InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, minor), "")
+                    .unwrap();
+            let config_file = tmp_dir.path().join(format!("pyo3-config-3.{minor}.txt"));
+            fs::write(&config_file, sysconfig.pyo3_config_file()).unwrap();
+            let parsed = InterpreterConfig::from_pyo3_config(&config_file, &target).unwrap();
+            assert_eq!(parsed, sysconfig);
+        }
+    }
+
     #[test]
     fn test_pyo3_config_file_free_threaded_python_3_13() {
         let sysconfig = InterpreterConfig::lookup_one(
@@ -838,6 +856,22 @@ mod test {
         expected.assert_eq(&config_file);
     }
 
+    #[test]
+    fn test_from_pyo3_config_free_threaded() {
+        let target =
+            Target::from_target_triple(Some("x86_64-unknown-linux-gnu".to_string())).unwrap();
+        let sysconfig =
+            InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, 13), "t").unwrap();
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_file = tmp_dir.path().join("pyo3-config-3.13t.txt");
+        fs::write(&config_file, sysconfig.pyo3_config_file()).unwrap();
+        let parsed = InterpreterConfig::from_pyo3_config(&config_file, &target).unwrap();
+        assert_eq!(parsed.abiflags, "t");
+        assert_eq!(parsed.ext_suffix, ".cpython-313t-x86_64-linux-gnu.so");
+        assert!(parsed.gil_disabled);
+        assert_eq!(parsed, sysconfig);
+    }
+
     #[test]
     fn test_pyo3_config_file_musl_python_3_11() {
         let sysconfig = InterpreterConfig::lookup_one(
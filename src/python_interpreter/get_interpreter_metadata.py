@@ -1,4 +1,5 @@
 import json
+import os
 import platform
 import sys
 import sysconfig
@@ -36,6 +37,18 @@ metadata = {
     # This one is for generating a config file for pyo3
     "pointer_width": struct.calcsize("P") * 8,
     "gil_disabled": sysconfig.get_config_var("Py_GIL_DISABLED") == 1,
+    # The minimum macOS version the interpreter was built for, only set on macOS
+    "macosx_deployment_target": str(
+        sysconfig.get_config_var("MACOSX_DEPLOYMENT_TARGET") or ""
+    )
+    or None,
+    # The directory with libpython, for the pyo3 config file of `--write-interpreter-config`
+    "lib_dir": (
+        os.path.join(sys.base_prefix, "libs")
+        if platform.system() == "Windows"
+        else sysconfig.get_config_var("LIBDIR")
+    )
+    or None,
 }
 
 print(json.dumps(metadata))
@@ -4,8 +4,9 @@ use crate::{BridgeModel, BuildContext, Target};
 use anyhow::{bail, ensure, format_err, Context, Result};
 use pep440_rs::{Version, VersionSpecifiers};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::env;
 use std::fmt;
 use std::io::{self, Write};
 use std::ops::Deref;
@@ -111,70 +112,84 @@ fn find_all_windows(
         .arg("--list-paths")
         .output();
     if let Ok(output) = execution {
-        // x86_64: ' -3.10-64 * C:\Users\xxx\AppData\Local\Programs\Python\Python310\python.exe'
-        // x86_64: ' -3.11 * C:\Users\xxx\AppData\Local\Programs\Python\Python310\python.exe'
-        // arm64:  ' -V:3.11-arm64 * C:\Users\xxx\AppData\Local\Programs\Python\Python311-arm64\python.exe
-        let expr = Regex::new(r" -(V:)?(\d).(\d+)-?(arm)?(\d*)\s*\*?\s*(.*)?").unwrap();
-        let stdout = str::from_utf8(&output.stdout).unwrap();
-        for line in stdout.lines() {
-            if let Some(capture) = expr.captures(line) {
-                let major = capture
-                    .get(2)
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .context("Expected a digit for major version")?;
-                let minor = capture
-                    .get(3)
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .context("Expected a digit for minor version")?;
-                if !versions_found.contains(&(major, minor)) {
-                    let pointer_width = capture
-                        .get(5)
-                        .map(|m| m.as_str())
-                        .filter(|m| !m.is_empty())
-                        .unwrap_or("64")
-                        .parse::<usize>()
-                        .context("Expected a digit for pointer width")?;
-
-                    if windows_interpreter_no_build(
-                        major,
-                        minor,
-                        target.pointer_width(),
-                        pointer_width,
-                        min_python_minor,
-                        requires_python,
-                    ) {
-                        continue;
-                    }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for entry in parse_py_launcher_list_paths(&stdout) {
+            // Entries like an active venv or a third party distribution don't carry the
+            // version in their tag, so we have to ask the interpreter itself
+            let (major, minor, pointer_width) = match entry.version {
+                Some((major, minor)) => (major, minor, entry.pointer_width.unwrap_or(64)),
+                None => match windows_python_info(Path::new(&entry.executable)) {
+                    Ok(Some(python_info)) => (
+                        python_info.major,
+                        python_info.minor,
+                        python_info.pointer_width.unwrap_or(64),
+                    ),
+                    _ => continue,
+                },
+            };
+            if versions_found.contains(&(major, minor)) {
+                continue;
+            }
+            if windows_interpreter_no_build(
+                major,
+                minor,
+                target.pointer_width(),
+                pointer_width,
+                min_python_minor,
+                requires_python,
+            ) {
+                continue;
+            }
 
-                    let executable = capture.get(6).unwrap().as_str();
-                    let output = Command::new(executable).args(["-c", code]).output();
-                    let output = match output {
-                        Ok(output) => output,
-                        Err(err) => {
-                            eprintln!(
-                                "⚠️  Warning: failed to determine the path to python for `{executable}`: {err}"
-                            );
-                            continue;
-                        }
-                    };
-                    let path = str::from_utf8(&output.stdout).unwrap().trim();
-                    if !output.status.success() || path.trim().is_empty() {
-                        eprintln!(
-                            "⚠️  Warning: couldn't determine the path to python for `{executable}`"
-                        );
-                        continue;
-                    }
-                    interpreter.push(path.to_string());
-                    versions_found.insert((major, minor));
+            let executable = &entry.executable;
+            let output = Command::new(executable).args(["-c", code]).output();
+            let output = match output {
+                Ok(output) => output,
+                Err(err) => {
+                    eprintln!(
+                        "⚠️  Warning: failed to determine the path to python for `{executable}`: {err}"
+                    );
+                    continue;
                 }
+            };
+            let path = str::from_utf8(&output.stdout).unwrap_or_default().trim();
+            if !output.status.success() || path.is_empty() {
+                eprintln!("⚠️  Warning: couldn't determine the path to python for `{executable}`");
+                continue;
             }
+            interpreter.push(path.to_string());
+            versions_found.insert((major, minor));
         }
     }
 
+    // Installations registered in the registry (PEP 514), which also covers machines where
+    // the py launcher isn't available. They're validated with `check_executable` afterwards
+    for executable in find_registry_pythons() {
+        let python_info = match windows_python_info(&executable) {
+            Ok(Some(python_info)) => python_info,
+            _ => continue,
+        };
+        if versions_found.contains(&(python_info.major, python_info.minor))
+            || interpreter
+                .iter()
+                .any(|found| Path::new(found) == executable.as_path())
+        {
+            continue;
+        }
+        if windows_interpreter_no_build(
+            python_info.major,
+            python_info.minor,
+            target.pointer_width(),
+            python_info.pointer_width.unwrap(),
+            min_python_minor,
+            requires_python,
+        ) {
+            continue;
+        }
+        interpreter.push(executable.to_string_lossy().into_owned());
+        versions_found.insert((python_info.major, python_info.minor));
+    }
+
     // Conda environments are also supported on windows
     let conda_info = Command::new("conda").arg("info").arg("-e").output();
     if let Ok(output) = conda_info {
@@ -246,6 +261,166 @@ fn find_all_windows(
     Ok(interpreter)
 }
 
+/// An interpreter listed by `py --list-paths`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PyLauncherEntry {
+    /// `(major, minor)` if the tag carries it, which isn't the case for an active venv
+    /// or for distributions registered under a company other than `PythonCore`
+    version: Option<(usize, usize)>,
+    /// The pointer width from a `-32`, `-64` or `-arm64` tag suffix
+    pointer_width: Option<usize>,
+    executable: String,
+}
+
+/// Parses the output of `py --list-paths`, which depending on the launcher version looks like
+///
+/// ```text
+/// Installed Pythons found by py Launcher for Windows
+///  -3.10-64 *      C:\Python310\python.exe
+///  -V:3.11-arm64 * C:\Python311-arm64\python.exe
+///  -V:3.13t        C:\Python313\python3.13t.exe
+///  -V:3.12 [Store] C:\...\WindowsApps\...\python.exe
+///  -V:ContinuumAnalytics/Anaconda39-64 C:\anaconda3\python.exe
+///   *              C:\project\.venv\Scripts\python.exe
+/// ```
+///
+/// Lines that can't be understood are skipped instead of failing the whole discovery.
+fn parse_py_launcher_list_paths(stdout: &str) -> Vec<PyLauncherEntry> {
+    let mut entries = Vec::new();
+    for line in stdout.lines() {
+        let line = line.trim();
+        let (tag, rest) = if let Some(rest) = line.strip_prefix('*') {
+            // The active virtualenv is listed without a tag
+            (None, rest)
+        } else if let Some(rest) = line.strip_prefix('-') {
+            match rest.split_once(char::is_whitespace) {
+                Some((tag, rest)) => (Some(tag), rest),
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+        // Strip the `*` default marker and annotations such as `[Store]`
+        let mut executable = rest.trim_start();
+        loop {
+            if let Some(stripped) = executable.strip_prefix('*') {
+                executable = stripped.trim_start();
+            } else if let Some((_, stripped)) = executable
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once(']'))
+            {
+                executable = stripped.trim_start();
+            } else {
+                break;
+            }
+        }
+        let executable = executable.trim().trim_matches('"');
+        // Skip placeholders like `Active venv` or `(no path)`
+        if !executable.contains(['\\', '/']) {
+            continue;
+        }
+        let (version, pointer_width) = tag.map(parse_py_launcher_tag).unwrap_or_default();
+        entries.push(PyLauncherEntry {
+            version,
+            pointer_width,
+            executable: executable.to_string(),
+        });
+    }
+    entries
+}
+
+/// Parses a `py` launcher tag such as `3.10-32`, `V:3.11-arm64` or `V:PythonCore/3.12`
+fn parse_py_launcher_tag(tag: &str) -> (Option<(usize, usize)>, Option<usize>) {
+    let tag = tag.strip_prefix("V:").unwrap_or(tag);
+    let tag = match tag.split_once('/') {
+        Some(("PythonCore", tag)) => tag,
+        Some(_) => return (None, None),
+        None => tag,
+    };
+    let expr = Regex::new(r"^(\d+)\.(\d+)t?(?:-(32|64|arm64))?$").unwrap();
+    let Some(capture) = expr.captures(tag) else {
+        return (None, None);
+    };
+    let (Ok(major), Ok(minor)) = (capture[1].parse(), capture[2].parse()) else {
+        return (None, None);
+    };
+    let pointer_width = capture.get(3).map(|width| match width.as_str() {
+        "32" => 32,
+        _ => 64,
+    });
+    (Some((major, minor)), pointer_width)
+}
+
+/// The python executables registered under `Software\Python` in `HKEY_CURRENT_USER` and both
+/// views of `HKEY_LOCAL_MACHINE`, see PEP 514
+#[cfg(windows)]
+fn find_registry_pythons() -> Vec<PathBuf> {
+    use winreg::enums::{
+        HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_32KEY, KEY_WOW64_64KEY,
+    };
+    use winreg::RegKey;
+
+    let roots = [
+        (HKEY_CURRENT_USER, KEY_READ),
+        (HKEY_LOCAL_MACHINE, KEY_READ | KEY_WOW64_64KEY),
+        (HKEY_LOCAL_MACHINE, KEY_READ | KEY_WOW64_32KEY),
+    ];
+    let mut executables = Vec::new();
+    for (root, flags) in roots {
+        let Ok(python_key) = RegKey::predef(root).open_subkey_with_flags(r"Software\Python", flags)
+        else {
+            continue;
+        };
+        for company in python_key.enum_keys().flatten() {
+            // The launcher registers itself next to the interpreters
+            if company == "PyLauncher" {
+                continue;
+            }
+            let Ok(company_key) = python_key.open_subkey_with_flags(&company, flags) else {
+                continue;
+            };
+            for tag in company_key.enum_keys().flatten() {
+                let Ok(install_key) =
+                    company_key.open_subkey_with_flags(format!(r"{tag}\InstallPath"), flags)
+                else {
+                    continue;
+                };
+                let install_path: Option<String> = install_key.get_value("").ok();
+                let executable_path: Option<String> = install_key.get_value("ExecutablePath").ok();
+                if let Some(executable) =
+                    registry_python_executable(install_path.as_deref(), executable_path.as_deref())
+                {
+                    if !executables.contains(&executable) {
+                        executables.push(executable);
+                    }
+                }
+            }
+        }
+    }
+    executables
+}
+
+#[cfg(not(windows))]
+fn find_registry_pythons() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// The executable of a PEP 514 `InstallPath` key, from its `ExecutablePath` value or otherwise
+/// `python.exe` in the install directory given by the default value
+#[cfg_attr(not(windows), allow(dead_code))]
+fn registry_python_executable(
+    install_path: Option<&str>,
+    executable_path: Option<&str>,
+) -> Option<PathBuf> {
+    if let Some(executable_path) = executable_path.filter(|path| !path.trim().is_empty()) {
+        return Some(PathBuf::from(executable_path.trim()));
+    }
+    let install_path = install_path
+        .map(str::trim)
+        .filter(|path| !path.is_empty())?;
+    Some(Path::new(install_path).join("python.exe"))
+}
+
 struct WindowsPythonInfo {
     major: usize,
     minor: usize,
@@ -274,7 +449,7 @@ fn windows_python_info(executable: &Path) -> Result<Option<WindowsPythonInfo>> {
     };
 
     let version_info = str::from_utf8(&python_info.stdout).unwrap();
-    let expr = Regex::new(r"(\d).(\d).(\d+)").unwrap();
+    let expr = Regex::new(r"(\d+)\.(\d+)\.(\d+)").unwrap();
     if let Some(capture) = expr.captures(version_info) {
         let major = capture.get(1).unwrap().as_str().parse::<usize>().unwrap();
         let minor = capture.get(2).unwrap().as_str().parse::<usize>().unwrap();
@@ -293,7 +468,84 @@ fn windows_python_info(executable: &Path) -> Result<Option<WindowsPythonInfo>> {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, clap::ValueEnum)]
+/// Finds the interpreter selected by a pyenv `.python-version` file in `dir` or one of its
+/// parents up to the `project_root`, the same way `pyenv local` does
+///
+/// Returns `None` if a virtualenv or conda environment is active, there is no
+/// `.python-version` file or pyenv isn't installed, so that the caller can fall back to the
+/// default interpreter. Like pyenv, the first version that is installed is used, the ones that
+/// aren't are skipped with a warning.
+pub(crate) fn find_pyenv_interpreter(dir: &Path, project_root: &Path) -> Result<Option<PathBuf>> {
+    if env::var_os("VIRTUAL_ENV").is_some() || env::var_os("CONDA_PREFIX").is_some() {
+        return Ok(None);
+    }
+    // Compare the paths with symlinks resolved
+    let dir = dunce::canonicalize(dir)?;
+    let project_root = dunce::canonicalize(project_root)?;
+    let Some(version_file) = find_python_version_file(&dir, &project_root) else {
+        return Ok(None);
+    };
+    let versions = parse_python_version_file(&fs_err::read_to_string(&version_file)?);
+
+    for version in versions {
+        let output = match Command::new("pyenv").args(["prefix", &version]).output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                debug!(
+                    "Ignoring {} because pyenv isn't installed",
+                    version_file.display()
+                );
+                return Ok(None);
+            }
+            Err(err) => return Err(err).context("Failed to run `pyenv prefix`"),
+        };
+        if !output.status.success() {
+            eprintln!(
+                "⚠️  Warning: skipped unavailable python interpreter '{}' from pyenv: {}",
+                version,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            continue;
+        }
+        let prefix = PathBuf::from(str::from_utf8(&output.stdout)?.trim());
+        let executable = if cfg!(windows) {
+            // pyenv-win doesn't have a `bin` directory
+            prefix.join("python.exe")
+        } else {
+            prefix.join("bin").join("python")
+        };
+        eprintln!(
+            "🐍 Using the python {} from {}",
+            version,
+            version_file.display()
+        );
+        return Ok(Some(executable));
+    }
+    Ok(None)
+}
+
+/// Finds the `.python-version` file in `dir` or one of its parents, without leaving the
+/// project, so that a file higher up, e.g. in the home directory, doesn't change the build
+fn find_python_version_file(dir: &Path, project_root: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .take_while(|dir| dir.starts_with(project_root))
+        .map(|dir| dir.join(".python-version"))
+        .find(|path| path.is_file())
+}
+
+/// Parses the versions from a pyenv `.python-version` file, one per line or separated by
+/// whitespace. The `system` version is skipped since it's the default interpreter anyway.
+fn parse_python_version_file(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(str::split_whitespace)
+        .filter(|version| *version != "system")
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 #[clap(rename_all = "lower")]
 pub enum InterpreterKind {
@@ -358,6 +610,12 @@ struct InterpreterMetadataMessage {
     system: String,
     soabi: Option<String>,
     gil_disabled: bool,
+    // comes from `struct.calcsize("P")`
+    pointer_width: Option<usize>,
+    // comes from sysconfig var `MACOSX_DEPLOYMENT_TARGET`
+    macosx_deployment_target: Option<String>,
+    // comes from sysconfig var `LIBDIR`, or `<base_prefix>\libs` on windows
+    lib_dir: Option<String>,
 }
 
 /// The location and version of an interpreter
@@ -383,6 +641,38 @@ pub struct PythonInterpreter {
     pub implementation_name: String,
     /// Comes from sysconfig var `SOABI`
     pub soabi: Option<String>,
+    /// Comes from sysconfig var `MACOSX_DEPLOYMENT_TARGET`, the minimum macOS version the
+    /// interpreter was built for
+    pub macosx_deployment_target: Option<String>,
+    /// The directory with libpython, comes from sysconfig var `LIBDIR` or `<base_prefix>\libs`
+    /// on windows
+    pub lib_dir: Option<PathBuf>,
+}
+
+/// An interpreter as recorded in the lockfile of `--write-interpreter-lock`
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedInterpreter {
+    /// Path to the python interpreter
+    pub executable: PathBuf,
+    /// See [PythonInterpreter::environment_signature]
+    pub environment_signature: String,
+    /// Comes from sysconfig var `SOABI`
+    pub soabi: Option<String>,
+    /// Python's sysconfig
+    pub config: InterpreterConfig,
+}
+
+impl fmt::Display for LockedInterpreter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let abi = self.soabi.as_deref().unwrap_or(&self.environment_signature);
+        if self.executable.as_os_str().is_empty() {
+            // Interpreters that aren't run, e.g. for abi3 or cross compiling
+            write!(f, "{abi}")
+        } else {
+            write!(f, "{} ({abi})", self.executable.display())
+        }
+    }
 }
 
 impl Deref for PythonInterpreter {
@@ -472,6 +762,20 @@ impl PythonInterpreter {
         }
     }
 
+    /// Generate the pyo3 config file content, which for interpreters that were run also
+    /// contains the executable and the directory with libpython that pyo3 would otherwise
+    /// query from the interpreter
+    pub fn pyo3_config_file(&self) -> String {
+        let mut content = self.config.pyo3_config_file();
+        if let Some(lib_dir) = &self.lib_dir {
+            content.push_str(&format!("\nlib_dir={}", lib_dir.display()));
+        }
+        if self.runnable {
+            content.push_str(&format!("\nexecutable={}", self.executable.display()));
+        }
+        content
+    }
+
     /// Returns the supported python environment in the PEP 425 format used for the wheel filename:
     /// {python tag}-{abi tag}-{platform tag}
     ///
@@ -489,59 +793,105 @@ impl PythonInterpreter {
             if let Some(platform) = self.platform.clone() {
                 platform
             } else {
-                context.get_platform_tag(platform_tags)?
+                context.get_interpreter_platform_tag(platform_tags, Some(self))?
             }
         } else {
-            context.get_platform_tag(platform_tags)?
+            context.get_interpreter_platform_tag(platform_tags, Some(self))?
+        };
+        self.format_tag(
+            &platform,
+            context.python_tag.as_deref(),
+            context.abi_tag.as_deref(),
+            context.soabi.as_deref(),
+        )
+    }
+
+    /// Whether the wheel tag is derived generically from `sys.implementation.name` and `SOABI`
+    /// instead of from the known interpreter kind, for example for Pyston
+    pub(crate) fn uses_generic_tag(&self) -> bool {
+        self.implementation_name.parse::<InterpreterKind>().is_err()
+    }
+
+    /// Assembles the wheel tag from the platform tag and either the given abi tag or
+    /// the one derived from the interpreter
+    ///
+    /// An explicit python tag replaces the interpreter specific one, the abi tag then defaults
+    /// to `none`. For generic tags, `soabi` replaces the interpreter's reported `SOABI`.
+    fn format_tag(
+        &self,
+        platform: &str,
+        python_tag: Option<&str>,
+        abi_tag: Option<&str>,
+        soabi: Option<&str>,
+    ) -> Result<String> {
+        if let Some(python_tag) = python_tag {
+            return Ok(format!(
+                "{python_tag}-{abi_tag}-{platform}",
+                abi_tag = abi_tag.unwrap_or("none")
+            ));
+        }
+        let derive_abi_tag = || {
+            calculate_abi_tag(&self.ext_suffix).with_context(|| {
+                format!(
+                    "Failed to derive the ABI tag of {self} from `EXT_SUFFIX` `{}`, \
+                     please specify it with `--abi-tag`",
+                    self.ext_suffix
+                )
+            })
         };
-        let tag = if self.implementation_name.parse::<InterpreterKind>().is_err() {
+        let tag = if self.uses_generic_tag() {
             // Use generic tags when `sys.implementation.name` != `platform.python_implementation()`, for example Pyston
             // See also https://github.com/pypa/packaging/blob/0031046f7fad649580bc3127d1cef9157da0dd79/packaging/tags.py#L234-L261
+            let abi_tag = match abi_tag {
+                Some(abi_tag) => abi_tag.to_string(),
+                None => soabi
+                    .or(self.soabi.as_deref())
+                    .unwrap_or("none")
+                    .replace(['-', '.'], "_"),
+            };
             format!(
-                "{interpreter}{major}{minor}-{soabi}-{platform}",
+                "{interpreter}{major}{minor}-{abi_tag}-{platform}",
                 interpreter = self.implementation_name,
                 major = self.major,
                 minor = self.minor,
-                soabi = self
-                    .soabi
-                    .as_deref()
-                    .unwrap_or("none")
-                    .replace(['-', '.'], "_"),
-                platform = platform
             )
         } else {
             match self.interpreter_kind {
                 InterpreterKind::CPython => {
+                    let abi_tag = match abi_tag {
+                        Some(abi_tag) => abi_tag.to_string(),
+                        None => format!("cp{}{}{}", self.major, self.minor, self.abiflags),
+                    };
                     format!(
-                        "cp{major}{minor}-cp{major}{minor}{abiflags}-{platform}",
+                        "cp{major}{minor}-{abi_tag}-{platform}",
                         major = self.major,
                         minor = self.minor,
-                        abiflags = self.abiflags,
-                        platform = platform
                     )
                 }
                 InterpreterKind::PyPy => {
                     // pypy uses its version as part of the ABI, e.g.
                     // pypy 3.7 7.3 => numpy-1.20.1-pp37-pypy37_pp73-manylinux2014_x86_64.whl
+                    let abi_tag = match abi_tag {
+                        Some(abi_tag) => abi_tag.to_string(),
+                        None => derive_abi_tag()?,
+                    };
                     format!(
                         "pp{major}{minor}-{abi_tag}-{platform}",
                         major = self.major,
                         minor = self.minor,
-                        abi_tag = calculate_abi_tag(&self.ext_suffix)
-                            .expect("PyPy's syconfig didn't define a valid `EXT_SUFFIX` ಠ_ಠ"),
-                        platform = platform,
                     )
                 }
                 InterpreterKind::GraalPy => {
                     // GraalPy like PyPy uses its version as part of the ABI
                     // graalpy 3.10 23.1 => numpy-1.23.5-graalpy310-graalpy231_310_native-manylinux2014_x86_64.whl
+                    let abi_tag = match abi_tag {
+                        Some(abi_tag) => abi_tag.to_string(),
+                        None => derive_abi_tag()?,
+                    };
                     format!(
                         "graalpy{major}{minor}-{abi_tag}-{platform}",
                         major = self.major,
                         minor = self.minor,
-                        abi_tag = calculate_abi_tag(&self.ext_suffix)
-                            .expect("GraalPy's syconfig didn't define a valid `EXT_SUFFIX` ಠ_ಠ"),
-                        platform = platform,
                     )
                 }
             }
@@ -702,7 +1052,7 @@ impl PythonInterpreter {
                 ext_suffix: message
                     .ext_suffix
                     .context("syconfig didn't define an `EXT_SUFFIX` ಠ_ಠ")?,
-                pointer_width: None,
+                pointer_width: message.pointer_width,
                 gil_disabled: message.gil_disabled,
             },
             executable,
@@ -710,6 +1060,8 @@ impl PythonInterpreter {
             runnable: true,
             implementation_name: message.implementation_name,
             soabi: message.soabi,
+            macosx_deployment_target: message.macosx_deployment_target,
+            lib_dir: message.lib_dir.map(PathBuf::from),
         }))
     }
 
@@ -723,6 +1075,8 @@ impl PythonInterpreter {
             runnable: false,
             implementation_name,
             soabi: None,
+            macosx_deployment_target: None,
+            lib_dir: None,
         }
     }
 
@@ -947,23 +1301,51 @@ impl PythonInterpreter {
         )
     }
 
+    /// The entry of this interpreter in an interpreter lockfile
+    pub fn to_locked(&self) -> LockedInterpreter {
+        LockedInterpreter {
+            executable: self.executable.clone(),
+            environment_signature: self.environment_signature(),
+            soabi: self.soabi.clone(),
+            config: self.config.clone(),
+        }
+    }
+
     /// Returns the site-packages directory inside a venv e.g.
-    /// {venv_base}/lib/python{x}.{y} on unix or {venv_base}/Lib on window
-    pub fn get_venv_site_package(&self, venv_base: impl AsRef<Path>, target: &Target) -> PathBuf {
-        if target.is_unix() {
-            match self.interpreter_kind {
-                InterpreterKind::CPython | InterpreterKind::GraalPy => {
-                    let python_dir = format!("python{}.{}", self.major, self.minor);
-                    venv_base
-                        .as_ref()
-                        .join("lib")
-                        .join(python_dir)
-                        .join("site-packages")
-                }
-                InterpreterKind::PyPy => venv_base.as_ref().join("site-packages"),
-            }
+    /// {venv_base}/lib/python{x}.{y}/site-packages on unix or {venv_base}/Lib/site-packages on
+    /// windows.
+    ///
+    /// On windows both virtualenvs and conda environments use `Lib\site-packages`, but msys2
+    /// pythons create POSIX-like venvs, so we check which one actually exists.
+    pub fn get_venv_site_package(
+        &self,
+        venv_base: impl AsRef<Path>,
+        target: &Target,
+    ) -> Result<PathBuf> {
+        let venv_base = venv_base.as_ref();
+        let posix = match self.interpreter_kind {
+            InterpreterKind::CPython | InterpreterKind::GraalPy => venv_base
+                .join("lib")
+                .join(format!("python{}.{}", self.major, self.minor))
+                .join("site-packages"),
+            InterpreterKind::PyPy => venv_base.join("site-packages"),
+        };
+        let candidates = if target.is_unix() {
+            vec![posix]
         } else {
-            venv_base.as_ref().join("Lib").join("site-packages")
+            vec![venv_base.join("Lib").join("site-packages"), posix]
+        };
+        match candidates.iter().find(|candidate| candidate.is_dir()) {
+            Some(site_packages) => Ok(site_packages.clone()),
+            None => bail!(
+                "Couldn't find the site-packages directory of the environment at {}, tried {}",
+                venv_base.display(),
+                candidates
+                    .iter()
+                    .map(|x| x.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -1026,9 +1408,44 @@ fn calculate_abi_tag(ext_suffix: &str) -> Option<String> {
 mod tests {
     use crate::Bindings;
     use expect_test::expect;
+    use fs_err as fs;
 
     use super::*;
 
+    #[test]
+    fn test_get_venv_site_package() {
+        let target =
+            Target::from_target_triple(Some("x86_64-pc-windows-msvc".to_string())).unwrap();
+        let python = PythonInterpreter::find_by_target(&target, None, None)
+            .into_iter()
+            .find(|python| python.interpreter_kind == InterpreterKind::CPython)
+            .unwrap();
+        let venv = tempfile::tempdir().unwrap();
+
+        // Nothing there yet
+        assert!(python.get_venv_site_package(venv.path(), &target).is_err());
+
+        // msys2 style POSIX-like venv
+        let posix = venv
+            .path()
+            .join("lib")
+            .join(format!("python{}.{}", python.major, python.minor))
+            .join("site-packages");
+        fs_err::create_dir_all(&posix).unwrap();
+        assert_eq!(
+            python.get_venv_site_package(venv.path(), &target).unwrap(),
+            posix
+        );
+
+        // virtualenv and conda style
+        let lib = venv.path().join("Lib").join("site-packages");
+        fs_err::create_dir_all(&lib).unwrap();
+        assert_eq!(
+            python.get_venv_site_package(venv.path(), &target).unwrap(),
+            lib
+        );
+    }
+
     #[test]
     fn test_find_interpreter_by_target() {
         let target =
@@ -1175,4 +1592,258 @@ mod tests {
             assert_eq!(calculate_abi_tag(ext_suffix).as_deref(), expected);
         }
     }
+
+    #[test]
+    fn test_registry_python_executable() {
+        assert_eq!(
+            registry_python_executable(Some(r"C:\Python312\"), Some(r"C:\Python312\python.exe")),
+            Some(PathBuf::from(r"C:\Python312\python.exe"))
+        );
+        assert_eq!(
+            registry_python_executable(Some("C:/Python311"), None),
+            Some(Path::new("C:/Python311").join("python.exe"))
+        );
+        assert_eq!(
+            registry_python_executable(Some("C:/Python311"), Some("")),
+            Some(Path::new("C:/Python311").join("python.exe"))
+        );
+        assert_eq!(registry_python_executable(Some(" "), None), None);
+        assert_eq!(registry_python_executable(None, None), None);
+    }
+
+    #[test]
+    fn test_parse_py_launcher_list_paths() {
+        let summarize = |stdout: &str| {
+            parse_py_launcher_list_paths(stdout)
+                .into_iter()
+                .map(|entry| {
+                    let version = match entry.version {
+                        Some((major, minor)) => format!("{major}.{minor}"),
+                        None => "?".to_string(),
+                    };
+                    let width = match entry.pointer_width {
+                        Some(width) => width.to_string(),
+                        None => "?".to_string(),
+                    };
+                    format!("{version} {width} {}", entry.executable)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let expected = expect![[r#"
+            [
+                "3.10 64 C:\\Users\\dev\\AppData\\Local\\Programs\\Python\\Python310\\python.exe",
+                "3.9 32 C:\\Users\\dev\\AppData\\Local\\Programs\\Python\\Python39-32\\python.exe",
+                "3.8 64 C:\\Python38\\python.exe",
+            ]
+        "#]];
+        expected.assert_debug_eq(&summarize(include_str!(
+            "../../test-data/py-list-paths/legacy.txt"
+        )));
+
+        let expected = expect![[r#"
+            [
+                "3.12 ? C:\\Users\\dev\\AppData\\Local\\Programs\\Python\\Python312\\python.exe",
+                "3.13 ? C:\\Users\\dev\\AppData\\Local\\Programs\\Python\\Python313\\python3.13t.exe",
+                "3.11 64 C:\\Users\\dev\\AppData\\Local\\Programs\\Python\\Python311-arm64\\python.exe",
+                "3.10 32 C:\\Users\\dev\\AppData\\Local\\Programs\\Python\\Python310-32\\python.exe",
+            ]
+        "#]];
+        expected.assert_debug_eq(&summarize(include_str!(
+            "../../test-data/py-list-paths/modern.txt"
+        )));
+
+        let expected = expect![[r#"
+            [
+                "? ? C:\\Users\\dev\\project\\.venv\\Scripts\\python.exe",
+                "3.12 ? C:\\Program Files\\Python312\\python.exe",
+                "? ? C:\\Users\\dev\\anaconda3\\python.exe",
+                "3.11 ? C:\\Python311\\python.exe",
+            ]
+        "#]];
+        expected.assert_debug_eq(&summarize(include_str!(
+            "../../test-data/py-list-paths/venv_and_company.txt"
+        )));
+
+        let expected = expect![[r#"
+            [
+                "3.12 ? C:\\Users\\dev\\AppData\\Local\\Microsoft\\WindowsApps\\PythonSoftwareFoundation.Python.3.12_qbz5n2kfra8p0\\python.exe",
+                "3.11 ? C:\\Users\\dev\\AppData\\Local\\Microsoft\\WindowsApps\\PythonSoftwareFoundation.Python.3.11_qbz5n2kfra8p0\\python.exe",
+            ]
+        "#]];
+        expected.assert_debug_eq(&summarize(include_str!(
+            "../../test-data/py-list-paths/store.txt"
+        )));
+    }
+
+    #[test]
+    fn test_find_python_version_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project_root = temp_dir.path().join("project");
+        let subdir = project_root.join("python").join("package");
+        fs::create_dir_all(&subdir).unwrap();
+        // Outside of the project
+        fs::write(temp_dir.path().join(".python-version"), "3.10\n").unwrap();
+        assert_eq!(find_python_version_file(&subdir, &project_root), None);
+        fs::write(project_root.join(".python-version"), "3.12\n").unwrap();
+        assert_eq!(
+            find_python_version_file(&subdir, &project_root),
+            Some(project_root.join(".python-version"))
+        );
+        // Not in the project at all
+        assert_eq!(
+            find_python_version_file(temp_dir.path(), &project_root),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_python_version_file() {
+        assert_eq!(parse_python_version_file("3.12.1\n"), ["3.12.1"]);
+        assert_eq!(
+            parse_python_version_file("# comment\n3.11\npypy3.10-7.3.17 # trailing\n\nsystem\n"),
+            ["3.11", "pypy3.10-7.3.17"]
+        );
+        assert_eq!(parse_python_version_file("3.9 3.10"), ["3.9", "3.10"]);
+        assert!(parse_python_version_file("system").is_empty());
+    }
+
+    #[test]
+    fn test_get_tag_universal2_free_threaded() {
+        use crate::BuildOptions;
+        use clap::Parser;
+
+        let context = BuildOptions::try_parse_from([
+            "build",
+            "--manifest-path",
+            "test-crates/hello-world/Cargo.toml",
+            "--target",
+            "universal2-apple-darwin",
+        ])
+        .unwrap()
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()
+        .unwrap();
+        assert!(context.universal2);
+        // universal2 builds compile both arches against the same interpreter and fuse the
+        // binaries, so both halves must agree on the free-threaded abi
+        let mut tags = Vec::new();
+        for triple in ["aarch64-apple-darwin", "x86_64-apple-darwin"] {
+            let target = Target::from_target_triple(Some(triple.to_string())).unwrap();
+            let config =
+                InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, 13), "t")
+                    .unwrap()
+                    .clone();
+            assert!(config.gil_disabled);
+            assert_eq!(config.ext_suffix, ".cpython-313t-darwin.so");
+            let interpreter = PythonInterpreter::from_config(config);
+            tags.push(interpreter.get_tag(&context, &[]).unwrap());
+        }
+        assert_eq!(tags[0], tags[1]);
+        assert!(tags[0].starts_with("cp313-cp313t-macosx_"), "{}", tags[0]);
+        assert!(tags[0].ends_with("_universal2"), "{}", tags[0]);
+    }
+
+    #[test]
+    fn test_format_tag_abi_tag_override() {
+        let interpreter = PythonInterpreter::from_config(InterpreterConfig {
+            major: 3,
+            minor: 10,
+            interpreter_kind: InterpreterKind::PyPy,
+            abiflags: String::new(),
+            ext_suffix: ".so".to_string(),
+            pointer_width: Some(64),
+            gil_disabled: false,
+        });
+        let err = interpreter
+            .format_tag("linux_x86_64", None, None, None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--abi-tag"), "{err}");
+        assert_eq!(
+            interpreter
+                .format_tag("linux_x86_64", None, Some("pypy310_pp73"), None)
+                .unwrap(),
+            "pp310-pypy310_pp73-linux_x86_64"
+        );
+    }
+
+    #[test]
+    fn test_format_tag_python_tag_override() {
+        let target =
+            Target::from_target_triple(Some("x86_64-unknown-linux-gnu".to_string())).unwrap();
+        let config = InterpreterConfig::lookup_one(&target, InterpreterKind::CPython, (3, 12), "")
+            .unwrap()
+            .clone();
+        let interpreter = PythonInterpreter::from_config(config);
+        let platform = "manylinux_2_17_x86_64.manylinux2014_x86_64";
+        assert_eq!(
+            interpreter.format_tag(platform, None, None, None).unwrap(),
+            "cp312-cp312-manylinux_2_17_x86_64.manylinux2014_x86_64"
+        );
+        assert_eq!(
+            interpreter
+                .format_tag(platform, Some("py3"), None, None)
+                .unwrap(),
+            "py3-none-manylinux_2_17_x86_64.manylinux2014_x86_64"
+        );
+        assert_eq!(
+            interpreter
+                .format_tag(platform, Some("cp39"), Some("abi3"), None)
+                .unwrap(),
+            "cp39-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64"
+        );
+    }
+
+    #[test]
+    fn test_format_tag_generic_soabi_override() {
+        // Pyston reports `sys.implementation.name` = `pyston`, so the tag is derived from `SOABI`
+        let mut interpreter = PythonInterpreter::from_config(InterpreterConfig {
+            major: 3,
+            minor: 8,
+            interpreter_kind: InterpreterKind::CPython,
+            abiflags: String::new(),
+            ext_suffix: ".pyston-23-x86_64-linux-gnu.so".to_string(),
+            pointer_width: Some(64),
+            gil_disabled: false,
+        });
+        interpreter.implementation_name = "pyston".to_string();
+        interpreter.soabi = Some("pyston-23-x86_64-linux-gnu-patched".to_string());
+        assert!(interpreter.uses_generic_tag());
+        assert_eq!(
+            interpreter
+                .format_tag("linux_x86_64", None, None, None)
+                .unwrap(),
+            "pyston38-pyston_23_x86_64_linux_gnu_patched-linux_x86_64"
+        );
+        assert_eq!(
+            interpreter
+                .format_tag(
+                    "linux_x86_64",
+                    None,
+                    None,
+                    Some("pyston-23-x86_64-linux-gnu")
+                )
+                .unwrap(),
+            "pyston38-pyston_23_x86_64_linux_gnu-linux_x86_64"
+        );
+        // An explicit abi tag still wins over the soabi
+        assert_eq!(
+            interpreter
+                .format_tag("linux_x86_64", None, Some("pyston_23"), Some("ignored"))
+                .unwrap(),
+            "pyston38-pyston_23-linux_x86_64"
+        );
+        // Known interpreter kinds don't use the soabi at all
+        interpreter.implementation_name = "cpython".to_string();
+        assert_eq!(
+            interpreter
+                .format_tag("linux_x86_64", None, None, Some("pyston-23"))
+                .unwrap(),
+            "cp38-cp38-linux_x86_64"
+        );
+    }
 }
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, Package, PackageId};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::path::Path;
+
+/// The bom-ref and purl of a crate, e.g. `pkg:cargo/pyo3@0.23.1`
+fn purl(package: &Package) -> String {
+    format!("pkg:cargo/{}@{}", package.name, package.version)
+}
+
+fn component(package: &Package) -> Value {
+    let mut component = json!({
+        "type": "library",
+        "bom-ref": purl(package),
+        "name": package.name,
+        "version": package.version.to_string(),
+        "purl": purl(package),
+    });
+    if let Some(license) = &package.license {
+        component["licenses"] = json!([{ "expression": license }]);
+    }
+    component
+}
+
+/// Creates a CycloneDX JSON SBOM of the crates that are compiled into the artifacts of the
+/// package at `manifest_path`, i.e. its normal dependencies, recursively.
+///
+/// The SBOM has no serial number or timestamp so that builds stay reproducible.
+pub fn cyclonedx_sbom(cargo_metadata: &Metadata, manifest_path: &Path) -> Result<String> {
+    let root = cargo_metadata
+        .packages
+        .iter()
+        .find(|package| package.manifest_path == manifest_path)
+        .with_context(|| {
+            format!(
+                "Couldn't find the package of {} in the cargo metadata",
+                manifest_path.display()
+            )
+        })?;
+    let resolve = cargo_metadata
+        .resolve
+        .as_ref()
+        .context("Expected cargo to return metadata with resolve")?;
+    let packages: HashMap<&PackageId, &Package> = cargo_metadata
+        .packages
+        .iter()
+        .map(|package| (&package.id, package))
+        .collect();
+    let nodes: HashMap<_, _> = resolve.nodes.iter().map(|node| (&node.id, node)).collect();
+
+    // Breadth first walk over the normal dependency edges, dev and build dependencies
+    // don't end up in the built artifacts
+    let mut dependencies = Vec::new();
+    let mut seen = BTreeSet::from([&root.id]);
+    let mut queue = VecDeque::from([&root.id]);
+    while let Some(id) = queue.pop_front() {
+        let Some(node) = nodes.get(id) else {
+            continue;
+        };
+        let mut depends_on = BTreeSet::new();
+        for dep in &node.deps {
+            if !dep
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind == DependencyKind::Normal)
+            {
+                continue;
+            }
+            depends_on.insert(purl(packages[&dep.pkg]));
+            if seen.insert(&dep.pkg) {
+                queue.push_back(&dep.pkg);
+            }
+        }
+        dependencies.push(json!({
+            "ref": purl(packages[id]),
+            "dependsOn": depends_on,
+        }));
+    }
+    dependencies
+        .sort_by_key(|dependency| dependency["ref"].as_str().unwrap_or_default().to_string());
+    let components: Vec<Value> = seen
+        .iter()
+        .filter(|id| **id != &root.id)
+        .map(|id| packages[id])
+        .map(component)
+        .collect();
+
+    let sbom = json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "tools": [{ "name": "maturin", "version": env!("CARGO_PKG_VERSION") }],
+            "component": component(root),
+        },
+        "components": components,
+        "dependencies": dependencies,
+    });
+    Ok(serde_json::to_string_pretty(&sbom)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cyclonedx_sbom;
+    use cargo_metadata::MetadataCommand;
+    use std::path::Path;
+
+    #[test]
+    fn test_cyclonedx_sbom_direct_dependencies() {
+        let metadata = MetadataCommand::new()
+            .manifest_path(Path::new("test-crates/pyo3-pure").join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let manifest_path = metadata.root_package().unwrap().manifest_path.clone();
+        let sbom = cyclonedx_sbom(&metadata, manifest_path.as_std_path()).unwrap();
+        let sbom: serde_json::Value = serde_json::from_str(&sbom).unwrap();
+
+        assert_eq!(sbom["bomFormat"], "CycloneDX");
+        assert_eq!(sbom["metadata"]["component"]["name"], "pyo3-pure");
+        assert_eq!(
+            sbom["metadata"]["component"]["licenses"][0]["expression"],
+            "MIT"
+        );
+        let components = sbom["components"].as_array().unwrap();
+        let pyo3 = components
+            .iter()
+            .find(|component| component["name"] == "pyo3")
+            .expect("pyo3 is a direct dependency");
+        assert_eq!(
+            pyo3["purl"],
+            format!("pkg:cargo/pyo3@{}", pyo3["version"].as_str().unwrap())
+        );
+        assert!(pyo3["licenses"][0]["expression"].is_string());
+        // Transitive dependencies are listed too
+        assert!(components
+            .iter()
+            .any(|component| component["name"] == "pyo3-ffi"));
+        let root = sbom["dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|dependency| dependency["ref"] == sbom["metadata"]["component"]["bom-ref"])
+            .unwrap();
+        assert!(root["dependsOn"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|dependency| dependency == &pyo3["bom-ref"]));
+    }
+}
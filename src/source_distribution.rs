@@ -1,19 +1,22 @@
-use crate::module_writer::ModuleWriter;
-use crate::pyproject_toml::SdistGenerator;
+use crate::module_writer::{warn_unmatched_include, ModuleWriter};
+use crate::pyproject_toml::{SdistGenerator, VersionSource};
 use crate::{pyproject_toml::Format, BuildContext, PyProjectToml, SDistWriter};
 use anyhow::{bail, Context, Result};
 use cargo_metadata::camino::Utf8Path;
 use cargo_metadata::{Metadata, MetadataCommand, PackageId};
 use fs_err as fs;
 use ignore::overrides::Override;
+use ignore::WalkBuilder;
 use normpath::PathExt as _;
 use path_slash::PathExt as _;
+use pep440_rs::Version;
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 use std::str;
+use std::str::FromStr;
 use toml_edit::DocumentMut;
 use tracing::{debug, trace};
 
@@ -137,11 +140,25 @@ fn rewrite_cargo_toml_readme(
 
 /// When `pyproject.toml` is inside the Cargo workspace root,
 /// we need to update `tool.maturin.manifest-path` in `pyproject.toml`.
+///
+/// Also writes `version` into `project.version`, see [`version_overrides`].
 fn rewrite_pyproject_toml(
     pyproject_toml_path: &Path,
-    relative_manifest_path: &Path,
+    relative_manifest_path: Option<&Path>,
+    version: Option<&str>,
+    classifiers_file: Option<&Path>,
 ) -> Result<String> {
     let mut data = parse_toml_file(pyproject_toml_path, "pyproject.toml")?;
+    if let Some(version) = version {
+        data["project"]["version"] = toml_edit::value(version);
+    }
+    if let Some(classifiers_file) = classifiers_file {
+        data["tool"]["maturin"]["classifiers-file"] =
+            toml_edit::value(classifiers_file.to_slash().unwrap().as_ref());
+    }
+    let Some(relative_manifest_path) = relative_manifest_path else {
+        return Ok(data.to_string());
+    };
     let tool = data
         .entry("tool")
         .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()))
@@ -172,10 +189,149 @@ fn rewrite_pyproject_toml(
     Ok(data.to_string())
 }
 
+/// The path of `tool.maturin.classifiers-file` relative to pyproject.toml in the source
+/// distribution, if it has to differ from the configured one
+///
+/// Archive entries can't contain `..`, so a file outside of the project, e.g. one shared in a
+/// monorepo, is stored next to pyproject.toml instead.
+fn sdist_classifiers_file(
+    pyproject_dir: &Path,
+    classifiers_file: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut normalized = PathBuf::new();
+    let mut outside = false;
+    for component in classifiers_file.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    outside = true;
+                    break;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                outside = true;
+                break;
+            }
+        }
+    }
+    if outside {
+        let file_name = classifiers_file.file_name().with_context(|| {
+            format!(
+                "`tool.maturin.classifiers-file` must point to a file, got {}",
+                classifiers_file.display()
+            )
+        })?;
+        normalized = PathBuf::from(file_name);
+        if pyproject_dir.join(&normalized).exists() {
+            bail!(
+                "The classifiers file {} is outside of the project, so it would be stored as \
+                {} in the source distribution, but that file already exists",
+                classifiers_file.display(),
+                normalized.display()
+            );
+        }
+    }
+    Ok((normalized != classifiers_file).then_some(normalized))
+}
+
+/// The versions to write into the `pyproject.toml` and the `Cargo.toml` of the main crate
+/// when the version of the source distribution differs from the sources, e.g. with
+/// `--version-suffix`, so that wheels built from it get the version in its `PKG-INFO`
+///
+/// Versions derived from git are already read from `PKG-INFO` when building from the sdist.
+fn version_overrides(
+    build_context: &BuildContext,
+    pyproject: &PyProjectToml,
+) -> Result<(Option<String>, Option<String>)> {
+    let version = &build_context.metadata24.version;
+    let project = pyproject.project.as_ref();
+    if let Some(static_version) = project.and_then(|project| project.version.as_ref()) {
+        let changed = static_version != version;
+        return Ok((changed.then(|| version.to_string()), None));
+    }
+    let dynamic = project
+        .and_then(|project| project.dynamic.as_ref())
+        .is_some_and(|dynamic| dynamic.iter().any(|field| field == "version"));
+    if dynamic && pyproject.version_source() == VersionSource::Scm {
+        return Ok((None, None));
+    }
+    let cargo_version = &build_context
+        .cargo_metadata
+        .root_package()
+        .context("Expected cargo to return metadata with root_package")?
+        .version;
+    if Version::from_str(&cargo_version.to_string()).ok().as_ref() == Some(version) {
+        return Ok((None, None));
+    }
+    Ok((None, Some(cargo_version_for(version)?)))
+}
+
+/// Converts a PEP 440 version back into a SemVer version for `Cargo.toml`, e.g. `1.2.3.dev1`
+/// into `1.2.3-dev1`, failing if the result wouldn't be read as the same python version
+fn cargo_version_for(version: &Version) -> Result<String> {
+    let version_str = version.to_string();
+    let (public, local) = match version_str.split_once('+') {
+        Some((public, local)) => (public, Some(local)),
+        None => (version_str.as_str(), None),
+    };
+    let release = version
+        .release()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(".");
+    let mut cargo_version = release.clone();
+    if let Some(pre) = public
+        .strip_prefix(&release)
+        .map(|rest| rest.trim_start_matches('.'))
+        .filter(|rest| !rest.is_empty())
+    {
+        cargo_version.push('-');
+        cargo_version.push_str(pre);
+    }
+    if let Some(local) = local {
+        cargo_version.push('+');
+        cargo_version.push_str(local);
+    }
+    let round_trips = cargo_metadata::semver::Version::parse(&cargo_version).is_ok()
+        && Version::from_str(&cargo_version).ok().as_ref() == Some(version);
+    if !round_trips {
+        bail!("The version {version} can't be written to Cargo.toml as a SemVer version");
+    }
+    Ok(cargo_version)
+}
+
+/// Updates the version of the main crate in `Cargo.lock` to the one written into its
+/// `Cargo.toml` by [`version_overrides`], so that the lock file still matches with `--locked`
+fn rewrite_cargo_lock_version(
+    cargo_lock_path: &Path,
+    name: &str,
+    old_version: &str,
+    new_version: &str,
+) -> Result<String> {
+    let mut document = parse_toml_file(cargo_lock_path, "Cargo.lock")?;
+    if let Some(packages) = document
+        .get_mut("package")
+        .and_then(|packages| packages.as_array_of_tables_mut())
+    {
+        for package in packages.iter_mut() {
+            let matches = package.get("name").and_then(|name| name.as_str()) == Some(name)
+                && package.get("version").and_then(|version| version.as_str()) == Some(old_version);
+            if matches {
+                package["version"] = toml_edit::value(new_version);
+            }
+        }
+    }
+    Ok(document.to_string())
+}
+
 /// Copies the files of a crate to a source distribution, recursively adding path dependencies
 /// and rewriting path entries in Cargo.toml
 ///
 /// Runs `cargo package --list --allow-dirty` to obtain a list of files to package.
+#[allow(clippy::too_many_arguments)]
 fn add_crate_to_source_distribution(
     writer: &mut SDistWriter,
     manifest_path: impl AsRef<Path>,
@@ -184,6 +340,7 @@ fn add_crate_to_source_distribution(
     known_path_deps: &HashMap<String, PathDependency>,
     root_crate: bool,
     skip_cargo_toml: bool,
+    version: Option<&str>,
 ) -> Result<()> {
     debug!(
         "Getting cargo package file list for {}",
@@ -244,6 +401,9 @@ fn add_crate_to_source_distribution(
             } else if *target == "Cargo.toml" {
                 // We rewrite Cargo.toml and add it separately
                 false
+            } else if root_crate && version.is_some() && *target == "Cargo.lock" {
+                // The version of the crate in Cargo.lock is rewritten too, it's added separately
+                false
             } else if root_crate && *target == "pyproject.toml" {
                 // pyproject.toml is handled separately because it has to be put in the root dir
                 // of source distribution
@@ -284,6 +444,9 @@ fn add_crate_to_source_distribution(
         let mut document = parse_toml_file(manifest_path, "Cargo.toml")?;
         rewrite_cargo_toml_readme(&mut document, manifest_path, readme_name)?;
         rewrite_cargo_toml(&mut document, manifest_path, known_path_deps)?;
+        if let Some(version) = version {
+            document["package"]["version"] = toml_edit::value(version);
+        }
         writer.add_bytes(
             cargo_toml_path,
             Some(manifest_path),
@@ -360,29 +523,31 @@ fn find_path_deps(cargo_metadata: &Metadata) -> Result<HashMap<String, PathDepen
                 // we search for the respective package by `manifest_path`, there seems
                 // to be no way to query the dependency graph given `dependency`
                 let dep_manifest_path = path.join("Cargo.toml");
-                // Path dependencies may not be in the same workspace as the root crate,
-                // thus we need to find out its workspace root from `cargo metadata`
-                let path_dep_metadata = MetadataCommand::new()
-                    .manifest_path(&dep_manifest_path)
-                    .verbose(true)
-                    // We don't need to resolve the dependency graph
-                    .no_deps()
-                    .exec()
-                    .with_context(|| {
-                        format!(
-                            "Failed to resolve workspace root for {} at '{}'",
-                            dep_id, dep_manifest_path
-                        )
-                    })?;
+                let workspace_root = if cargo_metadata.workspace_members.contains(dep_id) {
+                    cargo_metadata.workspace_root.clone()
+                } else {
+                    // Path dependencies may not be in the same workspace as the root crate,
+                    // thus we need to find out its workspace root from `cargo metadata`
+                    MetadataCommand::new()
+                        .manifest_path(&dep_manifest_path)
+                        .verbose(true)
+                        // We don't need to resolve the dependency graph
+                        .no_deps()
+                        .exec()
+                        .with_context(|| {
+                            format!(
+                                "Failed to resolve workspace root for {} at '{}'",
+                                dep_id, dep_manifest_path
+                            )
+                        })?
+                        .workspace_root
+                };
 
                 path_deps.insert(
                     dep_name.clone(),
                     PathDependency {
                         manifest_path: PathBuf::from(dep_manifest_path.clone()),
-                        workspace_root: path_dep_metadata
-                            .workspace_root
-                            .clone()
-                            .into_std_path_buf(),
+                        workspace_root: workspace_root.into_std_path_buf(),
                         readme: pkg_readmes.get(dep_id).cloned(),
                     },
                 );
@@ -445,16 +610,55 @@ fn add_cargo_package_files_to_sdist(
     pyproject_toml_path: &Path,
     writer: &mut SDistWriter,
     root_dir: &Path,
+    (pyproject_version, cargo_version): (Option<&str>, Option<&str>),
+    classifiers_file: Option<&Path>,
 ) -> Result<()> {
     let manifest_path = &build_context.manifest_path;
     let workspace_root = &build_context.cargo_metadata.workspace_root;
     let workspace_manifest_path = workspace_root.join("Cargo.toml");
 
-    let known_path_deps = find_path_deps(&build_context.cargo_metadata)?;
+    let mut known_path_deps = find_path_deps(&build_context.cargo_metadata)?;
     debug!(
         "Found path dependencies: {:?}",
         known_path_deps.keys().collect::<Vec<_>>()
     );
+    // `tool.maturin.workspace-members` are built from the sdist too, even if the main crate
+    // doesn't depend on them. They are keyed by their path like in `workspace.members`
+    for member in &build_context.workspace_members {
+        let member_dir = member.manifest_path.parent().unwrap();
+        if known_path_deps
+            .values()
+            .any(|path_dep| path_dep.manifest_path.parent() == Some(member_dir))
+        {
+            continue;
+        }
+        let relative_member_dir = member_dir
+            .strip_prefix(workspace_root)
+            .with_context(|| {
+                format!(
+                    "The workspace member {} is not inside the workspace root {}",
+                    member.crate_name, workspace_root
+                )
+            })?
+            .to_slash()
+            .unwrap()
+            .to_string();
+        let readme = build_context
+            .cargo_metadata
+            .packages
+            .iter()
+            .find(|package| package.manifest_path == member.manifest_path)
+            .and_then(|package| package.readme.clone())
+            .map(|readme| readme.into_std_path_buf());
+        known_path_deps.insert(
+            relative_member_dir,
+            PathDependency {
+                manifest_path: member.manifest_path.clone(),
+                workspace_root: workspace_root.clone().into_std_path_buf(),
+                readme,
+            },
+        );
+    }
     let mut sdist_root =
         common_path_prefix(workspace_root.as_std_path(), pyproject_toml_path).unwrap();
     for path_dep in known_path_deps.values() {
@@ -510,6 +714,7 @@ fn add_cargo_package_files_to_sdist(
         &known_path_deps,
         true,
         false,
+        cargo_version,
     )?;
     // Handle possible relative readme field in Cargo.toml
     if let Some(readme) = main_crate.readme.as_ref() {
@@ -550,7 +755,21 @@ fn add_cargo_package_files_to_sdist(
                 pyproject_root
             };
         let relative_cargo_lock = cargo_lock_path.strip_prefix(project_root).unwrap();
-        writer.add_file(root_dir.join(relative_cargo_lock), &cargo_lock_path)?;
+        if let Some(cargo_version) = cargo_version {
+            let cargo_lock = rewrite_cargo_lock_version(
+                &cargo_lock_path,
+                &main_crate.name,
+                &main_crate.version.to_string(),
+                cargo_version,
+            )?;
+            writer.add_bytes(
+                root_dir.join(relative_cargo_lock),
+                Some(&cargo_lock_path),
+                cargo_lock.as_bytes(),
+            )?;
+        } else {
+            writer.add_file(root_dir.join(relative_cargo_lock), &cargo_lock_path)?;
+        }
         if use_workspace_cargo_lock {
             let relative_workspace_cargo_toml = relative_cargo_lock.with_file_name("Cargo.toml");
             let mut deps_to_keep = known_path_deps.clone();
@@ -593,11 +812,16 @@ fn add_cargo_package_files_to_sdist(
 
     // Add pyproject.toml
     let pyproject_dir = pyproject_toml_path.parent().unwrap();
-    if pyproject_dir != sdist_root {
-        // rewrite `tool.maturin.manifest-path` in pyproject.toml
+    // rewrite `tool.maturin.manifest-path` in pyproject.toml
+    let relative_manifest_path =
+        (pyproject_dir != sdist_root).then(|| relative_main_crate_manifest_dir.join("Cargo.toml"));
+    if relative_manifest_path.is_some() || pyproject_version.is_some() || classifiers_file.is_some()
+    {
         let rewritten_pyproject_toml = rewrite_pyproject_toml(
             pyproject_toml_path,
-            &relative_main_crate_manifest_dir.join("Cargo.toml"),
+            relative_manifest_path.as_deref(),
+            pyproject_version,
+            classifiers_file,
         )?;
         writer.add_bytes(
             root_dir.join("pyproject.toml"),
@@ -615,7 +839,7 @@ fn add_cargo_package_files_to_sdist(
     }
     for package in &build_context.project_layout.python_packages {
         let package_path = build_context.project_layout.python_dir.join(package);
-        if python_packages.iter().any(|p| *p == package_path) {
+        if python_packages.contains(&package_path) {
             continue;
         }
         python_packages.push(package_path);
@@ -676,6 +900,7 @@ fn add_path_dep(
         known_path_deps,
         false,
         skip_cargo_toml,
+        None,
     )
     .with_context(|| {
         format!(
@@ -714,7 +939,7 @@ fn add_path_dep(
     Ok(())
 }
 
-/// Creates a source distribution, packing the root crate and all local dependencies
+/// Creates a source distribution in `out`, packing the root crate and all local dependencies
 ///
 /// The source distribution format is specified in
 /// [PEP 517 under "build_sdist"](https://www.python.org/dev/peps/pep-0517/#build-sdist)
@@ -724,6 +949,7 @@ pub fn source_distribution(
     build_context: &BuildContext,
     pyproject: &PyProjectToml,
     excludes: Override,
+    out: &Path,
 ) -> Result<PathBuf> {
     let pyproject_toml_path = build_context
         .pyproject_toml_path
@@ -736,36 +962,49 @@ pub fn source_distribution(
         })?
         .into_path_buf();
     let metadata24 = &build_context.metadata24;
-    let mut writer = SDistWriter::new(&build_context.out, metadata24, excludes)?;
+    let mut writer = SDistWriter::new(out, metadata24, excludes)?
+        .with_follow_symlinks(pyproject.sdist_follow_symlinks());
     let root_dir = PathBuf::from(format!(
         "{}-{}",
         &metadata24.get_distribution_escaped(),
         &metadata24.get_version_escaped()
     ));
 
+    let pyproject_dir = pyproject_toml_path.parent().unwrap();
+    let (pyproject_version, cargo_version) = version_overrides(build_context, pyproject)?;
+    let sdist_classifiers_file = pyproject
+        .classifiers_file()
+        .map(|classifiers_file| sdist_classifiers_file(pyproject_dir, classifiers_file))
+        .transpose()?
+        .flatten();
     match pyproject.sdist_generator() {
         SdistGenerator::Cargo => add_cargo_package_files_to_sdist(
             build_context,
             &pyproject_toml_path,
             &mut writer,
             &root_dir,
+            (pyproject_version.as_deref(), cargo_version.as_deref()),
+            sdist_classifiers_file.as_deref(),
         )?,
         SdistGenerator::Git => {
+            if pyproject_version.is_some() || cargo_version.is_some() {
+                bail!(
+                    "The version {} differs from the version in the sources, which \
+                    `sdist-generator = \"git\"` can't rewrite",
+                    metadata24.version
+                );
+            }
+            if let Some(classifiers_file) = &sdist_classifiers_file {
+                bail!(
+                    "The classifiers file must be stored as {} in the source distribution, \
+                    which `sdist-generator = \"git\"` can't rewrite in pyproject.toml",
+                    classifiers_file.display()
+                );
+            }
             add_git_tracked_files_to_sdist(&pyproject_toml_path, &mut writer, &root_dir)?
         }
     }
 
-    let pyproject_toml_path = build_context
-        .pyproject_toml_path
-        .normalize()
-        .with_context(|| {
-            format!(
-                "failed to normalize pyproject.toml path `{}`",
-                build_context.pyproject_toml_path.display()
-            )
-        })?
-        .into_path_buf();
-    let pyproject_dir = pyproject_toml_path.parent().unwrap();
     // Add readme, license
     if let Some(project) = pyproject.project.as_ref() {
         if let Some(pyproject_toml::ReadMe::RelativePath(readme)) = project.readme.as_ref() {
@@ -775,13 +1014,18 @@ pub fn source_distribution(
             writer.add_file(root_dir.join(file), pyproject_dir.join(file))?;
         }
     }
+    // Building from the sdist reads the classifiers again
+    if let Some(classifiers_file) = pyproject.classifiers_file() {
+        let target = sdist_classifiers_file
+            .as_deref()
+            .unwrap_or(classifiers_file);
+        writer.add_file(root_dir.join(target), pyproject_dir.join(classifiers_file))?;
+    }
 
-    let mut include = |pattern| -> Result<()> {
+    for (pattern, sources) in pyproject.resolved_includes(pyproject_dir, Format::Sdist)? {
         eprintln!("📦 Including files matching \"{pattern}\"");
-        for source in glob::glob(&pyproject_dir.join(pattern).to_string_lossy())
-            .with_context(|| format!("Invalid glob pattern: {pattern}"))?
-            .filter_map(Result::ok)
-        {
+        warn_unmatched_include(pattern, &sources, Format::Sdist, pyproject_dir);
+        for source in sources {
             let target = root_dir.join(source.strip_prefix(pyproject_dir).unwrap());
             if source.is_dir() {
                 writer.add_directory(target)?;
@@ -789,16 +1033,10 @@ pub fn source_distribution(
                 writer.add_file(target, source)?;
             }
         }
-        Ok(())
-    };
+    }
 
-    if let Some(glob_patterns) = pyproject.include() {
-        for pattern in glob_patterns
-            .iter()
-            .filter_map(|glob_pattern| glob_pattern.targets(Format::Sdist))
-        {
-            include(pattern)?;
-        }
+    if build_context.vendor_crates {
+        add_vendored_crates_to_sdist(build_context, &mut writer, &root_dir)?;
     }
 
     writer.add_bytes(
@@ -817,6 +1055,75 @@ pub fn source_distribution(
     Ok(source_distribution_path)
 }
 
+/// Runs `cargo vendor` and adds the vendored sources to the sdist, together with a
+/// `.cargo/config.toml` that replaces crates.io and git sources with the `vendor` directory,
+/// so that the source distribution can be built offline
+fn add_vendored_crates_to_sdist(
+    build_context: &BuildContext,
+    writer: &mut SDistWriter,
+    root_dir: &Path,
+) -> Result<()> {
+    eprintln!("📦 Vendoring crates into the source distribution");
+    let manifest_path = build_context
+        .manifest_path
+        .normalize()
+        .with_context(|| {
+            format!(
+                "failed to normalize manifest path `{}`",
+                build_context.manifest_path.display()
+            )
+        })?
+        .into_path_buf();
+    let temp_dir = build_context.temp_dir()?;
+    let mut command = Command::new("cargo");
+    command
+        .args(["vendor", "--manifest-path"])
+        .arg(&manifest_path)
+        .current_dir(temp_dir.path());
+    if build_context.cargo_options.locked {
+        command.arg("--locked");
+    }
+    if build_context.cargo_options.frozen {
+        command.arg("--frozen");
+    }
+    // A relative path makes cargo print a relative `directory` in the config snippet
+    command.arg("vendor");
+    let output = command.output().context("Failed to run `cargo vendor`")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to vendor crates with cargo: {}\n--- Manifest path: {}\n--- Stdout:\n{}\n--- Stderr:\n{}",
+            output.status,
+            manifest_path.display(),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+    let cargo_config = str::from_utf8(&output.stdout).context("Cargo printed invalid utf-8 ಠ_ಠ")?;
+
+    let vendor_dir = temp_dir.path().join("vendor");
+    // Crates without any registry or git dependencies don't get a vendor directory
+    if vendor_dir.is_dir() {
+        for entry in WalkBuilder::new(&vendor_dir)
+            .standard_filters(false)
+            .build()
+        {
+            let entry = entry?;
+            if entry.file_type().is_some_and(|ty| ty.is_file()) {
+                let relative = entry.path().strip_prefix(temp_dir.path()).unwrap();
+                writer.add_file(root_dir.join(relative), entry.path())?;
+            }
+        }
+    }
+    if !cargo_config.trim().is_empty() {
+        writer.add_bytes(
+            root_dir.join(".cargo").join("config.toml"),
+            None,
+            cargo_config.as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
 /// Find the common prefix, if any, between two paths
 ///
 /// Taken from https://docs.rs/common-path/1.0.0/src/common_path/lib.rs.html#84-109
@@ -3,6 +3,7 @@ use crate::python_interpreter::InterpreterKind;
 use crate::python_interpreter::InterpreterKind::{CPython, GraalPy, PyPy};
 use crate::PlatformTag;
 use anyhow::{anyhow, bail, format_err, Result};
+use itertools::Itertools;
 use platform_info::*;
 use rustc_version::VersionMeta;
 use serde::Deserialize;
@@ -36,6 +37,10 @@ pub enum Os {
     Wasi,
     Aix,
     Hurd,
+    Redox,
+    /// An operating system maturin doesn't know about, only constructed when explicitly
+    /// allowed with `--allow-unsupported-target`
+    Unknown,
 }
 
 impl fmt::Display for Os {
@@ -56,10 +61,32 @@ impl fmt::Display for Os {
             Os::Wasi => write!(f, "Wasi"),
             Os::Aix => write!(f, "AIX"),
             Os::Hurd => write!(f, "Hurd"),
+            Os::Redox => write!(f, "Redox"),
+            Os::Unknown => write!(f, "Unknown"),
         }
     }
 }
 
+/// The operating systems maturin knows how to build for
+const SUPPORTED_OS: [Os; 16] = [
+    Os::Linux,
+    Os::Windows,
+    Os::Macos,
+    Os::Ios,
+    Os::FreeBsd,
+    Os::NetBsd,
+    Os::OpenBsd,
+    Os::Dragonfly,
+    Os::Solaris,
+    Os::Illumos,
+    Os::Haiku,
+    Os::Emscripten,
+    Os::Wasi,
+    Os::Aix,
+    Os::Hurd,
+    Os::Redox,
+];
+
 /// All supported CPU architectures
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -202,6 +229,30 @@ fn get_supported_architectures(os: &Os) -> Vec<Arch> {
         Os::Emscripten | Os::Wasi => vec![Arch::Wasm32],
         Os::Aix => vec![Arch::Powerpc64],
         Os::Hurd => vec![Arch::X86, Arch::X86_64],
+        Os::Redox => vec![Arch::X86, Arch::X86_64, Arch::Aarch64, Arch::Riscv64],
+        // We know nothing about this os, so let every architecture through
+        Os::Unknown => vec![
+            Arch::Aarch64,
+            Arch::Armv5teL,
+            Arch::Armv6L,
+            Arch::Armv7L,
+            Arch::Powerpc,
+            Arch::Powerpc64Le,
+            Arch::Powerpc64,
+            Arch::X86,
+            Arch::X86_64,
+            Arch::S390X,
+            Arch::Wasm32,
+            Arch::Riscv32,
+            Arch::Riscv64,
+            Arch::Mips64el,
+            Arch::Mips64,
+            Arch::Mipsel,
+            Arch::Mips,
+            Arch::Sparc64,
+            Arch::Sparcv9,
+            Arch::LoongArch64,
+        ],
     }
 }
 
@@ -225,12 +276,51 @@ impl fmt::Display for Target {
     }
 }
 
+/// Picks the platform tag architecture from the `uname -m` output
+///
+/// When running in Docker sometimes uname returns the 64-bit architecture of the host kernel
+/// while the container is actually 32-bit (or otherwise different), e.g. `aarch64` in an armv7
+/// container. In this case we trust the architecture of the rustc target.
+fn platform_arch_from_machine(machine: &str, arch: Arch) -> String {
+    let machine_matches = match machine {
+        "x86_64" => arch == Arch::X86_64,
+        // armv8l is what a 64-bit arm kernel reports with the linux32 personality
+        "aarch64" | "armv8l" => arch == Arch::Aarch64,
+        "ppc64le" => arch == Arch::Powerpc64Le,
+        "ppc64" => arch == Arch::Powerpc64,
+        "s390x" => arch == Arch::S390X,
+        // uname doesn't distinguish the endianness of mips
+        "mips64" => matches!(arch, Arch::Mips64 | Arch::Mips64el),
+        "riscv64" => arch == Arch::Riscv64,
+        "sparc64" => matches!(arch, Arch::Sparc64 | Arch::Sparcv9),
+        "loongarch64" => arch == Arch::LoongArch64,
+        // An armv6 build on a 32-bit Raspberry Pi 2 or newer must still be tagged armv6l and vice versa
+        "armv5tel" | "armv6l" | "armv7l" => machine == arch.to_string(),
+        // Not a 64-bit kernel, so the container can't be running a narrower userspace
+        _ => true,
+    };
+    if machine_matches {
+        machine.to_string()
+    } else {
+        arch.to_string()
+    }
+}
+
 impl Target {
     /// Uses the given target triple or tries the guess the current target by using the one used
     /// for compilation
     ///
     /// Fails if the target triple isn't supported
     pub fn from_target_triple(target_triple: Option<String>) -> Result<Self> {
+        Self::from_target_triple_with_options(target_triple, false)
+    }
+
+    /// Like [Target::from_target_triple], but with `allow_unsupported` an operating system
+    /// maturin doesn't support is accepted as [Os::Unknown] instead of being rejected
+    pub fn from_target_triple_with_options(
+        target_triple: Option<String>,
+        allow_unsupported: bool,
+    ) -> Result<Self> {
         use target_lexicon::{
             ArmArchitecture, Mips32Architecture, Mips64Architecture, OperatingSystem,
         };
@@ -265,7 +355,21 @@ impl Target {
             OperatingSystem::Wasi | OperatingSystem::WasiP1 | OperatingSystem::WasiP2 => Os::Wasi,
             OperatingSystem::Aix => Os::Aix,
             OperatingSystem::Hurd => Os::Hurd,
-            unsupported => bail!("The operating system {:?} is not supported", unsupported),
+            OperatingSystem::Redox => Os::Redox,
+            unsupported if allow_unsupported => {
+                eprintln!(
+                    "⚠️  Warning: The operating system {unsupported:?} is not supported, \
+                    building anyway because of `--allow-unsupported-target`. \
+                    The wheel will be tagged as linux, but it won't work on linux, use at your own risk!"
+                );
+                Os::Unknown
+            }
+            unsupported => bail!(
+                "The operating system {:?} is not supported, supported are: {}. \
+                Use `--allow-unsupported-target` to build anyway",
+                unsupported,
+                SUPPORTED_OS.iter().join(", ")
+            ),
         };
 
         let arch = match platform.architecture {
@@ -274,7 +378,7 @@ impl Target {
             Architecture::Arm(arm_arch) => match arm_arch {
                 ArmArchitecture::Armv5te => Arch::Armv5teL,
                 ArmArchitecture::Arm | ArmArchitecture::Armv6 => Arch::Armv6L,
-                ArmArchitecture::Armv7 => Arch::Armv7L,
+                ArmArchitecture::Armv7 | ArmArchitecture::Thumbv7neon => Arch::Armv7L,
                 _ => bail!("The architecture {} is not supported", arm_arch),
             },
             Architecture::Aarch64(_) => Arch::Aarch64,
@@ -325,17 +429,7 @@ impl Target {
         }
         let machine = PlatformInfo::new().map(|info| info.machine().to_string_lossy().into_owned());
         let arch = match machine {
-            Ok(machine) => {
-                let linux32 = (machine == "x86_64" && self.arch != Arch::X86_64)
-                    || (machine == "aarch64" && self.arch != Arch::Aarch64);
-                if linux32 {
-                    // When running in Docker sometimes uname returns 64-bit architecture while the container is actually 32-bit,
-                    // In this case we trust the architecture of rustc target
-                    self.arch.to_string()
-                } else {
-                    machine
-                }
-            }
+            Ok(machine) => platform_arch_from_machine(&machine, self.arch),
             Err(err) => {
                 error!("Failed to get machine architecture: {}", err);
                 self.arch.to_string()
@@ -445,6 +539,9 @@ impl Target {
             Os::Wasi => "wasi",
             Os::Aix => "aix",
             Os::Hurd => "gnu",
+            Os::Redox => "redox",
+            // Best effort, pretend to be linux
+            Os::Unknown => "linux",
         }
     }
 
@@ -479,6 +576,55 @@ impl Target {
         }
     }
 
+    /// Returns the default platform tag for musl targets, `musllinux_1_2`, or the plain `linux` tag
+    /// for architectures without a musllinux policy such as riscv32
+    pub fn get_default_musllinux_tag(&self) -> PlatformTag {
+        match self.arch {
+            Arch::Aarch64
+            | Arch::Armv7L
+            | Arch::Powerpc64Le
+            | Arch::S390X
+            | Arch::X86
+            | Arch::X86_64
+            | Arch::Riscv64
+            | Arch::LoongArch64 => PlatformTag::Musllinux { x: 1, y: 2 },
+            Arch::Armv5teL
+            | Arch::Armv6L
+            | Arch::Wasm32
+            | Arch::Riscv32
+            | Arch::Mips64el
+            | Arch::Mips64
+            | Arch::Mipsel
+            | Arch::Mips
+            | Arch::Powerpc
+            | Arch::Powerpc64
+            | Arch::Sparc64
+            | Arch::Sparcv9 => PlatformTag::Linux,
+        }
+    }
+
+    /// Returns whether wheels for this target can be tagged with `platform_tag`
+    ///
+    /// The `linux` tag works for any linux target, manylinux needs glibc and an architecture with
+    /// a manylinux policy no older than [Target::get_minimum_manylinux_tag], musllinux needs musl
+    /// and an architecture with a musllinux policy.
+    /// Statically linked musl binaries are manylinux compatible too, callers need to allow that.
+    pub fn supports_platform_tag(&self, platform_tag: &PlatformTag) -> bool {
+        if !self.is_linux() {
+            return false;
+        }
+        match platform_tag {
+            PlatformTag::Linux => true,
+            PlatformTag::Manylinux { .. } => {
+                let minimum = self.get_minimum_manylinux_tag();
+                !self.is_musl_libc() && minimum.is_manylinux() && *platform_tag >= minimum
+            }
+            PlatformTag::Musllinux { .. } => {
+                self.is_musl_libc() && self.get_default_musllinux_tag().is_musllinux()
+            }
+        }
+    }
+
     /// Returns whether the platform is 64 bit or 32 bit
     pub fn pointer_width(&self) -> usize {
         match self.arch {
@@ -534,7 +680,9 @@ impl Target {
             | Os::Emscripten
             | Os::Wasi
             | Os::Aix
-            | Os::Hurd => true,
+            | Os::Hurd
+            | Os::Redox
+            | Os::Unknown => true,
         }
     }
 
@@ -702,6 +850,8 @@ fn rustc_version_meta() -> Result<VersionMeta> {
     Ok(meta)
 }
 
+/// Detects the target to build for from the platform of a macOS interpreter, if it differs
+/// from `target`
 pub(crate) fn detect_arch_from_python(python: &PathBuf, target: &Target) -> Option<String> {
     match Command::new(python)
         .arg("-c")
@@ -710,15 +860,185 @@ pub(crate) fn detect_arch_from_python(python: &PathBuf, target: &Target) -> Opti
     {
         Ok(output) if output.status.success() => {
             let platform = String::from_utf8_lossy(&output.stdout);
-            if platform.contains("macos") {
-                if platform.contains("x86_64") && target.target_arch() != Arch::X86_64 {
-                    return Some("x86_64-apple-darwin".to_string());
-                } else if platform.contains("arm64") && target.target_arch() != Arch::Aarch64 {
-                    return Some("aarch64-apple-darwin".to_string());
-                }
-            }
+            detect_arch_from_platform(&platform, target)
+        }
+        _ => {
+            eprintln!("⚠️  Warning: Failed to determine python platform");
+            None
+        }
+    }
+}
+
+/// The target matching `sysconfig.get_platform()` of a macOS interpreter, e.g.
+/// `macosx-11.0-arm64`, if it differs from `target`
+///
+/// A universal2 interpreter (`macosx-10.9-universal2`) can load extensions of either arch, so
+/// `target` is kept instead of the arch the interpreter happens to run as.
+fn detect_arch_from_platform(platform: &str, target: &Target) -> Option<String> {
+    if !platform.starts_with("macos") {
+        return None;
+    }
+    match platform.rsplit('-').next()? {
+        "x86_64" if target.target_arch() != Arch::X86_64 => Some("x86_64-apple-darwin".to_string()),
+        "arm64" if target.target_arch() != Arch::Aarch64 => {
+            Some("aarch64-apple-darwin".to_string())
         }
-        _ => eprintln!("⚠️  Warning: Failed to determine python platform"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect_arch_from_platform() {
+        let x86_64 = Target::from_target_triple(Some("x86_64-apple-darwin".to_string())).unwrap();
+        let aarch64 = Target::from_target_triple(Some("aarch64-apple-darwin".to_string())).unwrap();
+        // A universal2 interpreter keeps the requested arch
+        assert_eq!(
+            detect_arch_from_platform("macosx-10.9-universal2", &x86_64),
+            None
+        );
+        assert_eq!(
+            detect_arch_from_platform("macosx-10.9-universal2", &aarch64),
+            None
+        );
+        // Single arch interpreters can only load their own arch
+        assert_eq!(
+            detect_arch_from_platform("macosx-11.0-arm64", &x86_64).as_deref(),
+            Some("aarch64-apple-darwin")
+        );
+        assert_eq!(
+            detect_arch_from_platform("macosx-10.9-x86_64", &aarch64).as_deref(),
+            Some("x86_64-apple-darwin")
+        );
+        assert_eq!(
+            detect_arch_from_platform("macosx-10.9-x86_64", &x86_64),
+            None
+        );
+        assert_eq!(detect_arch_from_platform("linux-x86_64", &aarch64), None);
+    }
+
+    #[test]
+    fn test_redox_target() {
+        let target = Target::from_target_triple(Some("x86_64-unknown-redox".to_string())).unwrap();
+        assert_eq!(target.target_os(), Os::Redox);
+        assert_eq!(target.get_python_os(), "redox");
+        assert!(target.is_unix());
+    }
+
+    #[test]
+    fn test_unsupported_os_lists_supported() {
+        let err = Target::from_target_triple(Some("x86_64-unknown-fuchsia".to_string()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("The operating system Fuchsia is not supported"));
+        assert!(err.contains("Linux, Windows, macOS"));
+        assert!(err.contains("Redox"));
+    }
+
+    #[test]
+    fn test_platform_arch_from_machine() {
+        // Native builds keep the uname value
+        assert_eq!(platform_arch_from_machine("x86_64", Arch::X86_64), "x86_64");
+        assert_eq!(
+            platform_arch_from_machine("aarch64", Arch::Aarch64),
+            "aarch64"
+        );
+        assert_eq!(platform_arch_from_machine("armv7l", Arch::Armv7L), "armv7l");
+        assert_eq!(platform_arch_from_machine("i586", Arch::X86), "i586");
+        assert_eq!(
+            platform_arch_from_machine("mips64", Arch::Mips64el),
+            "mips64"
+        );
+        // 32-bit containers on 64-bit hosts use the rustc target
+        assert_eq!(platform_arch_from_machine("x86_64", Arch::X86), "i686");
+        assert_eq!(
+            platform_arch_from_machine("aarch64", Arch::Armv7L),
+            "armv7l"
+        );
+        assert_eq!(
+            platform_arch_from_machine("aarch64", Arch::Armv6L),
+            "armv6l"
+        );
+        assert_eq!(platform_arch_from_machine("armv8l", Arch::Armv7L), "armv7l");
+        // 32-bit Raspberry Pi OS reports the cpu, not what the wheel was built for
+        assert_eq!(platform_arch_from_machine("armv7l", Arch::Armv6L), "armv6l");
+        assert_eq!(platform_arch_from_machine("armv6l", Arch::Armv7L), "armv7l");
+        assert_eq!(platform_arch_from_machine("armv6l", Arch::Armv6L), "armv6l");
+        assert_eq!(platform_arch_from_machine("ppc64le", Arch::Powerpc), "ppc");
+        assert_eq!(platform_arch_from_machine("mips64", Arch::Mipsel), "mipsel");
+        assert_eq!(
+            platform_arch_from_machine("riscv64", Arch::Riscv32),
+            "riscv32"
+        );
+        // Emulated or otherwise mismatched containers also trust the rustc target
+        assert_eq!(
+            platform_arch_from_machine("x86_64", Arch::Aarch64),
+            "aarch64"
+        );
+    }
+
+    #[test]
+    fn test_supports_platform_tag() {
+        let target = |triple: &str| Target::from_target_triple(Some(triple.to_string())).unwrap();
+        let gnu = target("x86_64-unknown-linux-gnu");
+        assert!(gnu.supports_platform_tag(&PlatformTag::Linux));
+        assert!(gnu.supports_platform_tag(&PlatformTag::manylinux2014()));
+        assert!(gnu.supports_platform_tag(&PlatformTag::Manylinux { x: 2, y: 28 }));
+        assert!(!gnu.supports_platform_tag(&PlatformTag::manylinux1()));
+        assert!(!gnu.supports_platform_tag(&PlatformTag::Musllinux { x: 1, y: 2 }));
+
+        let musl = target("aarch64-unknown-linux-musl");
+        assert!(musl.supports_platform_tag(&PlatformTag::Musllinux { x: 1, y: 2 }));
+        assert!(!musl.supports_platform_tag(&PlatformTag::manylinux2014()));
+
+        let riscv = target("riscv64gc-unknown-linux-gnu");
+        assert!(!riscv.supports_platform_tag(&PlatformTag::manylinux2014()));
+        assert!(riscv.supports_platform_tag(&PlatformTag::Manylinux { x: 2, y: 31 }));
+
+        let armv6 = target("arm-unknown-linux-gnueabihf");
+        assert!(!armv6.supports_platform_tag(&PlatformTag::Manylinux { x: 2, y: 31 }));
+        assert!(armv6.supports_platform_tag(&PlatformTag::Linux));
+
+        let riscv32_musl = target("riscv32gc-unknown-linux-musl");
+        assert!(!riscv32_musl.supports_platform_tag(&PlatformTag::Musllinux { x: 1, y: 2 }));
+        assert!(riscv32_musl.supports_platform_tag(&PlatformTag::Linux));
+
+        let macos = target("aarch64-apple-darwin");
+        assert!(!macos.supports_platform_tag(&PlatformTag::Linux));
+        assert!(!macos.supports_platform_tag(&PlatformTag::manylinux2014()));
+    }
+
+    #[test]
+    fn test_default_musllinux_tag() {
+        let target = |triple: &str| Target::from_target_triple(Some(triple.to_string())).unwrap();
+        assert_eq!(
+            target("x86_64-unknown-linux-musl").get_default_musllinux_tag(),
+            PlatformTag::Musllinux { x: 1, y: 2 }
+        );
+        assert_eq!(
+            target("riscv64gc-unknown-linux-musl").get_default_musllinux_tag(),
+            PlatformTag::Musllinux { x: 1, y: 2 }
+        );
+        // There is no musllinux policy for riscv32
+        assert_eq!(
+            target("riscv32gc-unknown-linux-musl").get_default_musllinux_tag(),
+            PlatformTag::Linux
+        );
+    }
+
+    #[test]
+    fn test_unsupported_os_requires_opt_in() {
+        let triple = "x86_64-wrs-vxworks".to_string();
+        assert!(Target::from_target_triple(Some(triple.clone())).is_err());
+
+        let target = Target::from_target_triple_with_options(Some(triple), true).unwrap();
+        assert_eq!(target.target_os(), Os::Unknown);
+        assert_eq!(target.target_arch(), Arch::X86_64);
+        assert_eq!(target.get_python_os(), "linux");
+        assert!(target.is_unix());
+        assert!(!target.is_linux());
     }
-    None
 }
@@ -9,6 +9,7 @@ use bytesize::ByteSize;
 use configparser::ini::Ini;
 use fs_err as fs;
 use fs_err::File;
+use itertools::Itertools;
 use multipart::client::lazy::Multipart;
 use regex::Regex;
 use serde::Deserialize;
@@ -56,6 +57,10 @@ pub struct PublishOpt {
     /// Can also be set via MATURIN_NON_INTERACTIVE environment variable.
     #[arg(long, env = "MATURIN_NON_INTERACTIVE")]
     non_interactive: bool,
+    /// Upload wheels to PyPI even if their platform tag is one PyPI rejects, such as
+    /// `linux_x86_64`, instead of failing before the upload
+    #[arg(long)]
+    force: bool,
 }
 
 impl PublishOpt {
@@ -70,6 +75,15 @@ impl PublishOpt {
             self.non_interactive = true;
         }
     }
+
+    /// Whether the selected registry is PyPI or TestPyPI, as opposed to a private registry
+    /// given with `--repository-url` or configured in `.pypirc`
+    pub fn targets_pypi(&self) -> Result<bool> {
+        let (_, registry_url) = resolve_registry_url(self, &load_pypirc())?;
+        let registry_url = registry_url.trim_end_matches('/');
+        Ok(registry_url == Self::DEFAULT_REPOSITORY_URL
+            || registry_url == Self::TEST_REPOSITORY_URL)
+    }
 }
 
 /// Error type for different types of errors that can happen when uploading a
@@ -147,16 +161,24 @@ impl Registry {
 }
 
 /// Attempts to fetch the password from the keyring (if enabled)
-/// and falls back to the interactive password prompt.
-fn get_password(_username: &str) -> String {
+fn keyring_password(_username: &str) -> Option<String> {
     #[cfg(feature = "keyring")]
     {
         let service = env!("CARGO_PKG_NAME");
         let keyring = keyring::Entry::new(service, _username);
         if let Ok(password) = keyring.and_then(|keyring| keyring.get_password()) {
-            return password;
+            return Some(password);
         };
     }
+    None
+}
+
+/// Attempts to fetch the password from the keyring (if enabled)
+/// and falls back to the interactive password prompt.
+fn get_password(username: &str) -> String {
+    if let Some(password) = keyring_password(username) {
+        return password;
+    }
 
     dialoguer::Password::new()
         .with_prompt("Please enter your password")
@@ -189,22 +211,26 @@ fn load_pypirc() -> Ini {
     config
 }
 
-fn load_pypi_cred_from_config(config: &Ini, registry_name: &str) -> Option<(String, String)> {
-    if let (Some(username), Some(password)) = (
-        config.get(registry_name, "username"),
-        config.get(registry_name, "password"),
-    ) {
-        return Some((username, password));
-    }
-    None
+/// Merges the username and password given as arguments with the ones in the `.pypirc` section
+/// of the registry, the arguments take precedence
+fn merge_pypirc_cred(
+    opt: &PublishOpt,
+    config: &Ini,
+    registry_name: Option<&str>,
+) -> (Option<String>, Option<String>) {
+    let from_config = |key| registry_name.and_then(|name| config.get(name, key));
+    (
+        opt.username.clone().or_else(|| from_config("username")),
+        opt.password.clone().or_else(|| from_config("password")),
+    )
 }
 
 /// Gets the PyPI credentials from (in precedence order):
 ///
 /// 1. `MATURIN_PYPI_TOKEN` environment variable
-/// 2. `.pypirc` config file
-/// 3. maturin command arguments
-/// 4. `MATURIN_USERNAME` and `MATURIN_PASSWORD` environment variables
+/// 2. maturin command arguments
+/// 3. `MATURIN_USERNAME` and `MATURIN_PASSWORD` environment variables
+/// 4. `.pypirc` config file
 /// 5. the password keyring
 /// 6. interactive prompt
 fn resolve_pypi_cred(
@@ -228,22 +254,26 @@ fn resolve_pypi_cred(
         Err(e) => eprintln!("⚠️ Warning: Failed to resolve PyPI token via OIDC: {}", e),
     }
 
-    if let Some((username, password)) =
-        registry_name.and_then(|name| load_pypi_cred_from_config(config, name))
+    let (username, password) = merge_pypirc_cred(opt, config, registry_name);
+    if opt.username.is_none() && username.is_some() || opt.password.is_none() && password.is_some()
     {
         eprintln!("🔐 Using credential in pypirc for upload");
-        return Ok((username, password));
     }
 
-    // fallback to username and password
-    if opt.non_interactive && (opt.username.is_none() || opt.password.is_none()) {
-        bail!("Credentials not found and non-interactive mode is enabled");
-    }
-    let username = opt.username.clone().unwrap_or_else(get_username);
-    let password = opt
-        .password
-        .clone()
-        .unwrap_or_else(|| get_password(&username));
+    // fallback to the keyring and prompting for what's still missing
+    let username = match username {
+        Some(username) => username,
+        None if opt.non_interactive => {
+            bail!("Credentials not found and non-interactive mode is enabled")
+        }
+        None => get_username(),
+    };
+    let password = match password {
+        Some(password) => password,
+        None if opt.non_interactive => keyring_password(&username)
+            .context("Credentials not found and non-interactive mode is enabled")?,
+        None => get_password(&username),
+    };
     Ok((username, password))
 }
 
@@ -315,8 +345,20 @@ fn resolve_pypi_token_via_oidc(registry_url: &str) -> Result<Option<String>> {
 fn complete_registry(opt: &PublishOpt) -> Result<Registry> {
     // load creds from pypirc if found
     let pypirc = load_pypirc();
-    let (registry_name, registry_url) = if let Some(repository_url) = opt.repository_url.as_deref()
-    {
+    let (registry_name, registry_url) = resolve_registry_url(opt, &pypirc)?;
+    let (username, password) = resolve_pypi_cred(opt, &pypirc, registry_name, &registry_url)?;
+    let registry = Registry::new(username, password, registry_url);
+
+    Ok(registry)
+}
+
+/// Returns the `.pypirc` section name, if any, and the upload URL of the registry selected
+/// with `--repository-url` or `--repository`
+fn resolve_registry_url<'a>(
+    opt: &'a PublishOpt,
+    pypirc: &Ini,
+) -> Result<(Option<&'a str>, String)> {
+    let resolved = if let Some(repository_url) = opt.repository_url.as_deref() {
         // to normalize URLs by removing trailing slashes
         match repository_url.trim_end_matches('/') {
             PublishOpt::DEFAULT_REPOSITORY_URL => (
@@ -346,17 +388,19 @@ fn complete_registry(opt: &PublishOpt) -> Result<Registry> {
             format!("{}/", PublishOpt::TEST_REPOSITORY_URL),
         )
     } else {
+        let mut sections = pypirc.sections();
+        sections.sort();
         bail!(
-            "Failed to get registry {} in .pypirc. \
-                Note: Your index didn't start with http:// or https://, \
-                which is required for non-pypirc indices.",
-            opt.repository
+            "Failed to get registry {} in .pypirc, the sections with a `repository` are: {}. \
+                Note: Use `--repository-url` for indices that aren't configured in .pypirc.",
+            opt.repository,
+            sections
+                .iter()
+                .filter(|section| pypirc.get(section, "repository").is_some())
+                .join(", ")
         );
     };
-    let (username, password) = resolve_pypi_cred(opt, &pypirc, registry_name, &registry_url)?;
-    let registry = Registry::new(username, password, registry_url);
-
-    Ok(registry)
+    Ok(resolved)
 }
 
 /// Port of pip's `canonicalize_name`
@@ -556,8 +600,56 @@ pub fn upload(registry: &Registry, wheel_path: &Path) -> Result<(), UploadError>
     }
 }
 
+/// Errors if the platform tag of a wheel is one PyPI doesn't accept, e.g. `linux_x86_64`
+///
+/// Source distributions and other files are always accepted.
+pub fn validate_wheel_filename_for_pypi(filename: &str) -> Result<()> {
+    let Some(stem) = filename.strip_suffix(".whl") else {
+        return Ok(());
+    };
+    let platform_tags = stem.rsplit('-').next().unwrap_or_default();
+    for tag in platform_tags.split('.') {
+        // Raspberry Pi wheels are the only plain linux wheels PyPI accepts
+        let accepted = matches!(tag, "any" | "win32" | "linux_armv6l" | "linux_armv7l")
+            || [
+                "win_",
+                "manylinux",
+                "musllinux",
+                "macosx_",
+                "ios_",
+                "android_",
+            ]
+            .iter()
+            .any(|prefix| tag.starts_with(prefix));
+        if accepted {
+            continue;
+        }
+        if tag.starts_with("linux_") {
+            bail!(
+                "{filename} has the platform tag `{tag}`, which PyPI rejects. \
+                Build manylinux or musllinux wheels instead, e.g. with `--compatibility manylinux2014`, \
+                or use `--force` to upload it anyway"
+            );
+        }
+        bail!(
+            "{filename} has the platform tag `{tag}`, which PyPI doesn't support. \
+            Use `--force` to upload it anyway"
+        );
+    }
+    Ok(())
+}
+
 /// Handles authentication/keyring integration and retrying of the publish subcommand
 pub fn upload_ui(items: &[PathBuf], publish: &PublishOpt) -> Result<()> {
+    if !publish.force {
+        // Check the files before contacting the server, including for the credentials
+        if publish.targets_pypi()? {
+            for item in items {
+                let filename = item.file_name().unwrap_or(item.as_os_str());
+                validate_wheel_filename_for_pypi(&filename.to_string_lossy())?;
+            }
+        }
+    }
     let registry = complete_registry(publish)?;
 
     eprintln!("🚀 Uploading {} packages", items.len());
@@ -640,3 +732,136 @@ pub fn upload_ui(items: &[PathBuf], publish: &PublishOpt) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+    use indoc::indoc;
+
+    fn sample_pypirc() -> Ini {
+        let mut config = Ini::new();
+        config
+            .read(
+                indoc! {"
+                    [distutils]
+                    index-servers =
+                        pypi
+                        company
+
+                    [pypi]
+                    username = __token__
+                    password = pypi-token
+
+                    [company]
+                    repository = https://pypi.example.com/legacy/
+                    username = alice
+                "}
+                .to_string(),
+            )
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn test_resolve_registry_url() {
+        let pypirc = sample_pypirc();
+
+        let opt = PublishOpt::try_parse_from(["upload", "--repository", "company"]).unwrap();
+        let (name, url) = resolve_registry_url(&opt, &pypirc).unwrap();
+        assert_eq!(name, Some("company"));
+        assert_eq!(url, "https://pypi.example.com/legacy/");
+
+        let opt = PublishOpt::try_parse_from(["upload", "--repository", "pypi"]).unwrap();
+        let (name, url) = resolve_registry_url(&opt, &pypirc).unwrap();
+        assert_eq!(name, Some("pypi"));
+        assert_eq!(url, "https://upload.pypi.org/legacy/");
+
+        let opt = PublishOpt::try_parse_from(["upload", "--repository", "missing"]).unwrap();
+        let err = resolve_registry_url(&opt, &pypirc).unwrap_err().to_string();
+        assert!(err.contains("Failed to get registry missing"), "{err}");
+        assert!(err.contains("are: company."), "{err}");
+    }
+
+    #[test]
+    fn test_targets_pypi() {
+        for (args, expected) in [
+            (&["upload"][..], true),
+            (&["upload", "--repository", "testpypi"], true),
+            (
+                &[
+                    "upload",
+                    "--repository-url",
+                    "https://test.pypi.org/legacy/",
+                ],
+                true,
+            ),
+            (
+                &[
+                    "upload",
+                    "--repository-url",
+                    "https://pypi.example.com/legacy/",
+                ],
+                false,
+            ),
+        ] {
+            let opt = PublishOpt::try_parse_from(args).unwrap();
+            assert_eq!(opt.targets_pypi().unwrap(), expected, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_validate_wheel_filename_for_pypi() {
+        for accepted in [
+            "foo-1.0.0.tar.gz",
+            "foo-1.0.0-py3-none-any.whl",
+            "foo-1.0.0-cp39-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+            "foo-1.0.0-cp312-cp312-musllinux_1_2_aarch64.whl",
+            "foo-1.0.0-1-cp312-cp312-win_amd64.whl",
+            "foo-1.0.0-cp312-cp312-macosx_10_12_x86_64.macosx_11_0_arm64.macosx_10_12_universal2.whl",
+            "foo-1.0.0-cp312-cp312-linux_armv7l.whl",
+        ] {
+            validate_wheel_filename_for_pypi(accepted).unwrap();
+        }
+
+        let err = validate_wheel_filename_for_pypi("foo-1.0.0-cp312-cp312-linux_x86_64.whl")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("platform tag `linux_x86_64`, which PyPI rejects"));
+        assert!(err.contains("--force"));
+        let err = validate_wheel_filename_for_pypi(
+            "foo-1.0.0-cp312-cp312-manylinux_2_17_x86_64.linux_x86_64.whl",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("`linux_x86_64`"));
+        let err =
+            validate_wheel_filename_for_pypi("foo-1.0.0-py3-none-freebsd_14_0_release_amd64.whl")
+                .unwrap_err();
+        assert!(err.to_string().contains("which PyPI doesn't support"));
+    }
+
+    #[test]
+    fn test_merge_pypirc_cred() {
+        let pypirc = sample_pypirc();
+
+        let opt = PublishOpt::try_parse_from(["upload"]).unwrap();
+        assert_eq!(
+            merge_pypirc_cred(&opt, &pypirc, Some("pypi")),
+            (
+                Some("__token__".to_string()),
+                Some("pypi-token".to_string())
+            )
+        );
+        // The password of `company` is left to the keyring
+        assert_eq!(
+            merge_pypirc_cred(&opt, &pypirc, Some("company")),
+            (Some("alice".to_string()), None)
+        );
+        // Arguments override .pypirc
+        let opt = PublishOpt::try_parse_from(["upload", "-u", "bob", "-p", "secret"]).unwrap();
+        assert_eq!(
+            merge_pypirc_cred(&opt, &pypirc, Some("pypi")),
+            (Some("bob".to_string()), Some("secret".to_string()))
+        );
+    }
+}
@@ -0,0 +1,8 @@
+use std::env;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=BUILD_SCRIPT_GREETING");
+    // Set with `maturin build --env BUILD_SCRIPT_GREETING=...`
+    let greeting = env::var("BUILD_SCRIPT_GREETING").expect("BUILD_SCRIPT_GREETING is not set");
+    println!("cargo:rustc-env=GREETING={greeting}");
+}
@@ -0,0 +1,12 @@
+use pyo3::prelude::*;
+
+#[pyfunction]
+fn name() -> &'static str {
+    "plugin-a"
+}
+
+#[pymodule]
+fn plugin_a(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(name, m)?)?;
+    Ok(())
+}
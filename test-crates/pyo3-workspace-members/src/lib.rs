@@ -0,0 +1,12 @@
+use pyo3::prelude::*;
+
+#[pyfunction]
+fn plugins() -> Vec<&'static str> {
+    vec!["plugin_a"]
+}
+
+#[pymodule]
+fn pyo3_workspace_members(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(plugins, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,8 @@
+#!/usr/bin/env python3
+
+import uniffi_multiple_udl
+
+assert uniffi_multiple_udl.add(1, 2) == 3
+assert uniffi_multiple_udl.greet("maturin") == "Hello, maturin!"
+
+print("SUCCESS")
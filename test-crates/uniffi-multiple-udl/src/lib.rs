@@ -0,0 +1,8 @@
+// Re-export the `strings` component so that its scaffolding is linked into the cdylib
+pub use strings;
+
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+uniffi::include_scaffolding!("math");
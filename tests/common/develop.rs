@@ -1,7 +1,8 @@
 use crate::common::{
     check_installed, create_conda_env, create_virtualenv, maybe_mock_cargo, TestInstallBackend,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
+use fs_err as fs;
 use maturin::{develop, CargoOptions, DevelopOptions};
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -61,9 +62,12 @@ pub fn test_develop(
         bindings,
         release: false,
         strip: false,
+        force_strip: false,
         extras: Vec::new(),
         skip_install: false,
+        no_editable: false,
         pip_path: None,
+        pip_args: Vec::new(),
         cargo_options: CargoOptions {
             manifest_path: Some(manifest_file),
             quiet: true,
@@ -77,3 +81,121 @@ pub fn test_develop(
     check_installed(package, &python)?;
     Ok(())
 }
+
+/// Installs the package with "maturin develop" once without and once with `--strip` and checks
+/// that the stripped extension module is smaller
+pub fn test_develop_strip(
+    package: impl AsRef<Path>,
+    module_name: &str,
+    unique_name: &str,
+) -> Result<()> {
+    maybe_mock_cargo();
+
+    let package = package.as_ref();
+    let (venv_dir, python) = create_virtualenv(unique_name, None)?;
+
+    let mut sizes = Vec::new();
+    for strip in [false, true] {
+        let develop_options = DevelopOptions {
+            bindings: None,
+            release: true,
+            strip,
+            force_strip: false,
+            extras: Vec::new(),
+            skip_install: false,
+            no_editable: false,
+            pip_path: None,
+            pip_args: Vec::new(),
+            cargo_options: CargoOptions {
+                manifest_path: Some(package.join("Cargo.toml")),
+                quiet: true,
+                target_dir: Some(PathBuf::from(format!("test-crates/targets/{unique_name}"))),
+                ..Default::default()
+            },
+            uv: false,
+        };
+        develop(develop_options, &venv_dir)?;
+        check_installed(package, &python)?;
+
+        let pattern = venv_dir
+            .join("**")
+            .join("site-packages")
+            .join(module_name)
+            .join(format!("{module_name}*"));
+        let extension = glob::glob(pattern.to_str().unwrap())?
+            .filter_map(Result::ok)
+            .find(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == "so" || ext == "pyd")
+            })
+            .context("Couldn't find the installed extension module")?;
+        sizes.push(fs::metadata(extension)?.len());
+    }
+    assert!(
+        sizes[1] < sizes[0],
+        "Expected the stripped extension module to be smaller: {} (--strip) vs. {}",
+        sizes[1],
+        sizes[0]
+    );
+    Ok(())
+}
+
+/// Installs a mixed project with "maturin develop --no-editable" and checks that the python
+/// sources were copied into site-packages instead of being linked to the project
+pub fn test_develop_no_editable(
+    package: impl AsRef<Path>,
+    module_name: &str,
+    unique_name: &str,
+) -> Result<()> {
+    maybe_mock_cargo();
+
+    let package = package.as_ref();
+    let (venv_dir, python) = create_virtualenv(unique_name, None)?;
+
+    let develop_options = DevelopOptions {
+        bindings: None,
+        release: false,
+        strip: false,
+        force_strip: false,
+        extras: Vec::new(),
+        skip_install: false,
+        no_editable: true,
+        pip_path: None,
+        pip_args: Vec::new(),
+        cargo_options: CargoOptions {
+            manifest_path: Some(package.join("Cargo.toml")),
+            quiet: true,
+            target_dir: Some(PathBuf::from(format!("test-crates/targets/{unique_name}"))),
+            ..Default::default()
+        },
+        uv: false,
+    };
+    develop(develop_options, &venv_dir)?;
+    check_installed(package, &python)?;
+
+    let pattern = venv_dir.join("**").join("site-packages");
+    let site_packages = glob::glob(pattern.to_str().unwrap())?
+        .filter_map(Result::ok)
+        .next()
+        .context("Couldn't find site-packages")?;
+    let init_py = site_packages.join(module_name).join("__init__.py");
+    let file_type = fs::symlink_metadata(&init_py)?.file_type();
+    assert!(
+        file_type.is_file() && !file_type.is_symlink(),
+        "Expected {} to be a copied file",
+        init_py.display()
+    );
+    // Editable installs point python at the project with a `.pth` file
+    for entry in fs::read_dir(&site_packages)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "pth") {
+            let contents = fs::read_to_string(&path)?;
+            assert!(
+                !contents.contains(&*package.canonicalize()?.to_string_lossy()),
+                "{} links to the project: {contents}",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
@@ -1,10 +1,11 @@
-use anyhow::{Context, Result};
+use crate::common::test_python_path;
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use expect_test::Expect;
 use flate2::read::GzDecoder;
 use fs_err::File;
 use maturin::pyproject_toml::{SdistGenerator, ToolMaturin};
-use maturin::{BuildOptions, CargoOptions, PlatformTag};
+use maturin::{AuditWheelMode, BridgeModel, BuildOptions, CargoOptions, PlatformTag};
 use pretty_assertions::assert_eq;
 use std::collections::BTreeSet;
 use std::io::Read;
@@ -93,6 +94,172 @@ pub fn test_musl() -> Result<bool> {
     Ok(true)
 }
 
+/// `--compatibility linux` must produce a plain `linux_<arch>` tag without any manylinux
+/// promotion, skipping auditwheel
+pub fn test_linux_compatibility_tag() -> Result<()> {
+    // The first arg gets ignored
+    let options: BuildOptions = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/hello-world/Cargo.toml",
+        "--compatibility",
+        "linux",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/test_linux_compatibility_tag",
+        "--out",
+        "test-crates/wheels/test_linux_compatibility_tag",
+    ])?;
+
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    assert_eq!(build_context.platform_tag, [PlatformTag::Linux]);
+    let arch = build_context.target.get_platform_arch()?;
+    let wheels = build_context.build_wheels()?;
+    assert_eq!(wheels.len(), 1);
+
+    let (wheel_path, _) = &wheels[0];
+    let filename = wheel_path.file_name().unwrap().to_str().unwrap();
+    assert!(
+        filename.ends_with(&format!("-py3-none-linux_{arch}.whl")),
+        "unexpected wheel filename {filename}"
+    );
+
+    let mut wheel = ZipArchive::new(File::open(wheel_path)?)?;
+    let mut wheel_file = String::new();
+    wheel
+        .by_name("hello_world-0.1.0.dist-info/WHEEL")?
+        .read_to_string(&mut wheel_file)?;
+    assert!(wheel_file.contains(&format!("Tag: py3-none-linux_{arch}\n")));
+    assert!(!wheel_file.contains("manylinux"));
+    Ok(())
+}
+
+/// `--emit-symbols` saves the debug info of the stripped binary next to the wheel,
+/// matching the binary in the wheel by build-id
+pub fn test_emit_symbols() -> Result<()> {
+    use goblin::elf::Elf;
+
+    let symbols_dir = PathBuf::from("test-crates/wheels/test_emit_symbols_symbols");
+    // The first arg gets ignored
+    let options: BuildOptions = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/hello-world/Cargo.toml",
+        "--compatibility",
+        "linux",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/test_emit_symbols",
+        "--out",
+        "test-crates/wheels/test_emit_symbols",
+        "--emit-symbols",
+        symbols_dir.to_str().unwrap(),
+        "--force-strip",
+    ])?;
+
+    // `--strip` is ignored for the debug build without `--force-strip`
+    let result = BuildOptions {
+        force_strip: false,
+        ..options.clone()
+    }
+    .into_build_context()
+    .release(false)
+    .strip(true)
+    .editable(false)
+    .build();
+    assert!(matches!(result, Err(err) if err.to_string().contains("--force-strip")));
+
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(true)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    assert_eq!(wheels.len(), 1);
+    let (wheel_path, _) = &wheels[0];
+
+    let mut wheel = ZipArchive::new(File::open(wheel_path)?)?;
+    let mut binary = Vec::new();
+    wheel
+        .by_name("hello_world-0.1.0.data/scripts/hello-world")?
+        .read_to_end(&mut binary)?;
+    let debug_info = fs_err::read(
+        symbols_dir
+            .join(wheel_path.file_stem().unwrap())
+            .join("hello-world.debug"),
+    )?;
+
+    let build_id = |bytes: &[u8]| -> Result<Option<Vec<u8>>> {
+        let elf = Elf::parse(bytes)?;
+        Ok(elf
+            .section_headers
+            .iter()
+            .find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(".note.gnu.build-id"))
+            .map(|header| bytes[header.file_range().unwrap()].to_vec()))
+    };
+    assert_eq!(build_id(&binary)?, build_id(&debug_info)?);
+    // The symbols were moved from the binary into the debug info
+    assert!(Elf::parse(&binary)?.syms.is_empty());
+    assert!(!Elf::parse(&debug_info)?.syms.is_empty());
+    Ok(())
+}
+
+/// `--emit-sbom` writes the SBOM of the crate graph and `--embed-sbom` puts the same SBOM
+/// into the `.dist-info` of the wheel
+pub fn test_emit_sbom() -> Result<()> {
+    let sbom_path = PathBuf::from("test-crates/wheels/test_emit_sbom/sbom.cdx.json");
+    // The first arg gets ignored
+    let options: BuildOptions = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/pyo3-pure/Cargo.toml",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/test_emit_sbom",
+        "--out",
+        "test-crates/wheels/test_emit_sbom",
+        "--emit-sbom",
+        sbom_path.to_str().unwrap(),
+        "--embed-sbom",
+    ])?;
+
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    assert_eq!(wheels.len(), 1);
+
+    let sbom = fs_err::read_to_string(&sbom_path)?;
+    let parsed: serde_json::Value = serde_json::from_str(&sbom)?;
+    assert_eq!(parsed["metadata"]["component"]["name"], "pyo3-pure");
+    assert!(parsed["components"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|component| component["name"] == "pyo3"));
+
+    let embedded_path = format!(
+        "{}/sboms/pyo3-pure.cdx.json",
+        build_context.metadata24.get_dist_info_dir().display()
+    );
+    let mut wheel = ZipArchive::new(File::open(&wheels[0].0)?)?;
+    let mut embedded = String::new();
+    wheel
+        .by_name(&embedded_path)?
+        .read_to_string(&mut embedded)?;
+    assert_eq!(embedded, sbom);
+    Ok(())
+}
+
 /// Test that we ignore non-existent Cargo.lock file listed by `cargo package --list`,
 /// which seems to only occur with workspaces.
 /// See https://github.com/rust-lang/cargo/issues/7938#issuecomment-593280660 and
@@ -207,6 +374,468 @@ pub fn test_source_distribution(
     Ok(())
 }
 
+/// `maturin build --sdist --sdist-out` writes the sdist to its own directory
+pub fn test_source_distribution_out() -> Result<()> {
+    let out = tempfile::tempdir()?;
+    let wheel_dir = out.path().join("wheels");
+    let sdist_dir = out.path().join("sdist").join("nested");
+    let build_options = BuildOptions {
+        out: Some(wheel_dir.clone()),
+        cargo: CargoOptions {
+            manifest_path: Some(PathBuf::from("test-crates/hello-world/Cargo.toml")),
+            quiet: true,
+            target_dir: Some(PathBuf::from("test-crates/targets/sdist_out")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let build_context = build_options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .sdist_only(true)
+        .build()?;
+    let (path, _) = build_context
+        .build_source_distribution_in(&sdist_dir)?
+        .context("Failed to build source distribution")?;
+    assert!(path.parent().unwrap().ends_with("sdist/nested"));
+    assert!(path.is_file());
+    assert!(!wheel_dir.exists());
+    Ok(())
+}
+
+/// Source distributions in the `--sdist-out` directory aren't included in the next sdist
+pub fn sdist_out_excluded() -> Result<()> {
+    use fs_err as fs;
+
+    let temp_dir = tempfile::tempdir()?;
+    let project_dir = temp_dir.path().join("project");
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(
+        project_dir.join("Cargo.toml"),
+        "[package]\nname = \"sdist-out-excluded\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )?;
+    fs::write(project_dir.join("src").join("main.rs"), "fn main() {}\n")?;
+    fs::write(
+        project_dir.join("pyproject.toml"),
+        indoc::indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [tool.maturin]
+            bindings = "bin"
+        "#},
+    )?;
+
+    let sdist_dir = project_dir.join("sdist");
+    let build_options = BuildOptions {
+        out: Some(temp_dir.path().join("dist")),
+        cargo: CargoOptions {
+            manifest_path: Some(project_dir.join("Cargo.toml")),
+            quiet: true,
+            target_dir: Some(temp_dir.path().join("target")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let build_context = build_options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .sdist_only(true)
+        .build()?;
+    // Leave an older sdist in the output directory
+    fs::create_dir_all(&sdist_dir)?;
+    fs::write(sdist_dir.join("sdist_out_excluded-0.0.1.tar.gz"), "")?;
+    let (path, _) = build_context
+        .build_source_distribution_in(&sdist_dir)?
+        .context("Failed to build source distribution")?;
+    let mut archive = Archive::new(GzDecoder::new(File::open(&path)?));
+    for entry in archive.entries()? {
+        let entry_path = entry?.path()?.into_owned();
+        assert!(
+            !entry_path.to_string_lossy().ends_with(".tar.gz"),
+            "{} should be excluded",
+            entry_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// `--version-suffix` is written back into the sources of the sdist, so that wheels built
+/// from it get the version from its `PKG-INFO`
+pub fn test_sdist_version_suffix() -> Result<()> {
+    for (package, suffix, expected) in [
+        // Static version in pyproject.toml
+        ("pyo3-pure", ".dev1", "0.1.0+abc123de.dev1"),
+        // Version from Cargo.toml
+        ("pyo3-mixed", ".dev20240101", "2.1.5.dev20240101"),
+    ] {
+        let out = tempfile::tempdir()?;
+        let build_options = BuildOptions {
+            out: Some(out.path().to_path_buf()),
+            cargo: CargoOptions {
+                manifest_path: Some(Path::new("test-crates").join(package).join("Cargo.toml")),
+                quiet: true,
+                target_dir: Some(PathBuf::from("test-crates/targets/sdist_version_suffix")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut build_context = build_options
+            .into_build_context()
+            .release(false)
+            .strip(false)
+            .editable(false)
+            .sdist_only(true)
+            .build()?;
+        build_context.metadata24.append_version_suffix(suffix)?;
+        let (path, _) = build_context
+            .build_source_distribution()?
+            .context("Failed to build source distribution")?;
+        Archive::new(GzDecoder::new(fs_err::File::open(&path)?)).unpack(out.path())?;
+
+        let unpacked = out.path().join(
+            path.file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .trim_end_matches(".tar.gz"),
+        );
+        let build_options = BuildOptions {
+            cargo: CargoOptions {
+                manifest_path: Some(unpacked.join("Cargo.toml")),
+                quiet: true,
+                target_dir: Some(PathBuf::from("test-crates/targets/sdist_version_suffix")),
+                // The rewritten version must also be in the bundled Cargo.lock
+                locked: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let build_context = build_options
+            .into_build_context()
+            .release(false)
+            .strip(false)
+            .editable(false)
+            .sdist_only(true)
+            .build()?;
+        assert_eq!(build_context.metadata24.version.to_string(), expected);
+    }
+    Ok(())
+}
+
+/// A classifiers file outside of the project is stored next to pyproject.toml in the sdist,
+/// since archive entries can't contain `..`
+pub fn sdist_shared_classifiers_file() -> Result<()> {
+    use fs_err as fs;
+
+    let temp_dir = tempfile::tempdir()?;
+    let project_dir = temp_dir.path().join("project");
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(
+        temp_dir.path().join("shared.txt"),
+        "Topic :: Software Development :: Libraries\n",
+    )?;
+    fs::write(
+        project_dir.join("Cargo.toml"),
+        "[package]\nname = \"shared-classifiers\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )?;
+    fs::write(project_dir.join("src").join("main.rs"), "fn main() {}\n")?;
+    fs::write(
+        project_dir.join("pyproject.toml"),
+        indoc::indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [project]
+            name = "shared-classifiers"
+            version = "0.1.0"
+            dynamic = ["classifiers"]
+
+            [tool.maturin]
+            bindings = "bin"
+            classifiers-file = "../shared.txt"
+        "#},
+    )?;
+
+    let build_options = BuildOptions {
+        out: Some(temp_dir.path().join("dist")),
+        cargo: CargoOptions {
+            manifest_path: Some(project_dir.join("Cargo.toml")),
+            quiet: true,
+            target_dir: Some(temp_dir.path().join("target")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let build_context = build_options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .sdist_only(true)
+        .build()?;
+    let (path, _) = build_context
+        .build_source_distribution()?
+        .context("Failed to build source distribution")?;
+    let mut archive = Archive::new(GzDecoder::new(File::open(&path)?));
+    let mut files = BTreeSet::new();
+    let mut pyproject_toml = String::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        if entry_path.ends_with("pyproject.toml") {
+            entry.read_to_string(&mut pyproject_toml)?;
+        }
+        files.insert(entry_path);
+    }
+    assert!(files.contains(Path::new("shared_classifiers-0.1.0/shared.txt")));
+    assert!(pyproject_toml.contains(r#"classifiers-file = "shared.txt""#));
+    Ok(())
+}
+
+/// Placeholders in `--out` are replaced with the tags of each wheel
+pub fn out_dir_placeholders() -> Result<()> {
+    let out = tempfile::tempdir()?;
+    let build_options = BuildOptions {
+        out: Some(
+            out.path()
+                .join("{version}/{python_tag}-{abi_tag}/{platform_tag}"),
+        ),
+        cargo: CargoOptions {
+            manifest_path: Some(PathBuf::from("test-crates/pyo3-pure/Cargo.toml")),
+            quiet: true,
+            target_dir: Some(PathBuf::from("test-crates/targets/out_dir_placeholders")),
+            ..Default::default()
+        },
+        platform_tag: vec![PlatformTag::Linux],
+        ..Default::default()
+    };
+    let build_context = build_options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    let (wheel, _) = &wheels[0];
+    let wheel_name = wheel.file_stem().unwrap().to_str().unwrap();
+    let platform = wheel_name.rsplit('-').next().unwrap();
+    let expected_dir = out
+        .path()
+        .join(build_context.metadata24.get_version_escaped())
+        .join("cp37-abi3")
+        .join(platform);
+    assert_eq!(wheel.parent().unwrap(), expected_dir);
+    assert!(wheel.is_file());
+
+    let build_options = BuildOptions {
+        out: Some(out.path().join("{os}")),
+        cargo: CargoOptions {
+            manifest_path: Some(PathBuf::from("test-crates/pyo3-pure/Cargo.toml")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let Err(err) = build_options.into_build_context().build() else {
+        bail!("Expected an error for the unknown placeholder");
+    };
+    let err = err.to_string();
+    assert!(err.starts_with("Unknown placeholder `{os}`"), "{err}");
+    Ok(())
+}
+
+/// `MATURIN_INTERPRETERS` selects the interpreters when `-i` isn't given
+pub fn interpreters_from_env() -> Result<()> {
+    use std::process::{Command, Output};
+
+    let python = test_python_path().unwrap_or_else(|| "python3".to_string());
+    let out = tempfile::tempdir()?;
+    let build = |interpreter_args: &[&str], env_interpreters: &str| -> Result<Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_maturin"))
+            .args(["build", "-m", "test-crates/pyo3-mixed/Cargo.toml"])
+            .args(["--target-dir", "test-crates/targets/interpreters_from_env"])
+            .args(["--compatibility", "linux", "--quiet", "--out"])
+            .arg(out.path())
+            .args(interpreter_args)
+            .env("MATURIN_INTERPRETERS", env_interpreters)
+            .output()?)
+    };
+
+    // Without the environment variable, the default interpreter would be used
+    let output = build(&[], "python-that-does-not-exist")?;
+    assert!(!output.status.success());
+
+    let output = build(&[], &format!(" {python}, "))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert_eq!(fs_err::read_dir(out.path())?.count(), 1);
+
+    // `-i` takes precedence over the environment variable
+    let output = build(&["-i", &python], "python-that-does-not-exist")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    Ok(())
+}
+
+pub fn test_source_distribution_vendor_crates() -> Result<()> {
+    let manifest_path = Path::new("test-crates/pyo3-pure/Cargo.toml");
+    let sdist_directory = Path::new("test-crates")
+        .join("wheels")
+        .join("sdist-vendor-crates");
+
+    let build_options = BuildOptions {
+        out: Some(sdist_directory),
+        cargo: CargoOptions {
+            manifest_path: Some(manifest_path.to_path_buf()),
+            quiet: true,
+            target_dir: Some(PathBuf::from("test-crates/targets/sdist-vendor-crates")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let build_context = build_options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .sdist_only(true)
+        .vendor_crates(true)
+        .build()?;
+    let (path, _) = build_context
+        .build_source_distribution()?
+        .context("Failed to build source distribution")?;
+
+    let mut archive = Archive::new(GzDecoder::new(fs_err::File::open(path)?));
+    let mut files = BTreeSet::new();
+    let mut cargo_config = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        if path == Path::new("pyo3_pure-0.1.0+abc123de/.cargo/config.toml") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            cargo_config = Some(contents);
+        }
+        files.insert(path);
+    }
+    let cargo_config = cargo_config.context(".cargo/config.toml not found in sdist")?;
+    assert!(cargo_config.contains("replace-with = \"vendored-sources\""));
+    assert!(cargo_config.contains("directory = \"vendor\""));
+    assert!(files.contains(Path::new("pyo3_pure-0.1.0+abc123de/vendor/pyo3/Cargo.toml")));
+    assert!(files.contains(Path::new(
+        "pyo3_pure-0.1.0+abc123de/vendor/pyo3/.cargo-checksum.json"
+    )));
+    Ok(())
+}
+
+/// Builds a `linux` tagged wheel and repairs it to a manylinux wheel in a separate step
+#[cfg(target_os = "linux")]
+pub fn test_repair_wheel() -> Result<()> {
+    let wheel_directory = Path::new("test-crates").join("wheels").join("repair");
+    let build_options = BuildOptions {
+        out: Some(wheel_directory.clone()),
+        cargo: CargoOptions {
+            manifest_path: Some(PathBuf::from("test-crates/pyo3-pure/Cargo.toml")),
+            quiet: true,
+            target_dir: Some(PathBuf::from("test-crates/targets/repair")),
+            ..Default::default()
+        },
+        platform_tag: vec![PlatformTag::Linux],
+        ..Default::default()
+    };
+    let build_context = build_options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    let (wheel_path, _) = &wheels[0];
+
+    // The highest tag depends on the glibc symbols the host toolchain links
+    let repaired = maturin::repair_wheel(wheel_path, None, &[], &wheel_directory.join("repaired"))?;
+    let file_name = repaired.file_name().unwrap().to_str().unwrap();
+    assert!(file_name.contains("-cp37-abi3-manylinux_"), "{file_name}");
+
+    let mut wheel = ZipArchive::new(File::open(&repaired)?)?;
+    let mut wheel_file = String::new();
+    wheel
+        .by_name("pyo3_pure-0.1.0+abc123de.dist-info/WHEEL")?
+        .read_to_string(&mut wheel_file)?;
+    assert!(!wheel_file.contains("-linux_"), "{wheel_file}");
+    assert!(
+        wheel_file.contains("Tag: cp37-abi3-manylinux_"),
+        "{wheel_file}"
+    );
+    Ok(())
+}
+
+/// Auditing a wheel that links a library outside the manylinux policy fails in check mode,
+/// both when building and on an already built wheel, without writing or modifying any wheel
+#[cfg(target_os = "linux")]
+pub fn test_auditwheel_check() -> Result<()> {
+    let wheel_directory = Path::new("test-crates")
+        .join("wheels")
+        .join("auditwheel_check");
+    let _ = fs_err::remove_dir_all(&wheel_directory);
+    let build_options = |platform_tag, auditwheel| BuildOptions {
+        out: Some(wheel_directory.clone()),
+        cargo: CargoOptions {
+            manifest_path: Some(PathBuf::from(
+                "test-crates/lib_with_disallowed_lib/Cargo.toml",
+            )),
+            quiet: true,
+            target_dir: Some(PathBuf::from("test-crates/targets/auditwheel_check")),
+            ..Default::default()
+        },
+        platform_tag: vec![platform_tag],
+        auditwheel: Some(auditwheel),
+        ..Default::default()
+    };
+
+    let build_context = build_options(PlatformTag::manylinux2014(), AuditWheelMode::Check)
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let err = build_context.build_wheels().unwrap_err();
+    assert!(format!("{err:#}").contains("libz"), "{err:#}");
+    let wheels = fs_err::read_dir(&wheel_directory)
+        .map(|entries| entries.count())
+        .unwrap_or_default();
+    assert_eq!(wheels, 0, "check mode must not leave a wheel behind");
+
+    let build_context = build_options(PlatformTag::Linux, AuditWheelMode::Repair)
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    let (wheel_path, _) = &wheels[0];
+    let before = fs_err::read(wheel_path)?;
+    let err =
+        maturin::audit_wheel(wheel_path, Some(PlatformTag::manylinux2014()), &[]).unwrap_err();
+    assert!(format!("{err:#}").contains("libz"), "{err:#}");
+    let err = maturin::audit_wheel(wheel_path, None, &[]).unwrap_err();
+    assert!(format!("{err:#}").contains("isn't compliant"), "{err:#}");
+    assert_eq!(
+        fs_err::read(wheel_path)?,
+        before,
+        "check mode modified the wheel"
+    );
+    assert_eq!(fs_err::read_dir(&wheel_directory)?.count(), 1);
+    Ok(())
+}
+
 fn build_wheel_files(package: impl AsRef<Path>, unique_name: &str) -> Result<ZipArchive<File>> {
     let manifest_path = package.as_ref().join("Cargo.toml");
     let wheel_directory = Path::new("test-crates").join("wheels").join(unique_name);
@@ -276,6 +905,380 @@ pub fn check_wheel_files(
     Ok(())
 }
 
+/// `tool.maturin.workspace-members` adds the other packages as subpackages of the main module
+pub fn workspace_members_wheel() -> Result<()> {
+    let wheel = build_wheel_files(
+        "test-crates/pyo3-workspace-members",
+        "workspace_members_wheel",
+    )?;
+    let files: BTreeSet<_> = wheel.file_names().collect();
+    for expected in [
+        "pyo3_workspace_members/__init__.py",
+        "pyo3_workspace_members/pyo3_workspace_members.abi3.so",
+        "pyo3_workspace_members/plugin_a/__init__.py",
+        "pyo3_workspace_members/plugin_a/plugin_a.abi3.so",
+    ] {
+        assert!(files.contains(expected), "{expected} missing in {files:?}");
+    }
+    Ok(())
+}
+
+/// `tool.maturin.cffi.header` installs the cffi header into the wheel's headers data dir
+pub fn cffi_header_in_wheel() -> Result<()> {
+    let options = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/cffi-pure/Cargo.toml",
+        "--compatibility",
+        "linux",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/cffi_header_in_wheel",
+        "--out",
+        "test-crates/wheels/cffi_header_in_wheel",
+    ])?;
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    let mut wheel = ZipArchive::new(File::open(&wheels[0].0)?)?;
+    let mut header = String::new();
+    wheel
+        .by_name("cffi_pure-0.1.0.data/headers/cffi_pure.h")?
+        .read_to_string(&mut header)?;
+    assert!(header.contains("Point"), "{header}");
+    Ok(())
+}
+
+/// Python shebangs of the scripts in the data directory are replaced by the `#!python`
+/// placeholder that installers rewrite
+pub fn data_scripts_shebang() -> Result<()> {
+    let mut wheel = build_wheel_files("test-crates/with-data", "data_scripts_shebang")?;
+    let mut script = String::new();
+    wheel
+        .by_name("with_data-0.1.0.data/scripts/with-data-script")?
+        .read_to_string(&mut script)?;
+    assert_eq!(script, "#!python\nprint(\"Hello from with-data-script\")\n");
+    Ok(())
+}
+
+/// The PEP 517 backend builds with `--release` by default, but `tool.maturin.profile` wins
+pub fn pep517_pyproject_profile() -> Result<()> {
+    use fs_err as fs;
+
+    let project = tempfile::tempdir()?;
+    fs::write(
+        project.path().join("Cargo.toml"),
+        indoc::indoc! {r#"
+            [package]
+            name = "pep517-profile"
+            version = "0.1.0"
+            edition = "2021"
+
+            [profile.dist]
+            inherits = "release"
+        "#},
+    )?;
+    fs::write(
+        project.path().join("pyproject.toml"),
+        indoc::indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [tool.maturin]
+            bindings = "bin"
+            profile = "dist"
+        "#},
+    )?;
+    fs::create_dir(project.path().join("src"))?;
+    fs::write(project.path().join("src").join("main.rs"), "fn main() {}\n")?;
+
+    let manifest_path = project.path().join("Cargo.toml");
+    let target_dir = project.path().join("target");
+    let options = BuildOptions::try_parse_from([
+        "build".as_ref(),
+        "--manifest-path".as_ref(),
+        manifest_path.as_os_str(),
+        "--compatibility".as_ref(),
+        "off".as_ref(),
+        "--quiet".as_ref(),
+        "--target-dir".as_ref(),
+        target_dir.as_os_str(),
+    ])?;
+    // Same as `maturin pep517 build-wheel`
+    let build_context = options
+        .into_build_context()
+        .release(true)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    build_context.build_wheels()?;
+    assert!(target_dir.join("dist").is_dir());
+    assert!(!target_dir.join("release").exists());
+    Ok(())
+}
+
+/// `tool.maturin.include-readme-in-wheel` adds the readme as a file next to the long
+/// description in METADATA
+pub fn include_readme_in_wheel() -> Result<()> {
+    use fs_err as fs;
+
+    let project = tempfile::tempdir()?;
+    fs::write(
+        project.path().join("Cargo.toml"),
+        indoc::indoc! {r#"
+            [package]
+            name = "readme-in-wheel"
+            version = "0.1.0"
+            edition = "2021"
+        "#},
+    )?;
+    fs::write(
+        project.path().join("pyproject.toml"),
+        indoc::indoc! {r#"
+            [build-system]
+            requires = ["maturin>=1.0,<2.0"]
+            build-backend = "maturin"
+
+            [project]
+            name = "readme-in-wheel"
+            readme = "README.md"
+
+            [tool.maturin]
+            bindings = "bin"
+            include-readme-in-wheel = "readme_in_wheel/README.md"
+        "#},
+    )?;
+    let readme = "# readme-in-wheel\n\nShipped inside the wheel.\n";
+    fs::write(project.path().join("README.md"), readme)?;
+    fs::create_dir(project.path().join("src"))?;
+    fs::write(project.path().join("src").join("main.rs"), "fn main() {}\n")?;
+
+    let manifest_path = project.path().join("Cargo.toml");
+    let target_dir = project.path().join("target");
+    let options = BuildOptions::try_parse_from([
+        "build".as_ref(),
+        "--manifest-path".as_ref(),
+        manifest_path.as_os_str(),
+        "--compatibility".as_ref(),
+        "off".as_ref(),
+        "--quiet".as_ref(),
+        "--target-dir".as_ref(),
+        target_dir.as_os_str(),
+    ])?;
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    let wheels = build_context.build_wheels()?;
+    let mut wheel = ZipArchive::new(File::open(&wheels[0].0)?)?;
+
+    let mut contents = String::new();
+    wheel
+        .by_name("readme_in_wheel/README.md")?
+        .read_to_string(&mut contents)?;
+    assert_eq!(contents, readme);
+    let mut metadata = String::new();
+    wheel
+        .by_name("readme_in_wheel-0.1.0.dist-info/METADATA")?
+        .read_to_string(&mut metadata)?;
+    assert!(metadata.contains("Description-Content-Type: text/markdown"));
+    assert!(metadata.contains(readme));
+    Ok(())
+}
+
+/// `compile_with_diagnostics` returns rustc's warnings and errors instead of printing them
+pub fn compile_with_diagnostics() -> Result<()> {
+    use cargo_metadata::diagnostic::DiagnosticLevel;
+    use fs_err as fs;
+
+    let project = tempfile::tempdir()?;
+    fs::write(
+        project.path().join("Cargo.toml"),
+        indoc::indoc! {r#"
+            [package]
+            name = "diagnostics"
+            version = "0.1.0"
+            edition = "2021"
+        "#},
+    )?;
+    fs::create_dir(project.path().join("src"))?;
+    let main_rs = project.path().join("src").join("main.rs");
+    fs::write(&main_rs, "fn main() {\n    let unused = 1;\n}\n")?;
+
+    let manifest_path = project.path().join("Cargo.toml");
+    let options = BuildOptions::try_parse_from([
+        "build".as_ref(),
+        "--manifest-path".as_ref(),
+        manifest_path.as_os_str(),
+        "--bindings".as_ref(),
+        "bin".as_ref(),
+        "--quiet".as_ref(),
+    ])?;
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+
+    let output =
+        maturin::compile_with_diagnostics(&build_context, None, &build_context.compile_targets);
+    assert_eq!(output.artifacts?.len(), 1);
+    let warning = output
+        .diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.level == DiagnosticLevel::Warning)
+        .context("Expected a warning for the unused variable")?;
+    assert!(warning.message.contains("unused"));
+    assert_eq!(warning.spans[0].file_name, "src/main.rs");
+    assert_eq!(warning.spans[0].line_start, 2);
+
+    fs::write(&main_rs, "fn main() {\n    let x: u32 = \"one\";\n}\n")?;
+    let output =
+        maturin::compile_with_diagnostics(&build_context, None, &build_context.compile_targets);
+    assert!(output.artifacts.is_err());
+    assert!(output
+        .diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.level == DiagnosticLevel::Error
+            && diagnostic.code.as_ref().map(|code| code.code.as_str()) == Some("E0308")));
+    Ok(())
+}
+
+/// `--rustc-wrapper` is passed to cargo as `RUSTC_WRAPPER`
+#[cfg(unix)]
+pub fn rustc_wrapper() -> Result<()> {
+    use fs_err as fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let project = tempfile::tempdir()?;
+    fs::write(
+        project.path().join("Cargo.toml"),
+        indoc::indoc! {r#"
+            [package]
+            name = "rustc-wrapper"
+            version = "0.1.0"
+            edition = "2021"
+        "#},
+    )?;
+    fs::create_dir(project.path().join("src"))?;
+    fs::write(project.path().join("src").join("main.rs"), "fn main() {}\n")?;
+    // Records that it was called and then runs the actual rustc
+    let wrapper = project.path().join("wrapper.sh");
+    let marker = project.path().join("wrapper-called");
+    fs::write(
+        &wrapper,
+        format!("#!/bin/sh\ntouch '{}'\nexec \"$@\"\n", marker.display()),
+    )?;
+    fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755))?;
+
+    let manifest_path = project.path().join("Cargo.toml");
+    let target_dir = project.path().join("target");
+    let options = BuildOptions::try_parse_from([
+        "build".as_ref(),
+        "--manifest-path".as_ref(),
+        manifest_path.as_os_str(),
+        "--bindings".as_ref(),
+        "bin".as_ref(),
+        "--compatibility".as_ref(),
+        "off".as_ref(),
+        "--quiet".as_ref(),
+        "--target-dir".as_ref(),
+        target_dir.as_os_str(),
+        "--rustc-wrapper".as_ref(),
+        wrapper.as_os_str(),
+    ])?;
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    build_context.build_wheels()?;
+    assert!(marker.is_file(), "The rustc wrapper wasn't called");
+    Ok(())
+}
+
+/// Environment variables given with `--env` are visible to build scripts
+pub fn build_script_env() -> Result<()> {
+    let options = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/build-script-env/Cargo.toml",
+        "--compatibility",
+        "linux",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/build_script_env",
+        "--out",
+        "test-crates/wheels/build_script_env",
+        "--env",
+        "BUILD_SCRIPT_GREETING=Hello from maturin",
+    ])?;
+    let build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(false)
+        .editable(false)
+        .build()?;
+    build_context.build_wheels()?;
+
+    let binary = Path::new("test-crates/targets/build_script_env/debug/build-script-env");
+    let output = std::process::Command::new(binary).output()?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?.trim(),
+        "Hello from maturin"
+    );
+    Ok(())
+}
+
+/// The abi3 minimum version is a floor for the wheel tag, it doesn't depend on the version of
+/// the interpreter that is used for the build
+pub fn abi3_tag_uses_minimum_version() -> Result<()> {
+    let options = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/pyo3-pure/Cargo.toml",
+        "--compatibility",
+        "linux",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/abi3_tag_uses_minimum_version",
+        "--out",
+        "test-crates/wheels/abi3_tag_uses_minimum_version",
+    ])?;
+    let mut build_context = options
+        .into_build_context()
+        .release(false)
+        .strip(cfg!(feature = "faster-tests"))
+        .editable(false)
+        .build()?;
+    assert!(!build_context.interpreter.is_empty());
+    // Pretend the crate uses `abi3-py312`, which can be higher than the host interpreter
+    let bindings = build_context.bridge().bindings().unwrap().clone();
+    for compile_target in &mut build_context.compile_targets {
+        compile_target.bridge_model = BridgeModel::BindingsAbi3 {
+            bindings: bindings.clone(),
+            major: 3,
+            minor: 12,
+        };
+    }
+    let wheels = build_context.build_wheels()?;
+    assert_eq!(wheels.len(), 1);
+    let (wheel_path, tag) = &wheels[0];
+    assert_eq!(tag, "cp312");
+    let filename = wheel_path.file_name().unwrap().to_str().unwrap();
+    assert!(filename.contains("-cp312-abi3-linux_"), "{filename}");
+    Ok(())
+}
+
 pub fn abi3_python_interpreter_args() -> Result<()> {
     // Case 1: maturin build without `-i`, should work
     let options = BuildOptions::try_parse_from(vec![
@@ -26,6 +26,15 @@ fn develop_pyo3_pure() {
     ));
 }
 
+#[test]
+fn develop_pyo3_pure_strip() {
+    handle_result(develop::test_develop_strip(
+        "test-crates/pyo3-pure",
+        "pyo3_pure",
+        "develop-pyo3-pure-strip",
+    ));
+}
+
 #[test]
 #[ignore]
 fn develop_pyo3_pure_conda() {
@@ -52,6 +61,15 @@ fn develop_pyo3_mixed() {
     ));
 }
 
+#[test]
+fn develop_pyo3_mixed_no_editable() {
+    handle_result(develop::test_develop_no_editable(
+        "test-crates/pyo3-mixed",
+        "pyo3_mixed",
+        "develop-pyo3-mixed-no-editable",
+    ));
+}
+
 #[test]
 fn develop_pyo3_mixed_include_exclude() {
     handle_result(develop::test_develop(
@@ -427,6 +445,17 @@ fn integration_uniffi_pure_proc_macro() {
     ));
 }
 
+#[test]
+fn integration_uniffi_multiple_udl() {
+    handle_result(integration::test_integration(
+        "test-crates/uniffi-multiple-udl",
+        None,
+        "integration-uniffi-multiple-udl",
+        false,
+        None,
+    ));
+}
+
 #[test]
 fn integration_uniffi_mixed() {
     if env::var("GITHUB_ACTIONS").is_ok() || which("uniffi-bindgen").is_ok() {
@@ -473,6 +502,11 @@ fn integration_with_data() {
     ));
 }
 
+#[test]
+fn data_scripts_shebang() {
+    handle_result(other::data_scripts_shebang())
+}
+
 #[test]
 fn integration_readme_duplication() {
     handle_result(integration::test_integration(
@@ -592,6 +626,23 @@ fn musl() {
     }
 }
 
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn linux_compatibility_tag() {
+    handle_result(other::test_linux_compatibility_tag())
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn emit_symbols() {
+    handle_result(other::test_emit_symbols())
+}
+
+#[test]
+fn emit_sbom() {
+    handle_result(other::test_emit_sbom())
+}
+
 #[test]
 fn workspace_cargo_lock() {
     handle_result(other::test_workspace_cargo_lock())
@@ -672,6 +723,94 @@ fn workspace_members_non_local_dep_sdist() {
     ))
 }
 
+#[test]
+fn workspace_members_sdist() {
+    let cargo_toml = expect![[r#"
+        [package]
+        name = "pyo3-workspace-members"
+        version = "0.1.0"
+        edition = "2021"
+        description = "A namespace of pyo3 modules built from multiple workspace members"
+
+        [dependencies]
+        pyo3 = { version = "0.23.1", features = ["abi3-py37", "extension-module"] }
+
+        [lib]
+        name = "pyo3_workspace_members"
+        crate-type = ["cdylib"]
+
+        [workspace]
+        members = ["plugins/plugin-a"]
+    "#]];
+    handle_result(other::test_source_distribution(
+        "test-crates/pyo3-workspace-members",
+        SdistGenerator::Cargo,
+        expect![[r#"
+            {
+                "pyo3_workspace_members-0.1.0/Cargo.lock",
+                "pyo3_workspace_members-0.1.0/Cargo.toml",
+                "pyo3_workspace_members-0.1.0/PKG-INFO",
+                "pyo3_workspace_members-0.1.0/plugins/plugin-a/Cargo.toml",
+                "pyo3_workspace_members-0.1.0/plugins/plugin-a/src/lib.rs",
+                "pyo3_workspace_members-0.1.0/pyproject.toml",
+                "pyo3_workspace_members-0.1.0/src/lib.rs",
+            }
+        "#]],
+        Some((
+            Path::new("pyo3_workspace_members-0.1.0/Cargo.toml"),
+            cargo_toml,
+        )),
+        "sdist-workspace-members",
+    ))
+}
+
+#[test]
+fn sdist_out() {
+    handle_result(other::test_source_distribution_out())
+}
+
+#[test]
+fn sdist_out_excluded() {
+    handle_result(other::sdist_out_excluded())
+}
+
+#[test]
+fn sdist_version_suffix() {
+    handle_result(other::test_sdist_version_suffix())
+}
+
+#[test]
+fn sdist_shared_classifiers_file() {
+    handle_result(other::sdist_shared_classifiers_file())
+}
+
+#[test]
+fn out_dir_placeholders() {
+    handle_result(other::out_dir_placeholders())
+}
+
+#[test]
+fn interpreters_from_env() {
+    handle_result(other::interpreters_from_env())
+}
+
+#[test]
+fn vendor_crates_sdist() {
+    handle_result(other::test_source_distribution_vendor_crates())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn repair_wheel() {
+    handle_result(other::test_repair_wheel())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn auditwheel_check() {
+    handle_result(other::test_auditwheel_check())
+}
+
 #[test]
 fn lib_with_path_dep_sdist() {
     handle_result(other::test_source_distribution(
@@ -836,6 +975,24 @@ fn pyo3_mixed_include_exclude_wheel_files() {
     ))
 }
 
+#[test]
+fn pyo3_mixed_src_layout_wheel_files() {
+    handle_result(other::check_wheel_files(
+        "test-crates/pyo3-mixed-src/rust",
+        vec![
+            "pyo3_mixed_src-2.1.3.dist-info/METADATA",
+            "pyo3_mixed_src-2.1.3.dist-info/RECORD",
+            "pyo3_mixed_src-2.1.3.dist-info/WHEEL",
+            "pyo3_mixed_src-2.1.3.dist-info/entry_points.txt",
+            "pyo3_mixed_src/__init__.py",
+            "pyo3_mixed_src/python_module/__init__.py",
+            "pyo3_mixed_src/python_module/double.py",
+            "tests/test_pyo3_mixed.py",
+        ],
+        "wheel-files-pyo3-mixed-src",
+    ))
+}
+
 #[test]
 fn workspace_sdist() {
     handle_result(other::test_source_distribution(
@@ -924,6 +1081,51 @@ fn workspace_inheritance_sdist() {
     ))
 }
 
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn workspace_members_wheel() {
+    handle_result(other::workspace_members_wheel());
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn cffi_header_in_wheel() {
+    handle_result(other::cffi_header_in_wheel());
+}
+
+#[test]
+fn pep517_pyproject_profile() {
+    handle_result(other::pep517_pyproject_profile());
+}
+
+#[test]
+fn include_readme_in_wheel() {
+    handle_result(other::include_readme_in_wheel());
+}
+
+#[test]
+fn compile_with_diagnostics() {
+    handle_result(other::compile_with_diagnostics());
+}
+
+#[test]
+#[cfg(unix)]
+fn rustc_wrapper() {
+    handle_result(other::rustc_wrapper());
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn build_script_env() {
+    handle_result(other::build_script_env());
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn abi3_tag_uses_minimum_version() {
+    handle_result(other::abi3_tag_uses_minimum_version());
+}
+
 #[test]
 fn abi3_python_interpreter_args() {
     handle_result(other::abi3_python_interpreter_args());